@@ -0,0 +1,120 @@
+//! Pins processes to particular CPUs, so the scheduler can't move a benchmarked command between
+//! cores (or between sockets, or between big and little cores) from one iteration to the next.
+//!
+//! CPUs are given as taskset-style lists, like `2-3` or `0,2,4-7`. Pinning is only supported on
+//! Linux.
+
+use std::io;
+
+/// Parses a taskset-style CPU list, like `0,2,4-7`, into CPU numbers in ascending order.
+pub fn parse_cpu_list(text: &str) -> Result<Vec<usize>, String> {
+    let parse = |cpu: &str| {
+        cpu.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid CPU list {:?}", text))
+    };
+    let mut cpus = vec![];
+    for part in text.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse(first)?, parse(last)?);
+                if first > last {
+                    return Err(format!("invalid CPU list {:?}", text));
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(parse(part)?),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+/// A set of CPUs in the form the kernel takes it, built ahead of time so that applying it in a
+/// freshly forked child doesn't need to allocate.
+#[derive(Clone, Copy)]
+pub struct CpuSet {
+    #[cfg(target_os = "linux")]
+    set: libc::cpu_set_t,
+}
+
+impl CpuSet {
+    /// Builds the set of `cpus`, or fails if pinning isn't supported here or a CPU number is too
+    /// large for the kernel's set.
+    #[cfg(target_os = "linux")]
+    pub fn new(cpus: &[usize]) -> io::Result<CpuSet> {
+        // SAFETY: cpu_set_t is plain old data, so all-zeroes is a valid (empty) set.
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for &cpu in cpus {
+            if cpu >= libc::CPU_SETSIZE as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("CPU {} is out of range", cpu),
+                ));
+            }
+            // SAFETY: cpu was just checked to be within the set.
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+        }
+        Ok(CpuSet { set })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(_cpus: &[usize]) -> io::Result<CpuSet> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "CPU affinity is only supported on Linux",
+        ))
+    }
+
+    /// Pins the calling thread (and any processes it later starts) to the set. This only makes a
+    /// single system call, so it's safe to use between `fork` and `exec`.
+    #[cfg(target_os = "linux")]
+    pub fn apply(&self) -> io::Result<()> {
+        // SAFETY: set is a valid cpu_set_t and the size passed is its size.
+        let result = unsafe {
+            libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &self.set)
+        };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply(&self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "CPU affinity is only supported on Linux",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod parse_cpu_list_tests {
+    use super::*;
+
+    #[test]
+    fn parses_lists_and_ranges() {
+        assert_eq!(vec![0, 2, 4, 5, 6, 7], parse_cpu_list("0,2,4-7").unwrap());
+        assert_eq!(vec![1, 3], parse_cpu_list("3, 1,3").unwrap());
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse_cpu_list("").is_err());
+        assert!(parse_cpu_list("a-b").is_err());
+        assert!(parse_cpu_list("5-2").is_err());
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod cpu_set_tests {
+    use super::*;
+
+    #[test]
+    fn rejects_out_of_range_cpus() {
+        assert!(CpuSet::new(&[usize::MAX]).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+//! Strips what identifies a machine and the people using it out of results, so they can be
+//! attached to a public issue. What makes timings comparable stays: the CPU, its core count, the
+//! OS, and roughly how much memory there is. What doesn't goes: the hostname, full paths, and the
+//! user's name wherever it turns up, such as in a command's arguments or what it wrote to stderr.
+
+use crate::config::Binary;
+use crate::results::{Metadata, SuiteResults};
+use std::env;
+use std::path::{Path, PathBuf};
+
+const GIB: u64 = 1 << 30;
+
+/// Directories under which each user has a home of their own, on Linux and macOS.
+const HOME_PARENTS: [&str; 2] = ["/home/", "/Users/"];
+
+/// Anonymizes `results` in place, along with the metadata of every run merged into them.
+pub fn anonymize(results: &mut SuiteResults) {
+    // Results made on another machine, or by another user, name that user rather than us.
+    let json = serde_json::to_string(&*results).unwrap_or_default();
+    let scrubber = Scrubber::from_env().with_homes_in(&json);
+    anonymize_metadata(&mut results.metadata, &scrubber);
+    for metadata in results.runs.values_mut() {
+        anonymize_metadata(metadata, &scrubber);
+    }
+    for benchmark in &mut results.benchmarks {
+        benchmark.sample = scrubber.scrub(&benchmark.sample);
+        benchmark.variant = benchmark.variant.as_deref().map(|v| scrubber.scrub(v));
+        scrubber.scrub_all(&mut benchmark.command);
+        for iteration in &mut benchmark.iterations {
+            iteration.stderr = scrubber.scrub(&iteration.stderr);
+        }
+    }
+}
+
+fn anonymize_metadata(metadata: &mut Metadata, scrubber: &Scrubber) {
+    if let Some(environment) = &mut metadata.environment {
+        environment.hostname = None;
+        environment.memory_bytes = environment.memory_bytes.map(memory_bucket);
+    }
+    metadata.config_path = metadata.config_path.as_deref().map(file_name);
+    if let Some(suite) = &mut metadata.suite {
+        suite.sample_dir = file_name(&suite.sample_dir);
+        suite.stage_dir = suite.stage_dir.as_deref().map(file_name);
+        for case in &mut suite.cases {
+            scrubber.scrub_all(&mut case.command);
+            for binary in &mut case.binaries {
+                *binary = match binary {
+                    Binary::Path(path) => Binary::Path(scrubber.scrub(path)),
+                    Binary::Named { name, path } => Binary::Named {
+                        name: scrubber.scrub(name),
+                        path: scrubber.scrub(path),
+                    },
+                };
+            }
+            case.source = case.source.as_deref().map(file_name);
+            case.cwd = case.cwd.as_deref().map(file_name);
+            for value in case.env.values_mut() {
+                *value = scrubber.scrub(value);
+            }
+        }
+    }
+}
+
+/// Rounds an amount of memory up to a power of two gibibytes, which tells machines apart by class
+/// but not individually: 31.2 GiB, say, becomes 32 GiB.
+fn memory_bucket(bytes: u64) -> u64 {
+    bytes.div_ceil(GIB).max(1).next_power_of_two() * GIB
+}
+
+/// Keeps just the last component of a path.
+fn file_name(path: &Path) -> PathBuf {
+    path.file_name()
+        .map_or_else(|| path.to_path_buf(), PathBuf::from)
+}
+
+/// Removes users' names and home directories from text, and the directories from absolute paths.
+struct Scrubber {
+    homes: Vec<String>,
+    users: Vec<String>,
+}
+
+impl Scrubber {
+    fn from_env() -> Scrubber {
+        // A name of a letter or two would turn up inside too many other words to replace.
+        let var = |name| env::var(name).ok().filter(|value: &String| value.len() > 2);
+        Scrubber {
+            homes: var("HOME").into_iter().collect(),
+            users: var("USER").or_else(|| var("LOGNAME")).into_iter().collect(),
+        }
+    }
+
+    /// Adds the home directories that turn up in `text`, such as `/home/alice`, and their users.
+    fn with_homes_in(mut self, text: &str) -> Scrubber {
+        for parent in HOME_PARENTS {
+            for (i, _) in text.match_indices(parent) {
+                let rest = &text[i + parent.len()..];
+                let user = &rest[..rest
+                    .find(|c: char| c == '/' || c == '"' || c.is_whitespace())
+                    .unwrap_or(rest.len())];
+                if user.len() > 2 && !self.users.iter().any(|known| known == user) {
+                    self.homes.push(format!("{}{}", parent, user));
+                    self.users.push(user.to_string());
+                }
+            }
+        }
+        self
+    }
+
+    fn scrub(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for home in &self.homes {
+            text = text.replace(home.as_str(), "~");
+        }
+        let words: Vec<String> = text.split(' ').map(strip_dirs).collect();
+        let mut text = words.join(" ");
+        for user in &self.users {
+            text = text.replace(user.as_str(), "user");
+        }
+        text
+    }
+
+    fn scrub_all(&self, texts: &mut [String]) {
+        for text in texts {
+            *text = self.scrub(text);
+        }
+    }
+}
+
+/// Strips the directories from a word that's an absolute or home-relative path, or an option set
+/// to one, e.g. `--input=/data/a.txt` becomes `--input=a.txt`.
+fn strip_dirs(word: &str) -> String {
+    let (prefix, value) = match word.split_once('=') {
+        Some((option, value)) => (format!("{}=", option), value),
+        None => (String::new(), word),
+    };
+    if !(value.starts_with('/') || value.starts_with("~/")) {
+        return word.to_string();
+    }
+    match value.trim_end_matches('/').rsplit_once('/') {
+        Some((_, name)) if !name.is_empty() => format!("{}{}", prefix, name),
+        _ => word.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod anonymize_tests {
+    use super::*;
+    use crate::environment::Environment;
+    use crate::results::BenchmarkResults;
+
+    #[test]
+    fn keeps_hardware_class() {
+        let mut results = SuiteResults {
+            benchmarks: vec![BenchmarkResults {
+                command: vec!["/opt/bin/groupby".to_string(), "s.txt".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        results.metadata.environment = Some(Environment {
+            hostname: Some("alices-laptop".to_string()),
+            cpu_cores: Some(8),
+            memory_bytes: Some(31 * GIB + GIB / 5),
+            ..Default::default()
+        });
+        results.metadata.config_path = Some(PathBuf::from("/srv/bench/bench.toml"));
+        results.metadata.suite = Some(
+            toml::from_str(
+                r#"
+                [[case]]
+                name = "groupby"
+                command = ["groupby"]
+                samples = ["s.txt"]
+                binaries = ["/opt/bin/groupby", { name = "main", path = "/opt/main/groupby" }]
+                "#,
+            )
+            .unwrap(),
+        );
+        anonymize(&mut results);
+
+        let environment = results.metadata.environment.unwrap();
+        assert_eq!(None, environment.hostname);
+        assert_eq!(Some(8), environment.cpu_cores);
+        assert_eq!(Some(32 * GIB), environment.memory_bytes);
+        assert_eq!(
+            Some(PathBuf::from("bench.toml")),
+            results.metadata.config_path
+        );
+        assert_eq!(vec!["groupby", "s.txt"], results.benchmarks[0].command);
+        assert_eq!(
+            vec![
+                Binary::Path("groupby".to_string()),
+                Binary::Named {
+                    name: "main".to_string(),
+                    path: "groupby".to_string()
+                }
+            ],
+            results.metadata.suite.unwrap().cases[0].binaries
+        );
+    }
+}
+
+#[cfg(test)]
+mod scrub_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        let scrubber = Scrubber {
+            homes: vec!["/home/alice".to_string()],
+            users: vec!["alice".to_string()],
+        };
+        assert_eq!(
+            "groupby: can't open a.txt (owned by user)",
+            scrubber.scrub("groupby: can't open /home/alice/data/a.txt (owned by alice)")
+        );
+        assert_eq!("--input=a.txt", scrubber.scrub("--input=~/a.txt"));
+        assert_eq!("s/a/b/", scrubber.scrub("s/a/b/"));
+        assert_eq!("/", scrubber.scrub("/"));
+    }
+}
+
+#[cfg(test)]
+mod memory_bucket_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        assert_eq!(GIB, memory_bucket(GIB / 2));
+        assert_eq!(16 * GIB, memory_bucket(16 * GIB));
+        assert_eq!(64 * GIB, memory_bucket(33 * GIB));
+    }
+}
+
+#[cfg(test)]
+mod with_homes_in_tests {
+    use super::*;
+
+    #[test]
+    fn finds_other_users() {
+        let scrubber = Scrubber {
+            homes: vec![],
+            users: vec![],
+        }
+        .with_homes_in(
+            r#"["/home/bob/groupby/target/release/groupby","/Users/carol","/home/al/x"]"#,
+        );
+        assert_eq!(vec!["/home/bob", "/Users/carol"], scrubber.homes);
+        assert_eq!(vec!["bob", "carol"], scrubber.users);
+        assert_eq!(
+            "groupby (built by user)",
+            scrubber.scrub("/home/bob/src/target/release/groupby (built by bob)")
+        );
+    }
+}
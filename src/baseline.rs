@@ -0,0 +1,140 @@
+//! Classic Unix ways of grouping lines, run on the same samples as the suite's own cases, so every
+//! report shows groupby's numbers next to what the standard tools manage.
+
+use crate::reference::Grouping;
+use serde::{Deserialize, Serialize};
+
+/// A preset baseline command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Baseline {
+    /// `sort | uniq -c`, after cutting each line down to its key.
+    SortUniq,
+
+    /// An `awk` one-liner counting lines in an associative array.
+    Awk,
+
+    /// GNU `datamash`'s `groupby` operation, after cutting each line down to its key.
+    Datamash,
+}
+
+impl Baseline {
+    /// The baseline's name in config files, e.g. `sort-uniq`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Baseline::SortUniq => "sort-uniq",
+            Baseline::Awk => "awk",
+            Baseline::Datamash => "datamash",
+        }
+    }
+
+    /// The program the baseline needs, besides `sh`, for checking that it's installed.
+    pub fn program(&self) -> &'static str {
+        match self {
+            Baseline::SortUniq => "sort",
+            Baseline::Awk => "awk",
+            Baseline::Datamash => "datamash",
+        }
+    }
+
+    /// The command that groups its stdin the way `grouping` does.
+    pub fn command(&self, grouping: Grouping) -> Vec<String> {
+        let script = match self {
+            Baseline::SortUniq => format!("{}sort | uniq -c", cut(grouping)),
+            Baseline::Awk => format!(
+                "{}awk '{{ n[{}]++ }} END {{ for (k in n) print k, n[k] }}'",
+                UTF8_LOCALE,
+                awk_key(grouping)
+            ),
+            Baseline::Datamash => format!("{}datamash --sort --group 1 count 1", cut(grouping)),
+        };
+        vec!["sh".to_string(), "-c".to_string(), script]
+    }
+}
+
+/// Runs awk in a UTF-8 locale, in which an awk that understands UTF-8, such as gawk, counts
+/// characters rather than bytes, as groupby does.
+const UTF8_LOCALE: &str = "LC_ALL=C.UTF-8 ";
+
+/// The start of a pipeline that cuts each line down to its key, if it isn't the whole line. `cut
+/// -c` counts bytes, whatever the locale, so we cut with awk instead.
+fn cut(grouping: Grouping) -> String {
+    match grouping {
+        Grouping::Line => String::new(),
+        _ => format!("{}awk '{{ print {} }}' | ", UTF8_LOCALE, awk_key(grouping)),
+    }
+}
+
+/// The awk expression for a line's key.
+fn awk_key(grouping: Grouping) -> String {
+    match grouping {
+        Grouping::FirstChars(n) => format!("substr($0, 1, {})", n),
+        Grouping::LastChars(n) => format!(
+            "substr($0, length($0) > {n} ? length($0) - {n} + 1 : 1)",
+            n = n
+        ),
+        Grouping::Line => "$0".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod baseline_tests {
+    use super::*;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    /// Runs a baseline's command on `input`, returning its output lines in sorted order.
+    fn run(baseline: Baseline, grouping: Grouping, input: &str) -> Vec<String> {
+        let command = baseline.command(grouping);
+        let mut child = Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        let mut lines: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect();
+        lines.sort();
+        lines
+    }
+
+    #[test]
+    fn sort_uniq_groups() {
+        let input = "apple\navocado\nbanana\n";
+        assert_eq!(
+            vec!["1 b", "2 a"],
+            run(Baseline::SortUniq, Grouping::FirstChars(1), input)
+        );
+        assert_eq!(
+            vec!["1 do", "1 le", "1 na"],
+            run(Baseline::SortUniq, Grouping::LastChars(2), input)
+        );
+    }
+
+    #[test]
+    fn awk_groups() {
+        let input = "apple\navocado\nbanana\n";
+        assert_eq!(
+            vec!["a 2", "b 1"],
+            run(Baseline::Awk, Grouping::FirstChars(1), input)
+        );
+        assert_eq!(
+            vec!["do 1", "le 1", "na 1"],
+            run(Baseline::Awk, Grouping::LastChars(2), input)
+        );
+        assert_eq!(
+            vec!["apple 1", "avocado 1", "banana 1"],
+            run(Baseline::Awk, Grouping::Line, input)
+        );
+    }
+}
@@ -1,64 +1,526 @@
-//! This program creates bounded random sample text for use in benchmarking `groupby`. It accepts
-//! no command-line inputs; all customization is performed through the `main` function. Thus, to
-//! generate all necessary sample files for benchmarking, simply run this program once.
+//! This program creates bounded random sample text for use in benchmarking `groupby`. The samples
+//! are customized in the `main` function, not on the command line. Thus, to generate all necessary
+//! sample files for benchmarking, simply run this program once; `--only` builds just some of them.
 
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use groupby_benchmarking::sample::{
+    accented, alphabet, build_dataset, build_sample_with, sample_seed, spec, validate, Encoding,
+    GroupCounter, GroupStats, LineLength, Metadata, Options, SampleLength, SizeDistribution,
+};
 use std::collections::VecDeque;
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::BufWriter;
-#[allow(unused_imports)]
-use std::iter;
-use std::ops::Range;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, IsTerminal};
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::Instant;
 
-/// Specifies the bounds for the lengths of lines in a sample file (excluding the newline character).
-pub enum LineLength {
-    /// Each line will be exactly this many characters.
-    Fixed(usize),
+#[derive(Parser)]
+#[command(about = "Builds the samples for benchmarking groupby")]
+struct Args {
+    /// Build only the samples whose names match this pattern, in which `*` stands for any run of
+    /// characters and `?` for any one, e.g. 'ranged-*-300MB-*'.
+    #[arg(long, value_name = "PATTERN")]
+    only: Option<String>,
 
-    /// Each line's length will be randomly chosen from this range.
-    Range(Range<usize>),
+    /// List the samples, and the ways samples can be generated, rather than building any.
+    #[arg(long)]
+    list: bool,
+
+    /// Log as JSON lines, for other programs to read, rather than as text.
+    #[arg(long)]
+    log_json: bool,
+
+    /// How to name the samples of the size and line length matrix, from their parameters:
+    /// `{dist}` (`fixed` or `ranged`), `{len}` (`20` or `5to80`), `{minlen}`, `{maxlen}`, `{size}`
+    /// (`30MB`, or `100lines`), `{charset}`, and `{seed}` (the `--seed`, or `noseed`).
+    #[arg(long, value_name = "TEMPLATE", default_value = DEFAULT_NAME_TEMPLATE)]
+    name_template: String,
+
+    /// Write the samples into this directory, creating it if need be, rather than the current one.
+    #[arg(long, value_name = "DIR", env = "MKSAMPLE_DIR")]
+    dir: Option<PathBuf>,
+
+    /// Build at most this many samples at once, rather than all of them.
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        env = "MKSAMPLE_JOBS",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    jobs: Option<u64>,
+
+    /// Seed the random text, so the same seed builds the same samples. Each sample's seed is mixed
+    /// with its name, so it doesn't depend on which others are built.
+    #[arg(long, value_name = "N", env = "MKSAMPLE_SEED")]
+    seed: Option<u64>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-/// Specifies the length of the output file as either a number of lines or a number of characters
-/// (including newlines).
-pub enum SampleLength {
-    Lines(usize),
-    Characters(usize),
+#[derive(Subcommand)]
+enum Command {
+    /// Prints a completion script for a shell, e.g. `mksample completions bash > ~/.mksample.bash`.
+    #[command(hide = true)]
+    Completions {
+        /// The shell: bash, elvish, fish, powershell, or zsh.
+        shell: Shell,
+    },
 }
 
+/// The names the matrix samples have always had, e.g. `ranged-5to80char-10MB-alphanumeric.txt`.
+const DEFAULT_NAME_TEMPLATE: &str = "{dist}-{len}char-{size}-{charset}.txt";
+
+/// The exit code when some samples were built but others failed, so scripts can tell it from 1,
+/// when none were, and from clap's 2, for bad arguments.
+const EXIT_PARTIAL_FAILURE: u8 = 3;
+
 /// Builds all preconfigured samples.
-fn main() {
+fn main() -> ExitCode {
+    let args = Args::parse();
+    if let Some(Command::Completions { shell }) = args.command {
+        clap_complete::generate(shell, &mut Args::command(), "mksample", &mut io::stdout());
+        return ExitCode::SUCCESS;
+    }
+    if args.log_json {
+        tracing_subscriber::fmt().json().init();
+    } else {
+        tracing_subscriber::fmt()
+            .without_time()
+            .with_target(false)
+            .with_ansi(io::stdout().is_terminal())
+            .init();
+    }
     let cg = fastrand::alphanumeric;
 
-    // TODO Build out the sample set once we're ready to build charts.
-
-    // Example builds....
+    let mut builder = match SampleBuilder::new(
+        args.only,
+        args.list,
+        args.name_template,
+        args.jobs.map(|jobs| jobs as usize),
+        args.seed,
+    ) {
+        Ok(builder) => builder,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+    if let (Some(dir), false) = (&args.dir, args.list) {
+        if let Err(e) = fs::create_dir_all(dir).and_then(|()| env::set_current_dir(dir)) {
+            eprintln!("{}: {}", dir.display(), e);
+            return ExitCode::FAILURE;
+        }
+    }
+    if args.list {
+        println!("Presets:");
+    }
 
-    let mut builder = SampleBuilder::new();
-    builder.sample(
-        "fixed-20char-30MB-alphanumeric.txt",
+    // The ranged samples grow by roughly a factor of three at a time, so charts of runtime against
+    // input size have enough points to show how each case scales.
+    builder.matrix_sample(
+        "alphanumeric",
         LineLength::Fixed(20),
         SampleLength::Characters(30_000_000),
         cg,
     );
 
-    builder.sample(
-        "ranged-5to80char-30MB-alphanumeric.txt",
+    builder.matrix_sample(
+        "alphanumeric",
+        LineLength::Range(5..81),
+        SampleLength::Characters(3_000_000),
+        cg,
+    );
+
+    builder.matrix_sample(
+        "alphanumeric",
+        LineLength::Range(5..81),
+        SampleLength::Characters(10_000_000),
+        cg,
+    );
+
+    builder.matrix_sample(
+        "alphanumeric",
         LineLength::Range(5..81),
         SampleLength::Characters(30_000_000),
         cg,
     );
 
-    builder.sample(
-        "ranged-5to80char-300MB-alphanumeric.txt",
+    builder.matrix_sample(
+        "alphanumeric",
+        LineLength::Range(5..81),
+        SampleLength::Characters(100_000_000),
+        cg,
+    );
+
+    builder.matrix_sample(
+        "alphanumeric",
         LineLength::Range(5..81),
         SampleLength::Characters(300_000_000),
         cg,
     );
+
+    // The most adversarial shape for a line reader: a single 300 MB line, with no newline until
+    // the very end. The line is built in memory before it's written, so this takes 300 MB of RAM.
+    builder.sample(
+        "single-line-300MB-alphanumeric.txt",
+        LineLength::Fixed(300_000_000 - 1),
+        SampleLength::Lines(1),
+        cg,
+    );
+
+    // On a compressed file system, this sample takes a fraction of the reads the alphanumeric one
+    // of the same size does, which tells I/O-bound cases from CPU-bound ones.
+    builder.matrix_sample(
+        "4chars",
+        LineLength::Range(5..81),
+        SampleLength::Characters(30_000_000),
+        alphabet(4),
+    );
+
+    // Binary-ish input: one character in a thousand is a NUL byte.
+    builder.sample_with(
+        "ranged-5to80char-10MB-nul.txt",
+        LineLength::Range(5..81),
+        SampleLength::Characters(10_000_000),
+        cg,
+        Options {
+            nul_rate: 0.001,
+            ..Default::default()
+        },
+    );
+
+    // Input for lossy decoding and encoding errors: one character in a thousand is invalid UTF-8.
+    builder.sample_with(
+        "ranged-5to80char-10MB-invalid-utf8.txt",
+        LineLength::Range(5..81),
+        SampleLength::Characters(10_000_000),
+        cg,
+        Options {
+            invalid_utf8_rate: 0.001,
+            ..Default::default()
+        },
+    );
+
+    // Files exported on Windows often start with a byte order mark.
+    builder.sample_with(
+        "ranged-5to80char-10MB-bom.txt",
+        LineLength::Range(5..81),
+        SampleLength::Characters(10_000_000),
+        cg,
+        Options {
+            bom: true,
+            ..Default::default()
+        },
+    );
+
+    // The same as ranged-5to80char-10MB-alphanumeric.txt but for the missing newline at the end,
+    // a classic edge case for line readers.
+    builder.sample_with(
+        "ranged-5to80char-10MB-no-final-newline.txt",
+        LineLength::Range(5..81),
+        SampleLength::Characters(10_000_000),
+        cg,
+        Options {
+            omit_final_newline: true,
+            ..Default::default()
+        },
+    );
+
+    // A file that's been through both Windows and Unix tools, with a third of its lines in CRLF.
+    builder.sample_with(
+        "ranged-5to80char-10MB-mixed-crlf.txt",
+        LineLength::Range(5..81),
+        SampleLength::Characters(10_000_000),
+        cg,
+        Options {
+            crlf_ratio: 1.0 / 3.0,
+            ..Default::default()
+        },
+    );
+
+    // The output of a legacy Windows program: text with accented letters, in Windows-1252.
+    builder.sample_with(
+        "ranged-5to80char-10MB-windows-1252.txt",
+        LineLength::Range(5..81),
+        SampleLength::Characters(10_000_000),
+        accented,
+        Options {
+            encoding: Encoding::Windows1252,
+            ..Default::default()
+        },
+    );
+
+    // A text export from a Windows tool: UTF-16LE with a byte order mark. 10M code units make
+    // 20 MB.
+    builder.sample_with(
+        "ranged-5to80char-20MB-utf-16le.txt",
+        LineLength::Range(5..81),
+        SampleLength::Characters(10_000_000),
+        accented,
+        Options {
+            encoding: Encoding::Utf16Le,
+            bom: true,
+            ..Default::default()
+        },
+    );
+
+    // Lines of pseudo-words from a vocabulary of 1000, for checking groupby's output by eye.
+    builder.sample_with(
+        "ranged-5to80char-10MB-words.txt",
+        LineLength::Range(5..81),
+        SampleLength::Characters(10_000_000),
+        cg,
+        Options {
+            words: Some(1_000),
+            ..Default::default()
+        },
+    );
+
+    // 1000 template lines, cycled through with no randomness, for validating how groupby scales
+    // with the number of groups.
+    builder.sample_with(
+        "ranged-5to80char-10MB-cycle-1000.txt",
+        LineLength::Range(5..81),
+        SampleLength::Characters(10_000_000),
+        cg,
+        Options {
+            cycle: Some(1_000),
+            ..Default::default()
+        },
+    );
+
+    // Lines like a web server's log, keyed by one of 1000 pseudo-words. Templated lines make their
+    // own lengths, so the line length only fits the last line to the sample's size.
+    builder.sample_with(
+        "templated-10MB-access-log.txt",
+        LineLength::Fixed(0),
+        SampleLength::Characters(10_000_000),
+        cg,
+        Options {
+            template: Some(
+                "{KEY} [{INT:100-599}] GET {PATH} {RAND:12}"
+                    .parse()
+                    .unwrap(),
+            ),
+            words: Some(1_000),
+            ..Default::default()
+        },
+    );
+
+    // Fixed-width records, as in a mainframe export, with fields of 10, 8, and 20 columns. Fields
+    // are made of an alphabet of 8, so grouping by a few columns at an offset finds few groups.
+    builder.sample_with(
+        "records-10-8-20col-10MB.txt",
+        LineLength::Fixed(0),
+        SampleLength::Characters(10_000_000),
+        alphabet(8),
+        Options {
+            columns: Some(vec![10, 8, 20]),
+            ..Default::default()
+        },
+    );
+
+    // A directory of 100 logs, from 10 kB to 10 MB but mostly small, whose lines start with keys
+    // from a vocabulary of 10,000 shared by every file, for benchmarking `groupby *.txt`.
+    builder.dataset(
+        "dataset-100files-words",
+        100,
+        SizeDistribution::LogUniform(10_000..10_000_000),
+        LineLength::Range(5..81),
+        cg,
+        Options {
+            words: Some(10_000),
+            ..Default::default()
+        },
+    );
+
+    if let (Some(pattern), true) = (&builder.only, builder.names.is_empty()) {
+        eprintln!("No samples match {}", pattern);
+        return ExitCode::FAILURE;
+    }
+    if let Err(e) = builder.build() {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
+    if args.list {
+        print_building_blocks();
+    }
+    match builder.join() {
+        (_, 0) => ExitCode::SUCCESS,
+        (0, _) => ExitCode::FAILURE,
+        (_, _) => ExitCode::from(EXIT_PARTIAL_FAILURE),
+    }
+}
+
+/// What presets are made of, for `--list`: the names are those of the library's functions and
+/// options.
+const BUILDING_BLOCKS: [(&str, &[(&str, &str)]); 4] = [
+    (
+        "Character generators",
+        &[
+            ("alphanumeric", "ASCII letters and digits, uniformly"),
+            (
+                "alphabet(n)",
+                "the first n of the digits, then letters, uniformly",
+            ),
+            (
+                "accented",
+                "ASCII letters and digits, and Latin-1's accented letters",
+            ),
+        ],
+    ),
+    (
+        "Length distributions",
+        &[
+            ("LineLength::Fixed(n)", "every line n characters long"),
+            (
+                "LineLength::Range(a..b)",
+                "line lengths drawn uniformly from a..b",
+            ),
+            ("SampleLength::Lines(n)", "a sample of n lines"),
+            (
+                "SampleLength::Characters(n)",
+                "a sample of n characters, newlines included",
+            ),
+            (
+                "SizeDistribution::Fixed(n)",
+                "dataset files of n characters each",
+            ),
+            (
+                "SizeDistribution::Uniform(a..b)",
+                "dataset file sizes drawn uniformly from a..b",
+            ),
+            (
+                "SizeDistribution::LogUniform(a..b)",
+                "dataset file sizes, mostly small",
+            ),
+        ],
+    ),
+    (
+        "Group structure",
+        &[
+            (
+                "duplicate_ratio",
+                "this fraction of lines repeat a recent line",
+            ),
+            (
+                "words",
+                "lines of pseudo-words from a vocabulary of this many",
+            ),
+            ("cycle", "lines cycle through this many fixed lines"),
+            (
+                "template",
+                "lines filled in from a template, keyed by {KEY}",
+            ),
+            ("columns", "fixed-width records with fields of these widths"),
+        ],
+    ),
+    (
+        "Other shapes",
+        &[
+            ("nul_rate", "this fraction of characters are NUL"),
+            (
+                "invalid_utf8_rate",
+                "this fraction of characters are invalid UTF-8",
+            ),
+            ("bom", "a byte order mark"),
+            ("omit_final_newline", "no newline at the end"),
+            ("crlf_ratio", "this fraction of lines end in CRLF"),
+            (
+                "blank_ratio",
+                "this fraction of lines are made only of spaces and tabs",
+            ),
+            (
+                "encoding",
+                "UTF-8, Latin-1, Windows-1252, UTF-16LE, or UTF-16BE",
+            ),
+        ],
+    ),
+];
+
+fn print_building_blocks() {
+    for (heading, entries) in BUILDING_BLOCKS {
+        println!();
+        println!("{}:", heading);
+        for (name, description) in entries {
+            println!("  {:<36} {}", name, description);
+        }
+    }
 }
 
-/// Wraps build_sample invocations in new threads for easy parallelism.
+/// Describes a preset in a line, for `--list`. The character generator is a closure, which can't
+/// be described, but the name usually says which it is.
+fn describe(
+    line_length: &LineLength,
+    sample_length: Option<&SampleLength>,
+    options: &Options,
+) -> String {
+    let mut parts = vec![match (&options.columns, &options.template, line_length) {
+        (Some(columns), _, _) => format!("records of columns {:?}", columns),
+        (None, Some(_), _) => "templated lines".to_string(),
+        (None, None, LineLength::Fixed(n)) => format!("lines of {}", characters(*n)),
+        (None, None, LineLength::Range(range)) => {
+            format!(
+                "lines of {} to {}",
+                range.start,
+                characters(range.end.saturating_sub(1))
+            )
+        }
+    }];
+    parts.extend(sample_length.map(|sample_length| match sample_length {
+        SampleLength::Lines(1) => "1 line".to_string(),
+        SampleLength::Lines(n) => format!("{} lines", n),
+        SampleLength::Characters(n) => characters(*n),
+    }));
+    if let Some(words) = options.words {
+        parts.push(format!("{} pseudo-words", words));
+    }
+    if let Some(cycle) = options.cycle {
+        parts.push(format!("cycling through {} lines", cycle));
+    }
+    let rates = [
+        ("duplicates", options.duplicate_ratio),
+        ("NULs", options.nul_rate),
+        ("invalid UTF-8", options.invalid_utf8_rate),
+        ("CRLF", options.crlf_ratio),
+        ("blank lines", options.blank_ratio),
+    ];
+    for (name, rate) in rates {
+        if rate > 0.0 {
+            parts.push(format!("{:.3}% {}", rate * 100.0, name));
+        }
+    }
+    if options.bom {
+        parts.push("BOM".to_string());
+    }
+    if options.omit_final_newline {
+        parts.push("no final newline".to_string());
+    }
+    if options.encoding != Encoding::Utf8 {
+        parts.push(options.encoding.name().to_string());
+    }
+    parts.join(", ")
+}
+
+/// Formats a count of characters, shortening round millions and thousands: `10M characters`.
+fn characters(n: usize) -> String {
+    match n {
+        1 => "1 character".to_string(),
+        n if n >= 1_000_000 && n % 1_000_000 == 0 => format!("{}M characters", n / 1_000_000),
+        n if n >= 1_000 && n % 1_000 == 0 => format!("{}k characters", n / 1_000),
+        n => format!("{} characters", n),
+    }
+}
+
+/// Wraps build_sample invocations in new threads for easy parallelism. Samples are queued, then
+/// all started at once by `build`, which first makes sure no two would be written to one file.
 //
 // Note that this struct is not unit-tested. It's simple, the type system does most of the work,
 // and testing it would require things like dependency injection to verify output to stdout and
@@ -67,322 +529,581 @@ fn main() {
 #[derive(Default)]
 pub struct SampleBuilder {
     samples: VecDeque<Sample>,
+
+    /// The samples waiting for `build`, by filename.
+    queued: Vec<(String, Job)>,
+
+    /// If set, only samples whose names match this pattern are built.
+    only: Option<String>,
+
+    /// Whether to list the samples rather than build them.
+    list: bool,
+
+    /// The names of the samples that matched `only`, built or listed.
+    names: Vec<String>,
+
+    /// What's wrong with the samples that can't be built, each with where it was queued.
+    errors: Vec<String>,
+
+    /// How `matrix_sample` names samples. See `Args::name_template`.
+    name_template: String,
+
+    /// How many samples to build at once, if not all of them.
+    jobs: Option<usize>,
+
+    /// What to seed each sample's random text with, mixed with its name, if anything.
+    seed: Option<u64>,
 }
 
+/// Builds a sample.
+type Job = Box<dyn FnOnce() + Send>;
+
 /// Holds the file handle and filename for a sample that's being built.
 struct Sample {
     handle: JoinHandle<()>,
-    filename: &'static str,
+    filename: String,
 }
 
 /// We implement Drop so we can automatically join all threads when the struct is dropped.
 impl Drop for SampleBuilder {
     fn drop(&mut self) {
-        while let Some(sample) = self.samples.pop_front() {
-            match sample.handle.join() {
-                Ok(_) => println!("Created sample: {}", sample.filename),
-                Err(e) => eprintln!("{:?}", e),
-            }
-        }
+        self.join();
     }
 }
 
 impl SampleBuilder {
-    pub fn new() -> Self {
-        SampleBuilder {
+    /// Makes a builder, or explains what's wrong with `name_template`.
+    pub fn new(
+        only: Option<String>,
+        list: bool,
+        name_template: String,
+        jobs: Option<usize>,
+        seed: Option<u64>,
+    ) -> Result<Self, String> {
+        matrix_name(
+            &name_template,
+            "",
+            &LineLength::Fixed(1),
+            &SampleLength::Lines(1),
+            seed,
+        )?;
+        Ok(SampleBuilder {
             samples: VecDeque::new(),
+            only,
+            list,
+            queued: vec![],
+            names: vec![],
+            errors: vec![],
+            name_template,
+            jobs,
+            seed,
+        })
+    }
+
+    /// Whether the sample or dataset named `name` can be built, given what validating it found. If
+    /// it can't, we note why, and where in `main` it was queued, for `build` to report.
+    #[track_caller]
+    fn valid(&mut self, name: &str, validation: Result<(), String>) -> bool {
+        let Err(e) = validation else {
+            return true;
+        };
+        let caller = Location::caller();
+        self.errors.push(format!(
+            "{}:{}: {}: {}",
+            caller.file(),
+            caller.line(),
+            name,
+            e
+        ));
+        false
+    }
+
+    /// Whether the sample or dataset named `name` should be built, counting it if so. When
+    /// listing, we list it instead, and it isn't.
+    fn wanted(&mut self, name: &str, description: impl FnOnce() -> String) -> bool {
+        if !self
+            .only
+            .as_ref()
+            .is_none_or(|pattern| glob_matches(pattern, name))
+        {
+            return false;
+        }
+        self.names.push(name.to_string());
+        if self.list {
+            println!("  {:<44} {}", name, description());
         }
+        !self.list
     }
 
-    /// Builds a sample in a new thread.
+    /// Queues a sample of the matrix of sizes and line lengths, naming it with the name template.
+    /// `charset` names the character generator.
+    #[track_caller]
+    pub fn matrix_sample(
+        &mut self,
+        charset: &str,
+        line_length: LineLength,
+        sample_length: SampleLength,
+        character_generator: impl Fn() -> char + Send + 'static,
+    ) {
+        let filename = matrix_name(
+            &self.name_template,
+            charset,
+            &line_length,
+            &sample_length,
+            self.seed,
+        )
+        .expect("the template is checked when the builder is made");
+        self.sample(&filename, line_length, sample_length, character_generator);
+    }
+
+    /// Queues a sample.
+    #[track_caller]
     pub fn sample(
         &mut self,
-        filename: &'static str,
+        filename: &str,
         line_length: LineLength,
         sample_length: SampleLength,
-        character_generator: fn() -> char,
+        character_generator: impl Fn() -> char + Send + 'static,
+    ) {
+        self.sample_with(
+            filename,
+            line_length,
+            sample_length,
+            character_generator,
+            Options::default(),
+        );
+    }
+
+    /// Queues a dataset, a directory of `files` samples. See `build_dataset`.
+    #[track_caller]
+    pub fn dataset(
+        &mut self,
+        dirname: &str,
+        files: usize,
+        sizes: SizeDistribution,
+        line_length: LineLength,
+        character_generator: impl Fn() -> char + Send + 'static,
+        options: Options,
     ) {
-        let handle = thread::spawn(move || {
-            build_sample(
-                File::create(filename).unwrap(),
+        let description = || {
+            format!(
+                "{} files of {:?}: {}",
+                files,
+                sizes,
+                describe(&line_length, None, &options)
+            )
+        };
+        let valid = sizes
+            .validate()
+            .and_then(|()| validate(&line_length, &SampleLength::Lines(1), &options));
+        if !self.valid(dirname, valid) || !self.wanted(dirname, description) {
+            return;
+        }
+        let mut metadata = Metadata::from_spec(format!(
+            "{} {} files {:?} {}",
+            dirname,
+            files,
+            sizes,
+            spec(&line_length, None, &options)
+        ));
+        let has_groups = options.has_groups();
+        let dir = PathBuf::from(dirname);
+        let job = Box::new(move || {
+            build_dataset(
+                &dir,
+                files,
+                &sizes,
                 line_length,
-                sample_length,
                 character_generator,
-            )
+                options,
+            );
+            if has_groups {
+                let mut paths: Vec<_> = fs::read_dir(&dir)
+                    .unwrap()
+                    .map(|entry| entry.unwrap().path())
+                    .collect();
+                paths.sort();
+                metadata.groups = Some(count_groups(&paths));
+            }
+            metadata.write(&dir).unwrap();
         });
-        self.samples.push_back(Sample { handle, filename });
+        self.queued.push((dirname.to_string(), job));
     }
-}
 
-/// Builds a sample based on the provided parameters and writes it to `file`.
-pub fn build_sample(
-    file: impl Write,
-    line_length: LineLength,
-    sample_length: SampleLength,
-    character_generator: impl Fn() -> char,
-) {
-    // Let's buffer our writer, since we'll make lots of small writes.
-    let mut file = BufWriter::new(file);
-
-    match sample_length {
-        SampleLength::Lines(n) => {
-            for _ in 0..n {
-                let line = build_line(&line_length, &character_generator);
-                file.write_all(line.string.as_bytes()).unwrap();
-            }
+    /// Queues a sample shaped by `options`.
+    #[track_caller]
+    pub fn sample_with(
+        &mut self,
+        filename: &str,
+        line_length: LineLength,
+        sample_length: SampleLength,
+        character_generator: impl Fn() -> char + Send + 'static,
+        options: Options,
+    ) {
+        let valid = validate(&line_length, &sample_length, &options);
+        if !self.valid(filename, valid)
+            || !self.wanted(filename, || {
+                describe(&line_length, Some(&sample_length), &options)
+            })
+        {
+            return;
         }
-        SampleLength::Characters(limit) => {
-            let mut chars_written = 0;
-
-            // Calculate the upper bound on the length of a line so that we can handle the last
-            // line specially and ensure that we get the overall file length just right.
-            let max_line_length = match line_length {
-                LineLength::Fixed(n) => n + 1,     // +1 for newline.
-                LineLength::Range(ref r) => r.end, // Range is half open, so no need for +1.
-            };
-
-            // Be careful not to subtract from limit here or you'll get subtract with overflow.
-            while chars_written + max_line_length < limit {
-                let line = build_line(&line_length, &character_generator);
-                chars_written += line.length;
-                file.write_all(line.string.as_bytes()).unwrap();
+        let mut metadata = Metadata::new(filename, &line_length, &sample_length, &options);
+        let has_groups = options.has_groups();
+        let path = PathBuf::from(filename);
+        let job = Box::new(move || {
+            build_sample_with(
+                File::create(&path).unwrap(),
+                line_length,
+                sample_length,
+                character_generator,
+                options,
+            );
+            if has_groups {
+                metadata.groups = Some(count_groups(&[&path]));
             }
+            metadata.write(&path).unwrap();
+        });
+        self.queued.push((filename.to_string(), job));
+    }
 
-            // Write the last line.
-            if chars_written < limit {
-                let line = build_line(
-                    &LineLength::Fixed(limit - chars_written - 1),
-                    &character_generator,
-                );
-                file.write_all(line.string.as_bytes()).unwrap();
+    /// Waits for every sample being built, reporting each, and returns how many were built and how
+    /// many failed.
+    pub fn join(&mut self) -> (usize, usize) {
+        let (mut built, mut failed) = (0, 0);
+        while let Some(sample) = self.samples.pop_front() {
+            match sample.handle.join() {
+                // The thread logged the sample it built.
+                Ok(_) => built += 1,
+                // The thread panicked, and already printed why.
+                Err(_) => {
+                    tracing::error!(sample = %sample.filename, "failed to build sample");
+                    failed += 1;
+                }
             }
         }
+        (built, failed)
     }
 
-    // For safety.
-    file.flush().unwrap();
+    /// Starts building every queued sample, each in its own thread, unless any couldn't be built,
+    /// or two samples, listed or queued, have the same name, since they'd race to write the same
+    /// file.
+    pub fn build(&mut self) -> Result<(), String> {
+        if !self.errors.is_empty() {
+            return Err(self.errors.join("\n"));
+        }
+        let duplicates = duplicates(&self.names);
+        if !duplicates.is_empty() {
+            return Err(format!(
+                "More than one sample is named {}; give each its own name",
+                duplicates.join(", ")
+            ));
+        }
+        let slots = Arc::new(Slots::new(self.jobs.unwrap_or(self.queued.len())));
+        for (filename, job) in self.queued.drain(..) {
+            let span = tracing::info_span!("build", sample = %filename);
+            let slots = slots.clone();
+            let seed = self.seed.map(|seed| sample_seed(seed, &filename));
+            let handle = thread::spawn(move || {
+                let _slot = slots.take();
+                let _span = span.enter();
+                if let Some(seed) = seed {
+                    fastrand::seed(seed);
+                }
+                let start = Instant::now();
+                job();
+                let seconds = start.elapsed().as_secs_f64();
+                tracing::info!(seconds, "created sample");
+            });
+            self.samples.push_back(Sample { handle, filename });
+        }
+        Ok(())
+    }
 }
 
-/// Returned from `build_line`.
-pub struct Line {
-    /// A fully formed line (including newline).
-    pub string: String,
-
-    /// The length of the line in chars (including newline).
-    pub length: usize,
+/// The names that appear more than once, in the order they first do.
+fn duplicates(names: &[String]) -> Vec<&str> {
+    let mut duplicates = vec![];
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) && !duplicates.contains(&name.as_str()) {
+            duplicates.push(name.as_str());
+        }
+    }
+    duplicates
 }
 
-/// Builds a line based on the provided parameters.
-///
-/// # Panics
-///
-/// Panics if given a `LineLength::Range(r)` where `r` is empty, e.g. `0..0` or `6..6`.
-pub fn build_line(line_length: &LineLength, character_generator: &impl Fn() -> char) -> Line {
-    let mut string: String;
-    let length: usize;
-    match line_length {
-        LineLength::Fixed(n) => {
-            string = String::with_capacity(n + 1);
-            length = *n + 1;
-            for _ in 0..*n {
-                string.push(character_generator());
-            }
-        }
-        LineLength::Range(r) => {
-            assert_ne!(r.start, r.end);
+/// Limits how many samples are built at once.
+struct Slots {
+    free: Mutex<usize>,
+    freed: Condvar,
+}
 
-            // The length of the line, including newline.
-            length = fastrand::usize(r.clone()) + 1;
+/// A slot taken, which is freed when dropped, even by a thread that panics.
+struct Slot<'a>(&'a Slots);
 
-            string = String::with_capacity(length);
-            for _ in 0..(length - 1) {
-                string.push(character_generator());
-            }
+impl Slots {
+    fn new(count: usize) -> Slots {
+        Slots {
+            free: Mutex::new(count),
+            freed: Condvar::new(),
         }
     }
-    string.push('\n');
 
-    Line { string, length }
+    /// Waits for a free slot, and takes it.
+    fn take(&self) -> Slot<'_> {
+        let mut free = self
+            .freed
+            .wait_while(self.free.lock().unwrap(), |free| *free == 0)
+            .unwrap();
+        *free -= 1;
+        Slot(self)
+    }
 }
 
-#[cfg(test)]
-mod build_line_tests {
-    use super::*;
-
-    const CG_CHAR: char = 'c';
-
-    fn cg() -> char {
-        CG_CHAR
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        // A thread that panicked while holding the lock poisons it, but the count is still right.
+        *self.0.free.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        self.0.freed.notify_one();
     }
+}
 
-    #[test]
-    fn with_fixed_length_works() {
-        let line = build_line(&LineLength::Fixed(5), &cg);
-        assert_eq!(line.string, "ccccc\n");
-        assert_eq!(line.length, 6);
-        assert_eq!(line.string.len(), line.length); // Sanity check.
+/// Fills in a name template, described at `Args::name_template`, for a sample of the matrix.
+fn matrix_name(
+    template: &str,
+    charset: &str,
+    line_length: &LineLength,
+    sample_length: &SampleLength,
+    seed: Option<u64>,
+) -> Result<String, String> {
+    let (dist, min, max) = match line_length {
+        LineLength::Fixed(n) => ("fixed", *n, *n),
+        LineLength::Range(range) => ("ranged", range.start, range.end.saturating_sub(1)),
+    };
+    let size = match sample_length {
+        SampleLength::Lines(1) => "1line".to_string(),
+        SampleLength::Lines(n) => format!("{}lines", n),
+        SampleLength::Characters(n) => size(*n),
+    };
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!("unclosed {{ in name template {}", template));
+        };
+        let placeholder = &rest[start + 1..start + end];
+        match placeholder {
+            "dist" => name.push_str(dist),
+            "len" if min == max => name.push_str(&min.to_string()),
+            "len" => name.push_str(&format!("{}to{}", min, max)),
+            "minlen" => name.push_str(&min.to_string()),
+            "maxlen" => name.push_str(&max.to_string()),
+            "size" => name.push_str(&size),
+            "charset" => name.push_str(charset),
+            "seed" => match seed {
+                Some(seed) => name.push_str(&seed.to_string()),
+                None => name.push_str("noseed"),
+            },
+            _ => {
+                return Err(format!(
+                    "unknown placeholder {{{}}} in name template {}",
+                    placeholder, template
+                ))
+            }
+        }
+        rest = &rest[start + end + 1..];
     }
+    name.push_str(rest);
+    Ok(name)
+}
 
-    #[test]
-    fn with_fixed_length_0_works() {
-        let line = build_line(&LineLength::Fixed(0), &cg);
-        assert_eq!(line.string, "\n");
-        assert_eq!(line.length, 1);
-        assert_eq!(line.string.len(), line.length); // Sanity check.
+/// Formats a sample's size in decimal units, as the names have it: `3MB`, `1GB`, or `1500B`.
+fn size(characters: usize) -> String {
+    [(1_000_000_000, "GB"), (1_000_000, "MB"), (1_000, "kB")]
+        .into_iter()
+        .find(|(unit, _)| characters >= *unit && characters.is_multiple_of(*unit))
+        .map_or_else(
+            || format!("{}B", characters),
+            |(unit, name)| format!("{}{}", characters / unit, name),
+        )
+}
+
+/// Counts the groups in the given files, together, for the metadata.
+fn count_groups(paths: &[impl AsRef<Path>]) -> GroupStats {
+    let mut counter = GroupCounter::default();
+    for path in paths {
+        counter
+            .count(BufReader::new(File::open(path).unwrap()))
+            .unwrap();
     }
+    counter.stats()
+}
 
-    #[test]
-    fn with_range_length_works() {
-        // We can't actually definitively test this, since there's intentional randomness, so we'll
-        // generate multiple lines and verify that they're all in-range.
-        let range = 6..12;
-        let tries = 100;
-        for _ in 0..tries {
-            let line = build_line(&LineLength::Range(range.clone()), &cg);
-
-            // Verify that the number of CG_CHAR characters is within range.
-            let cg_char_count = line.string.matches(CG_CHAR).count();
-            assert!(range.start <= cg_char_count);
-            assert!(cg_char_count < range.end);
-
-            // Verify that length is correct.
-            assert_eq!(cg_char_count + 1, line.length);
-            assert_eq!(line.string.len(), line.length); // Sanity check.
-
-            // Verify that there's a newline at the end. If so, the string must consist of k
-            // repetitions of CG_CHAR followed by a single '\n' (for some k in range).
-            assert_eq!('\n', line.string.chars().last().unwrap());
+/// Whether `name` matches the glob `pattern`, in which `*` matches any run of characters, even an
+/// empty one, and `?` any single character.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*`, if what followed it stops matching: the pattern just
+    // past it, and the name one character further on than last time.
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after, matched)) => {
+                    p = after;
+                    n = matched + 1;
+                    star = Some((after, n));
+                }
+                None => return false,
+            },
         }
     }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod glob_matches_tests {
+    use super::*;
 
     #[test]
-    #[should_panic]
-    fn with_empty_range_length_panics() {
-        build_line(&LineLength::Range(10..10), &cg);
+    fn works() {
+        let name = "ranged-5to80char-300MB-alphanumeric.txt";
+        assert!(glob_matches("ranged-*-300MB-*", name));
+        assert!(glob_matches("*", name));
+        assert!(glob_matches(name, name));
+        assert!(glob_matches("ranged-5to?0char-*.txt", name));
+        assert!(!glob_matches("ranged-*-30MB-*", name));
+        assert!(!glob_matches("ranged", name));
+        assert!(!glob_matches("*.csv", name));
+        assert!(glob_matches("a*b*c", "abxbc"));
+        assert!(!glob_matches("?", ""));
+        assert!(glob_matches("**", ""));
     }
 }
 
-/// Testing for `build_sample` is much simpler than it might seem. We don't need to test all
-/// permutations of `line_length`, nor do we need to meaningfully test `character_generator`,
-/// because we don't actually use them in `build_sample`, we just pass them through to
-/// `build_line`, where they're used and tested. We really just need to test our `sample_length`
-/// processing with cursory checks that `build_line` is being called appropriately.
 #[cfg(test)]
-mod build_sample_tests {
+mod describe_tests {
     use super::*;
 
-    const CG_CHAR: char = 'c';
-
-    fn cg() -> char {
-        CG_CHAR
-    }
-
     #[test]
-    fn with_sample_length_lines_works() {
-        let mut sample = vec![];
-        build_sample(
-            &mut sample,
-            LineLength::Fixed(3),
-            SampleLength::Lines(3),
-            &cg,
+    fn works() {
+        let options = Options {
+            words: Some(1_000),
+            crlf_ratio: 0.5,
+            encoding: Encoding::Utf16Le,
+            ..Default::default()
+        };
+        assert_eq!(
+            "lines of 5 to 80 characters, 10M characters, 1000 pseudo-words, 50.000% CRLF, utf-16le",
+            describe(
+                &LineLength::Range(5..81),
+                Some(&SampleLength::Characters(10_000_000)),
+                &options
+            )
         );
-
-        let expected: Vec<u8> = "ccc\nccc\nccc\n".bytes().collect();
-        assert_eq!(expected, sample);
-    }
-
-    #[test]
-    fn with_sample_length_lines_0_works() {
-        let mut sample = vec![];
-        build_sample(
-            &mut sample,
-            LineLength::Fixed(3),
-            SampleLength::Lines(0),
-            &cg,
+        assert_eq!(
+            "lines of 1 character, 1500 lines",
+            describe(
+                &LineLength::Fixed(1),
+                Some(&SampleLength::Lines(1500)),
+                &Options::default()
+            )
         );
-
-        let expected: Vec<u8> = vec![];
-        assert_eq!(expected, sample);
     }
+}
 
-    #[test]
-    fn with_sample_length_lines_and_large_value_works() {
-        let line_length = 200;
-        let line_count = 1_000;
-        let mut sample = vec![];
-        build_sample(
-            &mut sample,
-            LineLength::Fixed(line_length),
-            SampleLength::Lines(line_count),
-            &cg,
-        );
-
-        let mut line: String = iter::repeat("c").take(line_length).collect();
-        line.push('\n');
-        let expected: String = iter::repeat(line).take(line_count).collect();
-        assert_eq!(expected.as_bytes(), sample);
-    }
+#[cfg(test)]
+mod matrix_name_tests {
+    use super::*;
 
     #[test]
-    fn with_sample_length_characters_works() {
-        let mut sample = vec![];
-        build_sample(
-            &mut sample,
-            LineLength::Fixed(3),
-            SampleLength::Characters(12),
-            &cg,
+    fn keeps_the_usual_names() {
+        let name = |line_length, characters| {
+            matrix_name(
+                DEFAULT_NAME_TEMPLATE,
+                "alphanumeric",
+                &line_length,
+                &SampleLength::Characters(characters),
+                None,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            "fixed-20char-30MB-alphanumeric.txt",
+            name(LineLength::Fixed(20), 30_000_000)
         );
-
-        let expected: Vec<u8> = "ccc\nccc\nccc\n".bytes().collect();
-        assert_eq!(expected, sample);
-    }
-
-    #[test]
-    fn with_sample_length_characters_0_works() {
-        let mut sample = vec![];
-        build_sample(
-            &mut sample,
-            LineLength::Fixed(3),
-            SampleLength::Characters(0),
-            &cg,
+        assert_eq!(
+            "ranged-5to80char-3MB-alphanumeric.txt",
+            name(LineLength::Range(5..81), 3_000_000)
         );
-
-        let expected: Vec<u8> = vec![];
-        assert_eq!(expected, sample);
     }
 
     #[test]
-    fn with_uneven_last_line_length_matches_size_precisely() {
-        let char_count = 31;
-        let line_length = 7;
-        let mut sample = vec![];
-        build_sample(
-            &mut sample,
-            LineLength::Fixed(line_length),
-            SampleLength::Characters(char_count),
-            &cg,
+    fn fills_every_placeholder() {
+        assert_eq!(
+            Ok("5-80-100lines-abc.txt".to_string()),
+            matrix_name(
+                "{minlen}-{maxlen}-{size}-{charset}.txt",
+                "abc",
+                &LineLength::Range(5..81),
+                &SampleLength::Lines(100),
+                None
+            )
         );
-        assert_eq!(char_count, sample.len());
+        let seeded = |seed| {
+            matrix_name(
+                "{len}-{seed}.txt",
+                "",
+                &LineLength::Fixed(1),
+                &SampleLength::Lines(1),
+                seed,
+            )
+        };
+        assert_eq!(Ok("1-42.txt".to_string()), seeded(Some(42)));
+        assert_eq!(Ok("1-noseed.txt".to_string()), seeded(None));
+        assert_eq!("1500B", size(1_500));
+        assert_eq!("2GB", size(2_000_000_000));
+        let error = |template| {
+            matrix_name(
+                template,
+                "",
+                &LineLength::Fixed(1),
+                &SampleLength::Lines(1),
+                None,
+            )
+            .unwrap_err()
+        };
+        assert_eq!(
+            "unknown placeholder {size2} in name template {len}-{size2}.txt",
+            error("{len}-{size2}.txt")
+        );
+        assert_eq!("unclosed { in name template {len", error("{len"));
     }
+}
+
+#[cfg(test)]
+mod duplicates_tests {
+    use super::*;
 
     #[test]
-    fn with_sample_length_characters_and_large_value_works() {
-        let line_length = 200;
-        let line_count = 1_000;
-        let jagged_last_line = "ccccc\n";
-        let char_count = (line_length + 1) * line_count + jagged_last_line.len();
-        let mut sample = vec![];
-        build_sample(
-            &mut sample,
-            LineLength::Fixed(line_length),
-            SampleLength::Characters(char_count),
-            &cg,
+    fn works() {
+        let names =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|n| n.to_string()).collect() };
+        assert!(duplicates(&names(&["a.txt", "b.txt"])).is_empty());
+        assert_eq!(
+            vec!["b.txt", "a.txt"],
+            duplicates(&names(&["a.txt", "b.txt", "b.txt", "a.txt", "b.txt"]))
         );
-
-        let mut line: String = iter::repeat("c").take(line_length).collect();
-        line.push('\n');
-        let mut expected: String = iter::repeat(line).take(line_count).collect();
-        expected.push_str(jagged_last_line);
-        assert_eq!(expected.as_bytes(), sample);
     }
 }
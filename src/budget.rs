@@ -0,0 +1,305 @@
+//! Checks a suite's samples against its disk budget before anything runs, so a matrix that has
+//! grown past what the machine holds fails up front, naming the samples to blame, rather than
+//! hours in with a full disk.
+//!
+//! Sample files count whole, once each however many cases read them. Files that aren't there yet
+//! count at the size their corpus entry gives, or that their name does, as `mksample` names its
+//! samples (`...-300MB-...`). With a staging directory, the largest files count again, one copy per
+//! case running at once. Ephemeral generated samples count only while they're on disk: one per case
+//! running at once, so the largest few. Generated samples streamed into the command take no disk
+//! at all. Samples whose size can't be told are listed, rather than counted as nothing.
+
+use crate::config::{Charset, GeneratedSample, Suite};
+use crate::results::Unit;
+use groupby_benchmarking::sample::Encoding;
+use std::collections::BTreeSet;
+use std::fs;
+
+const GIB: f64 = (1u64 << 30) as f64;
+
+/// How many of the largest samples a failure lists.
+const OFFENDERS: usize = 5;
+
+/// A sample that takes disk space.
+#[derive(Debug, PartialEq)]
+struct Usage {
+    sample: String,
+    bytes: u64,
+    kind: Kind,
+}
+
+/// Why a sample takes disk space, and how we know how much.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    /// A file that's there.
+    File,
+
+    /// A file that isn't there yet, at the size it will be.
+    Missing,
+
+    /// A file's copy in the staging directory.
+    Staged,
+
+    /// A generated sample, written before each case and deleted after.
+    Ephemeral,
+}
+
+impl Kind {
+    fn note(&self) -> &'static str {
+        match self {
+            Kind::File => "",
+            Kind::Missing => " (not there yet)",
+            Kind::Staged => " (staged copy)",
+            Kind::Ephemeral => " (ephemeral)",
+        }
+    }
+}
+
+/// Checks that the samples of `suite`, run with up to `parallel` cases at once, fit in its disk
+/// budget, if it has one. Returns the samples whose size couldn't be told, and so weren't counted.
+pub fn check(suite: &Suite, parallel: usize) -> Result<Vec<String>, String> {
+    let Some(budget_gib) = suite.disk_budget_gib else {
+        return Ok(vec![]);
+    };
+    let budget = (budget_gib * GIB) as u64;
+    let (usages, unknown) = usages(suite, parallel);
+    let total: u64 = usages.iter().map(|usage| usage.bytes).sum();
+    if total <= budget {
+        return Ok(unknown);
+    }
+
+    let bytes = |n: u64| Unit::Bytes.format(n as f64);
+    let mut message = format!(
+        "the samples need {} of disk, over the budget of {}; the largest are:",
+        bytes(total),
+        bytes(budget)
+    );
+    let largest = &usages[..usages.len().min(OFFENDERS)];
+    for usage in largest {
+        message += &format!(
+            "\n  {}: {}{}",
+            usage.sample,
+            bytes(usage.bytes),
+            usage.kind.note()
+        );
+    }
+    if !unknown.is_empty() {
+        message += &format!(
+            "\nThat's without {}, whose size can't be told yet.",
+            unknown.join(", ")
+        );
+    }
+    if largest
+        .iter()
+        .any(|usage| matches!(usage.kind, Kind::File | Kind::Missing))
+    {
+        message += "\nDescribe the largest files as [generated] samples instead, which are \
+                    streamed into the command, or written just before each case with \
+                    ephemeral = true if it must read a file.";
+    }
+    if largest
+        .iter()
+        .any(|usage| matches!(usage.kind, Kind::Staged | Kind::Ephemeral))
+        && parallel > 1
+    {
+        message += "\nRun fewer cases at once with a lower --parallel, so fewer ephemeral samples \
+                    and staged copies are on disk together.";
+    }
+    Err(message)
+}
+
+/// The disk each sample takes, largest first, and the samples whose size can't be told.
+fn usages(suite: &Suite, parallel: usize) -> (Vec<Usage>, Vec<String>) {
+    let samples: BTreeSet<&String> = suite.cases.iter().flat_map(|case| &case.samples).collect();
+    let mut files = vec![];
+    let mut ephemeral = vec![];
+    let mut unknown = vec![];
+    for sample in samples {
+        let usage = |bytes, kind| Usage {
+            sample: sample.clone(),
+            bytes,
+            kind,
+        };
+        match suite.generated.get(sample) {
+            Some(generated) if generated.ephemeral => match estimated_bytes(generated) {
+                Some(bytes) => ephemeral.push(usage(bytes, Kind::Ephemeral)),
+                None => unknown.push(sample.clone()),
+            },
+            Some(_) => {}
+            None => {
+                let bytes = match fs::metadata(suite.sample_path(sample)) {
+                    Ok(metadata) => Some(usage(metadata.len(), Kind::File)),
+                    Err(_) => suite
+                        .corpus
+                        .get(sample)
+                        .and_then(|file| file.bytes)
+                        .or_else(|| bytes_from_name(sample))
+                        .map(|bytes| usage(bytes, Kind::Missing)),
+                };
+                match bytes {
+                    Some(usage) => files.push(usage),
+                    None => unknown.push(sample.clone()),
+                }
+            }
+        }
+    }
+    files.sort_by_key(|usage| std::cmp::Reverse(usage.bytes));
+    if suite.stage_dir.is_some() {
+        let staged: Vec<Usage> = files
+            .iter()
+            .take(parallel)
+            .map(|usage| Usage {
+                sample: usage.sample.clone(),
+                bytes: usage.bytes,
+                kind: Kind::Staged,
+            })
+            .collect();
+        files.extend(staged);
+    }
+    ephemeral.sort_by_key(|usage| std::cmp::Reverse(usage.bytes));
+    ephemeral.truncate(parallel);
+    files.extend(ephemeral);
+    files.sort_by_key(|usage| std::cmp::Reverse(usage.bytes));
+    (files, unknown)
+}
+
+/// A generated sample's size in bytes: exactly if it's known, or else roughly, from its number of
+/// lines and their average length. Templated lines have no length to go by.
+fn estimated_bytes(generated: &GeneratedSample) -> Option<u64> {
+    if let Some(bytes) = generated.bytes() {
+        return Some(bytes);
+    }
+    let characters = match (generated.characters, generated.lines) {
+        (Some(characters), _) => characters as u64,
+        (None, Some(lines)) => {
+            let line = match (&generated.columns, generated.line_length) {
+                (Some(columns), _) => columns.iter().sum::<usize>(),
+                (None, Some([min, max])) => (min + max) / 2,
+                (None, None) => return None,
+            };
+            lines as u64 * (line as u64 + 1)
+        }
+        (None, None) => return None,
+    };
+    // Accented letters take two bytes in UTF-8, and we may as well assume they all are.
+    let per_character = match (generated.encoding, generated.charset) {
+        (Encoding::Utf8, Charset::Accented) => 2,
+        (encoding, _) => encoding.unit_size() as u64,
+    };
+    Some(characters * per_character)
+}
+
+/// The size in bytes a sample's name gives, as `mksample` puts it in names such as
+/// `ranged-5to80char-300MB-alphanumeric.txt`.
+fn bytes_from_name(name: &str) -> Option<u64> {
+    name.split(['-', '.', '_']).find_map(|part| {
+        let digits = part.len() - part.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let (number, unit) = part.split_at(digits);
+        let unit = match unit {
+            "GB" => 1_000_000_000,
+            "MB" => 1_000_000,
+            "kB" => 1_000,
+            _ => return None,
+        };
+        number.parse::<u64>().ok().map(|n| n * unit)
+    })
+}
+
+#[cfg(test)]
+mod check_tests {
+    use super::*;
+
+    #[test]
+    fn names_the_largest_samples() {
+        let dir = std::env::temp_dir().join(format!("budget-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("small.txt"), vec![b'a'; 1024]).unwrap();
+        fs::write(dir.join("big.txt"), vec![b'a'; 4096]).unwrap();
+        let mut suite: Suite = toml::from_str(&format!(
+            r#"
+            sample_dir = "{}"
+            disk_budget_gib = 1.0
+
+            [generated.streamed]
+            line_length = [5, 80]
+            characters = 1000000
+
+            [generated.eph-a]
+            line_length = [5, 80]
+            characters = 2048
+            ephemeral = true
+
+            [generated.eph-b]
+            line_length = [5, 80]
+            characters = 3072
+            ephemeral = true
+
+            [[case]]
+            name = "cat"
+            command = ["cat"]
+            samples = ["small.txt", "big.txt", "streamed", "eph-a", "eph-b", "missing.txt"]
+
+            [[case]]
+            name = "wc"
+            command = ["wc"]
+            samples = ["big.txt"]
+            "#,
+            dir.display()
+        ))
+        .unwrap();
+
+        assert_eq!(Ok(vec!["missing.txt".to_string()]), check(&suite, 1));
+        let order = |suite: &Suite, parallel| -> Vec<(String, u64)> {
+            usages(suite, parallel)
+                .0
+                .into_iter()
+                .map(|usage| (usage.sample, usage.bytes))
+                .collect()
+        };
+        assert_eq!(
+            vec![
+                ("big.txt".to_string(), 4096),
+                ("eph-b".to_string(), 3072),
+                ("small.txt".to_string(), 1024),
+            ],
+            order(&suite, 1)
+        );
+        assert_eq!(4, order(&suite, 2).len());
+
+        suite.disk_budget_gib = Some(8000.0 / GIB);
+        let message = check(&suite, 1).unwrap_err();
+        assert!(message.starts_with("the samples need 8.0 KiB of disk, over the budget of 7.8 KiB"));
+        assert!(message.contains("\n  big.txt: 4.0 KiB\n  eph-b: 3.0 KiB (ephemeral)\n"));
+        assert!(message.contains("without missing.txt"));
+        assert!(message.contains("[generated]"));
+        assert!(!message.contains("--parallel"));
+
+        suite.stage_dir = Some(dir.clone());
+        assert_eq!(
+            vec![
+                ("big.txt".to_string(), 4096),
+                ("big.txt".to_string(), 4096),
+                ("eph-b".to_string(), 3072),
+                ("small.txt".to_string(), 1024),
+            ],
+            order(&suite, 1)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn estimates_samples_not_built_yet() {
+        assert_eq!(
+            Some(300_000_000),
+            bytes_from_name("ranged-5to80char-300MB-alphanumeric.txt")
+        );
+        assert_eq!(Some(2_000), bytes_from_name("a_2kB.txt"));
+        assert_eq!(None, bytes_from_name("shakespeare.txt"));
+        let generated = GeneratedSample {
+            line_length: Some([10, 30]),
+            lines: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(Some(21_000), estimated_bytes(&generated));
+    }
+}
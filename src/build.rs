@@ -0,0 +1,82 @@
+//! Builds the benchmarked program from git refs, so a suite can be run against historical
+//! versions in one go, e.g. to find when a regression crept in.
+//!
+//! Each commit is checked out into its own git worktree under the build directory and built there
+//! with `cargo build --release`. Builds are kept, keyed by commit, so each commit is only built
+//! once. Deleting the build directory throws them away; `git worktree prune` then tidies up the
+//! repository's record of them.
+
+use crate::git;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The file we leave in a worktree once its build has succeeded.
+const BUILT_MARKER: &str = ".bench-built";
+
+/// A release build of a commit.
+#[derive(Debug)]
+pub struct Build {
+    /// The ref the build was asked for, e.g. `v0.3.0`.
+    pub reference: String,
+
+    /// The full hash of the commit the ref resolved to.
+    pub commit: String,
+
+    /// The directory the build's binaries are in.
+    pub release_dir: PathBuf,
+}
+
+/// Checks out `reference` from `repo` into `build_dir` and builds it, unless that commit has been
+/// built before.
+pub fn build(repo: &Path, reference: &str, build_dir: &Path) -> Result<Build, Box<dyn Error>> {
+    let commit = git::resolve(repo, reference)
+        .ok_or_else(|| format!("{}: no commit called {}", repo.display(), reference))?;
+    // git resolves relative worktree paths against the repository, not our working directory.
+    let build_dir = env::current_dir()?.join(build_dir);
+    let worktree = build_dir.join(&commit);
+    let release_dir = worktree.join("target").join("release");
+    let build = Build {
+        reference: reference.to_string(),
+        commit,
+        release_dir,
+    };
+    if worktree.join(BUILT_MARKER).exists() {
+        println!(
+            "Using the existing build of {} ({})",
+            reference,
+            git::short(&build.commit)
+        );
+        return Ok(build);
+    }
+
+    if !worktree.exists() {
+        fs::create_dir_all(&build_dir).map_err(|e| format!("{}: {}", build_dir.display(), e))?;
+        run(Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["worktree", "add", "--detach"])
+            .arg(&worktree)
+            .arg(&build.commit))
+        .map_err(|e| format!("checking out {}: {}", reference, e))?;
+    }
+    println!("Building {} ({})", reference, git::short(&build.commit));
+    run(Command::new("cargo")
+        .args(["build", "--release"])
+        .current_dir(&worktree))
+    .map_err(|e| format!("building {}: {}", reference, e))?;
+    fs::write(worktree.join(BUILT_MARKER), "")?;
+    Ok(build)
+}
+
+/// Runs a command, letting it print to our terminal, and fails if it does.
+fn run(command: &mut Command) -> Result<(), Box<dyn Error>> {
+    let status = command.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{:?} failed ({})", command.get_program(), status).into())
+    }
+}
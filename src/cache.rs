@@ -0,0 +1,117 @@
+//! Controls whether the sample is in the page cache when an iteration starts.
+//!
+//! By default we leave the cache alone, so the first iteration may read the sample from disk and
+//! the rest from memory. To measure cold reads on purpose, we can evict the sample from the cache
+//! before every iteration; to make sure every iteration reads from memory, including the first,
+//! we can read the whole sample once before any of them.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// What to do with the page cache before each iteration.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheMode {
+    /// Leave the cache alone.
+    #[default]
+    AsIs,
+
+    /// Read the whole sample before the first iteration, so every iteration finds it in the cache.
+    Warm,
+
+    /// Evict the sample from the cache, so every iteration reads it from disk.
+    Cold,
+
+    /// Drop the entire page cache, as `echo 1 > /proc/sys/vm/drop_caches` does. This also evicts
+    /// anything else the command reads, like its own executable, but needs root.
+    DropCaches,
+}
+
+impl CacheMode {
+    /// The mode's name in config files, e.g. `cold`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CacheMode::AsIs => "as-is",
+            CacheMode::Warm => "warm",
+            CacheMode::Cold => "cold",
+            CacheMode::DropCaches => "drop-caches",
+        }
+    }
+
+    /// Prepares the cache for a benchmark that reads `sample`, before any of its iterations.
+    pub fn before_benchmark(&self, sample: &Path) -> io::Result<()> {
+        match self {
+            CacheMode::Warm => read_through(sample),
+            _ => Ok(()),
+        }
+    }
+
+    /// Prepares the cache for an iteration that reads `sample`.
+    pub fn before_iteration(&self, sample: &Path) -> io::Result<()> {
+        match self {
+            CacheMode::AsIs | CacheMode::Warm => Ok(()),
+            CacheMode::Cold => evict(sample),
+            CacheMode::DropCaches => drop_caches(),
+        }
+    }
+}
+
+/// Reads all of `path` and throws it away, leaving it in the page cache.
+fn read_through(path: &Path) -> io::Result<()> {
+    io::copy(&mut File::open(path)?, &mut io::sink())?;
+    Ok(())
+}
+
+/// Asks the kernel to drop `path`'s pages from the page cache. Only clean pages can be dropped,
+/// which is all of them for a sample nobody is writing to.
+#[cfg(target_os = "linux")]
+fn evict(path: &Path) -> io::Result<()> {
+    let file = File::open(path)?;
+    // SAFETY: the descriptor is open for the duration of the call.
+    let result = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    match result {
+        0 => Ok(()),
+        error => Err(io::Error::from_raw_os_error(error)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn evict(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "evicting files from the page cache is only supported on Linux",
+    ))
+}
+
+/// Writes out dirty pages, which can't be dropped, then drops the whole page cache.
+fn drop_caches() -> io::Result<()> {
+    // SAFETY: sync takes no arguments and can't fail.
+    unsafe { libc::sync() };
+    fs::write("/proc/sys/vm/drop_caches", "1")
+        .map_err(|e| io::Error::new(e.kind(), format!("/proc/sys/vm/drop_caches: {}", e)))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod cache_mode_tests {
+    use super::*;
+
+    #[test]
+    fn modes_prepare_file() {
+        let path = std::env::temp_dir().join(format!("cache-{}.txt", std::process::id()));
+        fs::write(&path, "a\nb\n").unwrap();
+        CacheMode::Warm.before_benchmark(&path).unwrap();
+        CacheMode::Cold.before_iteration(&path).unwrap();
+        CacheMode::AsIs.before_iteration(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cold_with_missing_file_fails() {
+        assert!(CacheMode::Cold
+            .before_iteration(Path::new("/nonexistent"))
+            .is_err());
+    }
+}
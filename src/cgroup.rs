@@ -0,0 +1,172 @@
+//! Runs benchmarked commands inside a transient cgroup with a memory limit, so that how a command
+//! behaves with a given amount of memory can be measured on purpose rather than depending on how
+//! much the machine happens to have free.
+//!
+//! This needs cgroup v2 (the unified hierarchy, mounted at `/sys/fs/cgroup`) and write access to
+//! the harness's own cgroup, as root or under a delegated systemd scope such as
+//! `systemd-run --user --scope -p Delegate=yes`.
+//!
+//! cgroup v2 won't enable a controller for a cgroup's children while the cgroup has processes of
+//! its own, so the first time we need one, we move the harness, and anything else in its cgroup,
+//! into a leaf named `harness`, and put the cases' cgroups beside it.
+
+use std::error::Error;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Where the cgroup v2 hierarchy is mounted.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// The cgroup the harness started in, with the memory controller enabled for its children, or why
+/// it couldn't be.
+static PARENT: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+
+/// A cgroup created for one case, removed again when dropped.
+#[derive(Debug)]
+pub struct Cgroup {
+    dir: PathBuf,
+
+    /// The path of the cgroup's `cgroup.procs`, ready for use in a freshly forked child.
+    procs: CString,
+}
+
+impl Cgroup {
+    /// Creates a cgroup inside the one the harness started in, limited to `memory_max` bytes of
+    /// memory and no swap. `name` tells it apart from the harness's other cgroups.
+    pub fn create(name: &str, memory_max: u64) -> Result<Cgroup, Box<dyn Error>> {
+        let parent = PARENT
+            .get_or_init(|| delegate().map_err(|e| e.to_string()))
+            .clone()?;
+        let dir = parent.join(format!("bench-{}-{}", std::process::id(), name));
+        fs::create_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let cgroup = Cgroup {
+            procs: CString::new(dir.join("cgroup.procs").as_os_str().as_bytes())?,
+            dir,
+        };
+        let memory_max_path = cgroup.dir.join("memory.max");
+        if !memory_max_path.exists() {
+            return Err(format!(
+                "{}: the memory controller isn't enabled for this cgroup",
+                cgroup.dir.display()
+            )
+            .into());
+        }
+        fs::write(&memory_max_path, memory_max.to_string())
+            .map_err(|e| format!("{}: {}", memory_max_path.display(), e))?;
+        // Without swap the limit is a real one, rather than a point past which pages go to disk.
+        let swap_max_path = cgroup.dir.join("memory.swap.max");
+        if swap_max_path.exists() {
+            fs::write(&swap_max_path, "0")
+                .map_err(|e| format!("{}: {}", swap_max_path.display(), e))?;
+        }
+        Ok(cgroup)
+    }
+
+    /// Moves the calling process into the cgroup. This only makes system calls, without
+    /// allocating, so it's safe to use between `fork` and `exec`.
+    pub fn join(&self) -> io::Result<()> {
+        // SAFETY: procs is a valid, NUL-terminated path.
+        let fd = unsafe { libc::open(self.procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Writing 0 moves the writer itself.
+        // SAFETY: fd is open, and the buffer is valid for the length given.
+        let written = unsafe { libc::write(fd, b"0".as_ptr().cast(), 1) };
+        let error = io::Error::last_os_error();
+        // SAFETY: fd is open and not used again.
+        unsafe { libc::close(fd) };
+        if written == 1 {
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        // This fails if a process is somehow still inside, in which case the cgroup is left
+        // behind; there's nothing better to do about it.
+        let _ = fs::remove_dir(&self.dir);
+    }
+}
+
+/// Enables the memory controller for the children of the harness's cgroup, first moving every
+/// process in it into a leaf so that it's allowed to, and returns the cgroup.
+fn delegate() -> Result<PathBuf, Box<dyn Error>> {
+    let text = fs::read_to_string("/proc/self/cgroup")?;
+    let own = own_cgroup(&text)
+        .filter(|_| Path::new(CGROUP_ROOT).join("cgroup.controllers").exists())
+        .ok_or("memory limits need cgroup v2")?;
+    let parent = Path::new(CGROUP_ROOT).join(own.trim_start_matches('/'));
+    let subtree_control = parent.join("cgroup.subtree_control");
+    let enabled = fs::read_to_string(&subtree_control)
+        .map_err(|e| format!("{}: {}", subtree_control.display(), e))?;
+    if enabled
+        .split_whitespace()
+        .any(|controller| controller == "memory")
+    {
+        return Ok(parent);
+    }
+
+    // The root cgroup is exempt from the rule against processes in cgroups with controllers
+    // enabled for their children.
+    if own != "/" {
+        let leaf = parent.join("harness");
+        match fs::create_dir(&leaf) {
+            Err(e) if e.kind() != io::ErrorKind::AlreadyExists => {
+                return Err(format!("{}: {}", leaf.display(), e).into());
+            }
+            _ => {}
+        }
+        let procs = parent.join("cgroup.procs");
+        let pids = fs::read_to_string(&procs).map_err(|e| format!("{}: {}", procs.display(), e))?;
+        let leaf_procs = leaf.join("cgroup.procs");
+        for pid in pids.lines() {
+            match fs::write(&leaf_procs, pid) {
+                // A process that exited in the meantime needn't move.
+                Err(e) if e.raw_os_error() != Some(libc::ESRCH) => {
+                    return Err(format!("{}: moving {}: {}", leaf_procs.display(), pid, e).into());
+                }
+                _ => {}
+            }
+        }
+    }
+    fs::write(&subtree_control, "+memory").map_err(|e| {
+        format!(
+            "{}: enabling the memory controller: {}",
+            subtree_control.display(),
+            e
+        )
+    })?;
+    Ok(parent)
+}
+
+/// Finds the harness's cgroup v2 path in the contents of `/proc/self/cgroup`, e.g. `/user.slice`.
+fn own_cgroup(text: &str) -> Option<&str> {
+    text.lines().find_map(|line| line.strip_prefix("0::"))
+}
+
+#[cfg(test)]
+mod own_cgroup_tests {
+    use super::*;
+
+    #[test]
+    fn finds_unified_hierarchy() {
+        let text = "1:name=systemd:/\n0::/user.slice/user-1000.slice/session-2.scope\n";
+        assert_eq!(
+            Some("/user.slice/user-1000.slice/session-2.scope"),
+            own_cgroup(text)
+        );
+    }
+
+    #[test]
+    fn without_unified_hierarchy_is_none() {
+        assert_eq!(None, own_cgroup("4:memory:/\n1:cpu:/\n"));
+    }
+}
@@ -0,0 +1,660 @@
+//! Renders charts of results as SVG files, which reports can then reference.
+
+use crate::results::{BenchmarkResults, Metric, SuiteResults, Unit};
+use crate::timestamp;
+use crate::trend::{Point, Trend};
+use plotters::prelude::*;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// The size of every chart, in pixels.
+const SIZE: (u32, u32) = (800, 500);
+
+/// A chart that has been rendered to a file.
+#[derive(Debug)]
+pub struct Chart {
+    pub title: String,
+    pub path: PathBuf,
+}
+
+impl Chart {
+    /// The chart, with its path as seen from `dir`, for a report written there to link to it.
+    pub fn seen_from(&self, dir: &Path) -> io::Result<Chart> {
+        let path = std::path::absolute(&self.path)?;
+        // A report in the current directory has an empty parent, which can't be made absolute.
+        let dir = if dir.as_os_str().is_empty() {
+            std::path::absolute(".")?
+        } else {
+            std::path::absolute(dir)?
+        };
+        let common = path
+            .components()
+            .zip(dir.components())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let mut relative: PathBuf = dir
+            .components()
+            .skip(common)
+            .map(|_| Component::ParentDir)
+            .collect();
+        relative.extend(path.components().skip(common));
+        Ok(Chart {
+            title: self.title.clone(),
+            path: relative,
+        })
+    }
+}
+
+/// A named series of (x, y) points, drawn as one line.
+type Series = (String, Vec<(f64, f64)>);
+
+/// Picks the value to chart out of a benchmark's results, if it has one.
+type Value = fn(&BenchmarkResults) -> Option<f64>;
+
+/// An axis's description and how to format the values along it.
+type Axis<'a> = (&'a str, &'a dyn Fn(f64) -> String);
+
+/// Renders every chart we know how to draw for `results` into `dir`, returning the charts that
+/// had data to draw.
+pub fn render_all(results: &SuiteResults, dir: &Path) -> Result<Vec<Chart>, Box<dyn Error>> {
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut charts = vec![];
+
+    // Throughput charts complement the runtime chart: a case that scales linearly with its input
+    // draws a flat line, which is much easier to judge than the slope of a straight one.
+    let kinds: [(&str, &str, &str, Unit, Value); 3] = [
+        (
+            "wall_time-vs-size.svg",
+            "Mean wall time vs. input size",
+            "Mean wall time",
+            Unit::Seconds,
+            |b| Some(b.summary.get(&Metric::WallTime)?.mean),
+        ),
+        (
+            "bytes_per_second-vs-size.svg",
+            "Throughput in bytes vs. input size",
+            "Throughput",
+            Unit::BytesPerSecond,
+            BenchmarkResults::bytes_per_second,
+        ),
+        (
+            "lines_per_second-vs-size.svg",
+            "Throughput in lines vs. input size",
+            "Throughput",
+            Unit::LinesPerSecond,
+            BenchmarkResults::lines_per_second,
+        ),
+    ];
+
+    for (filename, title, y_label, y_unit, value) in kinds {
+        let series = by_case(results, value);
+        if series.is_empty() {
+            continue;
+        }
+        let chart = Chart {
+            title: title.to_string(),
+            path: dir.join(filename),
+        };
+        line_chart(
+            &chart,
+            &series,
+            ("Input size", &|x| Unit::Bytes.format(x)),
+            (y_label, &|y| y_unit.format(y)),
+        )?;
+        charts.push(chart);
+    }
+
+    let (mut speedup, efficiency) = scaling(results);
+    if !speedup.is_empty() {
+        // Perfect scaling, for reference.
+        let max_threads = speedup
+            .iter()
+            .flat_map(|(_, points)| points.iter().map(|p| p.0))
+            .fold(1.0, f64::max);
+        speedup.push((
+            "ideal".to_string(),
+            vec![(1.0, 1.0), (max_threads, max_threads)],
+        ));
+
+        let threads = |x: f64| format!("{:.0}", x);
+        for (filename, title, y_label, series, format) in [
+            (
+                "speedup-vs-threads.svg",
+                "Speedup vs. thread count",
+                "Speedup over 1 thread",
+                &speedup,
+                &(|y: f64| format!("{:.1}×", y)) as &dyn Fn(f64) -> String,
+            ),
+            (
+                "efficiency-vs-threads.svg",
+                "Parallel efficiency vs. thread count",
+                "Efficiency",
+                &efficiency,
+                &|y: f64| format!("{:.0}%", y * 100.0),
+            ),
+        ] {
+            let chart = Chart {
+                title: title.to_string(),
+                path: dir.join(filename),
+            };
+            line_chart(&chart, series, ("Threads", &threads), (y_label, format))?;
+            charts.push(chart);
+        }
+    }
+
+    // Box plots of the raw wall times show the spread, and any clusters, that a mean hides. Cases
+    // are only comparable on the same input, so there's one chart per sample.
+    for sample in results.samples() {
+        let boxes: Vec<(String, Vec<f64>)> = results
+            .benchmarks
+            .iter()
+            .filter(|b| b.sample == sample)
+            .map(|b| (b.case_label(), b.values(Metric::WallTime)))
+            .filter(|(_, values)| !values.is_empty())
+            .collect();
+        if boxes.is_empty() {
+            continue;
+        }
+        let chart = Chart {
+            title: format!("Wall time distribution on {}", sample),
+            path: dir.join(format!("wall_time-distribution-{}.svg", file_name(sample))),
+        };
+        box_plot(&chart, &boxes, ("Wall time", &|y| Unit::Seconds.format(y)))?;
+        charts.push(chart);
+    }
+
+    Ok(charts)
+}
+
+/// Makes a sample name safe to use in a file name.
+pub fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Collects the speedup and parallel efficiency of every swept case against its thread count, one
+/// series per case and sample. Speedup is the mean wall time at 1 thread over the mean wall time
+/// at n threads, and efficiency is the speedup over n. Benchmarks with no 1-thread counterpart to
+/// compare with are left out.
+fn scaling(results: &SuiteResults) -> (Vec<Series>, Vec<Series>) {
+    let wall_time = |b: &BenchmarkResults| Some(b.summary.get(&Metric::WallTime)?.mean);
+    let mut speedup: Vec<Series> = vec![];
+    let mut efficiency: Vec<Series> = vec![];
+    for benchmark in &results.benchmarks {
+        let threads = match benchmark.threads {
+            Some(threads) => threads as f64,
+            None => continue,
+        };
+        let base = results.benchmarks.iter().find(|b| {
+            b.case == benchmark.case
+                && b.variant == benchmark.variant
+                && b.sample == benchmark.sample
+                && b.threads == Some(1)
+        });
+        let (base, mean) = match (base.and_then(wall_time), wall_time(benchmark)) {
+            (Some(base), Some(mean)) if mean > 0.0 => (base, mean),
+            _ => continue,
+        };
+
+        let name = format!("{}/{}", benchmark.case_and_variant(), benchmark.sample);
+        let ratio = base / mean;
+        for (series, point) in [
+            (&mut speedup, (threads, ratio)),
+            (&mut efficiency, (threads, ratio / threads)),
+        ] {
+            match series.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, points)) => points.push(point),
+                None => series.push((name.clone(), vec![point])),
+            }
+        }
+    }
+    for (_, points) in speedup.iter_mut().chain(&mut efficiency) {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+    (speedup, efficiency)
+}
+
+/// Collects one series per case of `value` against sample size. Benchmarks without a known sample
+/// size or without a value are left out, as are cases left with no points.
+fn by_case(results: &SuiteResults, value: Value) -> Vec<Series> {
+    let mut series: Vec<Series> = vec![];
+    for benchmark in &results.benchmarks {
+        let (bytes, value) = match (benchmark.sample_bytes, value(benchmark)) {
+            (Some(bytes), Some(value)) => (bytes, value),
+            _ => continue,
+        };
+        let point = (bytes as f64, value);
+        let case = benchmark.case_label();
+        match series.iter_mut().find(|(name, _)| *name == case) {
+            Some((_, points)) => points.push(point),
+            None => series.push((case, vec![point])),
+        }
+    }
+    for (_, points) in &mut series {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+    series
+}
+
+/// Draws `series` as a line chart, with both axes starting at zero so that linear scaling looks
+/// like a straight line through the origin.
+fn line_chart(
+    chart: &Chart,
+    series: &[Series],
+    (x_label, x_format): Axis,
+    (y_label, y_format): Axis,
+) -> Result<(), Box<dyn Error>> {
+    let max = |axis: fn(&(f64, f64)) -> f64| {
+        series
+            .iter()
+            .flat_map(|(_, points)| points.iter().map(axis))
+            .fold(0.0, f64::max)
+    };
+    // Leave a little headroom, and avoid an empty range if every value is zero.
+    let x_max = (max(|p| p.0) * 1.05).max(f64::MIN_POSITIVE);
+    let y_max = (max(|p| p.1) * 1.1).max(f64::MIN_POSITIVE);
+
+    let root = SVGBackend::new(&chart.path, SIZE).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut context = ChartBuilder::on(&root)
+        .caption(&chart.title, ("sans-serif", 22))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(80)
+        .build_cartesian_2d(0.0..x_max, 0.0..y_max)?;
+    context
+        .configure_mesh()
+        .x_desc(x_label)
+        .y_desc(y_label)
+        .x_label_formatter(&|x| x_format(*x))
+        .y_label_formatter(&|y| y_format(*y))
+        .draw()?;
+
+    for (i, (name, points)) in series.iter().enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+        context
+            .draw_series(LineSeries::new(
+                points.iter().copied(),
+                color.stroke_width(2),
+            ))?
+            .label(name)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        context.draw_series(
+            points
+                .iter()
+                .map(|&point| Circle::new(point, 4, color.filled())),
+        )?;
+    }
+
+    context
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
+
+/// Draws a box plot for each named set of values, with every value also drawn as a point over its
+/// box. The y axis starts at zero.
+fn box_plot(
+    chart: &Chart,
+    boxes: &[(String, Vec<f64>)],
+    (y_label, y_format): Axis,
+) -> Result<(), Box<dyn Error>> {
+    let y_max = boxes
+        .iter()
+        .flat_map(|(_, values)| values.iter().copied())
+        .fold(0.0, f64::max);
+    let y_max = ((y_max * 1.1) as f32).max(f32::MIN_POSITIVE);
+
+    let root = SVGBackend::new(&chart.path, SIZE).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut context = ChartBuilder::on(&root)
+        .caption(&chart.title, ("sans-serif", 22))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(80)
+        .build_cartesian_2d((0..boxes.len() - 1).into_segmented(), 0f32..y_max)?;
+    context
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(boxes.len())
+        .x_label_formatter(&|x| match x {
+            SegmentValue::CenterOf(i) => boxes[*i].0.clone(),
+            _ => String::new(),
+        })
+        .y_desc(y_label)
+        .y_label_formatter(&|y| y_format(*y as f64))
+        .draw()?;
+
+    for (i, (_, values)) in boxes.iter().enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+        context.draw_series([Boxplot::new_vertical(
+            SegmentValue::CenterOf(i),
+            &Quartiles::new(values),
+        )
+        .width(30)
+        .style(color.stroke_width(2))])?;
+        context.draw_series(values.iter().map(|&value| {
+            Circle::new(
+                (SegmentValue::CenterOf(i), value as f32),
+                2,
+                color.mix(0.5).filled(),
+            )
+        }))?;
+    }
+
+    root.present()?;
+    Ok(())
+}
+
+/// Renders the trend of `metric` for one benchmark into `dir`: its mean over time, with the
+/// standard deviation as error bars, and each significant regression marked in red and labeled
+/// with its change and commit.
+pub fn render_trend(trend: &Trend, metric: Metric, dir: &Path) -> Result<Chart, Box<dyn Error>> {
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut title = format!("{} on {} over time", trend.case, trend.sample);
+    if let Some(change) = trend.overall_change() {
+        title += &format!(" ({:+.1}% overall)", change * 100.0);
+    }
+    let chart = Chart {
+        title,
+        path: dir.join(format!(
+            "trend-{}-{}-{}.svg",
+            metric.name(),
+            file_name(&trend.case),
+            file_name(&trend.sample)
+        )),
+    };
+    trend_chart(&chart, trend, metric)?;
+    Ok(chart)
+}
+
+/// Draws `trend` as a line chart against time, which starts a little before the first point.
+fn trend_chart(chart: &Chart, trend: &Trend, metric: Metric) -> Result<(), Box<dyn Error>> {
+    let points = &trend.points;
+    let first = points.first().map_or(0, |p| p.time) as f64;
+    let last = points.last().map_or(0, |p| p.time) as f64;
+    // A day either side keeps a single point, or a single day's points, off the chart's edges.
+    let margin = ((last - first) * 0.05).max(86_400.0);
+    let y_max = points.iter().map(|p| p.mean + p.stddev).fold(0.0, f64::max);
+    let y_max = (y_max * 1.15).max(f64::MIN_POSITIVE);
+    let unit = metric.unit();
+
+    let root = SVGBackend::new(&chart.path, SIZE).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut context = ChartBuilder::on(&root)
+        .caption(&chart.title, ("sans-serif", 22))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(80)
+        .build_cartesian_2d((first - margin)..(last + margin), 0.0..y_max)?;
+    context
+        .configure_mesh()
+        .x_desc("Commit date")
+        .y_desc(format!("Mean {}", metric.label()))
+        .x_labels(6)
+        .x_label_formatter(&|x| {
+            let seconds = x.max(0.0) as u64;
+            // Dates alone would repeat across a chart of a day or two, so show the time as well.
+            if last - first < 3.0 * 86_400.0 {
+                let time = timestamp::rfc3339(UNIX_EPOCH + Duration::from_secs(seconds));
+                time[5..16].replace('T', " ")
+            } else {
+                timestamp::date(seconds)
+            }
+        })
+        .y_label_formatter(&|y| unit.format(*y))
+        .draw()?;
+
+    let color = Palette99::pick(0).to_rgba();
+    let at = |p: &Point| (p.time as f64, p.mean);
+    context.draw_series(LineSeries::new(
+        points.iter().map(at),
+        color.stroke_width(2),
+    ))?;
+    context.draw_series(points.iter().map(|p| {
+        ErrorBar::new_vertical(
+            p.time as f64,
+            (p.mean - p.stddev).max(0.0),
+            p.mean,
+            p.mean + p.stddev,
+            color.mix(0.6).stroke_width(1),
+            6,
+        )
+    }))?;
+    context.draw_series(points.iter().map(|p| Circle::new(at(p), 3, color.filled())))?;
+
+    let regressions: Vec<&Point> = points.iter().filter(|p| p.regression.is_some()).collect();
+    context.draw_series(
+        regressions
+            .iter()
+            .map(|p| Circle::new(at(p), 6, RED.stroke_width(2))),
+    )?;
+    context.draw_series(regressions.iter().map(|p| {
+        let label = format!(
+            "{:+.1}% {}",
+            p.regression.unwrap_or_default() * 100.0,
+            p.commit.get(..7).unwrap_or(&p.commit)
+        );
+        Text::new(
+            label.trim_end().to_string(),
+            (p.time as f64, p.mean + p.stddev),
+            ("sans-serif", 13).into_font().color(&RED),
+        )
+    }))?;
+
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod by_case_tests {
+    use super::*;
+    use crate::stats::Summary;
+
+    fn benchmark(case: &str, bytes: Option<u64>, wall_time: f64) -> BenchmarkResults {
+        let mut benchmark = BenchmarkResults {
+            case: case.to_string(),
+            sample: format!("{:?}", bytes),
+            sample_bytes: bytes,
+            ..Default::default()
+        };
+        benchmark
+            .summary
+            .insert(Metric::WallTime, Summary::of(&[wall_time], &[]).unwrap());
+        benchmark
+    }
+
+    #[test]
+    fn groups_and_sorts_points_by_case() {
+        let results = SuiteResults {
+            benchmarks: vec![
+                benchmark("a", Some(300), 3.0),
+                benchmark("b", Some(100), 2.0),
+                benchmark("a", Some(100), 1.0),
+                benchmark("b", None, 9.0),
+            ],
+            ..Default::default()
+        };
+        let series = by_case(&results, |b| Some(b.summary.get(&Metric::WallTime)?.mean));
+        assert_eq!(
+            vec![
+                ("a".to_string(), vec![(100.0, 1.0), (300.0, 3.0)]),
+                ("b".to_string(), vec![(100.0, 2.0)]),
+            ],
+            series
+        );
+    }
+
+    #[test]
+    fn without_values_is_empty() {
+        let results = SuiteResults {
+            benchmarks: vec![benchmark("a", Some(100), 1.0)],
+            ..Default::default()
+        };
+        assert!(by_case(&results, BenchmarkResults::lines_per_second).is_empty());
+    }
+}
+
+#[cfg(test)]
+mod scaling_tests {
+    use super::*;
+    use crate::stats::Summary;
+
+    fn benchmark(threads: Option<usize>, wall_time: f64) -> BenchmarkResults {
+        let mut benchmark = BenchmarkResults {
+            case: "groupby".to_string(),
+            sample: "sample.txt".to_string(),
+            threads,
+            ..Default::default()
+        };
+        benchmark
+            .summary
+            .insert(Metric::WallTime, Summary::of(&[wall_time], &[]).unwrap());
+        benchmark
+    }
+
+    #[test]
+    fn works() {
+        let results = SuiteResults {
+            benchmarks: vec![
+                benchmark(Some(4), 2.0),
+                benchmark(Some(1), 6.0),
+                benchmark(Some(2), 4.0),
+            ],
+            ..Default::default()
+        };
+        let (speedup, efficiency) = scaling(&results);
+        assert_eq!(
+            vec![(
+                "groupby/sample.txt".to_string(),
+                vec![(1.0, 1.0), (2.0, 1.5), (4.0, 3.0)]
+            )],
+            speedup
+        );
+        assert_eq!(vec![(1.0, 1.0), (2.0, 0.75), (4.0, 0.75)], efficiency[0].1);
+    }
+
+    #[test]
+    fn without_sweep_is_empty() {
+        let results = SuiteResults {
+            benchmarks: vec![benchmark(None, 1.0)],
+            ..Default::default()
+        };
+        let (speedup, efficiency) = scaling(&results);
+        assert!(speedup.is_empty());
+        assert!(efficiency.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod file_name_tests {
+    use super::*;
+
+    #[test]
+    fn replaces_unsafe_characters() {
+        assert_eq!("a_b_c.txt", file_name("a/b c.txt"));
+    }
+}
+
+#[cfg(test)]
+mod seen_from_tests {
+    use super::*;
+
+    #[test]
+    fn links_from_the_report() {
+        let chart = Chart {
+            title: "Wall time".to_string(),
+            path: "out/charts/wall_time.svg".into(),
+        };
+        let path = |dir: &str| chart.seen_from(Path::new(dir)).unwrap().path;
+        assert_eq!(PathBuf::from("out/charts/wall_time.svg"), path(""));
+        assert_eq!(PathBuf::from("charts/wall_time.svg"), path("out"));
+        assert_eq!(
+            PathBuf::from("../out/charts/wall_time.svg"),
+            path("reports")
+        );
+        assert_eq!(
+            PathBuf::from("../../charts/wall_time.svg"),
+            path("out/reports/weekly")
+        );
+    }
+}
+
+#[cfg(test)]
+mod render_all_tests {
+    use super::*;
+    use crate::results::Iteration;
+    use crate::stats::Summary;
+
+    #[test]
+    fn writes_svg() {
+        let mut benchmark = BenchmarkResults {
+            case: "groupby".to_string(),
+            sample: "sample.txt".to_string(),
+            sample_bytes: Some(1024),
+            ..Default::default()
+        };
+        benchmark
+            .summary
+            .insert(Metric::WallTime, Summary::of(&[0.5], &[]).unwrap());
+        let results = SuiteResults {
+            benchmarks: vec![benchmark],
+            ..Default::default()
+        };
+
+        let dir = std::env::temp_dir().join(format!("charts-{}", std::process::id()));
+        let charts = render_all(&results, &dir).unwrap();
+        // Without a line count, there's no chart of lines per second, and without raw iterations,
+        // there's no distribution to draw.
+        assert_eq!(2, charts.len());
+        let svg = fs::read_to_string(&charts[0].path).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("groupby"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_distribution_per_sample() {
+        let mut benchmark = BenchmarkResults {
+            case: "groupby".to_string(),
+            sample: "sample.txt".to_string(),
+            iterations: [0.4, 0.5, 0.6]
+                .iter()
+                .map(|&wall_time| {
+                    let mut iteration = Iteration::default();
+                    iteration.metrics.insert(Metric::WallTime, wall_time);
+                    iteration
+                })
+                .collect(),
+            ..Default::default()
+        };
+        benchmark.summarize(&[]);
+        let results = SuiteResults {
+            benchmarks: vec![benchmark],
+            ..Default::default()
+        };
+
+        let dir = std::env::temp_dir().join(format!("charts-boxes-{}", std::process::id()));
+        let charts = render_all(&results, &dir).unwrap();
+        assert_eq!(1, charts.len());
+        assert_eq!(
+            dir.join("wall_time-distribution-sample.txt.svg"),
+            charts[0].path
+        );
+        assert!(fs::read_to_string(&charts[0].path)
+            .unwrap()
+            .contains("groupby"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+//! Finds the scratch files runs leave behind, as opposed to their results: profiles, and temporary
+//! files (staged sample copies, `perf` and `strace` output) left over by runs that were killed
+//! before they could delete them.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// The prefixes of the temporary files `perf stat` and `strace` write, before the process ID of the
+/// run they belong to.
+const TEMP_PREFIXES: [&str; 2] = ["bench-perf-", "bench-strace-"];
+
+/// The prefix of staged sample copies, before the process ID of the run they belong to.
+const STAGE_PREFIX: &str = "bench-";
+
+/// A file to remove.
+#[derive(Debug, PartialEq)]
+pub struct Scratch {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+/// Finds the scratch files to remove: profiles in `results_dir` last written more than
+/// `keep_profiles` ago, and temporary files in `temp_dir` and the staging directory, if there is
+/// one, whose run is no longer running. Results themselves are never touched.
+pub fn find(
+    results_dir: &Path,
+    temp_dir: &Path,
+    stage_dir: Option<&Path>,
+    keep_profiles: Duration,
+) -> io::Result<Vec<Scratch>> {
+    let mut scratch = vec![];
+    let now = SystemTime::now();
+    for (path, metadata) in files(&results_dir.join("profiles"))? {
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        if age >= keep_profiles {
+            scratch.push(Scratch {
+                path,
+                bytes: metadata.len(),
+            });
+        }
+    }
+
+    let mut leftovers = |dir: &Path, prefixes: &[&str]| -> io::Result<()> {
+        for (path, metadata) in files(dir)? {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let orphaned = prefixes
+                .iter()
+                .any(|prefix| run_of(&name, prefix).is_some_and(|pid| !alive(pid)));
+            if orphaned {
+                scratch.push(Scratch {
+                    path,
+                    bytes: metadata.len(),
+                });
+            }
+        }
+        Ok(())
+    };
+    leftovers(temp_dir, &TEMP_PREFIXES)?;
+    if let Some(dir) = stage_dir {
+        leftovers(dir, &[STAGE_PREFIX])?;
+    }
+    Ok(scratch)
+}
+
+/// Lists the files directly in `dir`, which needn't exist.
+fn files(dir: &Path) -> io::Result<Vec<(PathBuf, fs::Metadata)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut files = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            files.push((entry.path(), metadata));
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// Returns the process ID of the run a file named `prefix<pid>-...` belongs to, if it's named so.
+fn run_of(name: &str, prefix: &str) -> Option<u32> {
+    let (pid, _) = name.strip_prefix(prefix)?.split_once('-')?;
+    pid.parse().ok()
+}
+
+/// Whether a process with ID `pid` is running.
+fn alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists and may be signaled.
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(test)]
+mod run_of_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        assert_eq!(Some(42), run_of("bench-perf-42-0.csv", "bench-perf-"));
+        assert_eq!(Some(42), run_of("bench-42-sample.txt", "bench-"));
+        assert_eq!(None, run_of("bench-perf-42-0.csv", "bench-"));
+        assert_eq!(None, run_of("other-42-0.csv", "bench-"));
+    }
+}
+
+#[cfg(test)]
+mod find_tests {
+    use super::*;
+
+    #[test]
+    fn finds_profiles_and_orphaned_files() {
+        let dir = std::env::temp_dir().join(format!("clean-{}", std::process::id()));
+        let results_dir = dir.join("results");
+        let temp_dir = dir.join("tmp");
+        fs::create_dir_all(results_dir.join("profiles")).unwrap();
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(results_dir.join("latest.json"), "{}").unwrap();
+        fs::write(results_dir.join("profiles/groupby-a.txt.svg"), "<svg/>").unwrap();
+        // No process has the largest possible ID, but this one is still running.
+        let orphan = format!("bench-perf-{}-0.csv", libc::pid_t::MAX);
+        let current = format!("bench-perf-{}-0.csv", std::process::id());
+        for name in [&orphan, &current, &"unrelated.txt".to_string()] {
+            fs::write(temp_dir.join(name), "").unwrap();
+        }
+
+        let all = find(&results_dir, &temp_dir, None, Duration::ZERO).unwrap();
+        let recent = find(&results_dir, &temp_dir, None, Duration::from_secs(3600)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            vec![
+                Scratch {
+                    path: results_dir.join("profiles/groupby-a.txt.svg"),
+                    bytes: 6,
+                },
+                Scratch {
+                    path: temp_dir.join(&orphan),
+                    bytes: 0,
+                },
+            ],
+            all
+        );
+        assert_eq!(
+            vec![Scratch {
+                path: temp_dir.join(&orphan),
+                bytes: 0,
+            }],
+            recent
+        );
+    }
+}
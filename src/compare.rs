@@ -0,0 +1,469 @@
+//! Compares two sets of results benchmark by benchmark, testing whether each difference is
+//! statistically significant.
+
+use crate::results::{BenchmarkResults, Metric, SuiteResults};
+use crate::stats;
+
+/// How a benchmark changed between two sets of results. Every metric we record is better when
+/// lower, so an increase is a regression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Improvement,
+    Regression,
+
+    /// The difference, if any, isn't statistically significant.
+    Unchanged,
+}
+
+/// The comparison of one benchmark that appears in both sets of results.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    /// The benchmark's ID.
+    pub id: String,
+
+    /// The mean of the metric in the old results.
+    pub old_mean: f64,
+
+    /// The mean of the metric in the new results.
+    pub new_mean: f64,
+
+    /// The change in the mean, relative to the old mean (e.g. `0.05` for 5% higher).
+    pub relative_change: f64,
+
+    /// The p-value of a Mann-Whitney U test of the two sets of values.
+    pub p_value: f64,
+
+    pub verdict: Verdict,
+
+    /// The standard deviations and interquartile ranges of the metric in the old and new results.
+    pub old_stddev: f64,
+    pub new_stddev: f64,
+    pub old_iqr: f64,
+    pub new_iqr: f64,
+
+    /// The p-value of a test of whether the two sets of values are equally spread out.
+    pub spread_p_value: f64,
+
+    /// Whether the values got significantly more spread out, whatever happened to the mean. New
+    /// contention or I/O trouble tends to show up this way before it moves the mean much.
+    pub noisier: bool,
+}
+
+/// The full comparison of two sets of results.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub comparisons: Vec<Comparison>,
+
+    /// IDs of benchmarks that only appear in the old results.
+    pub removed: Vec<String>,
+
+    /// IDs of benchmarks that only appear in the new results.
+    pub added: Vec<String>,
+
+    /// IDs of benchmarks whose samples were generated differently in the old results and the new,
+    /// which makes them incomparable, however alike their names.
+    pub respecified: Vec<String>,
+}
+
+/// Compares `metric` for every benchmark in `old` and `new`. Differences with a p-value below
+/// `alpha` are considered significant.
+pub fn compare(old: &SuiteResults, new: &SuiteResults, metric: Metric, alpha: f64) -> Report {
+    let mut report = Report::default();
+
+    for old_benchmark in &old.benchmarks {
+        let id = old_benchmark.id();
+        let new_benchmark = match new.benchmarks.iter().find(|b| b.id() == id) {
+            Some(new_benchmark) => new_benchmark,
+            None => {
+                report.removed.push(id);
+                continue;
+            }
+        };
+        if let (Some(old_spec), Some(new_spec)) =
+            (&old_benchmark.sample_spec, &new_benchmark.sample_spec)
+        {
+            if old_spec != new_spec {
+                report.respecified.push(id);
+                continue;
+            }
+        }
+        report
+            .comparisons
+            .extend(comparison(id, old_benchmark, new_benchmark, metric, alpha));
+    }
+
+    report.added = new
+        .benchmarks
+        .iter()
+        .map(|b| b.id())
+        .filter(|id| !old.benchmarks.iter().any(|b| &b.id() == id))
+        .collect();
+
+    report
+}
+
+/// Compares `metric` for every variant of a case with the case's first variant, on the same
+/// sample at the same thread count, as though the first variant were the old results and the
+/// others the new.
+pub fn compare_variants(results: &SuiteResults, metric: Metric, alpha: f64) -> Report {
+    let mut report = Report::default();
+    for benchmark in results.benchmarks.iter().filter(|b| b.variant.is_some()) {
+        let first = results
+            .benchmarks
+            .iter()
+            .find(|b| {
+                b.case == benchmark.case
+                    && b.sample == benchmark.sample
+                    && b.threads == benchmark.threads
+            })
+            .expect("the benchmark itself matches");
+        if std::ptr::eq(first, benchmark) {
+            continue;
+        }
+        let id = format!(
+            "{} vs [{}]",
+            benchmark.id(),
+            first.variant.as_deref().unwrap_or_default()
+        );
+        report
+            .comparisons
+            .extend(comparison(id, first, benchmark, metric, alpha));
+    }
+    report
+}
+
+/// Compares `metric` between two benchmarks, or returns `None` if one of them didn't record it.
+pub fn comparison(
+    id: String,
+    old: &BenchmarkResults,
+    new: &BenchmarkResults,
+    metric: Metric,
+    alpha: f64,
+) -> Option<Comparison> {
+    let old_values = old.values(metric);
+    let new_values = new.values(metric);
+    // The metric may not have been recorded on one side, e.g. a command that printed nothing has
+    // no time to first output. Then there's nothing to compare.
+    let p_value = stats::mann_whitney_u(&old_values, &new_values)?;
+    let old_mean = stats::mean(&old_values);
+    let new_mean = stats::mean(&new_values);
+    let relative_change = if old_mean == 0.0 {
+        0.0
+    } else {
+        (new_mean - old_mean) / old_mean
+    };
+
+    let verdict = if p_value >= alpha {
+        Verdict::Unchanged
+    } else if new_mean > old_mean {
+        Verdict::Regression
+    } else {
+        Verdict::Improvement
+    };
+
+    let spread_p_value = stats::spread_test(&old_values, &new_values).unwrap_or(1.0);
+    let old_stddev = stats::stddev(&old_values);
+    let new_stddev = stats::stddev(&new_values);
+    let old_iqr = stats::interquartile_range(&old_values);
+    let new_iqr = stats::interquartile_range(&new_values);
+    let noisier = spread_p_value < alpha && new_iqr / new_mean > old_iqr / old_mean;
+
+    Some(Comparison {
+        id,
+        old_mean,
+        new_mean,
+        relative_change,
+        p_value,
+        verdict,
+        old_stddev,
+        new_stddev,
+        old_iqr,
+        new_iqr,
+        spread_p_value,
+        noisier,
+    })
+}
+
+impl Report {
+    /// Returns the significant regressions whose relative change exceeds `threshold` (e.g. `0.05`
+    /// for 5%).
+    pub fn regressions_beyond(&self, threshold: f64) -> Vec<&Comparison> {
+        self.comparisons
+            .iter()
+            .filter(|c| c.verdict == Verdict::Regression && c.relative_change > threshold)
+            .collect()
+    }
+
+    /// Sums the report up in one line for commit messages and chat notifications, e.g.
+    /// `3 regressions (worst +14.2% on groupby/big.txt), 12 improvements (best -8.0% on
+    /// groupby/small.txt), 20 unchanged`.
+    pub fn tldr(&self) -> String {
+        let count = |verdict| {
+            self.comparisons
+                .iter()
+                .filter(|c| c.verdict == verdict)
+                .collect::<Vec<_>>()
+        };
+        let extreme = |comparisons: &[&Comparison]| {
+            comparisons
+                .iter()
+                .max_by(|a, b| a.relative_change.abs().total_cmp(&b.relative_change.abs()))
+                .map(|c| format!("{:+.1}% on {}", c.relative_change * 100.0, c.id))
+        };
+        let regressions = count(Verdict::Regression);
+        let improvements = count(Verdict::Improvement);
+        let mut parts = vec![];
+        let mut describe = |comparisons: &[&Comparison], noun: &str, superlative: &str| {
+            let mut part = plural(comparisons.len(), noun);
+            if let Some(extreme) = extreme(comparisons) {
+                part += &format!(" ({} {})", superlative, extreme);
+            }
+            parts.push(part);
+        };
+        describe(&regressions, "regression", "worst");
+        describe(&improvements, "improvement", "best");
+        parts.push(format!(
+            "{} unchanged",
+            self.comparisons.len() - regressions.len() - improvements.len()
+        ));
+        let noisier = self.comparisons.iter().filter(|c| c.noisier).count();
+        if noisier > 0 {
+            parts.push(format!("{} noisier", noisier));
+        }
+        if !self.respecified.is_empty() {
+            parts.push(format!("{} on regenerated samples", self.respecified.len()));
+        }
+        parts.join(", ")
+    }
+
+    /// Prints the report as an aligned table.
+    pub fn print(&self, metric: Metric) {
+        let unit = metric.unit();
+        let width = self.id_width();
+        for c in &self.comparisons {
+            c.print(metric, width);
+        }
+
+        for c in self.comparisons.iter().filter(|c| c.noisier) {
+            println!(
+                "{}: NOISIER: stddev {} -> {}, IQR {} -> {}  (p = {:.4})",
+                c.id,
+                unit.format(c.old_stddev),
+                unit.format(c.new_stddev),
+                unit.format(c.old_iqr),
+                unit.format(c.new_iqr),
+                c.spread_p_value,
+            );
+        }
+        for id in &self.removed {
+            println!("{}: only in old results", id);
+        }
+        for id in &self.added {
+            println!("{}: only in new results", id);
+        }
+        for id in &self.respecified {
+            println!(
+                "{}: not compared, since its sample was generated differently",
+                id
+            );
+        }
+    }
+
+    /// Prints just the rows of the significant regressions, for a quiet run.
+    pub fn print_regressions(&self, metric: Metric) {
+        let width = self.id_width();
+        for c in self.regressions_beyond(0.0) {
+            c.print(metric, width);
+        }
+    }
+
+    /// The width of the longest benchmark ID, to align the table by.
+    fn id_width(&self) -> usize {
+        self.comparisons
+            .iter()
+            .map(|c| c.id.len())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl Comparison {
+    /// Prints the comparison as a row of the table, with its ID padded to `width`.
+    fn print(&self, metric: Metric, width: usize) {
+        let unit = metric.unit();
+        let verdict = match self.verdict {
+            Verdict::Improvement => "improved",
+            Verdict::Regression => "REGRESSED",
+            Verdict::Unchanged => "no change",
+        };
+        println!(
+            "{:<width$}  {:>10} -> {:>10}  {:>+8.2}%  (p = {:.4})  {}",
+            self.id,
+            unit.format(self.old_mean),
+            unit.format(self.new_mean),
+            self.relative_change * 100.0,
+            self.p_value,
+            verdict,
+            width = width,
+        );
+    }
+}
+
+/// Counts `n` of `noun`, e.g. `1 regression` or `3 regressions`.
+pub fn plural(n: usize, noun: &str) -> String {
+    if n == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", n, noun)
+    }
+}
+
+#[cfg(test)]
+mod compare_tests {
+    use super::*;
+    use crate::results::Iteration;
+
+    fn benchmark(case: &str, wall_times: &[f64]) -> BenchmarkResults {
+        BenchmarkResults {
+            case: case.to_string(),
+            sample: "sample".to_string(),
+            command: vec![case.to_string()],
+            iterations: wall_times
+                .iter()
+                .map(|&wall_time| {
+                    let mut iteration = Iteration::default();
+                    iteration.metrics.insert(Metric::WallTime, wall_time);
+                    iteration
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn results(benchmarks: Vec<BenchmarkResults>) -> SuiteResults {
+        SuiteResults {
+            benchmarks,
+            ..Default::default()
+        }
+    }
+
+    const FAST: [f64; 10] = [1.0, 1.1, 1.2, 1.3, 1.4, 1.0, 1.1, 1.2, 1.3, 1.4];
+    const SLOW: [f64; 10] = [2.0, 2.1, 2.2, 2.3, 2.4, 2.0, 2.1, 2.2, 2.3, 2.4];
+
+    #[test]
+    fn detects_regressions_and_improvements() {
+        let old = results(vec![benchmark("a", &FAST), benchmark("b", &SLOW)]);
+        let new = results(vec![benchmark("a", &SLOW), benchmark("b", &FAST)]);
+        let report = compare(&old, &new, Metric::WallTime, 0.05);
+
+        assert_eq!(2, report.comparisons.len());
+        assert_eq!(Verdict::Regression, report.comparisons[0].verdict);
+        assert!((report.comparisons[0].relative_change - 1.0 / 1.2).abs() < 1e-9);
+        assert_eq!(Verdict::Improvement, report.comparisons[1].verdict);
+    }
+
+    #[test]
+    fn with_same_values_is_unchanged() {
+        let old = results(vec![benchmark("a", &FAST)]);
+        let new = results(vec![benchmark("a", &FAST)]);
+        let report = compare(&old, &new, Metric::WallTime, 0.05);
+        assert_eq!(Verdict::Unchanged, report.comparisons[0].verdict);
+        assert_eq!(0.0, report.comparisons[0].relative_change);
+    }
+
+    #[test]
+    fn detects_more_noise_without_a_shift() {
+        let noisy = [1.2, 0.9, 1.5, 1.0, 1.6, 0.8, 1.2, 1.4, 0.9, 1.5];
+        let old = results(vec![benchmark("a", &FAST), benchmark("b", &FAST)]);
+        let new = results(vec![benchmark("a", &noisy), benchmark("b", &FAST)]);
+        let report = compare(&old, &new, Metric::WallTime, 0.05);
+        assert_eq!(Verdict::Unchanged, report.comparisons[0].verdict);
+        assert!(report.comparisons[0].noisier);
+        assert!(!report.comparisons[1].noisier);
+    }
+
+    #[test]
+    fn tldr_works() {
+        let old = results(vec![
+            benchmark("a", &FAST),
+            benchmark("b", &SLOW),
+            benchmark("c", &FAST),
+        ]);
+        let new = results(vec![
+            benchmark("a", &SLOW),
+            benchmark("b", &FAST),
+            benchmark("c", &FAST),
+        ]);
+        let report = compare(&old, &new, Metric::WallTime, 0.05);
+        assert_eq!(
+            "1 regression (worst +83.3% on a/sample), 1 improvement (best -45.5% on b/sample), \
+             1 unchanged",
+            report.tldr()
+        );
+        assert_eq!(
+            "0 regressions, 0 improvements, 0 unchanged",
+            Report::default().tldr()
+        );
+    }
+
+    #[test]
+    fn regressions_beyond_respects_threshold() {
+        let old = results(vec![benchmark("a", &FAST), benchmark("b", &SLOW)]);
+        let new = results(vec![benchmark("a", &SLOW), benchmark("b", &FAST)]);
+        let report = compare(&old, &new, Metric::WallTime, 0.05);
+
+        // a regressed by about 83%; b improved, so it never counts.
+        assert_eq!(1, report.regressions_beyond(0.05).len());
+        assert_eq!("a/sample", report.regressions_beyond(0.80)[0].id);
+        assert!(report.regressions_beyond(0.90).is_empty());
+    }
+
+    #[test]
+    fn reports_unmatched_benchmarks() {
+        let old = results(vec![benchmark("a", &FAST), benchmark("b", &FAST)]);
+        let new = results(vec![benchmark("a", &FAST), benchmark("c", &FAST)]);
+        let report = compare(&old, &new, Metric::WallTime, 0.05);
+        assert_eq!(1, report.comparisons.len());
+        assert_eq!(vec!["b/sample"], report.removed);
+        assert_eq!(vec!["c/sample"], report.added);
+    }
+
+    #[test]
+    fn skips_regenerated_samples() {
+        let with_spec = |case: &str, spec: Option<&str>| BenchmarkResults {
+            sample_spec: spec.map(str::to_string),
+            ..benchmark(case, &FAST)
+        };
+        let old = results(vec![
+            with_spec("a", Some("0badcafe")),
+            with_spec("b", Some("0badcafe")),
+            with_spec("c", None),
+        ]);
+        let new = results(vec![
+            with_spec("a", Some("0badcafe")),
+            with_spec("b", Some("deadbeef")),
+            with_spec("c", Some("deadbeef")),
+        ]);
+        let report = compare(&old, &new, Metric::WallTime, 0.05);
+        assert_eq!(2, report.comparisons.len());
+        assert_eq!(vec!["b/sample"], report.respecified);
+        assert!(report.tldr().ends_with(", 1 on regenerated samples"));
+    }
+
+    #[test]
+    fn compares_variants_with_the_first() {
+        let variant = |name: &str, wall_times: &[f64]| BenchmarkResults {
+            variant: Some(name.to_string()),
+            ..benchmark("groupby", wall_times)
+        };
+        let results = results(vec![
+            variant("old", &SLOW),
+            variant("new", &FAST),
+            benchmark("other", &FAST),
+        ]);
+        let report = compare_variants(&results, Metric::WallTime, 0.05);
+        assert_eq!(1, report.comparisons.len());
+        assert_eq!("groupby [new]/sample vs [old]", report.comparisons[0].id);
+        assert_eq!(Verdict::Improvement, report.comparisons[0].verdict);
+    }
+}
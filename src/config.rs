@@ -0,0 +1,1558 @@
+//! The benchmark suite configuration, read from a TOML file (`bench.toml` by default).
+//!
+//! A suite is a list of cases. Each case is a command and the samples it should be run against;
+//! every (case, sample) pair becomes one benchmark. Samples are usually files, but a suite can
+//! also describe samples to generate on the fly.
+
+use crate::affinity::{parse_cpu_list, CpuSet};
+use crate::baseline::Baseline;
+use crate::build::Build;
+use crate::cache::CacheMode;
+use crate::digest::OutputCheck;
+use crate::git;
+use crate::measure::{InputMode, MeasureOptions};
+use crate::priority::IoClass;
+use crate::reference::{CountPosition, Grouping};
+use crate::stats::OutlierMethod;
+use crate::thermal::CpuFiles;
+use groupby_benchmarking::sample::{
+    self, accented, alphabet, Encoding, LineLength, Options, SampleLength, SampleReader, Template,
+    ALPHANUMERIC,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The environment variable that overrides the sample directory, e.g. in a container that mounts
+/// the samples somewhere else.
+const SAMPLE_DIR_VAR: &str = "BENCH_SAMPLE_DIR";
+
+/// A full benchmark suite, as described by a config file.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+    /// The directory containing the sample files. Sample names are resolved relative to it. The
+    /// `BENCH_SAMPLE_DIR` environment variable, if set, overrides it.
+    #[serde(default = "default_sample_dir")]
+    pub sample_dir: PathBuf,
+
+    /// The number of measured iterations for each benchmark. With `target_precision`, this is
+    /// the minimum number instead.
+    #[serde(default = "default_iterations")]
+    pub iterations: usize,
+
+    /// If set, keep running iterations until the 95% confidence interval of the mean wall time is
+    /// within this fraction of the mean (e.g. `0.02` for ±2%), or until `max_iterations`.
+    #[serde(default)]
+    pub target_precision: Option<f64>,
+
+    /// The most iterations to run in pursuit of `target_precision`.
+    #[serde(default = "default_max_iterations")]
+    pub max_iterations: usize,
+
+    /// The number of unmeasured warmup iterations to run before the measured ones.
+    #[serde(default)]
+    pub warmup: usize,
+
+    /// The percentiles (from 0 to 100) to report for every metric, alongside the mean, median,
+    /// standard deviation, minimum, and maximum.
+    #[serde(default = "default_percentiles")]
+    pub percentiles: Vec<f64>,
+
+    /// How to detect outlying iterations (`none`, `iqr`, or `mad`). Outliers are recorded and
+    /// flagged in the results but excluded from summary statistics.
+    #[serde(default)]
+    pub outliers: OutlierMethod,
+
+    /// If set, copy each sample into this directory before benchmarking it, and feed the copy to
+    /// the command. Pointed at a tmpfs such as `/dev/shm`, this takes the disk out of the
+    /// measurement.
+    #[serde(default)]
+    pub stage_dir: Option<PathBuf>,
+
+    /// If set, refuse to run a suite whose samples take more than this many gibibytes of disk: the
+    /// files its cases read, and the ephemeral generated samples that are on disk at once.
+    #[serde(default)]
+    pub disk_budget_gib: Option<f64>,
+
+    /// What to do with the page cache before each benchmark and iteration (`as-is`, `warm`,
+    /// `cold`, or `drop-caches`).
+    #[serde(default)]
+    pub cache: CacheMode,
+
+    /// If set, record each process's RSS every this many milliseconds, so results include a
+    /// memory-over-time series for every iteration and not just the peak.
+    #[serde(default)]
+    pub memory_sample_interval_ms: Option<u64>,
+
+    /// Whether to refuse to run if the CPU's frequency settings make timings unstable, e.g. a
+    /// `powersave` governor or turbo boost, rather than just warning. `run --allow-unstable-cpu`
+    /// overrides it.
+    #[serde(default)]
+    pub require_stable_cpu: bool,
+
+    /// Whether to run each iteration under `perf stat` and record its hardware counters
+    /// (instructions, cycles, branch misses, and cache misses). Linux only, and needs `perf`. The
+    /// command's max RSS is then polled from `/proc`, as the kernel's would include perf's own.
+    #[serde(default)]
+    pub hardware_counters: bool,
+
+    /// Whether to run each iteration under `strace` and record how many system calls it made,
+    /// and how many reads and writes of what mean size. Linux only, and needs `strace`. Tracing
+    /// slows commands down a lot, so timings taken with it are only comparable with each other.
+    /// The command's max RSS is then polled from `/proc`, as the kernel's would include strace's.
+    #[serde(default)]
+    pub count_syscalls: bool,
+
+    /// If set, kill any iteration that runs for longer than this many milliseconds, abandon its
+    /// benchmark, and move on to the next.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+
+    /// Whether to leave iterations during which the CPU was thermally throttled out of the summary
+    /// statistics, as with outliers. Throttled iterations are flagged in the results either way.
+    #[serde(default = "default_exclude_throttled")]
+    pub exclude_throttled: bool,
+
+    /// How many times to retry an iteration that fails, e.g. because it ran out of memory under
+    /// its case's memory limit, before counting it as failed. The failed attempts' statuses are
+    /// recorded with the iteration.
+    #[serde(default)]
+    pub retries: usize,
+
+    /// If set, pin the benchmarked commands, and optionally the harness itself, to particular
+    /// CPUs.
+    #[serde(default)]
+    pub affinity: Option<Affinity>,
+
+    /// If set, run the benchmarked commands, and the harness, at different scheduling priorities.
+    #[serde(default)]
+    pub priority: Option<Priority>,
+
+    /// Samples generated as they're piped into the command rather than read from files, by name.
+    /// A case lists them among its samples like any other.
+    #[serde(default)]
+    pub generated: BTreeMap<String, GeneratedSample>,
+
+    /// Real-world samples, by name, that `bench corpus` downloads into the sample directory. A case
+    /// lists them among its samples like any file.
+    #[serde(default)]
+    pub corpus: BTreeMap<String, CorpusFile>,
+
+    /// If set, also run classic Unix tools on every sample the cases use, after the cases, to put
+    /// their results in context.
+    #[serde(default)]
+    pub baselines: Option<Baselines>,
+
+    /// The cases to run, in order.
+    #[serde(rename = "case")]
+    pub cases: Vec<Case>,
+}
+
+/// The CPUs to pin processes to, as taskset-style lists like `2-3` or `0,2,4-7`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Affinity {
+    /// The CPUs to run the benchmarked commands on.
+    pub command: String,
+
+    /// The CPUs to run the harness on. These should differ from `command`'s, so the harness's
+    /// own threads don't compete with the command being measured.
+    #[serde(default)]
+    pub harness: Option<String>,
+}
+
+/// Preset baseline commands to run alongside the cases.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Baselines {
+    /// The baselines to run: any of `sort-uniq`, `awk`, and `datamash`.
+    pub tools: Vec<Baseline>,
+
+    /// How the baselines group lines, which should match the cases: e.g. `{ first-chars = 1 }`,
+    /// `{ last-chars = 3 }`, or `"line"`.
+    pub group_by: Grouping,
+}
+
+/// Scheduling priorities for the benchmarked commands and the harness.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Priority {
+    /// The nice value to run the benchmarked commands at, from -20 (highest priority) to 19.
+    /// Values below zero usually require root.
+    #[serde(default)]
+    pub nice: Option<i32>,
+
+    /// The I/O scheduling class to run the harness in (`best-effort` or `idle`). The benchmarked
+    /// commands are returned to the default class, so they don't inherit it.
+    #[serde(default)]
+    pub harness_io: Option<IoClass>,
+}
+
+/// A real-world sample to download, pinned by its hash so that everyone benchmarks the same bytes.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorpusFile {
+    /// Where to download the sample from.
+    pub url: String,
+
+    /// The SHA-256 of the sample, as 64 hex digits. A download with any other hash is rejected.
+    pub sha256: String,
+
+    /// The sample's size in bytes, if known, so the disk budget can count it before it's
+    /// downloaded.
+    #[serde(default)]
+    pub bytes: Option<u64>,
+}
+
+/// A sample generated on the fly and streamed into the command, so samples far larger than the
+/// disk can hold (or than anyone wants to wait to write) can be benchmarked. Its lines are random
+/// alphanumeric text by default, as with `mksample`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratedSample {
+    /// The shortest and longest line lengths, excluding the newline. Each line's length is chosen
+    /// at random between them, inclusive; give the same length twice for fixed-length lines.
+    /// Give this or `template`.
+    #[serde(default)]
+    pub line_length: Option<[usize; 2]>,
+
+    /// The sample's length in lines. Give this or `characters`.
+    #[serde(default)]
+    pub lines: Option<usize>,
+
+    /// The sample's length in characters, including newlines. Give this or `lines`.
+    #[serde(default)]
+    pub characters: Option<usize>,
+
+    /// The fraction of lines, from 0 to 1, that exactly repeat a recent line.
+    #[serde(default)]
+    pub duplicate_ratio: f64,
+
+    /// The fraction of characters, from 0 to 1, that are NUL bytes instead.
+    #[serde(default)]
+    pub nul_rate: f64,
+
+    /// The fraction of characters, from 0 to 1, that are invalid UTF-8 instead.
+    #[serde(default)]
+    pub invalid_utf8_rate: f64,
+
+    /// Whether the sample starts with a byte order mark. It counts as three characters in UTF-8,
+    /// and one in UTF-16.
+    #[serde(default)]
+    pub bom: bool,
+
+    /// Whether the sample's last line goes without a newline.
+    #[serde(default)]
+    pub omit_final_newline: bool,
+
+    /// The fraction of lines, from 0 to 1, that end in CRLF rather than LF.
+    #[serde(default)]
+    pub crlf_ratio: f64,
+
+    /// The fraction of lines, from 0 to 1, made only of spaces and tabs.
+    #[serde(default)]
+    pub blank_ratio: f64,
+
+    /// How many distinct characters, from 1 to 62, lines are made of, which sets how well the
+    /// sample compresses: from one character, which compresses to almost nothing, to all 62
+    /// alphanumerics (the default), which carry about six bits of entropy each.
+    #[serde(default)]
+    pub alphabet_size: Option<usize>,
+
+    /// The characters lines are made of.
+    #[serde(default)]
+    pub charset: Charset,
+
+    /// If set, lines are made of pseudo-words like `word00421`, drawn from this many, instead of
+    /// characters from the charset, so the sample and a command's output are easy to check by eye.
+    #[serde(default)]
+    pub words: Option<usize>,
+
+    /// If set, lines cycle through this many fixed templates, with no randomness, so the sample
+    /// has exactly this many distinct lines, in a perfectly periodic order.
+    #[serde(default)]
+    pub cycle: Option<usize>,
+
+    /// If set, lines are made from this template, e.g. `{KEY} [{INT:100-599}] GET {PATH}
+    /// {RAND:12}`, rather than to a line length. Its `{KEY}`s are drawn from `words`, and its
+    /// `{RAND:n}`s and `{PATH}`s are made of characters from the charset.
+    #[serde(default)]
+    pub template: Option<String>,
+
+    /// If set, lines are fixed-width records with fields of these widths, each field's text
+    /// left-aligned and padded with spaces, in place of a line length. Fields are made of the
+    /// charset's characters, so they start at the same byte column in every line unless they're
+    /// accented and in UTF-8.
+    #[serde(default)]
+    pub columns: Option<Vec<usize>>,
+
+    /// Whether to write the sample to a file before the benchmarks of each case that uses it, and
+    /// delete it after, rather than piping it into the command as it's generated. The file goes
+    /// in the staging directory, if there is one, or else the sample directory. Only one such
+    /// sample is on disk at a time (per case run in parallel), so a suite can run on files larger
+    /// in total than the disk holds, and cases can read it as a file: as an argument, from the
+    /// page cache, and with their answers verified. Each case gets a freshly generated sample.
+    #[serde(default)]
+    pub ephemeral: bool,
+
+    /// How the sample is encoded: `utf-8` (the default), `latin-1`, `windows-1252`, `utf-16le`, or
+    /// `utf-16be`. Lengths in characters count code units, so in UTF-16 they're half the size in
+    /// bytes.
+    #[serde(default)]
+    pub encoding: Encoding,
+
+    /// What to seed the random text with, if anything: `bench run --seed` mixed with the sample's
+    /// name. Set by `Suite::seed`, not in the config.
+    #[serde(skip)]
+    pub seed: Option<u64>,
+}
+
+/// A generated sample's reader, which seeds the random numbers of the thread reading it before the
+/// first read. The generator draws from that thread's, and the sample is read from whichever thread
+/// pipes it into the command.
+pub struct Seeded<R> {
+    seed: Option<u64>,
+    reader: R,
+}
+
+impl<R: Read> Read for Seeded<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(seed) = self.seed.take() {
+            fastrand::seed(seed);
+        }
+        self.reader.read(buf)
+    }
+}
+
+/// The characters a generated sample's lines are made of.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Charset {
+    /// Letters and digits, as ASCII.
+    #[default]
+    Alphanumeric,
+
+    /// Letters and digits, and as many letters with diacritics, which aren't ASCII. These are
+    /// what make a sample in a legacy encoding differ from one in UTF-8.
+    Accented,
+}
+
+/// A single benchmark case: a command and the samples to run it against.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Case {
+    /// A short, unique name for the case, used to identify it in results.
+    pub name: String,
+
+    /// The program to run, followed by its arguments. The sample is fed to it on stdin.
+    pub command: Vec<String>,
+
+    /// The names of the sample files to run the command against.
+    pub samples: Vec<String>,
+
+    /// The git checkout the command was built from, recorded in the results so they can be
+    /// attributed to a commit. Defaults to the checkout containing the program, if there is one.
+    #[serde(default)]
+    pub source: Option<PathBuf>,
+
+    /// If set, the case is run with each of these programs in place of the command's own, as
+    /// variants of one case: e.g. an old and a new build of groupby. Their iterations are
+    /// interleaved, so that drift in the machine's speed affects them all alike.
+    #[serde(default)]
+    pub binaries: Vec<Binary>,
+
+    /// If set, the case is swept across thread counts to measure how well it scales.
+    #[serde(default)]
+    pub threads: Option<ThreadSweep>,
+
+    /// Axes of alternative flags. The case is expanded into one case per combination of them,
+    /// with each combination's flags added to the command, to find which is fastest.
+    #[serde(default)]
+    pub matrix: Vec<Axis>,
+
+    /// How the command receives the sample: `stdin` (the default) opens it as the command's stdin,
+    /// `pipe` pipes it into the command's stdin, and `argument` passes its path as an argument,
+    /// in place of `{sample}` or else at the end.
+    #[serde(default)]
+    pub input: InputMode,
+
+    /// How to check that the command's output is the same every iteration: `exact` (the
+    /// default), `sorted` for the same lines in any order, or `none`.
+    #[serde(default)]
+    pub output_check: OutputCheck,
+
+    /// If set, run the command in a cgroup limited to this many mebibytes of memory (Linux with
+    /// cgroup v2 only). Not with `hardware_counters` or `count_syscalls`, as perf or strace would
+    /// run in the cgroup too.
+    #[serde(default)]
+    pub memory_limit_mib: Option<u64>,
+
+    /// If set, pause between iterations so the machine can cool down.
+    #[serde(default)]
+    pub cooldown: Option<Cooldown>,
+
+    /// If set, check the command's answers against the harness's own grouping of each sample.
+    #[serde(default)]
+    pub verify: Option<Verify>,
+
+    /// Environment variables to set for the command, on top of those the harness was run with:
+    /// e.g. `LC_ALL`, `RUST_LOG`, or `RAYON_NUM_THREADS`, any of which can change how fast it runs.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    /// If set, the directory to run the command in. A relative program path is found from there,
+    /// but sample paths are still relative to the directory the harness was run in.
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+
+    /// Whether the case is one of the suite's `baselines`, whose standard tools weren't built from
+    /// a checkout. Set by `Suite::add_baselines`, not in the config.
+    #[serde(skip)]
+    pub baseline: bool,
+}
+
+/// A program to run in place of a case's own, as one of its variants.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Binary {
+    /// The program's path, whose file name also names the variant.
+    Path(String),
+
+    /// The program's path and a name for the variant, e.g. the git ref it was built from.
+    Named { name: String, path: String },
+}
+
+impl Binary {
+    /// The name of the variant the binary makes.
+    pub fn name(&self) -> &str {
+        match self {
+            Binary::Path(path) => Path::new(path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(path),
+            Binary::Named { name, .. } => name,
+        }
+    }
+
+    /// The path of the program.
+    pub fn path(&self) -> &str {
+        match self {
+            Binary::Path(path) | Binary::Named { path, .. } => path,
+        }
+    }
+}
+
+/// A sweep of a case across thread counts, for commands that take a parallelism flag.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThreadSweep {
+    /// The flag that sets the number of threads, e.g. `--threads`. The count is passed as the
+    /// following argument.
+    pub flag: String,
+
+    /// The highest thread count to run; every count from 1 up to it is run. Defaults to the
+    /// number of CPUs available.
+    #[serde(default)]
+    pub max: Option<usize>,
+}
+
+/// One axis of a case's flag matrix: alternative sets of arguments, of which each combination
+/// takes one.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Axis {
+    /// What the axis varies, e.g. `grouping`, for naming the expanded cases.
+    pub name: String,
+
+    /// The alternatives, each a list of arguments. An empty list adds no arguments, for comparing
+    /// a flag with its absence.
+    pub values: Vec<Vec<String>>,
+}
+
+/// A pause between a case's iterations, for laptops and small machines where back-to-back runs
+/// heat the CPU until it slows down.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cooldown {
+    /// How long to pause before each iteration after the first, in milliseconds.
+    #[serde(default)]
+    pub ms: u64,
+
+    /// If set, after the pause, also wait until the CPU is no hotter than this many degrees
+    /// Celsius. This is skipped if the temperature can't be read.
+    #[serde(default)]
+    pub below_celsius: Option<f64>,
+
+    /// The longest to wait for the CPU to cool below `below_celsius`, in milliseconds, so a
+    /// machine that never gets there doesn't stall the suite.
+    #[serde(default = "default_max_cooldown_ms")]
+    pub max_wait_ms: u64,
+}
+
+/// How to check a case's answers: the command is run once more per benchmark, asked for the size
+/// of each group, and its counts are compared with a reference grouping of the sample.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Verify {
+    /// Arguments added to the command to make it print each group's key and line count, one group
+    /// per line, e.g. `["--stats"]`.
+    pub arguments: Vec<String>,
+
+    /// How the reference grouping groups lines, which should match the command's own: e.g.
+    /// `{ first-chars = 1 }`, `{ last-chars = 3 }`, or `"line"`.
+    pub group_by: Grouping,
+
+    /// Whether each line's count comes `last`, after the key (the default), or `first`, as
+    /// `uniq -c` prints it.
+    #[serde(default)]
+    pub count: CountPosition,
+}
+
+fn default_sample_dir() -> PathBuf {
+    PathBuf::from(".")
+}
+
+fn default_iterations() -> usize {
+    10
+}
+
+fn default_max_iterations() -> usize {
+    100
+}
+
+fn default_max_cooldown_ms() -> u64 {
+    60_000
+}
+
+fn default_exclude_throttled() -> bool {
+    true
+}
+
+fn default_percentiles() -> Vec<f64> {
+    vec![90.0, 99.0]
+}
+
+impl Suite {
+    /// Reads and parses a suite config file.
+    pub fn load(path: &Path) -> Result<Suite, Box<dyn Error>> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut suite: Suite =
+            toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if let Some(dir) = env::var_os(SAMPLE_DIR_VAR).filter(|dir| !dir.is_empty()) {
+            suite.sample_dir = PathBuf::from(dir);
+        }
+        suite
+            .add_baselines()
+            .map_err(|e| format!("{}: baselines: {}", path.display(), e))?;
+        // Where a case is set up, by its name, which a matrix extends with its combination's.
+        let case_at = |case: &Case| {
+            let name = case.name.split(" {").next().unwrap_or_default();
+            let line = line_of(&text, |line| {
+                line.split_once('=').is_some_and(|(key, value)| {
+                    key.trim() == "name" && value.trim() == format!("\"{}\"", name)
+                })
+            });
+            at(path, line)
+        };
+        if let Some(case) = suite.cases.iter().find(|case| case.command.is_empty()) {
+            return Err(
+                format!("{}: case {} has an empty command", case_at(case), case.name).into(),
+            );
+        }
+        if let Some(case) = suite
+            .cases
+            .iter()
+            .find(|case| case.matrix.iter().any(|axis| axis.values.is_empty()))
+        {
+            return Err(format!(
+                "{}: case {} has a matrix axis with no values",
+                case_at(case),
+                case.name
+            )
+            .into());
+        }
+        suite.expand_matrices();
+        if let Some(case) = suite
+            .cases
+            .iter()
+            .find(|case| matches!(&case.threads, Some(ThreadSweep { max: Some(0), .. })))
+        {
+            return Err(format!(
+                "{}: case {} sweeps up to 0 threads",
+                case_at(case),
+                case.name
+            )
+            .into());
+        }
+        if let Some(case) = suite.cases.iter().find(|case| {
+            let names: Vec<&str> = case.binaries.iter().map(Binary::name).collect();
+            (1..names.len()).any(|i| names[..i].contains(&names[i]))
+        }) {
+            return Err(format!(
+                "{}: case {} has two binaries with the same name; name them, as with \
+                 {{ name = \"old\", path = \"...\" }}",
+                case_at(case),
+                case.name
+            )
+            .into());
+        }
+        if let Some((case, cwd)) = suite.cases.iter().find_map(|case| {
+            let cwd = case.cwd.as_ref()?;
+            (!cwd.is_dir()).then_some((case, cwd))
+        }) {
+            return Err(format!(
+                "{}: case {} runs in {}, which isn't a directory",
+                case_at(case),
+                case.name,
+                cwd.display()
+            )
+            .into());
+        }
+        if suite.hardware_counters && suite.count_syscalls {
+            return Err(format!(
+                "{}: hardware_counters and count_syscalls can't be combined, as the counters would \
+                 count strace too",
+                path.display()
+            )
+            .into());
+        }
+        let wrapper = match (suite.hardware_counters, suite.count_syscalls) {
+            (true, _) => Some(("hardware_counters", "perf")),
+            (false, true) => Some(("count_syscalls", "strace")),
+            (false, false) => None,
+        };
+        if let (Some((option, program)), Some(case)) = (
+            wrapper,
+            suite
+                .cases
+                .iter()
+                .find(|case| case.memory_limit_mib.is_some()),
+        ) {
+            return Err(format!(
+                "{}: case {} can't have a memory_limit_mib with {}, as the limit would cover {} too",
+                case_at(case),
+                case.name,
+                option,
+                program
+            )
+            .into());
+        }
+        for (name, sample) in &suite.generated {
+            sample.validate().map_err(|e| {
+                let line = line_of(&text, |line| {
+                    line == format!("[generated.{}]", name)
+                        || line == format!("[generated.\"{}\"]", name)
+                });
+                format!("{}: generated sample {}: {}", at(path, line), name, e)
+            })?;
+        }
+        for (name, file) in &mut suite.corpus {
+            // The name is the file's name in the sample directory, so it mustn't lead out of it.
+            if name.contains('/') || name.contains("..") {
+                return Err(format!(
+                    "{}: corpus sample {}: the name can't contain / or ..",
+                    path.display(),
+                    name
+                )
+                .into());
+            }
+            file.sha256.make_ascii_lowercase();
+            if file.sha256.len() != 64 || !file.sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(format!(
+                    "{}: corpus sample {}: sha256 must be 64 hex digits",
+                    path.display(),
+                    name
+                )
+                .into());
+            }
+        }
+        if let Some(case) = suite.cases.iter().find(|case| {
+            case.input == InputMode::Argument
+                && case
+                    .samples
+                    .iter()
+                    .filter_map(|sample| suite.generated.get(sample))
+                    .any(|sample| !sample.ephemeral)
+        }) {
+            return Err(format!(
+                "{}: case {} passes its samples as arguments, but generated samples can only be \
+                 piped unless they're ephemeral; set ephemeral = true to write them to files",
+                case_at(case),
+                case.name
+            )
+            .into());
+        }
+        if let Some(nice) = suite.priority.as_ref().and_then(|priority| priority.nice) {
+            if !(-20..=19).contains(&nice) {
+                return Err(format!(
+                    "{}: priority: nice value {} is outside -20 to 19",
+                    path.display(),
+                    nice
+                )
+                .into());
+            }
+        }
+        if let Some(affinity) = &suite.affinity {
+            for cpus in std::iter::once(&affinity.command).chain(&affinity.harness) {
+                let cpus = parse_cpu_list(cpus)
+                    .map_err(|e| format!("{}: affinity: {}", path.display(), e))?;
+                CpuSet::new(&cpus).map_err(|e| format!("{}: affinity: {}", path.display(), e))?;
+            }
+        }
+        Ok(suite)
+    }
+
+    /// Replaces each case that has a flag matrix with one case per combination of its axes' values,
+    /// named after the combination, e.g. `groupby {grouping: -f 1, output: (none)}`.
+    fn expand_matrices(&mut self) {
+        let mut cases = vec![];
+        for case in self.cases.drain(..) {
+            let mut combinations = vec![(case.clone(), vec![])];
+            for axis in &case.matrix {
+                combinations = combinations
+                    .into_iter()
+                    .flat_map(|(partial, labels)| {
+                        axis.values.iter().map(move |value| {
+                            let mut expanded = partial.clone();
+                            expanded.command.extend(value.iter().cloned());
+                            let mut labels = labels.clone();
+                            let value = if value.is_empty() {
+                                "(none)".to_string()
+                            } else {
+                                value.join(" ")
+                            };
+                            labels.push(format!("{}: {}", axis.name, value));
+                            (expanded, labels)
+                        })
+                    })
+                    .collect();
+            }
+            for (mut expanded, labels) in combinations {
+                if !labels.is_empty() {
+                    expanded.name = format!("{} {{{}}}", case.name, labels.join(", "));
+                    expanded.matrix.clear();
+                }
+                cases.push(expanded);
+            }
+        }
+        self.cases = cases;
+    }
+
+    /// Adds a case for each baseline, run against every sample any other case uses. Fails if a
+    /// baseline's tool isn't installed.
+    fn add_baselines(&mut self) -> Result<(), String> {
+        let Some(baselines) = &self.baselines else {
+            return Ok(());
+        };
+        let mut samples: Vec<String> = vec![];
+        for sample in self.cases.iter().flat_map(|case| &case.samples) {
+            if !samples.contains(sample) {
+                samples.push(sample.clone());
+            }
+        }
+        let mut cases = vec![];
+        for baseline in &baselines.tools {
+            if git::program_dir(baseline.program()).is_none() {
+                return Err(format!(
+                    "{} needs `{}`, which isn't installed",
+                    baseline.name(),
+                    baseline.program()
+                ));
+            }
+            cases.push(Case {
+                name: format!("baseline-{}", baseline.name()),
+                command: baseline.command(baselines.group_by),
+                samples: samples.clone(),
+                source: None,
+                binaries: vec![],
+                threads: None,
+                matrix: vec![],
+                input: InputMode::Stdin,
+                // `awk` prints its groups in whatever order its hash table keeps them.
+                output_check: OutputCheck::Sorted,
+                memory_limit_mib: None,
+                cooldown: None,
+                verify: None,
+                env: BTreeMap::new(),
+                cwd: None,
+                baseline: true,
+            });
+        }
+        self.cases.extend(cases);
+        Ok(())
+    }
+
+    /// Seeds every generated sample with `seed` mixed with its name, as `mksample` seeds the
+    /// samples it builds, so each is the same text from one run to the next.
+    pub fn seed(&mut self, seed: u64) {
+        for (name, generated) in &mut self.generated {
+            generated.seed = Some(sample::sample_seed(seed, name));
+        }
+    }
+
+    /// Replaces the program of every case whose program is among the builds' binaries with the
+    /// builds, as variants named after the refs they were built from. Fails if no case's is.
+    pub fn use_builds(&mut self, builds: &[Build]) -> Result<(), String> {
+        let mut used = false;
+        for case in &mut self.cases {
+            let Some(name) = Path::new(&case.command[0]).file_name() else {
+                continue;
+            };
+            let paths: Vec<PathBuf> = builds
+                .iter()
+                .map(|build| build.release_dir.join(name))
+                .collect();
+            if !paths.iter().all(|path| path.is_file()) {
+                continue;
+            }
+            case.binaries = builds
+                .iter()
+                .zip(paths)
+                .map(|(build, path)| Binary::Named {
+                    name: build.reference.clone(),
+                    path: path.to_string_lossy().into_owned(),
+                })
+                .collect();
+            // Each build is its own checkout, which is where its results should be attributed.
+            case.source = None;
+            used = true;
+        }
+        if used {
+            Ok(())
+        } else {
+            Err("none of the cases runs a program the builds produced".to_string())
+        }
+    }
+
+    /// Returns the CPUs to pin the harness to, if any.
+    pub fn harness_cpus(&self) -> Vec<usize> {
+        self.affinity
+            .as_ref()
+            .and_then(|affinity| affinity.harness.as_deref())
+            .map_or(vec![], |cpus| {
+                parse_cpu_list(cpus).expect("CPU lists are checked when loading")
+            })
+    }
+
+    /// Returns the measurement settings that apply to every iteration in the suite.
+    pub fn measure_options(&self) -> MeasureOptions {
+        MeasureOptions {
+            memory_sample_interval: self.memory_sample_interval_ms.map(Duration::from_millis),
+            timeout: self.timeout_ms.map(Duration::from_millis),
+            hardware_counters: self.hardware_counters,
+            count_syscalls: self.count_syscalls,
+            cpu_files: CpuFiles::discover().map(Arc::new),
+            // Input modes, output checks, memory limits, and environments differ between cases,
+            // so the runner sets them.
+            input_mode: InputMode::Stdin,
+            output_check: OutputCheck::Exact,
+            env: BTreeMap::new(),
+            cwd: None,
+            cgroup: None,
+            nice: self.priority.as_ref().and_then(|priority| priority.nice),
+            reset_io_class: self
+                .priority
+                .as_ref()
+                .is_some_and(|priority| priority.harness_io.is_some()),
+            cpus: self.affinity.as_ref().map_or(vec![], |affinity| {
+                parse_cpu_list(&affinity.command).expect("CPU lists are checked when loading")
+            }),
+        }
+    }
+
+    /// Returns the full path to a sample file.
+    pub fn sample_path(&self, sample: &str) -> PathBuf {
+        self.sample_dir.join(sample)
+    }
+}
+
+/// Where in a config file something is: `path:line` if we know the line, or just the path.
+fn at(path: &Path, line: Option<usize>) -> String {
+    match line {
+        Some(line) => format!("{}:{}", path.display(), line),
+        None => path.display().to_string(),
+    }
+}
+
+/// The number, counting from 1, of the first line of `text` that `matches`, once trimmed and
+/// stripped of any comment.
+fn line_of(text: &str, matches: impl Fn(&str) -> bool) -> Option<usize> {
+    text.lines()
+        .position(|line| matches(line.split('#').next().unwrap_or_default().trim()))
+        .map(|i| i + 1)
+}
+
+impl GeneratedSample {
+    /// Returns a reader that generates the sample. Each call generates a new one, with different
+    /// random text, unless the sample is seeded.
+    pub fn reader(&self) -> Seeded<SampleReader<impl Fn() -> char + Send>> {
+        let (line_length, sample_length, options) = self.parameters();
+        let alphabet = alphabet(self.alphabet_size.unwrap_or(ALPHANUMERIC.len()));
+        let charset = self.charset;
+        let generator = move || match charset {
+            Charset::Alphanumeric => alphabet(),
+            Charset::Accented => accented(),
+        };
+        Seeded {
+            seed: self.seed,
+            reader: SampleReader::with_options(line_length, sample_length, generator, options),
+        }
+    }
+
+    /// The sample's parameters, as the generator takes them.
+    fn parameters(&self) -> (LineLength, SampleLength, Options) {
+        let line_length = match self.line_length {
+            Some([min, max]) if min == max => LineLength::Fixed(min),
+            Some([min, max]) => LineLength::Range(min..max + 1),
+            // Templated lines make their own lengths.
+            None => LineLength::Fixed(0),
+        };
+        let sample_length = match (self.lines, self.characters) {
+            (Some(lines), _) => SampleLength::Lines(lines),
+            (None, characters) => SampleLength::Characters(characters.unwrap_or(0)),
+        };
+        let options = Options {
+            duplicate_ratio: self.duplicate_ratio,
+            nul_rate: self.nul_rate,
+            invalid_utf8_rate: self.invalid_utf8_rate,
+            bom: self.bom,
+            omit_final_newline: self.omit_final_newline,
+            crlf_ratio: self.crlf_ratio,
+            blank_ratio: self.blank_ratio,
+            words: self.words,
+            cycle: self.cycle,
+            template: self.template.as_ref().map(|t| t.parse().unwrap()),
+            columns: self.columns.clone(),
+            encoding: self.encoding,
+        };
+        (line_length, sample_length, options)
+    }
+
+    /// The hash of how the sample is generated, as `mksample` records for the samples it builds.
+    /// Only what shapes the text counts, so whether it's `ephemeral`, say, doesn't change it.
+    pub fn spec_hash(&self) -> String {
+        let (line_length, sample_length, options) = self.parameters();
+        let mut spec = sample::spec(&line_length, Some(&sample_length), &options);
+        if self.charset == Charset::Accented {
+            spec += " charset=accented";
+        }
+        if let Some(alphabet_size) = self.alphabet_size {
+            spec += &format!(" alphabet_size={}", alphabet_size);
+        }
+        sample::spec_hash(&spec)
+    }
+
+    /// The sample's size in bytes, if it's known before it's generated: if its length is given
+    /// in characters, and each character is a single code unit.
+    pub fn bytes(&self) -> Option<u64> {
+        if self.encoding == Encoding::Utf8 {
+            let template = self
+                .template
+                .as_ref()
+                .map(|t| t.parse::<Template>().unwrap());
+            if self.charset == Charset::Accented || template.is_some_and(|t| !t.is_ascii()) {
+                return None;
+            }
+        }
+        self.characters
+            .map(|n| n as u64 * self.encoding.unit_size() as u64)
+    }
+
+    /// Checks that the sample is fully and consistently described.
+    fn validate(&self) -> Result<(), String> {
+        match (self.line_length, &self.template, &self.columns) {
+            (Some([min, max]), None, None) if min > max => {
+                return Err("line_length's minimum is greater than its maximum".to_string());
+            }
+            (Some(_), None, None) => {}
+            (None, Some(template), None) => {
+                let template: Template = template.parse()?;
+                if template.has_key() && self.words.is_none() {
+                    return Err("the template's {KEY}s need words to draw from".to_string());
+                }
+                if self.cycle.is_some() || self.blank_ratio > 0.0 {
+                    return Err("templated lines can't be cycled or blank".to_string());
+                }
+            }
+            (None, None, Some(_)) => {
+                if self.cycle.is_some() || self.words.is_some() || self.blank_ratio > 0.0 {
+                    return Err(
+                        "records are made of characters, and can't be cycled or blank".to_string(),
+                    );
+                }
+            }
+            _ => return Err("give exactly one of line_length, template, and columns".to_string()),
+        }
+        if self.lines.is_some() == self.characters.is_some() {
+            return Err("give exactly one of lines and characters".to_string());
+        }
+        // What `mksample` checks too, of the parameters the generator takes.
+        let (line_length, sample_length, options) = self.parameters();
+        sample::validate(&line_length, &sample_length, &options)?;
+        for (name, fraction) in [
+            ("duplicate_ratio", self.duplicate_ratio),
+            ("nul_rate", self.nul_rate),
+            ("invalid_utf8_rate", self.invalid_utf8_rate),
+            ("crlf_ratio", self.crlf_ratio),
+            ("blank_ratio", self.blank_ratio),
+        ] {
+            if !(0.0..=1.0).contains(&fraction) {
+                return Err(format!("{} must be between 0 and 1", name));
+            }
+        }
+        if self.nul_rate + self.invalid_utf8_rate > 1.0 {
+            return Err("nul_rate and invalid_utf8_rate add up to more than 1".to_string());
+        }
+        if self.bom && self.encoding.bom().is_empty() {
+            return Err(format!(
+                "{:?} samples can't start with a byte order mark, since it has none",
+                self.encoding
+            ));
+        }
+        if self.invalid_utf8_rate > 0.0 && self.encoding != Encoding::Utf8 {
+            return Err("only UTF-8 samples can have invalid UTF-8".to_string());
+        }
+        if self.alphabet_size.is_some() && self.charset != Charset::Alphanumeric {
+            return Err("alphabet_size only applies to the alphanumeric charset".to_string());
+        }
+        if self.words.is_some()
+            && self.template.is_none()
+            && (self.alphabet_size.is_some() || self.charset != Charset::Alphanumeric)
+        {
+            return Err(
+                "lines of words have no charset or alphabet_size, unless templated".to_string(),
+            );
+        }
+        if self.cycle.is_some() {
+            let random = [
+                self.duplicate_ratio,
+                self.nul_rate,
+                self.invalid_utf8_rate,
+                self.crlf_ratio,
+                self.blank_ratio,
+            ];
+            if random.iter().any(|&fraction| fraction > 0.0)
+                || self.words.is_some()
+                || self.alphabet_size.is_some()
+                || self.charset != Charset::Alphanumeric
+            {
+                return Err("cycled templates have no randomness to shape".to_string());
+            }
+        }
+        if let Some(size) = self.alphabet_size {
+            if !(1..=ALPHANUMERIC.len()).contains(&size) {
+                return Err(format!(
+                    "alphabet_size must be between 1 and {}",
+                    ALPHANUMERIC.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Case {
+    /// The git checkout `program` was built from: the case's `source`, or else the checkout
+    /// containing the program, if there is one. Baselines run standard tools, so they have none.
+    pub fn source_dir(&self, program: &str) -> Option<PathBuf> {
+        match &self.source {
+            Some(source) => Some(source.clone()),
+            None if self.baseline => None,
+            None => git::program_dir(program),
+        }
+    }
+
+    /// Returns the thread counts to run the case at, or a single `None` if it isn't swept.
+    pub fn thread_counts(&self) -> Vec<Option<usize>> {
+        match &self.threads {
+            Some(sweep) => {
+                let max = sweep
+                    .max
+                    .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+                (1..=max).map(Some).collect()
+            }
+            None => vec![None],
+        }
+    }
+
+    /// Returns the case's variants as pairs of a name and the program to run: one per binary,
+    /// named as given, or a single unnamed variant running the command's own program.
+    pub fn variants(&self) -> Vec<(Option<String>, String)> {
+        if self.binaries.is_empty() {
+            return vec![(None, self.command[0].clone())];
+        }
+        self.binaries
+            .iter()
+            .map(|binary| (Some(binary.name().to_string()), binary.path().to_string()))
+            .collect()
+    }
+
+    /// Returns the command to run at the given thread count.
+    pub fn command_with_threads(&self, threads: Option<usize>) -> Vec<String> {
+        let mut command = self.command.clone();
+        if let (Some(sweep), Some(threads)) = (&self.threads, threads) {
+            command.push(sweep.flag.clone());
+            command.push(threads.to_string());
+        }
+        command
+    }
+}
+
+#[cfg(test)]
+mod suite_tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_applied() {
+        let suite: Suite = toml::from_str(
+            r#"
+            [[case]]
+            name = "cat"
+            command = ["cat"]
+            samples = ["a.txt"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(PathBuf::from("."), suite.sample_dir);
+        assert_eq!(10, suite.iterations);
+        assert_eq!(0, suite.warmup);
+        assert_eq!(1, suite.cases.len());
+        assert_eq!(vec!["cat"], suite.cases[0].command);
+    }
+
+    #[test]
+    fn cooldown_defaults_max_wait() {
+        let suite: Suite = toml::from_str(
+            r#"
+            [[case]]
+            name = "cat"
+            command = ["cat"]
+            samples = ["a.txt"]
+            cooldown = { ms = 500, below_celsius = 60.0 }
+            "#,
+        )
+        .unwrap();
+        let cooldown = suite.cases[0].cooldown.as_ref().unwrap();
+        assert_eq!(500, cooldown.ms);
+        assert_eq!(Some(60.0), cooldown.below_celsius);
+        assert_eq!(60_000, cooldown.max_wait_ms);
+    }
+
+    #[test]
+    fn verify_is_parsed() {
+        let suite: Suite = toml::from_str(
+            r#"
+            [[case]]
+            name = "groupby"
+            command = ["groupby"]
+            samples = ["a.txt"]
+            verify = { arguments = ["--stats"], group_by = { first-chars = 2 } }
+
+            [[case]]
+            name = "uniq"
+            command = ["uniq"]
+            samples = ["a.txt"]
+            verify = { arguments = ["-c"], group_by = "line", count = "first" }
+            "#,
+        )
+        .unwrap();
+        let verify = suite.cases[0].verify.as_ref().unwrap();
+        assert_eq!(vec!["--stats"], verify.arguments);
+        assert_eq!(Grouping::FirstChars(2), verify.group_by);
+        assert_eq!(
+            Grouping::Line,
+            suite.cases[1].verify.as_ref().unwrap().group_by
+        );
+    }
+
+    #[test]
+    fn matrices_are_expanded() {
+        let mut suite: Suite = toml::from_str(
+            r#"
+            [[case]]
+            name = "groupby"
+            command = ["groupby"]
+            samples = ["a.txt"]
+            matrix = [
+                { name = "grouping", values = [["-f", "1"], ["-l", "1"]] },
+                { name = "output", values = [[], ["--stats"]] },
+            ]
+
+            [[case]]
+            name = "cat"
+            command = ["cat"]
+            samples = ["a.txt"]
+            "#,
+        )
+        .unwrap();
+        suite.expand_matrices();
+        let cases: Vec<(&str, Vec<&str>)> = suite
+            .cases
+            .iter()
+            .map(|case| {
+                let command = case.command.iter().map(String::as_str).collect();
+                (case.name.as_str(), command)
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "groupby {grouping: -f 1, output: (none)}",
+                    vec!["groupby", "-f", "1"]
+                ),
+                (
+                    "groupby {grouping: -f 1, output: --stats}",
+                    vec!["groupby", "-f", "1", "--stats"]
+                ),
+                (
+                    "groupby {grouping: -l 1, output: (none)}",
+                    vec!["groupby", "-l", "1"]
+                ),
+                (
+                    "groupby {grouping: -l 1, output: --stats}",
+                    vec!["groupby", "-l", "1", "--stats"]
+                ),
+                ("cat", vec!["cat"]),
+            ],
+            cases
+        );
+    }
+
+    #[test]
+    fn baselines_run_on_every_sample() {
+        let mut suite: Suite = toml::from_str(
+            r#"
+            baselines = { tools = ["sort-uniq", "awk"], group_by = { first-chars = 1 } }
+
+            [[case]]
+            name = "a"
+            command = ["groupby"]
+            samples = ["a.txt", "b.txt"]
+
+            [[case]]
+            name = "b"
+            command = ["groupby"]
+            samples = ["b.txt", "c.txt"]
+            "#,
+        )
+        .unwrap();
+        suite.add_baselines().unwrap();
+        let names: Vec<&str> = suite.cases.iter().map(|case| case.name.as_str()).collect();
+        assert_eq!(vec!["a", "b", "baseline-sort-uniq", "baseline-awk"], names);
+        assert_eq!(vec!["a.txt", "b.txt", "c.txt"], suite.cases[3].samples);
+    }
+
+    #[test]
+    fn priority_is_parsed() {
+        let suite: Suite = toml::from_str(
+            r#"
+            priority = { nice = -5, harness_io = "idle" }
+
+            [[case]]
+            name = "cat"
+            command = ["cat"]
+            samples = ["a.txt"]
+            "#,
+        )
+        .unwrap();
+        let options = suite.measure_options();
+        assert_eq!(Some(-5), options.nice);
+        assert!(options.reset_io_class);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let path = std::env::temp_dir().join(format!("config-test-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            r#"
+            [generated.empty]  # Oops.
+            line_length = [5, 80]
+            characters = 0
+
+            [[case]]
+            name = "cat"
+            command = ["cat"]
+            samples = ["empty"]
+            "#,
+        )
+        .unwrap();
+        let error = Suite::load(&path).unwrap_err().to_string();
+        assert_eq!(
+            format!(
+                "{}:2: generated sample empty: the sample would be empty; give it a length of at \
+                 least 1",
+                path.display()
+            ),
+            error
+        );
+
+        fs::write(
+            &path,
+            r#"
+            [[case]]
+            name = "cat"
+            command = []
+            samples = ["a.txt"]
+            "#,
+        )
+        .unwrap();
+        let error = Suite::load(&path).unwrap_err().to_string();
+        assert!(error.starts_with(&format!("{}:3: case cat", path.display())));
+
+        fs::write(
+            &path,
+            r#"
+            case = []
+            [corpus."../escape.txt"]
+            url = "https://example.com/a.txt"
+            sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            "#,
+        )
+        .unwrap();
+        let error = Suite::load(&path).unwrap_err().to_string();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            format!(
+                "{}: corpus sample ../escape.txt: the name can't contain / or ..",
+                path.display()
+            ),
+            error
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let result: Result<Suite, _> = toml::from_str(
+            r#"
+            iteratons = 3
+
+            [[case]]
+            name = "cat"
+            command = ["cat"]
+            samples = ["a.txt"]
+            "#,
+        );
+        assert!(result.is_err());
+    }
+}
+
+#[cfg(test)]
+mod case_tests {
+    use super::*;
+
+    fn case(threads: Option<ThreadSweep>) -> Case {
+        Case {
+            name: "groupby".to_string(),
+            command: vec!["groupby".to_string()],
+            samples: vec![],
+            source: None,
+            binaries: vec![],
+            threads,
+            matrix: vec![],
+            input: InputMode::Stdin,
+            output_check: OutputCheck::Exact,
+            memory_limit_mib: None,
+            cooldown: None,
+            verify: None,
+            env: BTreeMap::new(),
+            cwd: None,
+            baseline: false,
+        }
+    }
+
+    #[test]
+    fn without_sweep_runs_once() {
+        let case = case(None);
+        assert_eq!(vec![None], case.thread_counts());
+        assert_eq!(vec!["groupby"], case.command_with_threads(None));
+    }
+
+    #[test]
+    fn sweep_appends_thread_flag() {
+        let case = case(Some(ThreadSweep {
+            flag: "--threads".to_string(),
+            max: Some(3),
+        }));
+        assert_eq!(vec![Some(1), Some(2), Some(3)], case.thread_counts());
+        assert_eq!(
+            vec!["groupby", "--threads", "2"],
+            case.command_with_threads(Some(2))
+        );
+    }
+
+    #[test]
+    fn baselines_have_no_source() {
+        let mut case = case(None);
+        case.command = vec!["sh".to_string()];
+        assert!(case.source_dir("sh").is_some());
+        case.baseline = true;
+        assert_eq!(None, case.source_dir("sh"));
+        case.source = Some(PathBuf::from("src"));
+        assert_eq!(Some(PathBuf::from("src")), case.source_dir("sh"));
+    }
+
+    #[test]
+    fn binaries_are_named_after_their_files() {
+        let mut case = case(None);
+        case.binaries = vec![
+            Binary::Path("../old/target/release/groupby-old".to_string()),
+            Binary::Named {
+                name: "new".to_string(),
+                path: "target/release/groupby".to_string(),
+            },
+        ];
+        assert_eq!(
+            vec![
+                (
+                    Some("groupby-old".to_string()),
+                    "../old/target/release/groupby-old".to_string()
+                ),
+                (
+                    Some("new".to_string()),
+                    "target/release/groupby".to_string()
+                ),
+            ],
+            case.variants()
+        );
+    }
+}
+
+#[cfg(test)]
+mod generated_sample_tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn generates_exact_size() {
+        let sample = GeneratedSample {
+            line_length: Some([5, 80]),
+            characters: Some(10_000),
+            ..Default::default()
+        };
+        let mut text = String::new();
+        sample.reader().read_to_string(&mut text).unwrap();
+        assert_eq!(10_000, text.len());
+        assert!(text.lines().all(|line| line.len() <= 80));
+    }
+
+    #[test]
+    fn seeded_samples_repeat() {
+        let mut suite: Suite = toml::from_str(
+            r#"
+            case = []
+            [generated.a]
+            line_length = [5, 80]
+            lines = 100
+            [generated.b]
+            line_length = [5, 80]
+            lines = 100
+            "#,
+        )
+        .unwrap();
+        let text = |sample: &GeneratedSample| {
+            let mut text = String::new();
+            sample.reader().read_to_string(&mut text).unwrap();
+            text
+        };
+        suite.seed(7);
+        let (a, b) = (&suite.generated["a"], &suite.generated["b"]);
+        assert_eq!(text(a), text(a));
+        assert_ne!(text(a), text(b));
+    }
+
+    #[test]
+    fn validate_requires_one_length() {
+        let mut sample = GeneratedSample {
+            line_length: Some([20, 20]),
+            lines: Some(10),
+            characters: Some(10),
+            ..Default::default()
+        };
+        assert!(sample.validate().is_err());
+        sample.characters = None;
+        assert!(sample.validate().is_ok());
+        sample.line_length = Some([20, 10]);
+        assert!(sample.validate().is_err());
+        sample.line_length = Some([20, 20]);
+        sample.duplicate_ratio = 1.5;
+        assert!(sample.validate().is_err());
+        sample.duplicate_ratio = 0.0;
+        sample.alphabet_size = Some(0);
+        assert!(sample.validate().is_err());
+        sample.alphabet_size = None;
+        sample.encoding = Encoding::Latin1;
+        assert!(sample.validate().is_ok());
+        sample.bom = true;
+        assert!(sample.validate().is_err());
+        sample.bom = false;
+        sample.template = Some("{KEY} {RAND:5}".to_string());
+        assert!(sample.validate().is_err());
+        sample.line_length = None;
+        assert!(sample.validate().is_err());
+        sample.words = Some(10);
+        assert!(sample.validate().is_ok());
+        sample.template = Some("{KEY".to_string());
+        assert!(sample.validate().is_err());
+        sample.template = None;
+        sample.words = None;
+        sample.columns = Some(vec![8, 12]);
+        assert!(sample.validate().is_ok());
+        sample.columns = Some(vec![8, 0]);
+        assert!(sample.validate().is_err());
+        sample.columns = Some(vec![8, 12]);
+        sample.line_length = Some([20, 20]);
+        assert!(sample.validate().is_err());
+        sample.columns = None;
+        sample.lines = Some(0);
+        assert!(sample.validate().is_err());
+        sample.lines = Some(10);
+        sample.cycle = Some(11);
+        assert!(sample.validate().is_err());
+        sample.cycle = Some(10);
+        assert!(sample.validate().is_ok());
+    }
+
+    #[test]
+    fn spec_hash_covers_only_the_text() {
+        let mut sample = GeneratedSample {
+            line_length: Some([5, 80]),
+            characters: Some(10_000),
+            ..Default::default()
+        };
+        let hash = sample.spec_hash();
+        assert_eq!(8, hash.len());
+        sample.ephemeral = true;
+        assert_eq!(hash, sample.spec_hash());
+        sample.charset = Charset::Accented;
+        assert_ne!(hash, sample.spec_hash());
+    }
+
+    #[test]
+    fn knows_its_size_in_a_single_byte_encoding() {
+        let mut sample = GeneratedSample {
+            line_length: Some([5, 80]),
+            characters: Some(10_000),
+            charset: Charset::Accented,
+            encoding: Encoding::Windows1252,
+            ..Default::default()
+        };
+        let mut bytes = vec![];
+        sample.reader().read_to_end(&mut bytes).unwrap();
+        assert_eq!(Some(bytes.len() as u64), sample.bytes());
+        sample.encoding = Encoding::Utf16Be;
+        sample.bom = true;
+        let mut bytes = vec![];
+        sample.reader().read_to_end(&mut bytes).unwrap();
+        assert_eq!(Some(bytes.len() as u64), sample.bytes());
+        sample.encoding = Encoding::Utf8;
+        assert_eq!(None, sample.bytes());
+    }
+}
@@ -0,0 +1,134 @@
+//! Downloads real-world samples, pinned by SHA-256, into the sample directory, so suites can mix
+//! synthetic and real inputs and still run on exactly the same data everywhere.
+//!
+//! We download with `curl`, like the Pushgateway export, and hash with the `sha2` crate, which
+//! saves depending on a platform's `sha256sum` or `shasum`.
+
+use crate::config::CorpusFile;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What `fetch` did.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Fetched {
+    /// The file was already there, with the right hash.
+    AlreadyPresent,
+
+    /// The file was downloaded, replacing any with the wrong hash.
+    Downloaded,
+}
+
+/// Makes sure `path` holds `file`: downloads it unless it's already there with the pinned hash,
+/// and checks the download's hash before putting it in place, so a failed or tampered download
+/// never passes for the real thing.
+pub fn fetch(file: &CorpusFile, path: &Path) -> Result<Fetched, Box<dyn Error>> {
+    if path.exists() && sha256_file(path)? == file.sha256 {
+        return Ok(Fetched::AlreadyPresent);
+    }
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+    let result = download(&file.url, &partial).and_then(|()| {
+        let sha256 = sha256_file(&partial)?;
+        if sha256 != file.sha256 {
+            return Err(format!(
+                "{}: expected SHA-256 {}, but got {}",
+                file.url, file.sha256, sha256
+            )
+            .into());
+        }
+        fs::rename(&partial, path).map_err(|e| format!("{}: {}", path.display(), e).into())
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result.map(|()| Fetched::Downloaded)
+}
+
+fn download(url: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+    let result = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--location",
+            "--output",
+        ])
+        .arg(path)
+        .arg(url)
+        .output()
+        .map_err(|e| format!("downloading {}: curl: {}", url, e))?;
+    if !result.status.success() {
+        return Err(format!(
+            "downloading {} failed ({}): {}",
+            url,
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn sha256_file(path: &Path) -> Result<String, Box<dyn Error>> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    sha256(BufReader::new(file)).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+/// Hashes everything `reader` reads with SHA-256, returning the hash as 64 hex digits.
+pub fn sha256(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod sha256_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        let hash = |text: &[u8]| sha256(text).unwrap();
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            hash(b"")
+        );
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            hash(b"abc")
+        );
+        assert_eq!(
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            hash(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")
+        );
+        assert_eq!(
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+            hash(&[b'a'; 1_000_000])
+        );
+    }
+}
+
+#[cfg(test)]
+mod fetch_tests {
+    use super::*;
+
+    #[test]
+    fn keeps_a_file_with_the_right_hash() {
+        let dir = std::env::temp_dir().join(format!("corpus-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("abc.txt");
+        fs::write(&path, "abc").unwrap();
+        let file = CorpusFile {
+            // Never fetched, since the file is already there.
+            url: "http://localhost:1/abc.txt".to_string(),
+            sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+            bytes: None,
+        };
+        assert_eq!(Fetched::AlreadyPresent, fetch(&file, &path).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
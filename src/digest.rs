@@ -0,0 +1,143 @@
+//! Hashes what a benchmarked command writes to stdout, so we can check that every iteration of a
+//! benchmark produced the same output. A command whose output changes from run to run is either
+//! nondeterministic or broken, and either way its timings can't be compared with confidence.
+//!
+//! We use 64-bit FNV-1a: it's simple, fast enough to keep up with any command worth benchmarking,
+//! and stable across versions of Rust and of this program, so hashes can be compared across runs.
+//! It's no defense against deliberate collisions, but nobody is trying to fool us.
+
+use serde::{Deserialize, Serialize};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// How to compare a command's output across iterations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputCheck {
+    /// Don't hash the output.
+    None,
+
+    /// The output must be byte-for-byte identical.
+    #[default]
+    Exact,
+
+    /// The output must have the same lines, in any order, for commands (like a parallel
+    /// `groupby`) that may emit groups in a different order each time.
+    Sorted,
+}
+
+/// A running hash of a command's output, fed as the output arrives.
+#[derive(Debug)]
+pub struct OutputDigest {
+    check: OutputCheck,
+
+    /// The hash of everything so far, for `Exact`; of the current line, for `Sorted`.
+    hash: u64,
+
+    /// For `Sorted`, the wrapping sum of every complete line's hash. Addition doesn't care about
+    /// order, so the sum is the same however the lines are arranged.
+    line_sum: u64,
+
+    /// Whether any bytes of a line not yet ended by a newline have arrived.
+    in_line: bool,
+}
+
+impl OutputDigest {
+    pub fn new(check: OutputCheck) -> Self {
+        OutputDigest {
+            check,
+            hash: FNV_OFFSET_BASIS,
+            line_sum: 0,
+            in_line: false,
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self.check {
+            OutputCheck::None => {}
+            OutputCheck::Exact => self.hash = fnv1a(self.hash, bytes),
+            OutputCheck::Sorted => {
+                for line in bytes.split_inclusive(|&byte| byte == b'\n') {
+                    self.hash = fnv1a(self.hash, line);
+                    self.in_line = true;
+                    if line.ends_with(b"\n") {
+                        self.end_line();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the hash as 16 hex digits, or `None` if the output isn't being checked.
+    pub fn finish(mut self) -> Option<String> {
+        let hash = match self.check {
+            OutputCheck::None => return None,
+            OutputCheck::Exact => self.hash,
+            OutputCheck::Sorted => {
+                // A final line without a newline still counts, as the same line with one.
+                if self.in_line {
+                    self.hash = fnv1a(self.hash, b"\n");
+                    self.end_line();
+                }
+                self.line_sum
+            }
+        };
+        Some(format!("{:016x}", hash))
+    }
+
+    fn end_line(&mut self) {
+        self.line_sum = self.line_sum.wrapping_add(self.hash);
+        self.hash = FNV_OFFSET_BASIS;
+        self.in_line = false;
+    }
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod output_digest_tests {
+    use super::*;
+
+    fn digest(check: OutputCheck, chunks: &[&str]) -> Option<String> {
+        let mut digest = OutputDigest::new(check);
+        for chunk in chunks {
+            digest.update(chunk.as_bytes());
+        }
+        digest.finish()
+    }
+
+    #[test]
+    fn exact_matches_known_value() {
+        // The FNV-1a test vector for "a".
+        assert_eq!(
+            Some("af63dc4c8601ec8c".to_string()),
+            digest(OutputCheck::Exact, &["a"])
+        );
+    }
+
+    #[test]
+    fn exact_ignores_chunking_but_not_order() {
+        let whole = digest(OutputCheck::Exact, &["a 1\nb 2\n"]);
+        assert_eq!(whole, digest(OutputCheck::Exact, &["a ", "1\nb", " 2\n"]));
+        assert_ne!(whole, digest(OutputCheck::Exact, &["b 2\na 1\n"]));
+    }
+
+    #[test]
+    fn sorted_ignores_line_order() {
+        let whole = digest(OutputCheck::Sorted, &["a 1\nb 2\n"]);
+        assert_eq!(whole, digest(OutputCheck::Sorted, &["b 2", "\na 1"]));
+        assert_ne!(whole, digest(OutputCheck::Sorted, &["a 1\nb 3\n"]));
+    }
+
+    #[test]
+    fn none_is_none() {
+        assert_eq!(None, digest(OutputCheck::None, &["a"]));
+    }
+}
@@ -0,0 +1,447 @@
+//! Describes the machine a suite ran on. Timings from different machines can't be compared
+//! without knowing how the machines differ, so every results file records this.
+//!
+//! Everything is best effort: anything we can't find out (for example, because `/proc` only exists
+//! on Linux) is left out rather than failing the run.
+
+use crate::results::Unit;
+use serde::{Deserialize, Serialize};
+use std::ffi::CStr;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// The hardware and software a suite ran on.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Environment {
+    #[serde(default)]
+    pub hostname: Option<String>,
+
+    #[serde(default)]
+    pub cpu_model: Option<String>,
+
+    /// The number of physical CPU cores.
+    #[serde(default)]
+    pub cpu_cores: Option<usize>,
+
+    /// The number of hardware threads, i.e. logical CPUs.
+    #[serde(default)]
+    pub cpu_threads: Option<usize>,
+
+    /// Total physical memory, in bytes.
+    #[serde(default)]
+    pub memory_bytes: Option<u64>,
+
+    /// The operating system, e.g. `Ubuntu 22.04 LTS`.
+    #[serde(default)]
+    pub os: Option<String>,
+
+    /// The kernel release, e.g. `5.15.0-25-generic`.
+    #[serde(default)]
+    pub kernel: Option<String>,
+
+    /// The output of `rustc --version`, for the toolchain on `PATH`.
+    #[serde(default)]
+    pub rustc: Option<String>,
+
+    /// The type of file system the samples were read from, e.g. `ext4` or `tmpfs`.
+    #[serde(default)]
+    pub sample_fs: Option<String>,
+
+    /// The distinct CPU frequency scaling governors in use, e.g. `performance` or `powersave`.
+    /// Empty if frequency scaling isn't available or visible.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cpu_governors: Vec<String>,
+
+    /// Whether turbo boost is enabled, if we can tell.
+    #[serde(default)]
+    pub turbo: Option<bool>,
+}
+
+/// Where Linux describes the CPUs.
+const SYS_CPU: &str = "/sys/devices/system/cpu";
+
+impl Environment {
+    /// Gathers what we can about this machine. `sample_dir` is where the samples are read from.
+    pub fn capture(sample_dir: &Path) -> Environment {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok();
+        let cpu = cpuinfo.as_deref().map(parse_cpuinfo).unwrap_or_default();
+        let uname = uname();
+        Environment {
+            hostname: uname.as_ref().map(|u| u.0.clone()),
+            cpu_model: cpu.model,
+            cpu_cores: cpu.cores,
+            cpu_threads: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .ok()
+                .or(cpu.threads),
+            memory_bytes: fs::read_to_string("/proc/meminfo")
+                .ok()
+                .and_then(|text| parse_meminfo_total(&text)),
+            os: fs::read_to_string("/etc/os-release")
+                .ok()
+                .and_then(|text| parse_os_release(&text))
+                .or_else(|| uname.as_ref().map(|u| u.1.clone())),
+            kernel: uname.map(|u| u.2),
+            rustc: rustc_version(),
+            sample_fs: fs::read_to_string("/proc/mounts").ok().and_then(|mounts| {
+                let dir = sample_dir.canonicalize().ok()?;
+                mount_fs_type(&mounts, &dir)
+            }),
+            cpu_governors: governors(Path::new(SYS_CPU)),
+            turbo: turbo(Path::new(SYS_CPU)),
+        }
+    }
+
+    /// Explains what about the CPU's frequency settings could make timings unstable: any governor
+    /// other than `performance` changes the frequency with the load, and turbo boost depends on
+    /// temperature and on how many cores are busy.
+    pub fn cpu_frequency_problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let unstable: Vec<&str> = self
+            .cpu_governors
+            .iter()
+            .map(String::as_str)
+            .filter(|&governor| governor != "performance")
+            .collect();
+        if !unstable.is_empty() {
+            problems.push(format!(
+                "the CPU frequency scaling governor is {} rather than performance",
+                unstable.join(", ")
+            ));
+        }
+        if self.turbo == Some(true) {
+            problems.push("turbo boost is enabled".to_string());
+        }
+        problems
+    }
+
+    /// Describes the machine in a line, e.g. `Intel(R) Core(TM) i7-8700 (6 cores, 12 threads),
+    /// 31.2 GiB RAM, Ubuntu 22.04 LTS`.
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+        let counts: Vec<String> = [(self.cpu_cores, "core"), (self.cpu_threads, "thread")]
+            .iter()
+            .filter_map(|&(count, name)| {
+                let plural = if count? == 1 { "" } else { "s" };
+                Some(format!("{} {}{}", count?, name, plural))
+            })
+            .collect();
+        match (&self.cpu_model, counts.is_empty()) {
+            (Some(model), true) => parts.push(model.clone()),
+            (Some(model), false) => parts.push(format!("{} ({})", model, counts.join(", "))),
+            (None, false) => parts.push(counts.join(", ")),
+            (None, true) => {}
+        }
+        if let Some(bytes) = self.memory_bytes {
+            parts.push(format!("{} RAM", Unit::Bytes.format(bytes as f64)));
+        }
+        if let Some(os) = &self.os {
+            parts.push(os.clone());
+        }
+        parts.join(", ")
+    }
+}
+
+/// Reads the distinct scaling governors of every CPU under `sys_cpu`.
+fn governors(sys_cpu: &Path) -> Vec<String> {
+    let entries = match fs::read_dir(sys_cpu) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let governors: std::collections::BTreeSet<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path().join("cpufreq/scaling_governor");
+            Some(fs::read_to_string(path).ok()?.trim().to_string())
+        })
+        .collect();
+    governors.into_iter().collect()
+}
+
+/// Reads whether turbo boost is enabled, from `intel_pstate` or else the generic `cpufreq` switch.
+fn turbo(sys_cpu: &Path) -> Option<bool> {
+    let read = |path: &str| Some(fs::read_to_string(sys_cpu.join(path)).ok()?.trim() == "1");
+    match read("intel_pstate/no_turbo") {
+        Some(no_turbo) => Some(!no_turbo),
+        None => read("cpufreq/boost"),
+    }
+}
+
+/// What `/proc/cpuinfo` tells us.
+#[derive(Debug, Default, PartialEq)]
+struct CpuInfo {
+    model: Option<String>,
+    cores: Option<usize>,
+    threads: Option<usize>,
+}
+
+/// Parses `/proc/cpuinfo`. Physical cores are the distinct (package, core) pairs; without that
+/// information, as on some ARM systems, we don't guess.
+fn parse_cpuinfo(text: &str) -> CpuInfo {
+    let mut info = CpuInfo::default();
+    let mut threads = 0;
+    let mut cores = std::collections::BTreeSet::new();
+    // Processors are listed in blocks separated by blank lines.
+    for block in text.split("\n\n") {
+        let field = |name: &str| {
+            block.lines().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                (key.trim() == name).then(|| value.trim().to_string())
+            })
+        };
+        if field("processor").is_none() {
+            continue;
+        }
+        threads += 1;
+        if info.model.is_none() {
+            info.model = field("model name");
+        }
+        if let (Some(package), Some(core)) = (field("physical id"), field("core id")) {
+            cores.insert((package, core));
+        }
+    }
+    info.threads = (threads > 0).then_some(threads);
+    info.cores = (!cores.is_empty()).then_some(cores.len());
+    info
+}
+
+/// Parses the total memory out of `/proc/meminfo`, in bytes.
+fn parse_meminfo_total(text: &str) -> Option<u64> {
+    let line = text.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let kb: u64 = line
+        .trim_start_matches("MemTotal:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// Parses the human-readable name out of `/etc/os-release`.
+fn parse_os_release(text: &str) -> Option<String> {
+    let value = text
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))?;
+    Some(value.trim_matches('"').to_string())
+}
+
+/// Finds the type of the file system containing `path` in the contents of `/proc/mounts`: the
+/// type of the mount with the longest mount point that contains `path`.
+fn mount_fs_type(mounts: &str, path: &Path) -> Option<String> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            // Spaces and other special characters in mount points are escaped as octal.
+            let mount_point = fields.next()?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            path.starts_with(&mount_point)
+                .then(|| (mount_point.len(), fs_type.to_string()))
+        })
+        .max_by_key(|(length, _)| *length)
+        .map(|(_, fs_type)| fs_type)
+}
+
+/// Returns the host name, operating system name, and kernel release from `uname`.
+fn uname() -> Option<(String, String, String)> {
+    // SAFETY: `uname` fills in the struct we give it, and the fields it fills are NUL-terminated.
+    unsafe {
+        let mut name: libc::utsname = std::mem::zeroed();
+        if libc::uname(&mut name) != 0 {
+            return None;
+        }
+        let field = |chars: &[libc::c_char]| {
+            CStr::from_ptr(chars.as_ptr())
+                .to_string_lossy()
+                .into_owned()
+        };
+        Some((
+            field(&name.nodename),
+            field(&name.sysname),
+            field(&name.release),
+        ))
+    }
+}
+
+fn rustc_version() -> Option<String> {
+    let output = Command::new("rustc")
+        .arg("--version")
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+#[cfg(test)]
+mod parse_cpuinfo_tests {
+    use super::*;
+
+    #[test]
+    fn counts_cores_and_threads() {
+        let text = "\
+processor\t: 0
+model name\t: Example CPU
+physical id\t: 0
+core id\t\t: 0
+
+processor\t: 1
+model name\t: Example CPU
+physical id\t: 0
+core id\t\t: 0
+
+processor\t: 2
+model name\t: Example CPU
+physical id\t: 0
+core id\t\t: 1
+";
+        assert_eq!(
+            CpuInfo {
+                model: Some("Example CPU".to_string()),
+                cores: Some(2),
+                threads: Some(3),
+            },
+            parse_cpuinfo(text)
+        );
+    }
+
+    #[test]
+    fn without_core_ids_leaves_cores_unknown() {
+        let info = parse_cpuinfo("processor\t: 0\nBogoMIPS\t: 50.00\n");
+        assert_eq!(None, info.cores);
+        assert_eq!(None, info.model);
+        assert_eq!(Some(1), info.threads);
+    }
+}
+
+#[cfg(test)]
+mod cpu_frequency_tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, contents: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn reads_sysfs() {
+        let root = std::env::temp_dir().join(format!("sys-cpu-{}", std::process::id()));
+        write(&root, "cpu0/cpufreq/scaling_governor", "powersave\n");
+        write(&root, "cpu1/cpufreq/scaling_governor", "performance\n");
+        write(&root, "cpu2/cpufreq/scaling_governor", "powersave\n");
+        write(&root, "intel_pstate/no_turbo", "0\n");
+        write(&root, "cpufreq/boost", "0\n");
+
+        assert_eq!(vec!["performance", "powersave"], governors(&root));
+        // intel_pstate takes precedence.
+        assert_eq!(Some(true), turbo(&root));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn without_sysfs_is_unknown() {
+        let root = Path::new("/nonexistent");
+        assert!(governors(root).is_empty());
+        assert_eq!(None, turbo(root));
+    }
+
+    #[test]
+    fn problems_explain_instability() {
+        let mut environment = Environment {
+            cpu_governors: vec!["performance".to_string()],
+            turbo: Some(false),
+            ..Default::default()
+        };
+        assert!(environment.cpu_frequency_problems().is_empty());
+
+        environment.cpu_governors.push("powersave".to_string());
+        environment.turbo = Some(true);
+        assert_eq!(
+            vec![
+                "the CPU frequency scaling governor is powersave rather than performance",
+                "turbo boost is enabled",
+            ],
+            environment.cpu_frequency_problems()
+        );
+    }
+}
+
+#[cfg(test)]
+mod parse_meminfo_total_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        let text = "MemTotal:       16314216 kB\nMemFree:         1000 kB\n";
+        assert_eq!(Some(16314216 * 1024), parse_meminfo_total(text));
+    }
+}
+
+#[cfg(test)]
+mod parse_os_release_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        let text = "NAME=\"Ubuntu\"\nPRETTY_NAME=\"Ubuntu 22.04 LTS\"\nID=ubuntu\n";
+        assert_eq!(Some("Ubuntu 22.04 LTS".to_string()), parse_os_release(text));
+    }
+}
+
+#[cfg(test)]
+mod mount_fs_type_tests {
+    use super::*;
+
+    const MOUNTS: &str = "\
+/dev/sda1 / ext4 rw,relatime 0 0
+tmpfs /tmp tmpfs rw 0 0
+/dev/sdb1 /mnt/my\\040data xfs rw 0 0
+";
+
+    #[test]
+    fn picks_longest_mount_point() {
+        assert_eq!(
+            Some("tmpfs".to_string()),
+            mount_fs_type(MOUNTS, Path::new("/tmp/samples"))
+        );
+        assert_eq!(
+            Some("ext4".to_string()),
+            mount_fs_type(MOUNTS, Path::new("/home/me"))
+        );
+    }
+
+    #[test]
+    fn unescapes_mount_points() {
+        assert_eq!(
+            Some("xfs".to_string()),
+            mount_fs_type(MOUNTS, Path::new("/mnt/my data/samples"))
+        );
+    }
+}
+
+#[cfg(test)]
+mod describe_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        let environment = Environment {
+            cpu_model: Some("Example CPU".to_string()),
+            cpu_cores: Some(4),
+            cpu_threads: Some(8),
+            memory_bytes: Some(16 * 1024 * 1024 * 1024),
+            os: Some("Linux".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            "Example CPU (4 cores, 8 threads), 16.0 GiB RAM, Linux",
+            environment.describe()
+        );
+        assert_eq!("", Environment::default().describe());
+    }
+}
@@ -0,0 +1,112 @@
+//! Exports results in Bencher Metric Format (BMF), the JSON that `bencher run` accepts for
+//! pushing results to a bencher.dev dashboard.
+//!
+//! BMF maps each benchmark name to its measures, and each measure to a value with optional lower
+//! and upper bounds. Wall time goes under Bencher's built-in `latency` measure, in nanoseconds like
+//! Bencher's own adapters use; every other metric gets a custom measure named after it. We report
+//! the mean as the value and the minimum and maximum as the bounds.
+
+use crate::results::{BenchmarkResults, Metric, SuiteResults, Unit};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Value {
+    value: f64,
+    lower_value: f64,
+    upper_value: f64,
+}
+
+type Measures = BTreeMap<String, Value>;
+
+pub fn export(results: &SuiteResults, output: &Path) -> Result<(), Box<dyn Error>> {
+    let bmf: BTreeMap<String, Measures> = results
+        .benchmarks
+        .iter()
+        .map(|benchmark| (benchmark.id(), measures(benchmark)))
+        .filter(|(_, measures)| !measures.is_empty())
+        .collect();
+    let file = File::create(output).map_err(|e| format!("{}: {}", output.display(), e))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &bmf)?;
+    Ok(())
+}
+
+/// Converts a benchmark's summary statistics to BMF measures.
+fn measures(benchmark: &BenchmarkResults) -> Measures {
+    benchmark
+        .summary
+        .iter()
+        .map(|(metric, summary)| {
+            // Bencher expects times in nanoseconds.
+            let scale = match metric.unit() {
+                Unit::Seconds => 1e9,
+                _ => 1.0,
+            };
+            let value = Value {
+                value: summary.mean * scale,
+                lower_value: summary.min * scale,
+                upper_value: summary.max * scale,
+            };
+            (measure_name(*metric), value)
+        })
+        .collect()
+}
+
+/// Names the Bencher measure for a metric.
+fn measure_name(metric: Metric) -> String {
+    match metric {
+        Metric::WallTime => "latency".to_string(),
+        // Bencher measure slugs are kebab-case.
+        metric => metric.name().replace('_', "-"),
+    }
+}
+
+#[cfg(test)]
+mod measures_tests {
+    use super::*;
+    use crate::stats::Summary;
+
+    #[test]
+    fn works() {
+        let mut benchmark = BenchmarkResults {
+            case: "groupby".to_string(),
+            sample: "sample.txt".to_string(),
+            command: vec!["groupby".to_string()],
+            iterations: vec![],
+            ..Default::default()
+        };
+        benchmark.summary.insert(
+            Metric::WallTime,
+            Summary::of(&[1.0, 2.0, 3.0], &[]).unwrap(),
+        );
+        benchmark
+            .summary
+            .insert(Metric::MaxRss, Summary::of(&[1024.0], &[]).unwrap());
+
+        let measures = measures(&benchmark);
+        assert_eq!(
+            Value {
+                value: 2e9,
+                lower_value: 1e9,
+                upper_value: 3e9,
+            },
+            measures["latency"]
+        );
+        assert_eq!(1024.0, measures["max-rss"].value);
+    }
+}
+
+#[cfg(test)]
+mod measure_name_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        assert_eq!("latency", measure_name(Metric::WallTime));
+        assert_eq!("first-output", measure_name(Metric::FirstOutput));
+    }
+}
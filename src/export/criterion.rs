@@ -0,0 +1,251 @@
+//! Exports results into a `target/criterion`-style directory tree, so tools built for Criterion
+//! (such as `critcmp`) can read our external-process measurements.
+//!
+//! Criterion stores each benchmark under `<group>/<value>/<baseline>/`, with its identity in
+//! `benchmark.json`, its statistics in `estimates.json`, its raw timings in `sample.json`, and its
+//! outlier fences in `tukey.json`. We map cases to groups and samples to values. All times are in
+//! nanoseconds, as Criterion expects.
+//!
+//! Criterion computes its confidence intervals by bootstrapping. We don't, so our intervals are
+//! the usual t-based ones for the mean and rougher normal approximations for everything else.
+
+use crate::results::{BenchmarkResults, Metric, SuiteResults};
+use crate::stats::{self, Summary};
+use serde::Serialize;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+#[derive(Debug, Serialize)]
+struct BenchmarkId {
+    group_id: String,
+    function_id: Option<String>,
+    value_str: Option<String>,
+    throughput: Option<()>,
+    full_id: String,
+    directory_name: String,
+    title: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Estimates {
+    mean: Estimate,
+    median: Estimate,
+    median_abs_dev: Estimate,
+    slope: Option<Estimate>,
+    std_dev: Estimate,
+}
+
+#[derive(Debug, Serialize)]
+struct Estimate {
+    confidence_interval: ConfidenceInterval,
+    point_estimate: f64,
+    standard_error: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct ConfidenceInterval {
+    confidence_level: f64,
+    lower_bound: f64,
+    upper_bound: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct SampleData {
+    sampling_mode: &'static str,
+    iters: Vec<f64>,
+    times: Vec<f64>,
+}
+
+/// Writes every benchmark in `results` into the criterion directory `output` under the name
+/// `baseline`. Criterion itself uses `new` for the latest run and `base` for the previous one.
+pub fn export(results: &SuiteResults, output: &Path, baseline: &str) -> Result<(), Box<dyn Error>> {
+    for benchmark in &results.benchmarks {
+        let times: Vec<f64> = benchmark
+            .values(Metric::WallTime)
+            .iter()
+            .map(|seconds| seconds * 1e9)
+            .collect();
+        let estimates = match estimates(&times) {
+            Some(estimates) => estimates,
+            None => continue,
+        };
+
+        let id = benchmark_id(benchmark);
+        let dir = output.join(&id.directory_name).join(baseline);
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        write_json(&dir.join("benchmark.json"), &id)?;
+        write_json(&dir.join("estimates.json"), &estimates)?;
+        write_json(&dir.join("tukey.json"), &tukey_fences(&times))?;
+        write_json(
+            &dir.join("sample.json"),
+            &SampleData {
+                // Each of our "samples" is a single execution of the command.
+                sampling_mode: "Flat",
+                iters: vec![1.0; times.len()],
+                times,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::to_writer(BufWriter::new(file), value)?;
+    Ok(())
+}
+
+fn benchmark_id(benchmark: &BenchmarkResults) -> BenchmarkId {
+    BenchmarkId {
+        group_id: benchmark.case_label(),
+        function_id: None,
+        value_str: Some(benchmark.sample.clone()),
+        throughput: None,
+        full_id: benchmark.id(),
+        directory_name: format!(
+            "{}/{}",
+            directory_name(&benchmark.case_label()),
+            directory_name(&benchmark.sample)
+        ),
+        title: benchmark.id(),
+    }
+}
+
+/// Makes a name safe to use as a directory name, the same way Criterion does.
+fn directory_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '?' | '"' | '/' | '\\' | '*' | '<' | '>' | ':' | '|' | '^' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Computes Criterion's estimates from `times`. Returns `None` if `times` is empty.
+fn estimates(times: &[f64]) -> Option<Estimates> {
+    let summary = Summary::of(times, &[])?;
+    let n = times.len() as f64;
+    let standard_error = summary.stddev / n.sqrt();
+    // With a single time, the standard error is zero and so is the interval's width.
+    let t = match times.len() {
+        1 => 0.0,
+        n => stats::t_critical_95(n - 1),
+    };
+
+    let estimate = |point_estimate: f64, standard_error: f64, critical_value: f64| Estimate {
+        confidence_interval: ConfidenceInterval {
+            confidence_level: 0.95,
+            lower_bound: point_estimate - critical_value * standard_error,
+            upper_bound: point_estimate + critical_value * standard_error,
+        },
+        point_estimate,
+        standard_error,
+    };
+
+    // Criterion scales the MAD by 1.4826 so it estimates the standard deviation of normal data.
+    let mad = stats::median_absolute_deviation(times) * 1.4826;
+
+    // The large-sample standard errors of the median, MAD, and standard deviation of normal data.
+    Some(Estimates {
+        mean: estimate(summary.mean, standard_error, t),
+        median: estimate(summary.median, 1.2533 * standard_error, 1.96),
+        median_abs_dev: estimate(mad, 1.1664 * mad / n.sqrt(), 1.96),
+        slope: None,
+        std_dev: estimate(summary.stddev, summary.stddev / (2.0 * n).sqrt(), 1.96),
+    })
+}
+
+/// Computes Criterion's Tukey fences: low severe, low mild, high mild, and high severe.
+fn tukey_fences(times: &[f64]) -> [f64; 4] {
+    let mut sorted = times.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let q1 = stats::percentile(&sorted, 25.0);
+    let q3 = stats::percentile(&sorted, 75.0);
+    let iqr = q3 - q1;
+    [
+        q1 - 3.0 * iqr,
+        q1 - 1.5 * iqr,
+        q3 + 1.5 * iqr,
+        q3 + 3.0 * iqr,
+    ]
+}
+
+#[cfg(test)]
+mod directory_name_tests {
+    use super::*;
+
+    #[test]
+    fn replaces_unsafe_characters() {
+        assert_eq!("a_b_c.txt", directory_name("a/b:c.txt"));
+    }
+}
+
+#[cfg(test)]
+mod estimates_tests {
+    use super::*;
+
+    #[test]
+    fn with_empty_times_returns_none() {
+        assert!(estimates(&[]).is_none());
+    }
+
+    #[test]
+    fn works() {
+        let estimates = estimates(&[1.0, 2.0, 3.0, 4.0, 5.0]).unwrap();
+        assert_eq!(3.0, estimates.mean.point_estimate);
+        assert_eq!(3.0, estimates.median.point_estimate);
+        assert!(estimates.mean.confidence_interval.lower_bound < 3.0);
+        assert!(estimates.mean.confidence_interval.upper_bound > 3.0);
+        assert!((estimates.median_abs_dev.point_estimate - 1.4826).abs() < 1e-9);
+    }
+
+    #[test]
+    fn with_single_time_has_finite_bounds() {
+        let estimates = estimates(&[7.0]).unwrap();
+        assert_eq!(7.0, estimates.mean.confidence_interval.lower_bound);
+        assert_eq!(7.0, estimates.mean.confidence_interval.upper_bound);
+    }
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::*;
+    use crate::results::Iteration;
+
+    #[test]
+    fn writes_criterion_layout() {
+        let mut iteration = Iteration::default();
+        iteration.metrics.insert(Metric::WallTime, 0.5);
+        let results = SuiteResults {
+            benchmarks: vec![BenchmarkResults {
+                case: "groupby".to_string(),
+                sample: "sample.txt".to_string(),
+                command: vec!["groupby".to_string()],
+                iterations: vec![iteration],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let output = std::env::temp_dir().join(format!("criterion-export-{}", std::process::id()));
+        export(&results, &output, "new").unwrap();
+
+        let dir = output.join("groupby/sample.txt/new");
+        for file in [
+            "benchmark.json",
+            "estimates.json",
+            "sample.json",
+            "tukey.json",
+        ] {
+            assert!(dir.join(file).exists(), "missing {}", file);
+        }
+        let sample: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("sample.json")).unwrap()).unwrap();
+        assert_eq!(serde_json::json!([5e8]), sample["times"]);
+
+        fs::remove_dir_all(&output).unwrap();
+    }
+}
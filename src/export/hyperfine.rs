@@ -0,0 +1,128 @@
+//! Exports results in the schema of `hyperfine --export-json`.
+//!
+//! Each benchmark becomes one hyperfine result. Its `command` is the equivalent shell command
+//! line, and its `parameters` name the case and sample (and thread count, if any), so scripts that
+//! group hyperfine results by parameter can tell benchmarks apart. Outliers are left out, just as
+//! they are from our own summary statistics.
+
+use crate::results::{BenchmarkResults, Metric, SuiteResults};
+use crate::stats::{self, Summary};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+#[derive(Debug, Serialize)]
+struct Export {
+    results: Vec<HyperfineResult>,
+}
+
+#[derive(Debug, Serialize)]
+struct HyperfineResult {
+    command: String,
+    mean: f64,
+    stddev: f64,
+    median: f64,
+    user: f64,
+    system: f64,
+    min: f64,
+    max: f64,
+    times: Vec<f64>,
+    parameters: BTreeMap<&'static str, String>,
+}
+
+pub fn export(results: &SuiteResults, output: &Path) -> Result<(), Box<dyn Error>> {
+    let export = Export {
+        results: results.benchmarks.iter().filter_map(convert).collect(),
+    };
+    let file = File::create(output).map_err(|e| format!("{}: {}", output.display(), e))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &export)?;
+    Ok(())
+}
+
+/// Converts one benchmark to a hyperfine result. Returns `None` if it has no measured iterations.
+fn convert(benchmark: &BenchmarkResults) -> Option<HyperfineResult> {
+    let times = benchmark.values(Metric::WallTime);
+    let summary = Summary::of(&times, &[])?;
+    let mean_of = |metric| {
+        let values = benchmark.values(metric);
+        if values.is_empty() {
+            0.0
+        } else {
+            stats::mean(&values)
+        }
+    };
+
+    let mut parameters = BTreeMap::from([
+        ("case", benchmark.case.clone()),
+        ("sample", benchmark.sample.clone()),
+    ]);
+    if let Some(threads) = benchmark.threads {
+        parameters.insert("threads", threads.to_string());
+    }
+
+    Some(HyperfineResult {
+        command: benchmark.command_line(),
+        mean: summary.mean,
+        stddev: summary.stddev,
+        median: summary.median,
+        user: mean_of(Metric::UserTime),
+        system: mean_of(Metric::SystemTime),
+        min: summary.min,
+        max: summary.max,
+        times,
+        parameters,
+    })
+}
+
+#[cfg(test)]
+mod convert_tests {
+    use super::*;
+    use crate::results::Iteration;
+
+    fn iteration(wall_time: f64, user_time: f64, outlier: bool) -> Iteration {
+        let mut iteration = Iteration::default();
+        iteration.metrics.insert(Metric::WallTime, wall_time);
+        iteration.metrics.insert(Metric::UserTime, user_time);
+        iteration.outlier = outlier;
+        iteration
+    }
+
+    fn benchmark(iterations: Vec<Iteration>) -> BenchmarkResults {
+        BenchmarkResults {
+            case: "groupby".to_string(),
+            sample: "sample.txt".to_string(),
+            command: vec!["groupby".to_string(), "-f".to_string()],
+            iterations,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn works() {
+        let benchmark = benchmark(vec![
+            iteration(1.0, 0.5, false),
+            iteration(3.0, 1.5, false),
+            iteration(100.0, 99.0, true),
+        ]);
+        let result = convert(&benchmark).unwrap();
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!("groupby -f < sample.txt", json["command"]);
+        assert_eq!(2.0, json["mean"]);
+        assert_eq!(2.0, json["median"]);
+        assert_eq!(1.0, json["user"]);
+        assert_eq!(0.0, json["system"]);
+        assert_eq!(1.0, json["min"]);
+        assert_eq!(3.0, json["max"]);
+        assert_eq!(serde_json::json!([1.0, 3.0]), json["times"]);
+        assert_eq!("sample.txt", json["parameters"]["sample"]);
+    }
+
+    #[test]
+    fn without_iterations_returns_none() {
+        assert!(convert(&benchmark(vec![])).is_none());
+    }
+}
@@ -0,0 +1,55 @@
+//! Exporters that write results in the formats of other benchmarking tools, so that existing
+//! analysis scripts and dashboards can consume them.
+
+mod bmf;
+mod criterion;
+mod hyperfine;
+mod openmetrics;
+
+use crate::results::SuiteResults;
+use clap::ValueEnum;
+use std::error::Error;
+use std::path::Path;
+
+/// A format that results can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// The JSON written by `hyperfine --export-json`.
+    Hyperfine,
+
+    /// A `target/criterion`-style directory, for `critcmp` and other Criterion tooling.
+    Criterion,
+
+    /// Bencher Metric Format JSON, for `bencher run` and bencher.dev dashboards.
+    Bmf,
+
+    /// OpenMetrics text, for Prometheus (through a textfile collector or a Pushgateway) and
+    /// Grafana.
+    Openmetrics,
+}
+
+/// Settings that only apply to some formats.
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    /// The Criterion baseline name to write results under.
+    pub criterion_baseline: String,
+
+    /// For OpenMetrics, the URL of a Pushgateway to push the results to as well.
+    pub pushgateway: Option<String>,
+}
+
+/// Exports `results` to `output` in the given format. Depending on the format, `output` is either
+/// a file or a directory.
+pub fn export(
+    results: &SuiteResults,
+    format: Format,
+    output: &Path,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Hyperfine => hyperfine::export(results, output),
+        Format::Criterion => criterion::export(results, output, &options.criterion_baseline),
+        Format::Bmf => bmf::export(results, output),
+        Format::Openmetrics => openmetrics::export(results, output, options.pushgateway.as_deref()),
+    }
+}
@@ -0,0 +1,224 @@
+//! Exports results as OpenMetrics text, the exposition format Prometheus scrapes, so results can
+//! be charted in Grafana alongside everything else a team monitors. The file can be served to
+//! Prometheus through node_exporter's textfile collector, or pushed to a Pushgateway after each
+//! run.
+//!
+//! Every metric becomes a gauge family named like `groupby_benchmark_wall_time_seconds`, with one
+//! series per benchmark and statistic, labeled by case, variant, sample, and thread count. The
+//! series carry no timestamps, since neither the textfile collector nor the Pushgateway accepts
+//! them; Prometheus stamps them when it scrapes. What was run, and when, goes in the labels of a
+//! `groupby_benchmark_run` series. `# UNIT` and `# EOF` lines are comments to the older Prometheus
+//! format, so the same text works for both.
+
+use crate::results::{BenchmarkResults, Metric, SuiteResults, Unit};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// The prefix of every metric family's name.
+const PREFIX: &str = "groupby_benchmark";
+
+/// The job results are pushed to a Pushgateway under, which groups them there.
+const JOB: &str = "groupby_benchmark";
+
+pub fn export(
+    results: &SuiteResults,
+    output: &Path,
+    pushgateway: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    fs::write(output, exposition(results)).map_err(|e| format!("{}: {}", output.display(), e))?;
+    if let Some(url) = pushgateway {
+        push(output, url)?;
+    }
+    Ok(())
+}
+
+/// Renders `results` as OpenMetrics text.
+fn exposition(results: &SuiteResults) -> String {
+    let mut families: BTreeMap<Metric, Vec<&BenchmarkResults>> = BTreeMap::new();
+    for benchmark in &results.benchmarks {
+        for metric in benchmark.summary.keys() {
+            families.entry(*metric).or_default().push(benchmark);
+        }
+    }
+
+    let mut text = String::new();
+    // An OpenMetrics info metric would say this best, but the Pushgateway only takes the older
+    // Prometheus format, which has no such type. A gauge that's always 1 is the usual stand-in.
+    let run = format!("{}_run", PREFIX);
+    let mut run_labels = vec![("started_at", results.metadata.started_at.clone())];
+    if let Some(environment) = &results.metadata.environment {
+        run_labels.extend(environment.hostname.clone().map(|host| ("host", host)));
+    }
+    // Results built from several checkouts have no one commit.
+    if let [git] = results.sources()[..] {
+        run_labels.push(("commit", git.commit.clone()));
+    }
+    let _ = writeln!(text, "# TYPE {} gauge", run);
+    let _ = writeln!(text, "# HELP {} The run the results come from.", run);
+    let _ = writeln!(text, "{}{} 1", run, labels(&run_labels));
+
+    for (metric, benchmarks) in families {
+        let name = family_name(metric);
+        let _ = writeln!(text, "# TYPE {} gauge", name);
+        if let Some(unit) = unit_suffix(metric.unit()) {
+            let _ = writeln!(text, "# UNIT {} {}", name, unit);
+        }
+        let _ = writeln!(
+            text,
+            "# HELP {} Summary statistics of {} over each benchmark's iterations.",
+            name,
+            metric.name()
+        );
+        for benchmark in benchmarks {
+            let summary = &benchmark.summary[&metric];
+            let mut statistics = vec![
+                ("mean".to_string(), summary.mean),
+                ("median".to_string(), summary.median),
+                ("stddev".to_string(), summary.stddev),
+                ("min".to_string(), summary.min),
+                ("max".to_string(), summary.max),
+            ];
+            statistics.extend(summary.percentiles.clone());
+            for (statistic, value) in statistics {
+                let mut series = benchmark_labels(benchmark);
+                series.push(("statistic", statistic));
+                let _ = writeln!(text, "{}{} {}", name, labels(&series), value);
+            }
+        }
+    }
+    text.push_str("# EOF\n");
+    text
+}
+
+/// Names the family for a metric, ending in its unit as OpenMetrics requires, e.g.
+/// `groupby_benchmark_max_rss_bytes`.
+fn family_name(metric: Metric) -> String {
+    match unit_suffix(metric.unit()) {
+        Some(unit) => format!("{}_{}_{}", PREFIX, metric.name(), unit),
+        None => format!("{}_{}", PREFIX, metric.name()),
+    }
+}
+
+/// The OpenMetrics name of a unit, if it has one. Counts of events have none.
+fn unit_suffix(unit: Unit) -> Option<&'static str> {
+    match unit {
+        Unit::Seconds => Some("seconds"),
+        Unit::Bytes => Some("bytes"),
+        Unit::Count => None,
+        Unit::BytesPerSecond => Some("bytes_per_second"),
+        Unit::LinesPerSecond => Some("lines_per_second"),
+        Unit::SecondsPerByte => Some("seconds_per_byte"),
+        Unit::SecondsPerLine => Some("seconds_per_line"),
+    }
+}
+
+/// The labels that identify a benchmark's series. Absent variants and thread counts are left out
+/// rather than given empty values.
+fn benchmark_labels(benchmark: &BenchmarkResults) -> Vec<(&'static str, String)> {
+    let mut labels = vec![
+        ("case", benchmark.case.clone()),
+        ("sample", benchmark.sample.clone()),
+    ];
+    if let Some(variant) = &benchmark.variant {
+        labels.push(("variant", variant.clone()));
+    }
+    if let Some(threads) = benchmark.threads {
+        labels.push(("threads", threads.to_string()));
+    }
+    if let Some(run) = &benchmark.run {
+        labels.push(("run", run.clone()));
+    }
+    labels
+}
+
+/// Formats a label set, e.g. `{case="groupby",sample="a.txt"}`.
+fn labels(labels: &[(&str, String)]) -> String {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+/// Escapes a label value: backslashes, double quotes, and newlines.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Pushes the exported file to the Pushgateway at `url` with `curl`, replacing whatever the last
+/// run pushed.
+fn push(output: &Path, url: &str) -> Result<(), Box<dyn Error>> {
+    let target = format!("{}/metrics/job/{}", url.trim_end_matches('/'), JOB);
+    let result = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--data-binary"])
+        .arg(format!("@{}", output.display()))
+        .arg(&target)
+        .output()
+        .map_err(|e| format!("pushing to {}: curl: {}", target, e))?;
+    if !result.status.success() {
+        return Err(format!(
+            "pushing to {} failed ({}): {}",
+            target,
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod exposition_tests {
+    use super::*;
+    use crate::stats::Summary;
+
+    #[test]
+    fn works() {
+        let mut benchmark = BenchmarkResults {
+            case: "groupby".to_string(),
+            sample: "a \"quoted\" name.txt".to_string(),
+            threads: Some(4),
+            ..Default::default()
+        };
+        benchmark
+            .summary
+            .insert(Metric::WallTime, Summary::of(&[1.0, 3.0], &[90.0]).unwrap());
+        benchmark
+            .summary
+            .insert(Metric::Instructions, Summary::of(&[10.0], &[]).unwrap());
+        let mut results = SuiteResults {
+            benchmarks: vec![benchmark],
+            ..Default::default()
+        };
+        results.metadata.started_at = "2022-05-01T12:34:56Z".to_string();
+
+        let text = exposition(&results);
+        assert!(text.starts_with(
+            "# TYPE groupby_benchmark_run gauge\n\
+             # HELP groupby_benchmark_run The run the results come from.\n\
+             groupby_benchmark_run{started_at=\"2022-05-01T12:34:56Z\"} 1\n"
+        ));
+        assert!(text.contains(
+            "# TYPE groupby_benchmark_wall_time_seconds gauge\n\
+             # UNIT groupby_benchmark_wall_time_seconds seconds\n"
+        ));
+        let labels = "case=\"groupby\",sample=\"a \\\"quoted\\\" name.txt\",threads=\"4\"";
+        assert!(text.contains(&format!(
+            "groupby_benchmark_wall_time_seconds{{{},statistic=\"mean\"}} 2\n",
+            labels
+        )));
+        assert!(text.contains(&format!(
+            "groupby_benchmark_wall_time_seconds{{{},statistic=\"p90\"}} 2.8\n",
+            labels
+        )));
+        assert!(text.contains("# TYPE groupby_benchmark_instructions gauge\n# HELP"));
+        assert!(text.ends_with("# EOF\n"));
+    }
+}
@@ -0,0 +1,186 @@
+//! Looks up which version of the code a run measured, by asking `git`.
+
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The state of a git checkout.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitState {
+    /// The full hash of the commit checked out.
+    pub commit: String,
+
+    /// The branch checked out, or `None` if the HEAD is detached.
+    #[serde(default)]
+    pub branch: Option<String>,
+
+    /// Whether tracked files have uncommitted changes, in which case the code that ran doesn't
+    /// exactly match `commit`.
+    #[serde(default)]
+    pub dirty: bool,
+}
+
+impl GitState {
+    /// Describes the state briefly, e.g. `1a2b3c4d5e6f (main, dirty)`.
+    pub fn describe(&self) -> String {
+        let mut details = vec![];
+        if let Some(branch) = &self.branch {
+            details.push(branch.as_str());
+        }
+        if self.dirty {
+            details.push("dirty");
+        }
+        if details.is_empty() {
+            short(&self.commit).to_string()
+        } else {
+            format!("{} ({})", short(&self.commit), details.join(", "))
+        }
+    }
+}
+
+/// Abbreviates a commit hash. Twelve digits are plenty to tell commits apart at a glance.
+pub fn short(commit: &str) -> &str {
+    commit.get(..12).unwrap_or(commit)
+}
+
+/// Returns the state of the git checkout containing `dir`, or `None` if `dir` isn't in a
+/// repository or git isn't installed.
+pub fn state(dir: &Path) -> Option<GitState> {
+    let commit = git(dir, &["rev-parse", "HEAD"])?;
+    let branch = git(dir, &["symbolic-ref", "--short", "-q", "HEAD"]);
+    let dirty = !git(dir, &["status", "--porcelain", "--untracked-files=no"])?.is_empty();
+    Some(GitState {
+        commit,
+        branch,
+        dirty,
+    })
+}
+
+/// Returns the top directory of the checkout containing `dir`.
+pub fn toplevel(dir: &Path) -> Option<PathBuf> {
+    git(dir, &["rev-parse", "--show-toplevel"]).map(PathBuf::from)
+}
+
+/// Resolves `reference`, such as a branch, tag, or abbreviated hash, to the full hash of a commit
+/// in the repository containing `dir`.
+pub fn resolve(dir: &Path, reference: &str) -> Option<String> {
+    git(
+        dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{}^{{commit}}", reference),
+        ],
+    )
+}
+
+/// Returns when `commit` was committed, in seconds since the Unix epoch, looking it up in the
+/// repository containing `dir`.
+pub fn commit_time(dir: &Path, commit: &str) -> Option<u64> {
+    git(dir, &["show", "--no-patch", "--format=%ct", commit])?
+        .parse()
+        .ok()
+}
+
+/// Returns the state of the checkout this program's own source lives in. This is where it was
+/// built from, unless the checkout has moved on since.
+pub fn own_state() -> Option<GitState> {
+    state(Path::new(env!("CARGO_MANIFEST_DIR")))
+}
+
+/// Finds the directory containing `program`, resolving it on `PATH` the way a shell would if it
+/// has no slashes. A program built in a checkout, such as `target/release/groupby`, lives inside
+/// that checkout, so its directory leads to the commit it was built from.
+pub fn program_dir(program: &str) -> Option<PathBuf> {
+    Some(program_path(program)?.parent()?.to_path_buf())
+}
+
+/// Finds the file `program` runs, resolving it on `PATH` the way a shell would if it has no
+/// slashes.
+pub fn program_path(program: &str) -> Option<PathBuf> {
+    let path = if program.contains('/') {
+        PathBuf::from(program)
+    } else {
+        env::split_paths(&env::var_os("PATH")?)
+            .map(|dir| dir.join(program))
+            .find(|path| path.is_file())?
+    };
+    // Follow symlinks, so a program linked into `~/bin` still leads back to its checkout.
+    path.canonicalize().ok()
+}
+
+/// Runs git in `dir` and returns its trimmed output, or `None` if it fails.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    Some(text.trim().to_string())
+}
+
+#[cfg(test)]
+mod state_tests {
+    use super::*;
+
+    #[test]
+    fn outside_repository_is_none() {
+        assert_eq!(None, state(Path::new("/")));
+    }
+}
+
+#[cfg(test)]
+mod resolve_tests {
+    use super::*;
+
+    #[test]
+    fn unknown_reference_is_none() {
+        assert_eq!(
+            None,
+            resolve(
+                Path::new(env!("CARGO_MANIFEST_DIR")),
+                "no-such-ref-anywhere"
+            )
+        );
+    }
+}
+
+#[cfg(test)]
+mod git_state_tests {
+    use super::*;
+
+    #[test]
+    fn describe_works() {
+        let mut state = GitState {
+            commit: "0123456789abcdef".to_string(),
+            branch: None,
+            dirty: false,
+        };
+        assert_eq!("0123456789ab", state.describe());
+        state.branch = Some("main".to_string());
+        state.dirty = true;
+        assert_eq!("0123456789ab (main, dirty)", state.describe());
+    }
+}
+
+#[cfg(test)]
+mod program_dir_tests {
+    use super::*;
+
+    #[test]
+    fn resolves_programs_on_path() {
+        assert!(program_dir("sh").unwrap().is_dir());
+    }
+
+    #[test]
+    fn missing_program_is_none() {
+        assert_eq!(None, program_dir("no-such-program-anywhere"));
+    }
+}
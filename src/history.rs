@@ -0,0 +1,218 @@
+//! A SQLite database of the summary statistics of every run, so that performance history outlives
+//! individual results files and can be gathered from many runs and machines into one place.
+//!
+//! Each row holds one metric of one benchmark from one run, keyed by case, sample, the commit the
+//! command was built from, and when the run started. Recording the same run twice replaces its
+//! rows rather than duplicating them.
+
+use crate::results::{Metric, SuiteResults};
+use rusqlite::{params, Connection};
+use std::error::Error;
+use std::path::Path;
+
+/// The version of the database schema, stored in SQLite's `user_version`. Bump it whenever the
+/// schema changes incompatibly.
+const SCHEMA_VERSION: i32 = 1;
+
+const CREATE: &str = "
+    CREATE TABLE IF NOT EXISTS measurements (
+        case_name TEXT NOT NULL,
+        sample TEXT NOT NULL,
+        git_commit TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        metric TEXT NOT NULL,
+        unit TEXT NOT NULL,
+        iterations INTEGER NOT NULL,
+        mean REAL NOT NULL,
+        median REAL NOT NULL,
+        stddev REAL NOT NULL,
+        min REAL NOT NULL,
+        max REAL NOT NULL,
+        PRIMARY KEY (case_name, sample, git_commit, started_at, metric)
+    )";
+
+/// An open history database.
+pub struct History {
+    connection: Connection,
+}
+
+/// One metric of one benchmark from one recorded run.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub case: String,
+    pub sample: String,
+
+    /// The commit the run measured, or an empty string if it wasn't known.
+    pub commit: String,
+
+    pub started_at: String,
+    pub iterations: usize,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl History {
+    /// Opens the database at `path`, creating it if it doesn't exist.
+    pub fn open(path: &Path) -> Result<History, Box<dyn Error>> {
+        let connection =
+            Connection::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let version: i32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        match version {
+            0 => {
+                connection.execute(CREATE, [])?;
+                connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            }
+            SCHEMA_VERSION => {}
+            _ => {
+                return Err(format!(
+                    "{}: unsupported history schema version {} (expected {})",
+                    path.display(),
+                    version,
+                    SCHEMA_VERSION
+                )
+                .into())
+            }
+        }
+        Ok(History { connection })
+    }
+
+    /// Records the summary statistics of every benchmark in `results`, returning the number of
+    /// rows written.
+    pub fn record(&mut self, results: &SuiteResults) -> Result<usize, Box<dyn Error>> {
+        let metadata = &results.metadata;
+        let transaction = self.connection.transaction()?;
+        let mut rows = 0;
+        {
+            let mut insert = transaction.prepare(
+                "INSERT OR REPLACE INTO measurements VALUES \
+                 (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            )?;
+            for benchmark in &results.benchmarks {
+                for (metric, summary) in &benchmark.summary {
+                    rows += insert.execute(params![
+                        benchmark.case_label(),
+                        benchmark.sample,
+                        benchmark.git.as_ref().map_or("", |git| git.commit.as_str()),
+                        metadata.started_at,
+                        metric.name(),
+                        metric.unit().name(),
+                        benchmark.values(*metric).len() as i64,
+                        summary.mean,
+                        summary.median,
+                        summary.stddev,
+                        summary.min,
+                        summary.max,
+                    ])?;
+                }
+            }
+        }
+        transaction.commit()?;
+        Ok(rows)
+    }
+
+    /// Returns the recorded history of `metric`, oldest first, optionally narrowed to one case
+    /// and sample.
+    pub fn entries(
+        &self,
+        metric: Metric,
+        case: Option<&str>,
+        sample: Option<&str>,
+    ) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let mut select = self.connection.prepare(
+            "SELECT case_name, sample, git_commit, started_at, iterations, mean, stddev \
+             FROM measurements \
+             WHERE metric = ?1 AND (?2 IS NULL OR case_name = ?2) AND (?3 IS NULL OR sample = ?3) \
+             ORDER BY started_at, case_name, sample",
+        )?;
+        let entries = select
+            .query_map(params![metric.name(), case, sample], |row| {
+                Ok(Entry {
+                    case: row.get(0)?,
+                    sample: row.get(1)?,
+                    commit: row.get(2)?,
+                    started_at: row.get(3)?,
+                    iterations: row.get::<_, i64>(4)? as usize,
+                    mean: row.get(5)?,
+                    stddev: row.get(6)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use super::*;
+    use crate::git::GitState;
+    use crate::results::{BenchmarkResults, Iteration};
+
+    fn results(started_at: &str, commit: Option<&str>, wall_times: &[f64]) -> SuiteResults {
+        let mut benchmark = BenchmarkResults {
+            case: "groupby".to_string(),
+            sample: "sample.txt".to_string(),
+            git: commit.map(|commit| GitState {
+                commit: commit.to_string(),
+                branch: None,
+                dirty: false,
+            }),
+            iterations: wall_times
+                .iter()
+                .map(|&wall_time| {
+                    let mut iteration = Iteration::default();
+                    iteration.metrics.insert(Metric::WallTime, wall_time);
+                    iteration
+                })
+                .collect(),
+            ..Default::default()
+        };
+        benchmark.summarize(&[]);
+        let mut results = SuiteResults {
+            benchmarks: vec![benchmark],
+            ..Default::default()
+        };
+        results.metadata.started_at = started_at.to_string();
+        results
+    }
+
+    #[test]
+    fn records_and_queries_runs() {
+        let path = std::env::temp_dir().join(format!("history-{}.sqlite", std::process::id()));
+        let mut history = History::open(&path).unwrap();
+        assert_eq!(
+            1,
+            history
+                .record(&results("2022-05-02T00:00:00Z", Some("abc"), &[2.0, 4.0]))
+                .unwrap()
+        );
+        history
+            .record(&results("2022-05-01T00:00:00Z", None, &[1.0]))
+            .unwrap();
+        // Recording a run again replaces it.
+        history
+            .record(&results("2022-05-01T00:00:00Z", None, &[1.0]))
+            .unwrap();
+        drop(history);
+
+        let history = History::open(&path).unwrap();
+        let entries = history
+            .entries(Metric::WallTime, Some("groupby"), None)
+            .unwrap();
+        assert_eq!(2, entries.len());
+        assert_eq!("", entries[0].commit);
+        assert_eq!(1.0, entries[0].mean);
+        assert_eq!("abc", entries[1].commit);
+        assert_eq!(3.0, entries[1].mean);
+        assert_eq!(2, entries[1].iterations);
+
+        assert!(history
+            .entries(Metric::MaxRss, None, None)
+            .unwrap()
+            .is_empty());
+        assert!(history
+            .entries(Metric::WallTime, None, Some("other.txt"))
+            .unwrap()
+            .is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,3 @@
+//! Code shared by the `bench` and `mksample` programs.
+
+pub mod sample;
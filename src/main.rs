@@ -1,3 +1,1179 @@
-fn main() {
-    println!("Hello, world!");
+//! This program runs benchmark suites against `groupby` (or any other command) using the samples
+//! built by `mksample`. Suites are described by a TOML config file; see `bench.toml` for an
+//! example.
+
+mod affinity;
+mod anonymize;
+mod baseline;
+mod budget;
+mod build;
+mod cache;
+mod cgroup;
+mod charts;
+mod clean;
+mod compare;
+mod config;
+mod corpus;
+mod digest;
+mod environment;
+mod export;
+mod git;
+mod history;
+mod measure;
+mod perf;
+mod priority;
+mod profile;
+mod progress;
+mod rawlog;
+mod reference;
+mod report;
+mod results;
+mod runner;
+mod scaling;
+mod scramble;
+mod stats;
+mod syscalls;
+mod tags;
+mod thermal;
+mod timestamp;
+mod trend;
+mod verbosity;
+mod watch;
+
+use cache::CacheMode;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use config::Suite;
+use environment::Environment;
+use profile::Profiler;
+use results::{Metric, SuiteResults, Unit};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Mutex;
+use std::time::Duration;
+use tags::TagFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+use verbosity::Verbosity;
+
+/// The default significance level for comparisons.
+const DEFAULT_ALPHA: f64 = 0.05;
+
+#[derive(Parser)]
+#[command(about = "Benchmarks groupby against generated samples")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Runs a benchmark suite and records the results.
+    Run(RunArgs),
+
+    /// Compares two sets of results, testing each benchmark's change for significance.
+    Compare(CompareArgs),
+
+    /// Exports results in another benchmarking tool's format.
+    Export(ExportArgs),
+
+    /// Writes a report on a set of results.
+    Report(ReportArgs),
+
+    /// Combines several sets of results, e.g. from different machines, into one.
+    Merge(MergeArgs),
+
+    /// Queries or adds to the history database.
+    History(HistoryArgs),
+
+    /// Copies a real input file with its content scrambled but its structure intact, so a sample
+    /// made from private data can be shared.
+    Scramble(ScrambleArgs),
+
+    /// Downloads the suite's real-world samples into its sample directory, checking each one's
+    /// SHA-256, and skipping those already there.
+    Corpus(CorpusArgs),
+
+    /// Removes the scratch files runs leave behind, such as profiles, keeping their results.
+    Clean(CleanArgs),
+
+    /// Reruns some quick cases every time the benchmarked program is rebuilt, printing how each
+    /// changed since the last build.
+    Watch(WatchArgs),
+
+    /// Prints a completion script for a shell, e.g. `bench completions bash > ~/.bench.bash`.
+    #[command(hide = true)]
+    Completions(CompletionsArgs),
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// The suite config file.
+    #[arg(short, long, default_value = "bench.toml")]
+    config: PathBuf,
+
+    /// Also write the results here.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// The directory in which results are stored. Every run is saved under `runs/`, with a copy of
+    /// the most recent in `latest.json`; named baselines are saved under `baselines/`. Every run's
+    /// summary statistics are also added to the history database, `history.sqlite`, and every
+    /// iteration's measurements to the log, `measurements.jsonl`, as they're taken. The results so
+    /// far are kept in `partial.json` until the run finishes, for `--resume`.
+    #[arg(long, env = "BENCH_RESULTS_DIR", default_value = "results")]
+    results_dir: PathBuf,
+
+    /// Also save the results as the named baseline, replacing any baseline of that name.
+    #[arg(long, value_name = "NAME")]
+    save_baseline: Option<String>,
+
+    /// After running, compare the results against the named baseline.
+    #[arg(long, value_name = "NAME")]
+    baseline: Option<String>,
+
+    /// Tag the run, e.g. with the machine or experiment, so it can be told apart from others.
+    /// Repeat for several tags.
+    #[arg(long, value_name = "TAG", value_parser = parse_tag)]
+    tag: Vec<String>,
+
+    /// Check out and build the benchmarked program at this git ref, and run every case that uses
+    /// the program with the build instead. Repeat to compare builds, as variants of each case.
+    #[arg(long, value_name = "REF")]
+    build_ref: Vec<String>,
+
+    /// The repository to build refs from. Defaults to the checkout containing the first case's
+    /// program.
+    #[arg(long, value_name = "DIR")]
+    repo: Option<PathBuf>,
+
+    /// Instead of benchmarking, profile one iteration of CASE on each of its samples, saving the
+    /// profiles under `profiles/` in the results directory.
+    #[arg(long, value_enum, value_name = "PROFILER", requires = "case")]
+    profile: Option<Profiler>,
+
+    /// The case to profile, with --profile.
+    #[arg(requires = "profile")]
+    case: Option<String>,
+
+    /// Print nothing but failures, warnings, and wrong answers.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print every iteration's timings too; twice, every iteration's raw measurements.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Also log what happens as the suite runs to this file, as JSON lines: an event for every
+    /// finished benchmark and every problem, and the time spent on each sample, for other programs
+    /// to follow a long run by.
+    #[arg(long, value_name = "PATH")]
+    log_json: Option<PathBuf>,
+
+    /// Run up to this many cases at once, never two that share a sample. Cases running side by side
+    /// compete for CPU, memory bandwidth, and I/O, so this is for quick smoke runs, not for
+    /// measurements that matter. Not with the `cold` or `drop-caches` cache modes.
+    #[arg(
+        long,
+        value_name = "N",
+        env = "BENCH_PARALLEL",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    parallel: u64,
+
+    /// Seed the random text of the generated samples, so every run pipes the same text into the
+    /// commands. Each sample's seed is mixed with its name, as with `mksample --seed`.
+    #[arg(long, value_name = "N", env = "BENCH_SEED")]
+    seed: Option<u64>,
+
+    /// Resume the last run, if it was interrupted, skipping the benchmarks it finished. The suite
+    /// must not have changed since.
+    #[arg(long)]
+    resume: bool,
+
+    /// Run even if the CPU's frequency settings make timings unstable, e.g. a `powersave`
+    /// governor or turbo boost, and the suite sets `require_stable_cpu`.
+    #[arg(long)]
+    allow_unstable_cpu: bool,
+}
+
+#[derive(Args)]
+struct CompareArgs {
+    /// The baseline results.
+    old: PathBuf,
+
+    /// The results to compare against the baseline.
+    new: PathBuf,
+
+    /// The metric to compare.
+    #[arg(short, long, value_enum, default_value = "wall_time")]
+    metric: Metric,
+
+    /// The significance level: changes with a p-value at or above this are reported as noise.
+    #[arg(long, default_value_t = DEFAULT_ALPHA)]
+    alpha: f64,
+
+    /// Refuse to compare results whose tags don't match this expression, e.g. `laptop & !nvme`.
+    #[arg(long, value_name = "EXPR", value_parser = TagFilter::parse)]
+    tags: Option<TagFilter>,
+
+    /// Exit with an error if any benchmark significantly regresses by more than this much, e.g.
+    /// `5%`.
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percentage)]
+    fail_on_regression: Option<f64>,
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    /// The results to export.
+    results: PathBuf,
+
+    /// The format to export to.
+    #[arg(short, long, value_enum)]
+    format: export::Format,
+
+    /// Where to write the exported results: a file, or for Criterion, a directory such as
+    /// `target/criterion`.
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// For Criterion, the baseline name to export as.
+    #[arg(long, value_name = "NAME", default_value = "new")]
+    criterion_baseline: String,
+
+    /// For OpenMetrics, also push the results to the Pushgateway at this URL, e.g.
+    /// `http://localhost:9091`, with `curl`.
+    #[arg(long, value_name = "URL")]
+    pushgateway: Option<String>,
+
+    /// Leave out the hostname, full paths, and the user's name, keeping only the hardware's class
+    /// (CPU, core count, memory rounded to a power of two), so the results can be shared publicly.
+    #[arg(long)]
+    anonymize: bool,
+}
+
+#[derive(Args)]
+struct ReportArgs {
+    /// The results to report on.
+    #[arg(default_value = "results/latest.json")]
+    results: PathBuf,
+
+    /// The report format.
+    #[arg(short, long, value_enum)]
+    format: report::Format,
+
+    /// Where to write the report. Defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Render charts as SVG files into this directory, and reference them from the report.
+    #[arg(long, value_name = "DIR")]
+    charts: Option<PathBuf>,
+
+    /// Refuse to report on results whose tags don't match this expression, e.g. `laptop & !nvme`.
+    #[arg(long, value_name = "EXPR", value_parser = TagFilter::parse)]
+    tags: Option<TagFilter>,
+
+    /// For pr-comment, the results to mark changes against, e.g. the base branch's. Without them,
+    /// each variant of a case is compared with its first.
+    #[arg(long, value_name = "RESULTS")]
+    baseline: Option<PathBuf>,
+
+    /// Leave out the hostname, full paths, and the user's name, keeping only the hardware's class
+    /// (CPU, core count, memory rounded to a power of two), so the results can be shared publicly.
+    #[arg(long)]
+    anonymize: bool,
+}
+
+#[derive(Args)]
+struct MergeArgs {
+    /// The results to merge, each as a path or as `NAME=PATH`. Each run is named in the merged
+    /// results, and its benchmarks labeled, by the name given or else by its file name.
+    #[arg(required = true, value_name = "[NAME=]PATH")]
+    results: Vec<String>,
+
+    /// Where to write the merged results.
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct ScrambleArgs {
+    /// The file to scramble.
+    input: PathBuf,
+
+    /// Where to write the scrambled copy.
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct CorpusArgs {
+    /// The suite config file.
+    #[arg(short, long, default_value = "bench.toml")]
+    config: PathBuf,
+}
+
+#[derive(Args)]
+struct CleanArgs {
+    /// The suite config file, whose staging directory is checked for sample copies left behind
+    /// by runs that were killed. Ignored if it doesn't exist.
+    #[arg(short, long, default_value = "bench.toml")]
+    config: PathBuf,
+
+    /// The directory in which results are stored.
+    #[arg(long, env = "BENCH_RESULTS_DIR", default_value = "results")]
+    results_dir: PathBuf,
+
+    /// Keep profiles recorded within this many days.
+    #[arg(long, value_name = "DAYS", default_value_t = 0)]
+    keep_profiles_days: u64,
+
+    /// List what would be removed without removing anything.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct WatchArgs {
+    /// The suite config file.
+    #[arg(short, long, default_value = "bench.toml")]
+    config: PathBuf,
+
+    /// Only run this case. Repeat for several; by default, every case runs.
+    #[arg(long, value_name = "NAME")]
+    case: Vec<String>,
+
+    /// The program to watch. Defaults to the first case's program.
+    #[arg(long, value_name = "PATH")]
+    binary: Option<PathBuf>,
+
+    /// Run this many iterations of each benchmark, rather than the suite's number, for a quicker
+    /// loop.
+    #[arg(short, long, value_name = "N")]
+    iterations: Option<usize>,
+
+    /// How often to check the program for a new build, in milliseconds.
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    interval_ms: u64,
+
+    /// The directory in which results are stored. Watched runs keep their measurement log under
+    /// `watch/`, and aren't saved or added to the history.
+    #[arg(long, env = "BENCH_RESULTS_DIR", default_value = "results")]
+    results_dir: PathBuf,
+}
+
+#[derive(Args)]
+struct CompletionsArgs {
+    /// The shell: bash, elvish, fish, powershell, or zsh.
+    shell: Shell,
+}
+
+#[derive(Args)]
+struct HistoryArgs {
+    /// The history database.
+    #[arg(long, default_value = "results/history.sqlite")]
+    database: PathBuf,
+
+    #[command(subcommand)]
+    command: HistoryCommand,
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Adds existing results files to the history, e.g. from other machines.
+    Import {
+        /// The results files to add.
+        #[arg(required = true)]
+        results: Vec<PathBuf>,
+    },
+
+    /// Prints the recorded history of a metric, oldest first.
+    Show {
+        /// The metric to show.
+        #[arg(short, long, value_enum, default_value = "wall_time")]
+        metric: Metric,
+
+        /// Only show this case.
+        #[arg(long)]
+        case: Option<String>,
+
+        /// Only show this sample.
+        #[arg(long)]
+        sample: Option<String>,
+    },
+
+    /// Charts each benchmark's recorded history of a metric against commit date, marking the
+    /// commits where it got significantly worse.
+    Chart {
+        /// The metric to chart.
+        #[arg(short, long, value_enum, default_value = "wall_time")]
+        metric: Metric,
+
+        /// Only chart this case.
+        #[arg(long)]
+        case: Option<String>,
+
+        /// Only chart this sample.
+        #[arg(long)]
+        sample: Option<String>,
+
+        /// The repository to look up commit dates in. Runs whose commit isn't found there are
+        /// placed at the time they started instead.
+        #[arg(long, default_value = ".")]
+        repo: PathBuf,
+
+        /// The directory to write the charts to.
+        #[arg(short, long, default_value = "results/trends")]
+        output_dir: PathBuf,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Run(args) => run(args),
+        Command::Compare(args) => compare(args),
+        Command::Export(args) => export(args),
+        Command::Report(args) => report(args),
+        Command::Merge(args) => merge(args),
+        Command::History(args) => history(args),
+        Command::Scramble(args) => scramble(args),
+        Command::Corpus(args) => corpus(args),
+        Command::Clean(args) => clean(args),
+        Command::Watch(args) => watch(args),
+        Command::Completions(args) => {
+            completions(args);
+            Ok(())
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        // Piping a report into `head` shouldn't count as a failure.
+        Err(e) if is_broken_pipe(e.as_ref()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            if e.is::<PartialFailure>() {
+                ExitCode::from(EXIT_PARTIAL_FAILURE)
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+/// The exit code when some of the work failed but the rest was done, such as some of a suite's
+/// benchmarks, so scripts can tell it from 1, when nothing was, and from clap's 2, for bad
+/// arguments.
+const EXIT_PARTIAL_FAILURE: u8 = 3;
+
+/// An error after which the rest of the work was still done, and saved.
+#[derive(Debug)]
+struct PartialFailure(String);
+
+impl fmt::Display for PartialFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for PartialFailure {}
+
+fn is_broken_pipe(e: &(dyn Error + 'static)) -> bool {
+    matches!(e.downcast_ref::<io::Error>(), Some(e) if e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// Runs every benchmark in the suite and saves the results.
+fn run(args: RunArgs) -> Result<(), Box<dyn Error>> {
+    verbosity::set(Verbosity::from_flags(args.quiet, args.verbose));
+    let normal = verbosity::shows(Verbosity::Normal);
+    if let Some(path) = &args.log_json {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        tracing_subscriber::fmt()
+            .json()
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(Mutex::new(file))
+            .init();
+    }
+    let mut suite = Suite::load(&args.config)?;
+    if let Some(seed) = args.seed {
+        suite.seed(seed);
+    }
+    if !args.build_ref.is_empty() {
+        let repo = args
+            .repo
+            .clone()
+            .or_else(|| {
+                let case = suite.cases.iter().find(|case| !case.baseline)?;
+                git::toplevel(&case.source_dir(&case.command[0])?)
+            })
+            .ok_or("couldn't find the repository to build from; pass --repo")?;
+        let build_dir = args.results_dir.join("builds");
+        let builds = args
+            .build_ref
+            .iter()
+            .map(|reference| build::build(&repo, reference, &build_dir))
+            .collect::<Result<Vec<_>, _>>()?;
+        suite.use_builds(&builds)?;
+    }
+    if let (Some(profiler), Some(case)) = (args.profile, &args.case) {
+        let dir = args.results_dir.join("profiles");
+        let profiles = match profiler {
+            Profiler::Flamegraph => profile::flamegraphs(&suite, case, &dir)?,
+        };
+        println!("Saved {} profiles to {}", profiles.len(), dir.display());
+        return Ok(());
+    }
+
+    // Evicting samples, let alone dropping the whole page cache, would reach into the iterations of
+    // every other case running at the time.
+    if args.parallel > 1 && matches!(suite.cache, CacheMode::Cold | CacheMode::DropCaches) {
+        return Err(format!(
+            "{}: cache = \"{}\" can't be used with --parallel, as it would evict the samples of \
+             the cases running alongside",
+            args.config.display(),
+            suite.cache.name()
+        )
+        .into());
+    }
+    let unknown = budget::check(&suite, args.parallel as usize)
+        .map_err(|e| format!("{}: {}", args.config.display(), e))?;
+    if !unknown.is_empty() {
+        eprintln!(
+            "WARNING: the disk budget leaves out {}, whose size can't be told yet",
+            unknown.join(", ")
+        );
+    }
+
+    // Load the baseline up front, so a typo doesn't cost us a whole suite run.
+    let baseline = match &args.baseline {
+        Some(name) => {
+            let path = results::baseline_path(&args.results_dir, name)?;
+            if !path.exists() {
+                return Err(format!(
+                    "no baseline named {} in {}",
+                    name,
+                    args.results_dir.display()
+                )
+                .into());
+            }
+            Some(SuiteResults::load(&path)?)
+        }
+        None => None,
+    };
+
+    // Staged samples are read from the staging directory, so that's the file system that matters.
+    let environment = Environment::capture(suite.stage_dir.as_ref().unwrap_or(&suite.sample_dir));
+    let problems = environment.cpu_frequency_problems();
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("WARNING: {}", problem);
+        }
+        eprintln!("WARNING: timings will vary with the CPU frequency, not just with the code");
+        if suite.require_stable_cpu && !args.allow_unstable_cpu {
+            return Err(
+                "refusing to run with an unstable CPU frequency, as the suite requires; fix the \
+                 settings above or pass --allow-unstable-cpu"
+                    .into(),
+            );
+        }
+    }
+
+    fs::create_dir_all(&args.results_dir)
+        .map_err(|e| format!("{}: {}", args.results_dir.display(), e))?;
+    if args.parallel > 1 {
+        eprintln!(
+            "WARNING: running up to {} cases at once; they'll compete for the machine, so their \
+             timings will be less precise than a sequential run's",
+            args.parallel
+        );
+    }
+    let mut results = runner::run_suite(
+        &suite,
+        environment,
+        &args.results_dir,
+        args.resume,
+        args.parallel as usize,
+    )?;
+    results.metadata.config_path = Some(args.config.clone());
+    results.metadata.tags = args.tag.clone();
+
+    if normal {
+        println!();
+        report::write(
+            &results,
+            report::Format::Table,
+            &[],
+            None,
+            &mut io::stdout().lock(),
+        )?;
+        println!();
+    }
+
+    let path = results::run_path(&args.results_dir, &results.metadata);
+    fs::create_dir_all(path.parent().unwrap())?;
+    results.save(&path)?;
+    results.save(&results::latest_path(&args.results_dir))?;
+    let _ = fs::remove_file(results::partial_path(&args.results_dir));
+    if normal {
+        println!("Saved results to {}", path.display());
+    }
+    if let Some(output) = &args.output {
+        results.save(output)?;
+        if normal {
+            println!("Saved results to {}", output.display());
+        }
+    }
+
+    let history_path = args.results_dir.join("history.sqlite");
+    history::History::open(&history_path)?.record(&results)?;
+    if normal {
+        println!("Added results to history in {}", history_path.display());
+    }
+
+    if let Some(name) = &args.save_baseline {
+        let path = results::baseline_path(&args.results_dir, name)?;
+        fs::create_dir_all(path.parent().unwrap())?;
+        results.save(&path)?;
+        if normal {
+            println!("Saved baseline {} to {}", name, path.display());
+        }
+    }
+    let outcome = failures(&results);
+
+    if normal && results.benchmarks.iter().any(|b| b.variant.is_some()) {
+        println!();
+        println!("Comparison of each case's variants with its first:");
+        compare::compare_variants(&results, Metric::WallTime, DEFAULT_ALPHA)
+            .print(Metric::WallTime);
+    }
+
+    // Quiet runs still show regressions, as they show failures, just not the full comparison.
+    if let Some(baseline) = baseline {
+        let report = compare::compare(&baseline, &results, Metric::WallTime, DEFAULT_ALPHA);
+        let name = args.baseline.unwrap();
+        if normal {
+            println!();
+            println!("Comparison with baseline {}:", name);
+            report.print(Metric::WallTime);
+        } else if !report.regressions_beyond(0.0).is_empty() {
+            println!("Regressions against baseline {}:", name);
+            report.print_regressions(Metric::WallTime);
+        }
+        print_scaling_regressions(&baseline, &results);
+    }
+    outcome
+}
+
+/// Fails if any benchmark did: if an iteration failed, it timed out, or it gave wrong answers. If
+/// only some did, the failure is partial.
+fn failures(results: &SuiteResults) -> Result<(), Box<dyn Error>> {
+    let failed: Vec<String> = results
+        .benchmarks
+        .iter()
+        .filter(|b| b.timed_out || !b.wrong_answers.is_empty() || !b.failures().is_empty())
+        .map(|b| b.id())
+        .collect();
+    if failed.is_empty() {
+        Ok(())
+    } else if failed.len() == results.benchmarks.len() {
+        Err(format!("every benchmark failed: {}", failed.join(", ")).into())
+    } else {
+        Err(PartialFailure(format!(
+            "{} of {} benchmarks failed: {}",
+            failed.len(),
+            results.benchmarks.len(),
+            failed.join(", ")
+        ))
+        .into())
+    }
+}
+
+/// Warns about cases that scale worse with sample size in `new` than in `old`, which a
+/// per-benchmark comparison would show only as bigger regressions on bigger samples.
+fn print_scaling_regressions(old: &SuiteResults, new: &SuiteResults) {
+    for regression in scaling::regressions(old, new) {
+        println!("warning: scales worse: {}", regression);
+    }
+}
+
+/// Compares two results files and prints the per-benchmark changes.
+fn compare(args: CompareArgs) -> Result<(), Box<dyn Error>> {
+    let old = SuiteResults::load(&args.old)?;
+    let new = SuiteResults::load(&args.new)?;
+    if let Some(filter) = &args.tags {
+        check_tags(&args.old, &old, filter)?;
+        check_tags(&args.new, &new, filter)?;
+    }
+    if old.metadata.tags != new.metadata.tags {
+        println!(
+            "warning: the results have different tags ({} and {})",
+            describe_tags(&old.metadata.tags),
+            describe_tags(&new.metadata.tags)
+        );
+    }
+    // Timings from different machines aren't comparable, so make sure nobody misses it.
+    let environments = [&old, &new].map(|r| r.metadata.environment.as_ref());
+    if let [Some(old_env), Some(new_env)] = environments {
+        if old_env.hostname != new_env.hostname || old_env.cpu_model != new_env.cpu_model {
+            println!("warning: the results come from different machines");
+            println!("  old: {}", old_env.describe());
+            println!("  new: {}", new_env.describe());
+        }
+    }
+
+    // Cold and warm reads measure different things, so comparing them is usually a mistake.
+    let caches = [&old, &new].map(|r| r.metadata.suite.as_ref().map(|suite| suite.cache));
+    if let [Some(old_cache), Some(new_cache)] = caches {
+        if old_cache != new_cache {
+            println!(
+                "warning: the results were measured with different page cache modes ({} and {})",
+                old_cache.name(),
+                new_cache.name()
+            );
+        }
+    }
+
+    // Say which code each side measured, so the comparison can be attributed.
+    for (label, results) in [("old", &old), ("new", &new)] {
+        let sources: Vec<String> = results.sources().iter().map(|git| git.describe()).collect();
+        if !sources.is_empty() {
+            println!("{}: built from {}", label, sources.join(", "));
+        }
+    }
+    let report = compare::compare(&old, &new, args.metric, args.alpha);
+    report.print(args.metric);
+    print_scaling_regressions(&old, &new);
+    println!();
+    println!("{}", report.tldr());
+
+    if let Some(threshold) = args.fail_on_regression {
+        let regressions = report.regressions_beyond(threshold);
+        if !regressions.is_empty() {
+            return Err(format!(
+                "{} benchmark(s) regressed by more than {}%",
+                regressions.len(),
+                threshold * 100.0
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Exports a results file to another tool's format.
+fn export(args: ExportArgs) -> Result<(), Box<dyn Error>> {
+    let mut results = SuiteResults::load(&args.results)?;
+    if args.anonymize {
+        anonymize::anonymize(&mut results);
+    }
+    let options = export::ExportOptions {
+        criterion_baseline: args.criterion_baseline,
+        pushgateway: args.pushgateway.clone(),
+    };
+    if args.pushgateway.is_some() && args.format != export::Format::Openmetrics {
+        return Err("--pushgateway only applies to --format openmetrics".into());
+    }
+    export::export(&results, args.format, &args.output, &options)?;
+    println!("Exported results to {}", args.output.display());
+    if let Some(url) = &args.pushgateway {
+        println!("Pushed results to {}", url);
+    }
+    Ok(())
+}
+
+/// Writes a report on a results file.
+fn report(args: ReportArgs) -> Result<(), Box<dyn Error>> {
+    let mut results = SuiteResults::load(&args.results)?;
+    if let Some(filter) = &args.tags {
+        check_tags(&args.results, &results, filter)?;
+    }
+    if args.anonymize {
+        anonymize::anonymize(&mut results);
+    }
+    let charts = match &args.charts {
+        Some(dir) => charts::render_all(&results, dir)?,
+        None => vec![],
+    };
+    if args.baseline.is_some() && args.format != report::Format::PrComment {
+        return Err("--baseline only applies to --format pr-comment".into());
+    }
+    let baseline = match &args.baseline {
+        Some(path) => {
+            let mut baseline = SuiteResults::load(path)?;
+            if args.anonymize {
+                anonymize::anonymize(&mut baseline);
+            }
+            Some(baseline)
+        }
+        None => None,
+    };
+    match &args.output {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let mut out = BufWriter::new(file);
+            // The report links to the charts from its own directory, not from ours.
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let charts = charts
+                .iter()
+                .map(|chart| chart.seen_from(dir))
+                .collect::<io::Result<Vec<_>>>()?;
+            report::write(&results, args.format, &charts, baseline.as_ref(), &mut out)?;
+            out.flush()?;
+            println!("{}", report::tldr(&results));
+        }
+        None => {
+            report::write(
+                &results,
+                args.format,
+                &charts,
+                baseline.as_ref(),
+                &mut io::stdout().lock(),
+            )?;
+            // On stderr, so it stays out of a report piped elsewhere.
+            eprintln!("{}", report::tldr(&results));
+        }
+    }
+    Ok(())
+}
+
+/// Writes a scrambled copy of a file.
+fn scramble(args: ScrambleArgs) -> Result<(), Box<dyn Error>> {
+    let input = File::open(&args.input).map_err(|e| format!("{}: {}", args.input.display(), e))?;
+    let output =
+        File::create(&args.output).map_err(|e| format!("{}: {}", args.output.display(), e))?;
+    let summary = scramble::Scrambler::default()
+        .scramble(io::BufReader::new(input), BufWriter::new(output))
+        .map_err(|e| format!("{}: {}", args.output.display(), e))?;
+    println!(
+        "Scrambled {} lines, replacing {} distinct words, into {}",
+        summary.lines,
+        summary.words,
+        args.output.display()
+    );
+    Ok(())
+}
+
+/// Downloads the suite's real-world samples.
+fn corpus(args: CorpusArgs) -> Result<(), Box<dyn Error>> {
+    let suite = Suite::load(&args.config)?;
+    if suite.corpus.is_empty() {
+        println!("{} lists no corpus samples", args.config.display());
+        return Ok(());
+    }
+    fs::create_dir_all(&suite.sample_dir)
+        .map_err(|e| format!("{}: {}", suite.sample_dir.display(), e))?;
+    for (name, file) in &suite.corpus {
+        let path = suite.sample_path(name);
+        match corpus::fetch(file, &path)? {
+            corpus::Fetched::AlreadyPresent => println!("{}: already present", path.display()),
+            corpus::Fetched::Downloaded => println!("{}: downloaded", path.display()),
+        }
+    }
+    Ok(())
+}
+
+/// Removes scratch files, or with `--dry-run`, lists them.
+fn clean(args: CleanArgs) -> Result<(), Box<dyn Error>> {
+    let suite = if args.config.exists() {
+        Some(Suite::load(&args.config)?)
+    } else {
+        None
+    };
+    let stage_dir = suite.as_ref().and_then(|suite| suite.stage_dir.as_deref());
+    let keep_profiles = args
+        .keep_profiles_days
+        .checked_mul(24 * 60 * 60)
+        .map(Duration::from_secs)
+        .ok_or("--keep-profiles-days is too large")?;
+    let scratch = clean::find(
+        &args.results_dir,
+        &std::env::temp_dir(),
+        stage_dir,
+        keep_profiles,
+    )?;
+    for file in &scratch {
+        if args.dry_run {
+            println!("would remove {}", file.path.display());
+        } else {
+            fs::remove_file(&file.path).map_err(|e| format!("{}: {}", file.path.display(), e))?;
+            println!("removed {}", file.path.display());
+        }
+    }
+    let bytes = scratch.iter().map(|file| file.bytes).sum::<u64>();
+    println!(
+        "{} {} file(s), {}",
+        if args.dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        },
+        scratch.len(),
+        Unit::Bytes.format(bytes as f64)
+    );
+    Ok(())
+}
+
+/// Reruns the suite, or some of its cases, on every new build of the program, until interrupted.
+fn watch(args: WatchArgs) -> Result<(), Box<dyn Error>> {
+    let mut suite = Suite::load(&args.config)?;
+    if let Some(name) = args
+        .case
+        .iter()
+        .find(|name| !suite.cases.iter().any(|case| &case.name == *name))
+    {
+        let names: Vec<&str> = suite.cases.iter().map(|case| case.name.as_str()).collect();
+        return Err(format!("no case named {}; the cases are {}", name, names.join(", ")).into());
+    }
+    if !args.case.is_empty() {
+        suite.cases.retain(|case| args.case.contains(&case.name));
+    }
+    if let Some(iterations) = args.iterations {
+        suite.iterations = iterations;
+    }
+    let program = match args.binary {
+        Some(binary) => binary,
+        None => suite
+            .cases
+            .first()
+            .and_then(|case| git::program_path(&case.command[0]))
+            .ok_or("couldn't find the first case's program; pass --binary")?,
+    };
+    let dir = args.results_dir.join("watch");
+    fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    // Progress and summaries would bury the comparison; failures and wrong answers still print.
+    verbosity::set(Verbosity::Quiet);
+
+    let interval = Duration::from_millis(args.interval_ms);
+    let mut stamp = None;
+    let mut previous: Option<SuiteResults> = None;
+    loop {
+        stamp = Some(watch::wait_for_change(&program, stamp, interval));
+        println!();
+        println!("Running {}", program.display());
+        let environment =
+            Environment::capture(suite.stage_dir.as_ref().unwrap_or(&suite.sample_dir));
+        // A bad build shouldn't end the loop; the next one may fix it.
+        let results = match runner::run_suite(&suite, environment, &dir, false, 1) {
+            Ok(results) => results,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                continue;
+            }
+        };
+        let _ = fs::remove_file(results::partial_path(&dir));
+        match &previous {
+            Some(previous) => compare::compare(previous, &results, Metric::WallTime, DEFAULT_ALPHA)
+                .print(Metric::WallTime),
+            None => report::write(
+                &results,
+                report::Format::Table,
+                &[],
+                None,
+                &mut io::stdout().lock(),
+            )?,
+        }
+        previous = Some(results);
+        println!("Waiting for {} to change...", program.display());
+    }
+}
+
+/// Prints a completion script for the shell to stdout.
+fn completions(args: CompletionsArgs) {
+    clap_complete::generate(args.shell, &mut Cli::command(), "bench", &mut io::stdout());
+}
+
+/// Merges results files into one.
+fn merge(args: MergeArgs) -> Result<(), Box<dyn Error>> {
+    let mut inputs = vec![];
+    for input in &args.results {
+        let (name, path) = match input.split_once('=') {
+            Some((name, path)) => (name.to_string(), Path::new(path)),
+            None => {
+                let path = Path::new(input);
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                (name.into_owned(), path)
+            }
+        };
+        inputs.push((name, SuiteResults::load(path)?));
+    }
+    let merged = SuiteResults::merge(inputs)?;
+    merged.save(&args.output)?;
+    println!(
+        "Merged {} run(s) into {}",
+        merged.runs.len(),
+        args.output.display()
+    );
+    Ok(())
+}
+
+/// Adds to or queries the history database.
+fn history(args: HistoryArgs) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = args.database.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut history = history::History::open(&args.database)?;
+    match args.command {
+        HistoryCommand::Import { results } => {
+            for path in results {
+                let rows = history.record(&SuiteResults::load(&path)?)?;
+                println!("{}: added {} measurement(s)", path.display(), rows);
+            }
+        }
+        HistoryCommand::Show {
+            metric,
+            case,
+            sample,
+        } => {
+            let entries = history.entries(metric, case.as_deref(), sample.as_deref())?;
+            let unit = metric.unit();
+            for entry in entries {
+                println!(
+                    "{:<20}  {:<12}  {}/{}  {} ± {}  ({} iterations)",
+                    entry.started_at,
+                    git::short(&entry.commit),
+                    entry.case,
+                    entry.sample,
+                    unit.format(entry.mean),
+                    unit.format(entry.stddev),
+                    entry.iterations,
+                );
+            }
+        }
+        HistoryCommand::Chart {
+            metric,
+            case,
+            sample,
+            repo,
+            output_dir,
+        } => {
+            let entries = history.entries(metric, case.as_deref(), sample.as_deref())?;
+            if entries.is_empty() {
+                return Err("no matching history to chart".into());
+            }
+            let mut commit_times = HashMap::new();
+            let trends = trend::trends(entries, |entry| {
+                let commit_time = match entry.commit.as_str() {
+                    "" => None,
+                    commit => *commit_times
+                        .entry(commit.to_string())
+                        .or_insert_with(|| git::commit_time(&repo, commit)),
+                };
+                commit_time
+                    .or_else(|| timestamp::parse_rfc3339(&entry.started_at))
+                    .unwrap_or_default()
+            });
+            for trend in &trends {
+                let chart = charts::render_trend(trend, metric, &output_dir)?;
+                let regressions = trend.points.iter().filter(|p| p.regression.is_some());
+                println!(
+                    "{}: {}",
+                    chart.path.display(),
+                    compare::plural(regressions.count(), "regression")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fails if the results at `path` have tags that don't match `filter`.
+fn check_tags(
+    path: &Path,
+    results: &SuiteResults,
+    filter: &TagFilter,
+) -> Result<(), Box<dyn Error>> {
+    if filter.matches(&results.metadata.tags) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}: its tags ({}) don't match the filter",
+            path.display(),
+            describe_tags(&results.metadata.tags)
+        )
+        .into())
+    }
+}
+
+/// Lists tags for humans, e.g. `laptop, nvme`.
+fn describe_tags(tags: &[String]) -> String {
+    if tags.is_empty() {
+        "none".to_string()
+    } else {
+        tags.join(", ")
+    }
+}
+
+/// Checks a tag given on the command line.
+fn parse_tag(s: &str) -> Result<String, String> {
+    tags::validate(s)?;
+    Ok(s.to_string())
+}
+
+/// Parses a percentage such as `5%` or `5` into a fraction (`0.05`).
+fn parse_percentage(s: &str) -> Result<f64, String> {
+    let number = s.strip_suffix('%').unwrap_or(s).trim();
+    match number.parse::<f64>() {
+        Ok(percent) if percent >= 0.0 => Ok(percent / 100.0),
+        _ => Err(format!(
+            "expected a non-negative percentage like 5%, got {}",
+            s
+        )),
+    }
+}
+
+#[cfg(test)]
+mod parse_percentage_tests {
+    use super::*;
+
+    #[test]
+    fn with_percent_sign_works() {
+        assert_eq!(Ok(0.05), parse_percentage("5%"));
+        assert_eq!(Ok(0.125), parse_percentage("12.5%"));
+    }
+
+    #[test]
+    fn without_percent_sign_works() {
+        assert_eq!(Ok(0.05), parse_percentage("5"));
+    }
+
+    #[test]
+    fn with_invalid_input_fails() {
+        assert!(parse_percentage("five%").is_err());
+        assert!(parse_percentage("-5%").is_err());
+    }
+}
+
+#[cfg(test)]
+mod failures_tests {
+    use super::*;
+    use results::BenchmarkResults;
+
+    #[test]
+    fn tells_partial_failure_apart() {
+        let benchmark = |case: &str, timed_out| BenchmarkResults {
+            case: case.to_string(),
+            sample: "a.txt".to_string(),
+            timed_out,
+            ..Default::default()
+        };
+        let mut results = SuiteResults {
+            benchmarks: vec![benchmark("ok", false)],
+            ..Default::default()
+        };
+        assert!(failures(&results).is_ok());
+
+        results.benchmarks.push(benchmark("slow", true));
+        let error = failures(&results).unwrap_err();
+        assert!(error.is::<PartialFailure>());
+        assert_eq!("1 of 2 benchmarks failed: slow/a.txt", error.to_string());
+
+        results.benchmarks.remove(0);
+        assert!(!failures(&results).unwrap_err().is::<PartialFailure>());
+    }
 }
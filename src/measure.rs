@@ -0,0 +1,930 @@
+//! Runs a single iteration of a benchmarked command and measures it.
+//!
+//! We spawn the command ourselves and reap it with `wait4`, which hands us the child's resource
+//! usage (including its peak RSS) without mixing in any other children of the harness. The
+//! child's stdout is piped back to us rather than sent to `/dev/null`, so that we can tell when it
+//! starts producing output, and so that we can hash it (see `digest`). Its stderr is piped back
+//! too, and we keep the end of it, so failures can be explained in the results.
+//!
+//! The sample reaches the command in one of three ways (see `InputMode`): as stdin opened on the
+//! file, as stdin piped from the harness, or as a file name among the command's arguments. Pipes
+//! are read in small chunks and files can be read in large ones, so the modes can perform quite
+//! differently. Generated samples (see `Input`) don't exist as files, so they're always piped.
+
+use crate::affinity::CpuSet;
+use crate::cgroup::Cgroup;
+use crate::config::GeneratedSample;
+use crate::digest::{OutputCheck, OutputDigest};
+use crate::perf::PerfStat;
+use crate::priority;
+use crate::results::{Iteration, MemorySample, Metric};
+use crate::syscalls::SyscallTrace;
+use crate::thermal::{CpuFiles, CpuMonitor};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The most of a command's stderr we keep, from the end. Error messages usually come last.
+const MAX_STDERR_BYTES: usize = 4096;
+
+/// How often the `/proc` fallback checks the child's memory usage.
+const RSS_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How a command receives the sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputMode {
+    /// Stdin is the sample file itself, as with `groupby < sample`.
+    #[default]
+    Stdin,
+
+    /// Stdin is a pipe the harness copies the sample into, as with `cat sample | groupby`.
+    Pipe,
+
+    /// The sample's path is passed as an argument: in place of any argument that's exactly
+    /// `{sample}`, or else after all the others.
+    Argument,
+}
+
+/// Where an iteration's sample comes from.
+#[derive(Clone, Copy, Debug)]
+pub enum Input<'a> {
+    /// A sample file.
+    File(&'a Path),
+
+    /// A sample generated as the command reads it.
+    Generated(&'a GeneratedSample),
+}
+
+/// The placeholder for the sample's path in commands run in `InputMode::Argument`.
+pub const SAMPLE_PLACEHOLDER: &str = "{sample}";
+
+/// Settings that control how an iteration is measured.
+#[derive(Clone, Debug, Default)]
+pub struct MeasureOptions {
+    /// How the child receives the sample.
+    pub input_mode: InputMode,
+
+    /// How to hash the child's output, so iterations can be checked against each other.
+    pub output_check: OutputCheck,
+
+    /// If set, kill the child, along with any processes it started, if it runs for longer than
+    /// this.
+    pub timeout: Option<Duration>,
+
+    /// Whether to run the child under `perf stat` and record its hardware counters.
+    pub hardware_counters: bool,
+
+    /// Whether to run the child under `strace` and record its system calls.
+    pub count_syscalls: bool,
+
+    /// If set, record the child's RSS at this interval, producing a memory-over-time series.
+    pub memory_sample_interval: Option<Duration>,
+
+    /// If set, watch the CPU for thermal throttling while the child runs.
+    pub cpu_files: Option<Arc<CpuFiles>>,
+
+    /// If not empty, pin the child to these CPUs.
+    pub cpus: Vec<usize>,
+
+    /// If set, run the child at this nice value.
+    pub nice: Option<i32>,
+
+    /// If set, run the child in this cgroup.
+    pub cgroup: Option<Arc<Cgroup>>,
+
+    /// Whether to return the child to the default I/O class, rather than have it inherit the
+    /// harness's.
+    pub reset_io_class: bool,
+
+    /// Environment variables to set for the child, on top of the harness's own.
+    pub env: BTreeMap<String, String>,
+
+    /// If set, the directory to run the child in.
+    pub cwd: Option<PathBuf>,
+}
+
+/// Runs `command` once with `input` as its sample and measures it.
+///
+/// The command's output is read and discarded, and the end of its stderr is kept. Returns an error
+/// if the command can't be started or if waiting on it fails; a command that runs but fails is
+/// still measured, and its exit status recorded.
+pub fn measure(
+    command: &[String],
+    input: Input,
+    options: &MeasureOptions,
+) -> io::Result<Iteration> {
+    let perf = options.hardware_counters.then(PerfStat::new);
+    let strace = options.count_syscalls.then(SyscallTrace::new);
+    let command = match (&perf, &strace) {
+        (Some(perf), _) => perf.wrap(command),
+        (None, Some(strace)) => strace.wrap(command),
+        (None, None) => command.to_vec(),
+    };
+    // What to copy into the child's stdin, if it's a pipe.
+    let mut piped: Option<Box<dyn Read + Send>> = None;
+    let mut child = Command::new(&command[0]);
+    child
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .envs(&options.env);
+    if let Some(cwd) = &options.cwd {
+        child.current_dir(cwd);
+    }
+    match (input, options.input_mode) {
+        (Input::File(path), InputMode::Stdin) => {
+            child.args(&command[1..]).stdin(File::open(path)?);
+        }
+        (Input::File(path), InputMode::Pipe) => {
+            // Open the sample before starting the command, so a missing one fails up front.
+            piped = Some(Box::new(File::open(path)?));
+            child.args(&command[1..]).stdin(Stdio::piped());
+        }
+        (Input::File(path), InputMode::Argument) => {
+            // A relative path would be found from the wrong directory if the child runs elsewhere.
+            let path = match &options.cwd {
+                Some(_) => std::path::absolute(path)?,
+                None => path.to_path_buf(),
+            };
+            child
+                .args(arguments_with_sample(&command[1..], &path))
+                .stdin(Stdio::null());
+        }
+        (Input::Generated(_), InputMode::Argument) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "generated samples can't be passed as arguments",
+            ));
+        }
+        (Input::Generated(sample), _) => {
+            piped = Some(Box::new(sample.reader()));
+            child.args(&command[1..]).stdin(Stdio::piped());
+        }
+    }
+    if !options.cpus.is_empty() {
+        let cpus = CpuSet::new(&options.cpus)?;
+        // SAFETY: applying the set is a single system call, which is safe between fork and exec.
+        unsafe {
+            child.pre_exec(move || cpus.apply());
+        }
+    }
+    if let Some(nice) = options.nice {
+        // SAFETY: setting the nice value is a single system call.
+        unsafe {
+            child.pre_exec(move || priority::set_nice(nice));
+        }
+    }
+    if let Some(cgroup) = options.cgroup.clone() {
+        // SAFETY: joining the cgroup makes only system calls, without allocating.
+        unsafe {
+            child.pre_exec(move || cgroup.join());
+        }
+    }
+    if options.timeout.is_some() {
+        // Put the child in a process group of its own, so a timeout can kill everything it
+        // started along with it.
+        child.process_group(0);
+    }
+    if options.reset_io_class {
+        // SAFETY: setting the I/O class is a single system call.
+        unsafe {
+            child.pre_exec(|| priority::set_io_class(None));
+        }
+    }
+
+    let cpu_monitor = options.cpu_files.clone().map(CpuMonitor::start);
+    let start = Instant::now();
+    let mut child = child.spawn().map_err(|e| match (&perf, &strace) {
+        (Some(_), _) => io::Error::new(
+            e.kind(),
+            format!("running perf for hardware counters: {}", e),
+        ),
+        (None, Some(_)) => io::Error::new(
+            e.kind(),
+            format!("running strace to count system calls: {}", e),
+        ),
+        (None, None) => e,
+    })?;
+    let wrapper = perf.is_some() || strace.is_some();
+    let poller = RssPoller::start(child.id(), wrapper, start, options.memory_sample_interval);
+    let reader = spawn_output_reader(
+        child.stdout.take().unwrap(),
+        start,
+        OutputDigest::new(options.output_check),
+    );
+    let stderr_reader = spawn_stderr_reader(child.stderr.take().unwrap());
+    let writer = piped.map(|input| spawn_input_writer(input, child.stdin.take().unwrap()));
+    let watchdog = options
+        .timeout
+        .map(|timeout| Watchdog::start(child.id(), timeout));
+    let exited = wait_for_exit(child.id() as libc::pid_t);
+    let wall_time = start.elapsed();
+    // The child is a zombie until we reap it, so its I/O accounting, which by now includes that
+    // of any children it reaped, is still there to read.
+    let io = exited.ok().and_then(|()| {
+        let text = std::fs::read_to_string(format!("/proc/{}/io", child.id())).ok()?;
+        Some(parse_proc_io(&text))
+    });
+    let waited = wait4(child.id() as libc::pid_t);
+    let timed_out = watchdog.is_some_and(Watchdog::stop);
+    let polled = poller.stop();
+    let cpu = cpu_monitor.map(CpuMonitor::stop).unwrap_or_default();
+    let (status, usage) = waited?;
+
+    // The child has exited, so its end of the pipe is closed unless it left a grandchild holding
+    // it open. We don't support that, so this join shouldn't block for long.
+    let (first_output, output_hash) = reader.join().unwrap()?;
+    let stderr = stderr_reader.join().unwrap()?;
+    if let Some(writer) = writer {
+        writer.join().unwrap()?;
+    }
+
+    let mut iteration = Iteration::default();
+    iteration
+        .metrics
+        .insert(Metric::WallTime, wall_time.as_secs_f64());
+    if let Some(first_output) = first_output {
+        // If the reader thread wasn't scheduled until after the child exited, it will have seen
+        // the first byte "late." The output can't have come after the exit, though.
+        let first_output = first_output.min(wall_time);
+        iteration
+            .metrics
+            .insert(Metric::FirstOutput, first_output.as_secs_f64());
+    }
+
+    // Prefer the kernel's own accounting, but some environments (e.g. gVisor, WSL1) report zero.
+    // Under a wrapper (perf or strace), though, it's the larger of the wrapper's peak and the
+    // command's, so only the poll of the command itself will do, and if that found nothing, we'd
+    // rather have no peak than the wrapper's.
+    let max_rss = match max_rss_bytes(&usage) {
+        n if n > 0 && !wrapper => n,
+        _ => polled.peak,
+    };
+    if max_rss > 0 || !wrapper {
+        iteration.metrics.insert(Metric::MaxRss, max_rss as f64);
+    }
+    iteration
+        .metrics
+        .insert(Metric::UserTime, seconds(&usage.ru_utime));
+    iteration
+        .metrics
+        .insert(Metric::SystemTime, seconds(&usage.ru_stime));
+    // These often explain variance the timings alone can't, like a run that lost its CPU to
+    // another process, or one that had to fault its input back in from disk.
+    for (metric, count) in [
+        (Metric::VoluntarySwitches, usage.ru_nvcsw),
+        (Metric::InvoluntarySwitches, usage.ru_nivcsw),
+        (Metric::MajorFaults, usage.ru_majflt),
+        (Metric::MinorFaults, usage.ru_minflt),
+    ] {
+        iteration.metrics.insert(metric, count as f64);
+    }
+    iteration.metrics.extend(io.unwrap_or_default());
+    if let Some(perf) = perf {
+        iteration.metrics.extend(perf.finish()?);
+    }
+    if let Some(strace) = strace {
+        iteration.metrics.extend(strace.finish()?);
+    }
+    iteration.memory_timeline = polled.timeline;
+    iteration.cpu_mhz = cpu.mhz;
+    iteration.cpu_celsius = cpu.celsius;
+    iteration.throttled = cpu.throttled;
+    iteration.timed_out = timed_out;
+    iteration.output_hash = output_hash;
+    if libc::WIFEXITED(status) {
+        iteration.exit_code = Some(libc::WEXITSTATUS(status));
+    } else if libc::WIFSIGNALED(status) {
+        iteration.signal = Some(libc::WTERMSIG(status));
+    }
+    iteration.stderr = String::from_utf8_lossy(&stderr).into_owned();
+
+    Ok(iteration)
+}
+
+/// Waits for the child process `pid` to exit, without reaping it.
+fn wait_for_exit(pid: libc::pid_t) -> io::Result<()> {
+    // SAFETY: siginfo_t is plain old data, so all-zeroes is a valid value.
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: info is valid, exclusively borrowed for the duration of the call.
+        let result = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if result == 0 {
+            return Ok(());
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// Whether the child `pid` has exited, without waiting for it or reaping it.
+fn has_exited(pid: libc::pid_t) -> bool {
+    // SAFETY: siginfo_t is plain old data, so all-zeroes is a valid value.
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    // SAFETY: info is valid, exclusively borrowed for the duration of the call.
+    let result = unsafe {
+        libc::waitid(
+            libc::P_PID,
+            pid as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+        )
+    };
+    // With WNOHANG, a child that's still running leaves the pid zero.
+    // SAFETY: waitid filled in info, or left it zeroed.
+    result == 0 && unsafe { info.si_pid() } != 0
+}
+
+/// Waits for the child process `pid` to exit and returns its wait status and resource usage.
+fn wait4(pid: libc::pid_t) -> io::Result<(libc::c_int, libc::rusage)> {
+    let mut status = 0;
+    // SAFETY: rusage is plain old data, so all-zeroes is a valid value.
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: status and usage are valid, exclusively borrowed for the duration of the call.
+        let result = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+        if result == pid {
+            return Ok((status, usage));
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// Kills a process group in a background thread if it's still running after a timeout.
+struct Watchdog {
+    cancel: mpsc::Sender<()>,
+    handle: JoinHandle<bool>,
+}
+
+impl Watchdog {
+    /// Starts watching the process group led by `pid`.
+    fn start(pid: u32, timeout: Duration) -> Self {
+        let (cancel, cancelled) = mpsc::channel();
+        let handle = thread::spawn(move || match cancelled.recv_timeout(timeout) {
+            // The leader may have exited just before the timeout, before it could be stopped, and
+            // that's no timeout.
+            Err(mpsc::RecvTimeoutError::Timeout) if !has_exited(pid as libc::pid_t) => {
+                // SAFETY: kill takes no pointers. The group's leader hasn't been reaped yet, so its
+                // ID can't have been reused.
+                unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+                true
+            }
+            _ => false,
+        });
+        Watchdog { cancel, handle }
+    }
+
+    /// Stops watching, returning whether the timeout expired and the process group was killed.
+    fn stop(self) -> bool {
+        let _ = self.cancel.send(());
+        self.handle.join().unwrap_or(false)
+    }
+}
+
+/// Returns `arguments` with the sample's path substituted for the placeholder, or appended if
+/// there isn't one.
+pub fn arguments_with_sample(arguments: &[String], sample: &Path) -> Vec<OsString> {
+    let mut arguments: Vec<OsString> = arguments.iter().map(OsString::from).collect();
+    match arguments
+        .iter_mut()
+        .find(|argument| *argument == SAMPLE_PLACEHOLDER)
+    {
+        Some(argument) => *argument = sample.as_os_str().to_owned(),
+        None => arguments.push(sample.as_os_str().to_owned()),
+    }
+    arguments
+}
+
+/// Copies `input` into the child's `stdin` in a background thread, closing it when done so the
+/// child sees the end of its input.
+fn spawn_input_writer(
+    mut input: impl Read + Send + 'static,
+    mut stdin: impl Write + Send + 'static,
+) -> JoinHandle<io::Result<()>> {
+    thread::spawn(move || match io::copy(&mut input, &mut stdin) {
+        // A command that exits without reading all of its input closes the pipe on us. That's
+        // its business; it's measured all the same.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map(|_| ()),
+    })
+}
+
+/// Drains `stdout` in a background thread, returning how long after `start` the first byte
+/// arrived (or `None` if the process wrote nothing) and the output's hash, if it's being hashed.
+fn spawn_output_reader(
+    mut stdout: impl Read + Send + 'static,
+    start: Instant,
+    mut digest: OutputDigest,
+) -> JoinHandle<io::Result<(Option<Duration>, Option<String>)>> {
+    thread::spawn(move || {
+        let mut buffer = vec![0; 64 * 1024];
+        let mut first_output = None;
+        loop {
+            match stdout.read(&mut buffer) {
+                Ok(0) => return Ok((first_output, digest.finish())),
+                Ok(n) => {
+                    if first_output.is_none() {
+                        first_output = Some(start.elapsed());
+                    }
+                    digest.update(&buffer[..n]);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    })
+}
+
+/// Drains `stderr` in a background thread, returning the last `MAX_STDERR_BYTES` of it.
+fn spawn_stderr_reader(mut stderr: impl Read + Send + 'static) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut kept = vec![];
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            match stderr.read(&mut buffer) {
+                Ok(0) => return Ok(kept),
+                Ok(n) => {
+                    kept.extend_from_slice(&buffer[..n]);
+                    if kept.len() > MAX_STDERR_BYTES {
+                        kept.drain(..kept.len() - MAX_STDERR_BYTES);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    })
+}
+
+/// Converts a `timeval` from `rusage` to seconds.
+fn seconds(time: &libc::timeval) -> f64 {
+    time.tv_sec as f64 + time.tv_usec as f64 / 1e6
+}
+
+/// Extracts `ru_maxrss` in bytes. Linux reports it in kilobytes; macOS reports it in bytes.
+fn max_rss_bytes(usage: &libc::rusage) -> u64 {
+    let max_rss = usage.ru_maxrss.max(0) as u64;
+    if cfg!(target_os = "macos") {
+        max_rss
+    } else {
+        max_rss * 1024
+    }
+}
+
+/// Polls `/proc/<pid>/status` in a background thread to track a process's memory usage.
+///
+/// By default, this is only a fallback for when `ru_maxrss` is unavailable: it can miss
+/// short-lived peaks between polls, and on systems without `/proc` it simply reports zero. Under a
+/// wrapper, whose `ru_maxrss` would include its own, it's the only measure of the command's. When
+/// given a sampling interval, it also records the process's RSS at each poll, which lets reports
+/// show how memory grows over a run rather than just where it ends up.
+struct RssPoller {
+    done: Arc<AtomicBool>,
+    handle: JoinHandle<Polled>,
+}
+
+/// What an `RssPoller` observed.
+#[derive(Default)]
+struct Polled {
+    /// The peak RSS observed, in bytes.
+    peak: u64,
+
+    /// The RSS at each poll, if sampling was requested.
+    timeline: Vec<MemorySample>,
+}
+
+impl RssPoller {
+    /// Starts polling the process `pid`, which was spawned at `start`, or if it's a `wrapper` such
+    /// as `perf stat` or `strace`, the command it runs.
+    fn start(pid: u32, wrapper: bool, start: Instant, sample_interval: Option<Duration>) -> Self {
+        let done = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let done = done.clone();
+            thread::spawn(move || {
+                let mut polled = Polled::default();
+                let pid = if wrapper {
+                    match wrapped_pid(pid, &done) {
+                        Some(pid) => pid,
+                        None => return polled,
+                    }
+                } else {
+                    pid
+                };
+                let path = format!("/proc/{}/status", pid);
+                while !done.load(Ordering::Relaxed) {
+                    let status = match std::fs::read_to_string(&path) {
+                        Ok(status) => status,
+                        Err(_) => break,
+                    };
+                    let peak = parse_status_kb(&status, "VmHWM:").unwrap_or(0);
+                    polled.peak = polled.peak.max(peak);
+                    if sample_interval.is_some() {
+                        if let Some(rss) = parse_status_kb(&status, "VmRSS:") {
+                            polled.timeline.push(MemorySample {
+                                time: start.elapsed().as_secs_f64(),
+                                rss,
+                            });
+                        }
+                    }
+                    thread::sleep(sample_interval.unwrap_or(RSS_POLL_INTERVAL));
+                }
+                polled
+            })
+        };
+        RssPoller { done, handle }
+    }
+
+    /// Stops polling and returns what was observed.
+    fn stop(self) -> Polled {
+        self.done.store(true, Ordering::Relaxed);
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+/// Waits for the wrapper `pid` to start the command it wraps, and returns the command's pid, or
+/// `None` if the wrapper exits first. The command counts as started once it has executed a program
+/// other than the wrapper's, so the wrapper's own memory, copied by `fork`, never shows up as its.
+fn wrapped_pid(pid: u32, done: &AtomicBool) -> Option<u32> {
+    let wrapper_exe = std::fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    while !done.load(Ordering::Relaxed) {
+        for child in children(pid)? {
+            let exe = std::fs::read_link(format!("/proc/{}/exe", child));
+            if exe.is_ok_and(|exe| exe != wrapper_exe) {
+                return Some(child);
+            }
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    None
+}
+
+/// The pids of the children of `pid`, or `None` if it's gone. Each thread's `children` file lists
+/// them, but only in kernels built with `CONFIG_PROC_CHILDREN`, so without it we look through every
+/// process's `stat` for those whose parent is `pid`.
+fn children(pid: u32) -> Option<Vec<u32>> {
+    let tasks = std::fs::read_dir(format!("/proc/{}/task", pid)).ok()?;
+    let mut children = vec![];
+    for task in tasks.flatten() {
+        match std::fs::read_to_string(task.path().join("children")) {
+            Ok(list) => children.extend(
+                list.split_whitespace()
+                    .filter_map(|c| c.parse::<u32>().ok()),
+            ),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Some(children_by_stat(pid)),
+            Err(_) => {}
+        }
+    }
+    Some(children)
+}
+
+/// The pids of the processes whose parent, as their `/proc/<pid>/stat` gives it, is `pid`.
+fn children_by_stat(pid: u32) -> Vec<u32> {
+    let Ok(processes) = std::fs::read_dir("/proc") else {
+        return vec![];
+    };
+    processes
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|&child| {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", child)).unwrap_or_default();
+            parse_stat_ppid(&stat) == Some(pid)
+        })
+        .collect()
+}
+
+/// Parses the parent's pid out of a `/proc/<pid>/stat` file. It's the second field after the
+/// command's name, which is in parentheses and can itself contain spaces and parentheses.
+fn parse_stat_ppid(stat: &str) -> Option<u32> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Parses a memory line (e.g. `VmHWM:`, the peak RSS) of a `/proc/<pid>/status` file, returning
+/// bytes.
+fn parse_status_kb(status: &str, key: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with(key))?;
+    let kilobytes = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Parses a `/proc/<pid>/io` file into the I/O metrics: `rchar` and `wchar` count every byte
+/// passed to `read` and `write`, while `read_bytes` and `write_bytes` count only those that reached
+/// storage.
+fn parse_proc_io(text: &str) -> Vec<(Metric, f64)> {
+    const FIELDS: [(&str, Metric); 4] = [
+        ("rchar:", Metric::BytesRead),
+        ("read_bytes:", Metric::DiskRead),
+        ("wchar:", Metric::BytesWritten),
+        ("write_bytes:", Metric::DiskWritten),
+    ];
+    let mut metrics = vec![];
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(key), Some(value)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let metric = FIELDS.iter().find(|&&(name, _)| name == key);
+        if let (Some(&(_, metric)), Ok(value)) = (metric, value.parse::<u64>()) {
+            metrics.push((metric, value as f64));
+        }
+    }
+    metrics
+}
+
+#[cfg(test)]
+mod parse_proc_io_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        let text = "rchar: 5000\nwchar: 120\nsyscr: 3\nsyscw: 1\nread_bytes: 4096\n\
+                    write_bytes: 0\ncancelled_write_bytes: 0\n";
+        assert_eq!(
+            vec![
+                (Metric::BytesRead, 5000.0),
+                (Metric::BytesWritten, 120.0),
+                (Metric::DiskRead, 4096.0),
+                (Metric::DiskWritten, 0.0),
+            ],
+            parse_proc_io(text)
+        );
+    }
+}
+
+#[cfg(test)]
+mod parse_status_kb_tests {
+    use super::*;
+
+    #[test]
+    fn with_matching_line_works() {
+        let status =
+            "Name:\tgroupby\nVmPeak:\t   10000 kB\nVmHWM:\t    2048 kB\nVmRSS:\t    1024 kB\n";
+        assert_eq!(Some(2048 * 1024), parse_status_kb(status, "VmHWM:"));
+        assert_eq!(Some(1024 * 1024), parse_status_kb(status, "VmRSS:"));
+    }
+
+    #[test]
+    fn without_matching_line_returns_none() {
+        // Kernel threads, for instance, have no memory lines at all.
+        let status = "Name:\tkthreadd\nState:\tS (sleeping)\n";
+        assert_eq!(None, parse_status_kb(status, "VmHWM:"));
+    }
+}
+
+#[cfg(test)]
+mod wrapped_pid_tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_wrapped_command() {
+        // The shell forks sleep rather than executing it, as it has more to run after.
+        let mut wrapper = Command::new("sh")
+            .args(["-c", "sleep 2; true"])
+            .spawn()
+            .unwrap();
+        let pid = wrapped_pid(wrapper.id(), &AtomicBool::new(false)).unwrap();
+        let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).unwrap();
+        assert_eq!("sleep", comm.trim_end());
+        assert_eq!(vec![pid], children_by_stat(wrapper.id()));
+        wrapper.kill().unwrap();
+        wrapper.wait().unwrap();
+    }
+
+    #[test]
+    fn parses_the_parent_pid() {
+        assert_eq!(Some(1), parse_stat_ppid("42 (a) b) S 1 42 42 0"));
+        assert_eq!(None, parse_stat_ppid("42 (a"));
+    }
+}
+
+#[cfg(test)]
+mod measure_tests {
+    use super::*;
+
+    #[test]
+    fn records_wall_time_and_max_rss() {
+        let command = vec!["cat".to_string()];
+        let options = MeasureOptions::default();
+        let iteration = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        assert!(iteration.metrics[&Metric::WallTime] > 0.0);
+        assert!(iteration.metrics[&Metric::MaxRss] > 0.0);
+        assert!(iteration.metrics.contains_key(&Metric::UserTime));
+        assert!(iteration.metrics.contains_key(&Metric::SystemTime));
+        assert!(iteration.metrics[&Metric::MinorFaults] > 0.0);
+        assert!(iteration.metrics.contains_key(&Metric::VoluntarySwitches));
+        assert!(iteration.memory_timeline.is_empty());
+    }
+
+    #[test]
+    fn records_bytes_read() {
+        if !Path::new("/proc/self/io").exists() {
+            return;
+        }
+        let command = vec!["cat".to_string()];
+        let options = MeasureOptions::default();
+        let iteration = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        let size = std::fs::metadata("Cargo.toml").unwrap().len() as f64;
+        assert!(iteration.metrics[&Metric::BytesRead] >= size);
+        assert!(iteration.metrics[&Metric::BytesWritten] >= size);
+    }
+
+    #[test]
+    fn hashes_output() {
+        let command = vec!["cat".to_string()];
+        let options = MeasureOptions::default();
+        let first = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        let second = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        assert!(first.output_hash.is_some());
+        assert_eq!(first.output_hash, second.output_hash);
+    }
+
+    #[test]
+    fn records_time_to_first_output() {
+        let command = vec!["cat".to_string()];
+        let options = MeasureOptions::default();
+        let iteration = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        let first_output = iteration.metrics[&Metric::FirstOutput];
+        assert!(first_output > 0.0);
+        assert!(first_output <= iteration.metrics[&Metric::WallTime]);
+    }
+
+    #[test]
+    fn without_output_omits_time_to_first_output() {
+        let command = vec!["true".to_string()];
+        let options = MeasureOptions::default();
+        let iteration = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        assert!(!iteration.metrics.contains_key(&Metric::FirstOutput));
+    }
+
+    #[test]
+    fn pipes_input() {
+        let command = vec!["wc".to_string(), "-c".to_string()];
+        let options = MeasureOptions {
+            input_mode: InputMode::Pipe,
+            ..Default::default()
+        };
+        let iteration = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        assert!(iteration.metrics.contains_key(&Metric::FirstOutput));
+    }
+
+    #[test]
+    fn with_early_exit_ignores_closed_pipe() {
+        let command = vec!["true".to_string()];
+        let options = MeasureOptions {
+            input_mode: InputMode::Pipe,
+            ..Default::default()
+        };
+        assert!(measure(&command, Input::File(Path::new("Cargo.toml")), &options).is_ok());
+    }
+
+    #[test]
+    fn pipes_generated_input() {
+        let command = vec!["wc".to_string(), "-c".to_string()];
+        let sample = GeneratedSample {
+            line_length: Some([5, 80]),
+            characters: Some(1_000_000),
+            ..Default::default()
+        };
+        let options = MeasureOptions::default();
+        let iteration = measure(&command, Input::Generated(&sample), &options).unwrap();
+        assert!(iteration.metrics.contains_key(&Metric::FirstOutput));
+    }
+
+    #[test]
+    fn sets_environment_and_directory() {
+        let command = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "[ \"$GROUPBY_TEST\" = yes ] && [ \"$(pwd)\" = / ] && cat \"$1\"".to_string(),
+            "sh".to_string(),
+        ];
+        let options = MeasureOptions {
+            input_mode: InputMode::Argument,
+            env: BTreeMap::from([("GROUPBY_TEST".to_string(), "yes".to_string())]),
+            cwd: Some(PathBuf::from("/")),
+            ..Default::default()
+        };
+        let iteration = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        assert_eq!(Some(0), iteration.exit_code);
+    }
+
+    #[test]
+    fn with_memory_sampling_records_timeline() {
+        let command = vec!["sleep".to_string(), "0.1".to_string()];
+        let options = MeasureOptions {
+            memory_sample_interval: Some(Duration::from_millis(5)),
+            ..Default::default()
+        };
+        let iteration = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        assert!(!iteration.memory_timeline.is_empty());
+        let times: Vec<f64> = iteration.memory_timeline.iter().map(|s| s.time).collect();
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn with_timeout_kills_process_tree() {
+        let command = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "sleep 10; sleep 10".to_string(),
+        ];
+        let options = MeasureOptions {
+            timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let iteration = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        assert!(iteration.timed_out);
+        assert_eq!(Some(libc::SIGKILL), iteration.signal);
+        assert!(iteration.metrics[&Metric::WallTime] < 5.0);
+    }
+
+    #[test]
+    fn within_timeout_is_not_timed_out() {
+        let command = vec!["true".to_string()];
+        let options = MeasureOptions {
+            timeout: Some(Duration::from_secs(10)),
+            ..Default::default()
+        };
+        let iteration = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        assert!(!iteration.timed_out);
+    }
+
+    #[test]
+    fn records_exit_code_and_stderr() {
+        let command = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "echo oops >&2; exit 3".to_string(),
+        ];
+        let options = MeasureOptions::default();
+        let iteration = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        assert_eq!(Some(3), iteration.exit_code);
+        assert_eq!("oops\n", iteration.stderr);
+        assert!(iteration.failed());
+    }
+
+    #[test]
+    fn keeps_end_of_long_stderr() {
+        let command = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "head -c 100000 /dev/zero | tr '\\0' x >&2; echo end >&2".to_string(),
+        ];
+        let options = MeasureOptions::default();
+        let iteration = measure(&command, Input::File(Path::new("Cargo.toml")), &options).unwrap();
+        assert_eq!(Some(0), iteration.exit_code);
+        assert_eq!(MAX_STDERR_BYTES, iteration.stderr.len());
+        assert!(iteration.stderr.ends_with("xend\n"));
+    }
+
+    #[test]
+    fn with_missing_program_fails() {
+        let command = vec!["/nonexistent/groupby".to_string()];
+        let options = MeasureOptions::default();
+        assert!(measure(&command, Input::File(Path::new("Cargo.toml")), &options).is_err());
+    }
+}
+
+#[cfg(test)]
+mod arguments_with_sample_tests {
+    use super::*;
+
+    #[test]
+    fn replaces_placeholder() {
+        let arguments = ["-i".to_string(), "{sample}".to_string(), "-v".to_string()];
+        assert_eq!(
+            vec!["-i", "a.txt", "-v"],
+            arguments_with_sample(&arguments, Path::new("a.txt"))
+        );
+    }
+
+    #[test]
+    fn without_placeholder_appends() {
+        let arguments = ["-v".to_string()];
+        assert_eq!(
+            vec!["-v", "a.txt"],
+            arguments_with_sample(&arguments, Path::new("a.txt"))
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Hardware performance counters, read by running each iteration under `perf stat` (Linux only).
+//!
+//! Counters explain timings: whether a change executed fewer instructions, or the same ones with
+//! fewer cache misses. `perf stat` counts the command and everything it starts, and writes the
+//! counts to a file of its own, leaving the command's stdout and stderr alone. It does add its
+//! own startup to the wall time, a few milliseconds, and its own memory to the peak RSS, so
+//! compare counted runs with counted runs.
+
+use crate::results::Metric;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The events we count, as `perf` names them, and the metrics we record them as.
+const EVENTS: [(&str, Metric); 4] = [
+    ("instructions", Metric::Instructions),
+    ("cycles", Metric::Cycles),
+    ("branch-misses", Metric::BranchMisses),
+    ("cache-misses", Metric::CacheMisses),
+];
+
+/// Numbers the output files of `perf stat` runs, so no two share one.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// One iteration's run under `perf stat`.
+pub struct PerfStat {
+    /// Where `perf stat` writes its counts.
+    output: PathBuf,
+}
+
+impl PerfStat {
+    pub fn new() -> PerfStat {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        PerfStat {
+            output: std::env::temp_dir().join(format!(
+                "bench-perf-{}-{}.csv",
+                std::process::id(),
+                id
+            )),
+        }
+    }
+
+    /// Returns `command` wrapped in `perf stat`.
+    pub fn wrap(&self, command: &[String]) -> Vec<String> {
+        let events: Vec<&str> = EVENTS.iter().map(|&(event, _)| event).collect();
+        let mut wrapped: Vec<String> = ["perf", "stat", "-x", ",", "-e", &events.join(","), "-o"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        wrapped.push(self.output.to_string_lossy().into_owned());
+        wrapped.push("--".to_string());
+        wrapped.extend(command.iter().cloned());
+        wrapped
+    }
+
+    /// Reads the counts once the command has exited. Events the machine can't count (common in
+    /// virtual machines) are left out.
+    pub fn finish(self) -> io::Result<Vec<(Metric, f64)>> {
+        let text = fs::read_to_string(&self.output);
+        let _ = fs::remove_file(&self.output);
+        Ok(parse(&text?))
+    }
+}
+
+/// Parses the CSV `perf stat -x ,` writes, whose lines start with a count, a unit, and an event
+/// name, e.g. `123456,,instructions:u,...`. Uncountable events have a count like `<not
+/// supported>`.
+fn parse(text: &str) -> Vec<(Metric, f64)> {
+    let mut counts = vec![];
+    for line in text.lines().filter(|line| !line.starts_with('#')) {
+        let fields: Vec<&str> = line.split(',').collect();
+        let (Some(count), Some(event)) = (fields.first(), fields.get(2)) else {
+            continue;
+        };
+        // Events can carry modifiers, e.g. `:u` when only user space could be counted.
+        let event = event.split(':').next().unwrap_or_default();
+        let metric = EVENTS.iter().find(|&&(name, _)| name == event);
+        if let (Some(&(_, metric)), Ok(count)) = (metric, count.parse::<f64>()) {
+            counts.push((metric, count));
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod perf_stat_tests {
+    use super::*;
+
+    #[test]
+    fn wrap_works() {
+        let perf = PerfStat::new();
+        let wrapped = perf.wrap(&["groupby".to_string(), "-f".to_string()]);
+        assert_eq!(
+            vec!["perf", "stat", "-x", ","],
+            wrapped[..4].iter().map(String::as_str).collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["--", "groupby", "-f"], wrapped[wrapped.len() - 3..]);
+    }
+}
+
+#[cfg(test)]
+mod parse_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        let text = "\
+# started on Mon Jan  1 00:00:00 2024
+
+1234567,,instructions:u,1000000,100.00,1.50,insn per cycle
+823045,,cycles:u,1000000,100.00,,
+<not supported>,,branch-misses,0,100.00,,
+42,,cache-misses,1000000,100.00,,
+";
+        assert_eq!(
+            vec![
+                (Metric::Instructions, 1234567.0),
+                (Metric::Cycles, 823045.0),
+                (Metric::CacheMisses, 42.0),
+            ],
+            parse(text)
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Sets the CPU and I/O scheduling priorities of the benchmarked commands and of the harness.
+//!
+//! Running the command at a higher CPU priority keeps other work on the machine from preempting
+//! it, and running the harness's own bookkeeping (such as saving results) at idle I/O priority
+//! keeps it from competing with the command for the disk. Both are the same knobs as `nice` and
+//! `ionice`. I/O priorities are only supported on Linux.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// An I/O scheduling class, as for `ionice -c`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoClass {
+    /// The kernel's default: a share of the disk in line with the process's CPU priority.
+    BestEffort,
+
+    /// Only use the disk when nothing else wants it.
+    Idle,
+}
+
+impl IoClass {
+    /// The class's number in the kernel's `ioprio` values.
+    fn number(&self) -> libc::c_int {
+        match self {
+            IoClass::BestEffort => 2,
+            IoClass::Idle => 3,
+        }
+    }
+}
+
+/// Sets the nice value of the calling process, from -20 (highest priority) to 19 (lowest).
+/// Lowering it below zero usually requires root. This only makes a single system call, so it's
+/// safe to use between `fork` and `exec`.
+pub fn set_nice(nice: i32) -> io::Result<()> {
+    // SAFETY: setpriority takes no pointers.
+    let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Sets the I/O scheduling class of the calling thread, or with `None`, returns it to the
+/// default of following its CPU priority. Threads and processes started afterwards inherit it.
+/// This only makes a single system call, so it's safe to use between `fork` and `exec`.
+#[cfg(target_os = "linux")]
+pub fn set_io_class(class: Option<IoClass>) -> io::Result<()> {
+    // Within a class, priorities run from 0 (highest) to 7; we use the middle one, as `ionice`
+    // does. The idle class has no levels.
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    let ioprio = match class {
+        None => 0,
+        Some(IoClass::Idle) => IoClass::Idle.number() << IOPRIO_CLASS_SHIFT,
+        Some(class) => class.number() << IOPRIO_CLASS_SHIFT | 4,
+    };
+    // SAFETY: ioprio_set takes no pointers.
+    let result = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_io_class(_class: Option<IoClass>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "I/O priorities are only supported on Linux",
+    ))
+}
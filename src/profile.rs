@@ -0,0 +1,218 @@
+//! Profiles of a single case, for when the numbers say a case got slower but not where. Each is
+//! one iteration recorded with `perf record` and rendered as a flamegraph with inferno's
+//! `inferno-collapse-perf` and `inferno-flamegraph` (`cargo install inferno`). A description of
+//! exactly what was profiled is written next to every flamegraph, so a profile can't be mistaken
+//! for one of a different sample or set of flags later.
+
+use crate::charts;
+use crate::config::Suite;
+use crate::git::{self, GitState};
+use crate::measure::{self, Input};
+use crate::runner::StagedSample;
+use crate::timestamp;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+/// How to profile a case.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Profiler {
+    /// Sample call stacks with `perf record` and render them as a flamegraph.
+    Flamegraph,
+}
+
+/// How often `perf record` samples the call stack, per second. An odd number, so the sampling
+/// doesn't fall into step with anything periodic in the program.
+const SAMPLE_FREQUENCY: u32 = 999;
+
+/// What a profile is of, saved as JSON next to it.
+#[derive(Serialize)]
+struct Description<'a> {
+    case: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variant: Option<&'a str>,
+    sample: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threads: Option<usize>,
+
+    /// The command profiled, with its flags, before `perf record` was wrapped around it.
+    command: &'a [String],
+    recorded_at: String,
+
+    /// The checkout the program was built from, if we could find it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    git: Option<GitState>,
+}
+
+/// Profiles one iteration of the case named `name` on each of its samples, once for each of its
+/// variants, writing flamegraphs, the data they were rendered from, and descriptions into `dir`.
+/// Swept cases are profiled at their highest thread count. Returns the paths of the flamegraphs.
+pub fn flamegraphs(suite: &Suite, name: &str, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let case = suite
+        .cases
+        .iter()
+        .find(|case| case.name == name)
+        .ok_or_else(|| {
+            let names: Vec<&str> = suite.cases.iter().map(|case| case.name.as_str()).collect();
+            format!("no case named {}; the cases are {}", name, names.join(", "))
+        })?;
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut options = suite.measure_options();
+    options.input_mode = case.input;
+    options.output_check = case.output_check;
+    options.env = case.env.clone();
+    options.cwd = case.cwd.clone();
+    // Counting under `perf stat` or `strace` while sampling under `perf record` would only skew the
+    // profile.
+    options.hardware_counters = false;
+    options.count_syscalls = false;
+    let threads = case.thread_counts().pop().flatten();
+
+    let mut flamegraphs = vec![];
+    for (variant, program) in case.variants() {
+        let mut command = case.command_with_threads(threads);
+        command[0] = program;
+        let source = case.source_dir(&command[0]);
+        for sample in &case.samples {
+            let mut stem = format!("{}-{}", case.name, sample);
+            if let Some(variant) = &variant {
+                stem = format!("{}-{}", stem, variant);
+            }
+            let stem = charts::file_name(&stem);
+            let data = dir.join(format!("{}.perf.data", stem));
+            let flamegraph = dir.join(format!("{}.svg", stem));
+            let label = format!("{} on {}", variant.as_deref().unwrap_or(&case.name), sample);
+            println!("Profiling {}", label);
+
+            let path = suite.sample_path(sample);
+            let generated = suite.generated.get(sample);
+            let ephemeral = match generated {
+                Some(generated) if generated.ephemeral => {
+                    let dir = suite.stage_dir.as_deref().unwrap_or(&suite.sample_dir);
+                    Some(
+                        StagedSample::generate(generated, sample, dir)
+                            .map_err(|e| format!("generating {}: {}", sample, e))?,
+                    )
+                }
+                _ => None,
+            };
+            let input = match (generated, &ephemeral) {
+                (_, Some(ephemeral)) => Input::File(&ephemeral.path),
+                (Some(generated), None) => Input::Generated(generated),
+                (None, None) => Input::File(&path),
+            };
+            let iteration = measure::measure(&record_command(&command, &data), input, &options)
+                .map_err(|e| format!("{}: perf record: {}", label, e))?;
+            if iteration.failed() || iteration.timed_out {
+                return Err(format!(
+                    "{}: profiled iteration failed ({})",
+                    label,
+                    iteration.status()
+                )
+                .into());
+            }
+            render(
+                &data,
+                &format!("{}: {}", label, command.join(" ")),
+                &flamegraph,
+            )
+            .map_err(|e| format!("{}: rendering the flamegraph: {}", label, e))?;
+
+            let description = Description {
+                case: &case.name,
+                variant: variant.as_deref(),
+                sample,
+                threads,
+                command: &command,
+                recorded_at: timestamp::rfc3339(SystemTime::now()),
+                git: source.as_deref().and_then(git::state),
+            };
+            let description_path = dir.join(format!("{}.json", stem));
+            fs::write(
+                &description_path,
+                serde_json::to_string_pretty(&description)?,
+            )
+            .map_err(|e| format!("{}: {}", description_path.display(), e))?;
+            println!("  wrote {}", flamegraph.display());
+            flamegraphs.push(flamegraph);
+        }
+    }
+    Ok(flamegraphs)
+}
+
+/// Returns `command` wrapped in `perf record`, sampling call stacks into `data`.
+fn record_command(command: &[String], data: &Path) -> Vec<String> {
+    let mut wrapped: Vec<String> = vec![
+        "perf".to_string(),
+        "record".to_string(),
+        "--quiet".to_string(),
+        "--call-graph".to_string(),
+        "dwarf".to_string(),
+        "-F".to_string(),
+        SAMPLE_FREQUENCY.to_string(),
+        "-o".to_string(),
+        data.to_string_lossy().into_owned(),
+        "--".to_string(),
+    ];
+    wrapped.extend(command.iter().cloned());
+    wrapped
+}
+
+/// Renders the stacks `perf record` sampled into `data` as a flamegraph in `output`, by piping
+/// `perf script` through inferno.
+fn render(data: &Path, title: &str, output: &Path) -> Result<(), String> {
+    let spawn = |command: &mut Command, program: &str| {
+        command
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("{}: {}", program, e))
+    };
+    let mut script = spawn(
+        Command::new("perf").arg("script").arg("-i").arg(data),
+        "perf",
+    )?;
+    let mut collapse = spawn(
+        Command::new("inferno-collapse-perf").stdin(script.stdout.take().unwrap()),
+        "inferno-collapse-perf",
+    )?;
+    let svg = File::create(output).map_err(|e| format!("{}: {}", output.display(), e))?;
+    let mut flamegraph = Command::new("inferno-flamegraph")
+        .arg("--title")
+        .arg(title)
+        .stdin(collapse.stdout.take().unwrap())
+        .stdout(svg)
+        .spawn()
+        .map_err(|e| format!("inferno-flamegraph: {}", e))?;
+    for (program, child) in [
+        ("perf script", &mut script),
+        ("inferno-collapse-perf", &mut collapse),
+        ("inferno-flamegraph", &mut flamegraph),
+    ] {
+        let status = child.wait().map_err(|e| format!("{}: {}", program, e))?;
+        if !status.success() {
+            return Err(format!("{} failed ({})", program, status));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod record_command_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        let command = vec!["groupby".to_string(), "-f".to_string()];
+        let wrapped = record_command(&command, Path::new("out.perf.data"));
+        assert_eq!("perf", wrapped[0]);
+        assert_eq!("record", wrapped[1]);
+        assert_eq!(
+            vec!["-o", "out.perf.data", "--", "groupby", "-f"],
+            wrapped[7..]
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Tracks how far through a suite a run is, and estimates how long the rest will take from how
+//! long the iterations so far have taken, so a run of several hours can be checked on at a
+//! glance.
+
+use std::time::{Duration, Instant};
+
+pub struct Progress {
+    started: Instant,
+
+    /// How many benchmarks the suite has, and how many have been started (or skipped).
+    benchmarks: usize,
+    benchmarks_started: usize,
+
+    /// How many iterations, warmups included, the suite is expected to run, and how many it has.
+    /// Precision targets can add iterations, so the expected count can grow.
+    iterations: usize,
+    iterations_done: usize,
+}
+
+impl Progress {
+    /// Starts tracking a suite of `benchmarks` benchmarks, each expected to run
+    /// `iterations_each` iterations.
+    pub fn start(benchmarks: usize, iterations_each: usize) -> Progress {
+        Progress {
+            started: Instant::now(),
+            benchmarks,
+            benchmarks_started: 0,
+            iterations: benchmarks * iterations_each,
+            iterations_done: 0,
+        }
+    }
+
+    /// Records that a benchmark finished after running `iterations` iterations, warmups included,
+    /// rather than the `iterations_each` expected (it may have needed more for precision, or been
+    /// abandoned after a timeout).
+    pub fn finished(&mut self, iterations: usize, iterations_each: usize) {
+        self.iterations_done += iterations;
+        self.iterations = (self.iterations + iterations).saturating_sub(iterations_each);
+    }
+
+    /// Records that `count` benchmarks were skipped, having been run before the run was resumed.
+    pub fn skipped(&mut self, count: usize, iterations_each: usize) {
+        self.benchmarks_started += count;
+        self.iterations = self.iterations.saturating_sub(count * iterations_each);
+    }
+
+    /// Records that `count` benchmarks are starting, and describes the progress so far, e.g.
+    /// `benchmark 3 of 12, about 1 h 05 min left`.
+    pub fn starting(&mut self, count: usize) -> String {
+        let mut description = format!(
+            "benchmark {} of {}",
+            (self.benchmarks_started + 1).min(self.benchmarks),
+            self.benchmarks
+        );
+        self.benchmarks_started += count;
+        if let Some(left) = estimate(
+            self.started.elapsed(),
+            self.iterations_done,
+            self.iterations,
+        ) {
+            description += &format!(", {}", time_left(left));
+        }
+        description
+    }
+}
+
+/// Estimates the time left to run `total` iterations, after `done` took `elapsed`, assuming the
+/// rest take as long on average. There's no estimate until an iteration has finished.
+fn estimate(elapsed: Duration, done: usize, total: usize) -> Option<Duration> {
+    if done == 0 {
+        return None;
+    }
+    let left = total.saturating_sub(done);
+    Some(elapsed.mul_f64(left as f64 / done as f64))
+}
+
+/// Describes an estimate of the time left to the minute, e.g. `about 1 h 05 min left`, which is as
+/// precise as an estimate can honestly be.
+fn time_left(left: Duration) -> String {
+    let minutes = (left.as_secs() + 30) / 60;
+    match minutes {
+        0 => "less than a minute left".to_string(),
+        1..=59 => format!("about {} min left", minutes),
+        _ => format!("about {} h {:02} min left", minutes / 60, minutes % 60),
+    }
+}
+
+#[cfg(test)]
+mod estimate_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        assert_eq!(None, estimate(Duration::from_secs(5), 0, 10));
+        assert_eq!(
+            Some(Duration::from_secs(30)),
+            estimate(Duration::from_secs(10), 5, 20)
+        );
+        assert_eq!(
+            Some(Duration::ZERO),
+            estimate(Duration::from_secs(10), 20, 20)
+        );
+    }
+}
+
+#[cfg(test)]
+mod time_left_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        assert_eq!(
+            "less than a minute left",
+            time_left(Duration::from_secs(20))
+        );
+        assert_eq!(
+            "about 12 min left",
+            time_left(Duration::from_secs(12 * 60 + 10))
+        );
+        assert_eq!(
+            "about 1 h 05 min left",
+            time_left(Duration::from_secs(65 * 60))
+        );
+    }
+}
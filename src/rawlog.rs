@@ -0,0 +1,125 @@
+//! An append-only log of every iteration's raw measurements, one JSON object per line, written as
+//! each iteration finishes. Results files are only written once a run finishes, one per run; the
+//! log collects every run's iterations, warmups included, in one file as they happen. An
+//! interrupted run still leaves its measurements behind, and any number of runs can be
+//! re-analyzed together later, with different statistics, without rerunning anything.
+
+use crate::results::{BenchmarkResults, Iteration};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// One line of the log.
+#[derive(Serialize)]
+struct Entry<'a> {
+    /// When the run the iteration belongs to started, which identifies the run.
+    run: &'a str,
+    case: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variant: Option<&'a str>,
+    sample: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threads: Option<usize>,
+
+    /// Whether this was a warmup iteration, whose measurements the results throw away.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    warmup: bool,
+
+    /// The iteration's number within the benchmark's warmup or measured iterations, from 1.
+    iteration: usize,
+
+    #[serde(flatten)]
+    measurements: &'a Iteration,
+}
+
+/// The log, open for appending.
+pub struct RawLog {
+    path: PathBuf,
+    file: File,
+
+    /// When the current run started.
+    run: String,
+}
+
+impl RawLog {
+    /// Opens the log at `path`, creating it if need be, to record the run that started at `run`.
+    pub fn open(path: &Path, run: &str) -> Result<RawLog, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(RawLog {
+            path: path.to_path_buf(),
+            file,
+            run: run.to_string(),
+        })
+    }
+
+    /// Appends an iteration of `benchmark`, numbered from 0.
+    pub fn record(
+        &mut self,
+        benchmark: &BenchmarkResults,
+        warmup: bool,
+        index: usize,
+        iteration: &Iteration,
+    ) -> Result<(), String> {
+        let entry = Entry {
+            run: &self.run,
+            case: &benchmark.case,
+            variant: benchmark.variant.as_deref(),
+            sample: &benchmark.sample,
+            threads: benchmark.threads,
+            warmup,
+            iteration: index + 1,
+            measurements: iteration,
+        };
+        let mut line = serde_json::to_vec(&entry).map_err(|e| e.to_string())?;
+        line.push(b'\n');
+        // One write per line, so lines from an interrupted run are whole or missing, never torn.
+        self.write(&line)
+            .map_err(|e| format!("{}: {}", self.path.display(), e))
+    }
+
+    fn write(&mut self, line: &[u8]) -> io::Result<()> {
+        self.file.write_all(line)?;
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod raw_log_tests {
+    use super::*;
+    use crate::results::Metric;
+    use std::fs;
+
+    #[test]
+    fn appends_lines() {
+        let path = std::env::temp_dir().join(format!("rawlog-{}.jsonl", std::process::id()));
+        let benchmark = BenchmarkResults {
+            case: "groupby".to_string(),
+            sample: "a.txt".to_string(),
+            ..Default::default()
+        };
+        let mut iteration = Iteration::default();
+        iteration.metrics.insert(Metric::WallTime, 1.5);
+        for run in ["first", "second"] {
+            let mut log = RawLog::open(&path, run).unwrap();
+            log.record(&benchmark, true, 0, &iteration).unwrap();
+        }
+
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(2, lines.len());
+        assert_eq!("second", lines[1]["run"]);
+        assert_eq!("groupby", lines[1]["case"]);
+        assert_eq!(true, lines[1]["warmup"]);
+        assert_eq!(1, lines[1]["iteration"]);
+        assert_eq!(1.5, lines[1]["metrics"]["wall_time"]);
+    }
+}
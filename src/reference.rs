@@ -0,0 +1,206 @@
+//! A simple, obviously correct grouping of a sample's lines, to check a command's answers
+//! against. A speedup that comes from a wrong answer isn't a speedup, and timings alone can't tell
+//! the difference.
+//!
+//! We count the lines in each group with a `HashMap`, and compare the counts with those the
+//! command reports when asked for per-group statistics (e.g. `groupby --stats`).
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+
+/// How lines are assigned to groups.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Grouping {
+    /// By their first this many characters.
+    FirstChars(usize),
+
+    /// By their last this many characters.
+    LastChars(usize),
+
+    /// By the whole line.
+    Line,
+}
+
+impl Grouping {
+    /// Returns the key of the group `line` belongs in.
+    fn key<'a>(&self, line: &'a str) -> &'a str {
+        match *self {
+            Grouping::FirstChars(n) => match line.char_indices().nth(n) {
+                Some((end, _)) => &line[..end],
+                None => line,
+            },
+            Grouping::LastChars(0) => "",
+            Grouping::LastChars(n) => match line.char_indices().nth_back(n - 1) {
+                Some((start, _)) => &line[start..],
+                None => line,
+            },
+            Grouping::Line => line,
+        }
+    }
+}
+
+/// Counts the lines of `reader` in each group. Invalid UTF-8 is replaced, as lossy decoding does.
+pub fn group_counts(
+    mut reader: impl BufRead,
+    grouping: Grouping,
+) -> io::Result<HashMap<String, u64>> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let mut buffer = vec![];
+    loop {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            return Ok(counts);
+        }
+        if buffer.ends_with(b"\n") {
+            buffer.pop();
+        }
+        let line = String::from_utf8_lossy(&buffer);
+        let key = grouping.key(&line).trim();
+        match counts.get_mut(key) {
+            Some(count) => *count += 1,
+            None => {
+                counts.insert(key.to_string(), 1);
+            }
+        }
+    }
+}
+
+/// Where the count is on each line of a command's per-group counts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CountPosition {
+    /// Before the key, as `uniq -c` prints it.
+    First,
+
+    /// After the key, as in `key: 12`.
+    #[default]
+    Last,
+}
+
+/// Parses a command's per-group counts. Each nonblank line holds a group's key and its count,
+/// separated by whitespace, with the count where `position` says. Keys are trimmed, along with a
+/// colon after them.
+pub fn parse_counts(text: &str, position: CountPosition) -> Result<HashMap<String, u64>, String> {
+    let mut counts = HashMap::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let line = line.trim();
+        let (key, count) = match position {
+            CountPosition::First => line.split_once(char::is_whitespace).map(|(c, k)| (k, c)),
+            CountPosition::Last => line.rsplit_once(char::is_whitespace),
+        }
+        .unwrap_or(("", line));
+        let count = count
+            .parse()
+            .map_err(|_| format!("no count in line {:?}", line))?;
+        let key = key.trim().trim_end_matches(':').trim_end();
+        counts.insert(key.to_string(), count);
+    }
+    Ok(counts)
+}
+
+/// Describes how `actual` differs from `expected`, at most `limit` differences' worth, in key
+/// order. Empty if they agree.
+pub fn differences(
+    expected: &HashMap<String, u64>,
+    actual: &HashMap<String, u64>,
+    limit: usize,
+) -> Vec<String> {
+    let mut keys: Vec<&String> = expected.keys().chain(actual.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter_map(|key| match (expected.get(key), actual.get(key)) {
+            (Some(e), Some(a)) if e == a => None,
+            (Some(e), Some(a)) => Some(format!("group {:?}: expected {} lines, got {}", key, e, a)),
+            (Some(e), None) => Some(format!(
+                "group {:?}: expected {} lines, but it's missing",
+                key, e
+            )),
+            (None, Some(a)) => Some(format!("group {:?}: unexpected, with {} lines", key, a)),
+            (None, None) => None,
+        })
+        .take(limit)
+        .collect()
+}
+
+#[cfg(test)]
+mod grouping_tests {
+    use super::*;
+
+    #[test]
+    fn keys_work() {
+        assert_eq!("ab", Grouping::FirstChars(2).key("abc"));
+        assert_eq!("a", Grouping::FirstChars(2).key("a"));
+        assert_eq!("é", Grouping::FirstChars(1).key("éa"));
+        assert_eq!("bc", Grouping::LastChars(2).key("abc"));
+        assert_eq!("a", Grouping::LastChars(2).key("a"));
+        assert_eq!("", Grouping::LastChars(0).key("abc"));
+        assert_eq!("abc", Grouping::Line.key("abc"));
+    }
+}
+
+#[cfg(test)]
+mod group_counts_tests {
+    use super::*;
+
+    #[test]
+    fn counts_groups() {
+        let counts = group_counts(
+            "apple\navocado\nbanana\nblueberry\ncherry".as_bytes(),
+            Grouping::FirstChars(1),
+        )
+        .unwrap();
+        assert_eq!(3, counts.len());
+        assert_eq!(2, counts["a"]);
+        assert_eq!(2, counts["b"]);
+        assert_eq!(1, counts["c"]);
+    }
+}
+
+#[cfg(test)]
+mod parse_counts_tests {
+    use super::*;
+
+    #[test]
+    fn parses_count_last() {
+        let counts = parse_counts("a: 2\n\n1 3\n", CountPosition::Last).unwrap();
+        assert_eq!(2, counts["a"]);
+        assert_eq!(3, counts["1"]);
+    }
+
+    #[test]
+    fn parses_count_first() {
+        let counts = parse_counts("      2 a\n      3 1\n", CountPosition::First).unwrap();
+        assert_eq!(2, counts["a"]);
+        assert_eq!(3, counts["1"]);
+    }
+
+    #[test]
+    fn without_count_fails() {
+        assert!(parse_counts("a b\n", CountPosition::Last).is_err());
+        assert!(parse_counts("a b\n", CountPosition::First).is_err());
+    }
+}
+
+#[cfg(test)]
+mod differences_tests {
+    use super::*;
+
+    #[test]
+    fn lists_differences() {
+        let expected = parse_counts("a 2\nb 3\nc 1\n", CountPosition::Last).unwrap();
+        let actual = parse_counts("a 2\nb 4\nd 1\n", CountPosition::Last).unwrap();
+        assert_eq!(
+            vec![
+                r#"group "b": expected 3 lines, got 4"#,
+                r#"group "c": expected 1 lines, but it's missing"#,
+                r#"group "d": unexpected, with 1 lines"#,
+            ],
+            differences(&expected, &actual, 10)
+        );
+        assert_eq!(1, differences(&expected, &actual, 1).len());
+        assert!(differences(&expected, &expected, 10).is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+//! Formats the cells the reports' tables have in common, so a benchmark reads the same in each.
+
+use crate::results::{BenchmarkResults, Metric};
+
+/// What a cell shows when there's nothing to put in it.
+const MISSING: &str = "–";
+
+/// The mean wall time of the fastest of `benchmarks` that didn't time out, for `relative`.
+pub fn fastest<'a>(benchmarks: impl IntoIterator<Item = &'a BenchmarkResults>) -> f64 {
+    benchmarks
+        .into_iter()
+        .filter(|b| !b.timed_out)
+        .filter_map(|b| b.summary.get(&Metric::WallTime))
+        .map(|summary| summary.mean)
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Formats the mean and standard deviation of a benchmark's wall time, unless it timed out.
+pub fn wall_time(benchmark: &BenchmarkResults) -> String {
+    if benchmark.timed_out {
+        "timed out".to_string()
+    } else {
+        mean_and_stddev(benchmark, Metric::WallTime)
+    }
+}
+
+/// Formats a metric's mean and standard deviation, e.g. `1.234 s ± 5.6 ms`.
+pub fn mean_and_stddev(benchmark: &BenchmarkResults, metric: Metric) -> String {
+    match benchmark.summary.get(&metric) {
+        Some(summary) => format!(
+            "{} ± {}",
+            metric.unit().format(summary.mean),
+            metric.unit().format(summary.stddev)
+        ),
+        None => MISSING.to_string(),
+    }
+}
+
+/// A benchmark's mean wall time as a multiple of the `fastest`, unless it timed out.
+pub fn ratio(benchmark: &BenchmarkResults, fastest: f64) -> Option<f64> {
+    match benchmark.summary.get(&Metric::WallTime) {
+        Some(summary) if fastest > 0.0 && !benchmark.timed_out => Some(summary.mean / fastest),
+        _ => None,
+    }
+}
+
+/// Formats a benchmark's mean wall time relative to the fastest, e.g. `2.35×`.
+pub fn relative(benchmark: &BenchmarkResults, fastest: f64) -> String {
+    match ratio(benchmark, fastest) {
+        Some(ratio) => format!("{:.2}×", ratio),
+        None => MISSING.to_string(),
+    }
+}
@@ -0,0 +1,150 @@
+//! Flattens results into CSV with one row per (benchmark, metric, statistic), which is the
+//! easiest shape to pivot in a spreadsheet or load into a data frame.
+
+use crate::results::{Metric, SuiteResults, Unit};
+use std::error::Error;
+use std::io::Write;
+
+const HEADER: [&str; 8] = [
+    "case",
+    "variant",
+    "sample",
+    "threads",
+    "metric",
+    "unit",
+    "statistic",
+    "value",
+];
+
+pub fn write(results: &SuiteResults, out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    write_row(out, &HEADER)?;
+    for benchmark in &results.benchmarks {
+        let variant = benchmark.variant.as_deref().unwrap_or_default();
+        let threads = benchmark.threads.map_or(String::new(), |t| t.to_string());
+        for (metric, summary) in &benchmark.summary {
+            let mut statistics = vec![
+                ("mean", summary.mean),
+                ("median", summary.median),
+                ("stddev", summary.stddev),
+                ("min", summary.min),
+                ("max", summary.max),
+            ];
+            statistics.extend(
+                summary
+                    .percentiles
+                    .iter()
+                    .map(|(name, &value)| (name.as_str(), value)),
+            );
+
+            for (statistic, value) in statistics {
+                write_row(
+                    out,
+                    &[
+                        &benchmark.case,
+                        variant,
+                        &benchmark.sample,
+                        &threads,
+                        metric.name(),
+                        metric.unit().name(),
+                        statistic,
+                        &value.to_string(),
+                    ],
+                )?;
+            }
+        }
+        // Costs are derived from the mean wall time, so they're its rows, in other units, and
+        // have no other statistics.
+        for (unit, cost) in [
+            (Unit::SecondsPerByte, benchmark.seconds_per_byte()),
+            (Unit::SecondsPerLine, benchmark.seconds_per_line()),
+        ] {
+            if let Some(cost) = cost {
+                write_row(
+                    out,
+                    &[
+                        &benchmark.case,
+                        variant,
+                        &benchmark.sample,
+                        &threads,
+                        Metric::WallTime.name(),
+                        unit.name(),
+                        "mean",
+                        &cost.to_string(),
+                    ],
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_row(out: &mut impl Write, fields: &[&str]) -> Result<(), Box<dyn Error>> {
+    let fields: Vec<String> = fields.iter().map(|field| escape(field)).collect();
+    writeln!(out, "{}", fields.join(","))?;
+    Ok(())
+}
+
+/// Quotes a field if it contains anything that would otherwise break the CSV structure.
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod write_tests {
+    use super::*;
+    use crate::results::{BenchmarkResults, Metric};
+    use crate::stats::Summary;
+
+    #[test]
+    fn works() {
+        let mut benchmark = BenchmarkResults {
+            case: "groupby, fast".to_string(),
+            sample: "sample.txt".to_string(),
+            command: vec!["groupby".to_string()],
+            sample_bytes: Some(4),
+            iterations: vec![],
+            ..Default::default()
+        };
+        benchmark
+            .summary
+            .insert(Metric::WallTime, Summary::of(&[1.0, 3.0], &[90.0]).unwrap());
+        let results = SuiteResults {
+            benchmarks: vec![benchmark],
+            ..Default::default()
+        };
+
+        let mut out = vec![];
+        write(&results, &mut out).unwrap();
+        let expected = "\
+case,variant,sample,threads,metric,unit,statistic,value
+\"groupby, fast\",,sample.txt,,wall_time,seconds,mean,2
+\"groupby, fast\",,sample.txt,,wall_time,seconds,median,2
+\"groupby, fast\",,sample.txt,,wall_time,seconds,stddev,1.4142135623730951
+\"groupby, fast\",,sample.txt,,wall_time,seconds,min,1
+\"groupby, fast\",,sample.txt,,wall_time,seconds,max,3
+\"groupby, fast\",,sample.txt,,wall_time,seconds,p90,2.8
+\"groupby, fast\",,sample.txt,,wall_time,seconds_per_byte,mean,0.5
+";
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod escape_tests {
+    use super::*;
+
+    #[test]
+    fn leaves_plain_fields_alone() {
+        assert_eq!("wall_time", escape("wall_time"));
+    }
+
+    #[test]
+    fn quotes_special_fields() {
+        assert_eq!("\"a,b\"", escape("a,b"));
+        assert_eq!("\"say \"\"hi\"\"\"", escape("say \"hi\""));
+    }
+}
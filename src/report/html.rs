@@ -0,0 +1,280 @@
+//! A self-contained HTML report with interactive charts and sortable tables.
+//!
+//! Everything the page needs is inlined, so the report is a single file that can be attached to
+//! an issue or opened offline. The results are embedded as JSON, and a small script draws the
+//! charts as SVG: hovering over a point shows its value, and clicking a legend entry hides or shows
+//! that case.
+
+use crate::results::{BenchmarkResults, Metric, SuiteResults, Unit};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::io::Write;
+
+pub fn write(results: &SuiteResults, out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    let title = match results.metadata.started_at.as_str() {
+        "" => "Benchmark results".to_string(),
+        started_at => format!("Benchmark results, {}", started_at),
+    };
+    let data: Vec<Value> = results.benchmarks.iter().map(benchmark_data).collect();
+
+    let page = TEMPLATE
+        .replace("{{title}}", &escape(&title))
+        .replace("{{table}}", &table(results))
+        .replace("{{data}}", &script_safe(&serde_json::to_string(&data)?));
+    out.write_all(page.as_bytes())?;
+    Ok(())
+}
+
+/// Summarizes a benchmark for the charts.
+fn benchmark_data(benchmark: &BenchmarkResults) -> Value {
+    let mean = |metric| benchmark.summary.get(&metric).map(|summary| summary.mean);
+    json!({
+        "case": benchmark.case_label(),
+        "sample": benchmark.sample,
+        "bytes": benchmark.sample_bytes,
+        "keys": benchmark.sample_keys,
+        "wall_time": mean(Metric::WallTime),
+        "max_rss": mean(Metric::MaxRss),
+    })
+}
+
+/// Renders the sortable results table. Each cell carries its raw value in `data-sort`, so sorting
+/// by a column of "1.2 ms" and "3.4 s" works numerically.
+fn table(results: &SuiteResults) -> String {
+    let mut html = String::from(
+        "<table class=\"sortable\">\n<thead><tr><th>Case</th><th>Sample</th><th>Size</th>\
+         <th>Mean wall time</th><th>Std. dev.</th><th>Min</th><th>Max</th><th>Max RSS</th>\
+         <th>Per byte</th><th>Per line</th></tr></thead>\n<tbody>\n",
+    );
+    for benchmark in &results.benchmarks {
+        let wall_time = benchmark.summary.get(&Metric::WallTime);
+        let seconds = Metric::WallTime.unit();
+        let cells = [
+            text_cell(&benchmark.case_label()),
+            text_cell(&benchmark.sample),
+            number_cell(
+                benchmark.sample_bytes.map(|bytes| bytes as f64),
+                Metric::MaxRss.unit(),
+            ),
+            number_cell(wall_time.map(|s| s.mean), seconds),
+            number_cell(wall_time.map(|s| s.stddev), seconds),
+            number_cell(wall_time.map(|s| s.min), seconds),
+            number_cell(wall_time.map(|s| s.max), seconds),
+            number_cell(
+                benchmark.summary.get(&Metric::MaxRss).map(|s| s.mean),
+                Metric::MaxRss.unit(),
+            ),
+            number_cell(benchmark.seconds_per_byte(), Unit::SecondsPerByte),
+            number_cell(benchmark.seconds_per_line(), Unit::SecondsPerLine),
+        ];
+        html.push_str(&format!("<tr>{}</tr>\n", cells.concat()));
+    }
+    html.push_str("</tbody>\n</table>");
+    html
+}
+
+fn text_cell(text: &str) -> String {
+    format!("<td data-sort=\"{0}\">{0}</td>", escape(text))
+}
+
+fn number_cell(value: Option<f64>, unit: Unit) -> String {
+    match value {
+        Some(value) => format!(
+            "<td class=\"number\" data-sort=\"{}\">{}</td>",
+            value,
+            escape(&unit.format(value))
+        ),
+        None => "<td class=\"number\" data-sort=\"\">–</td>".to_string(),
+    }
+}
+
+/// Escapes text for use in HTML content and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Makes JSON safe to embed in a `<script>` element, which would otherwise end at the first
+/// `</script>` in any string.
+fn script_safe(json: &str) -> String {
+    json.replace("</", "<\\/")
+}
+
+const TEMPLATE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{{title}}</title>
+<style>
+  body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 72em; color: #222; }
+  table { border-collapse: collapse; width: 100%; margin-bottom: 2em; }
+  th, td { padding: 0.3em 0.6em; border-bottom: 1px solid #ddd; text-align: left; }
+  th { cursor: pointer; user-select: none; background: #f4f4f4; }
+  th.asc::after { content: " ▲"; } th.desc::after { content: " ▼"; }
+  td.number { text-align: right; font-variant-numeric: tabular-nums; }
+  .charts { display: flex; flex-wrap: wrap; gap: 2em; }
+  .chart h2 { font-size: 1.1em; }
+  .legend span { cursor: pointer; margin-right: 1em; }
+  .legend span.hidden { opacity: 0.3; }
+  svg text { font-size: 11px; fill: #444; }
+</style>
+</head>
+<body>
+<h1>{{title}}</h1>
+{{table}}
+<div class="charts">
+  <div class="chart" id="wall_time"><h2>Mean wall time vs. input size</h2></div>
+  <div class="chart" id="max_rss"><h2>Mean max RSS vs. number of groups</h2></div>
+</div>
+<script type="application/json" id="data">{{data}}</script>
+<script>
+"use strict";
+const data = JSON.parse(document.getElementById("data").textContent);
+const colors = ["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2"];
+const units = {
+  seconds: [[1, "s"], [1e-3, "ms"], [1e-6, "µs"]],
+  bytes: [[2 ** 30, "GiB"], [2 ** 20, "MiB"], [2 ** 10, "KiB"], [1, "B"]],
+  count: [[1e9, "G"], [1e6, "M"], [1e3, "k"], [1, ""]],
+};
+
+function format(value, unit) {
+  for (const [scale, name] of units[unit]) {
+    if (Math.abs(value) >= scale) return (value / scale).toPrecision(3) + " " + name;
+  }
+  const [scale, name] = units[unit][units[unit].length - 1];
+  return (value / scale).toPrecision(3) + " " + name;
+}
+
+function svg(name, attributes, parent) {
+  const element = document.createElementNS("http://www.w3.org/2000/svg", name);
+  for (const [key, value] of Object.entries(attributes)) element.setAttribute(key, value);
+  if (parent) parent.appendChild(element);
+  return element;
+}
+
+function lineChart(container, along, alongUnit, metric, unit) {
+  const cases = [...new Set(data.map(d => d.case))];
+  const points = data.filter(d => d[along] != null && d[metric] != null);
+  if (points.length === 0) {
+    container.appendChild(document.createTextNode("No data."));
+    return;
+  }
+  const [width, height, margin] = [520, 320, 60];
+  const maxX = Math.max(...points.map(d => d[along]));
+  const maxY = Math.max(...points.map(d => d[metric]));
+  const x = v => margin + (width - 2 * margin) * v / maxX;
+  const y = v => height - margin + (2 * margin - height) * v / maxY;
+
+  const chart = svg("svg", { width, height }, container);
+  svg("line", { x1: margin, y1: height - margin, x2: width - margin, y2: height - margin, stroke: "#888" }, chart);
+  svg("line", { x1: margin, y1: margin, x2: margin, y2: height - margin, stroke: "#888" }, chart);
+  for (let i = 0; i <= 4; i++) {
+    svg("text", { x: x(maxX * i / 4), y: height - margin + 16, "text-anchor": "middle" }, chart)
+      .textContent = format(maxX * i / 4, alongUnit);
+    svg("text", { x: margin - 6, y: y(maxY * i / 4) + 4, "text-anchor": "end" }, chart)
+      .textContent = format(maxY * i / 4, unit);
+  }
+
+  const legend = document.createElement("div");
+  legend.className = "legend";
+  container.appendChild(legend);
+
+  cases.forEach((name, i) => {
+    const color = colors[i % colors.length];
+    const series = points.filter(d => d.case === name).sort((a, b) => a[along] - b[along]);
+    const group = svg("g", {}, chart);
+    svg("polyline", {
+      points: series.map(d => x(d[along]) + "," + y(d[metric])).join(" "),
+      fill: "none", stroke: color, "stroke-width": 2,
+    }, group);
+    for (const d of series) {
+      const point = svg("circle", { cx: x(d[along]), cy: y(d[metric]), r: 4, fill: color }, group);
+      svg("title", {}, point).textContent =
+        name + " on " + d.sample + ": " + format(d[metric], unit);
+    }
+
+    const entry = document.createElement("span");
+    entry.style.color = color;
+    entry.textContent = "● " + name;
+    entry.onclick = () => {
+      entry.classList.toggle("hidden");
+      group.style.display = entry.classList.contains("hidden") ? "none" : "";
+    };
+    legend.appendChild(entry);
+  });
+}
+
+function makeSortable(table) {
+  const headers = [...table.querySelectorAll("th")];
+  headers.forEach((header, column) => {
+    header.onclick = () => {
+      const ascending = !header.classList.contains("asc");
+      headers.forEach(h => h.classList.remove("asc", "desc"));
+      header.classList.add(ascending ? "asc" : "desc");
+      const body = table.tBodies[0];
+      const key = row => row.cells[column].dataset.sort;
+      const rows = [...body.rows].sort((a, b) => {
+        const [p, q] = [key(a), key(b)];
+        const order = p !== "" && q !== "" && !isNaN(p) && !isNaN(q)
+          ? Number(p) - Number(q)
+          : p.localeCompare(q);
+        return ascending ? order : -order;
+      });
+      rows.forEach(row => body.appendChild(row));
+    };
+  });
+}
+
+lineChart(document.getElementById("wall_time"), "bytes", "bytes", "wall_time", "seconds");
+// Memory grows with the groups a command keeps, rather than the input it streams through.
+lineChart(document.getElementById("max_rss"), "keys", "count", "max_rss", "bytes");
+document.querySelectorAll("table.sortable").forEach(makeSortable);
+</script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod write_tests {
+    use super::*;
+    use crate::stats::Summary;
+
+    #[test]
+    fn embeds_table_and_data() {
+        let mut benchmark = BenchmarkResults {
+            case: "<groupby>".to_string(),
+            sample: "sample.txt".to_string(),
+            sample_bytes: Some(1024),
+            ..Default::default()
+        };
+        benchmark
+            .summary
+            .insert(Metric::WallTime, Summary::of(&[0.5], &[]).unwrap());
+        let results = SuiteResults {
+            benchmarks: vec![benchmark],
+            ..Default::default()
+        };
+
+        let mut out = vec![];
+        write(&results, &mut out).unwrap();
+        let page = String::from_utf8(out).unwrap();
+
+        assert!(page.contains("<td data-sort=\"&lt;groupby&gt;\">&lt;groupby&gt;</td>"));
+        assert!(page.contains("<td class=\"number\" data-sort=\"0.5\">500.0 ms</td>"));
+        assert!(page.contains(r#""bytes":1024"#));
+        assert!(page.contains(r#""keys":null"#));
+        assert!(!page.contains("{{"));
+    }
+}
+
+#[cfg(test)]
+mod script_safe_tests {
+    use super::*;
+
+    #[test]
+    fn escapes_closing_tags() {
+        assert_eq!(r#"["<\/script>"]"#, script_safe(r#"["</script>"]"#));
+    }
+}
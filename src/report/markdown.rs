@@ -0,0 +1,358 @@
+//! A Markdown report, suitable for pasting into pull requests and release notes.
+//!
+//! There's one table per sample, with a row per case, so that every table compares cases on equal
+//! footing. Details about how the results were gathered go in footnotes below the tables.
+
+use super::cells;
+use crate::charts::Chart;
+use crate::results::{BenchmarkResults, Metric, SuiteResults, Unit};
+use crate::scaling;
+use std::error::Error;
+use std::io::Write;
+
+pub fn write(
+    results: &SuiteResults,
+    charts: &[Chart],
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(out, "# Benchmark results[^run]")?;
+
+    for sample in results.samples() {
+        let benchmarks: Vec<&BenchmarkResults> = results
+            .benchmarks
+            .iter()
+            .filter(|b| b.sample == sample)
+            .collect();
+        let fastest = cells::fastest(benchmarks.iter().copied());
+
+        writeln!(out)?;
+        writeln!(out, "## `{}`", sample)?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "| Case | Wall time | Relative | Throughput | Cost | Max RSS |"
+        )?;
+        writeln!(
+            out,
+            "|:-----|----------:|---------:|-----------:|-----:|--------:|"
+        )?;
+        for benchmark in benchmarks {
+            writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} |",
+                escape(&benchmark.case_label()),
+                cells::wall_time(benchmark),
+                relative(benchmark, fastest),
+                throughput(benchmark),
+                cost(benchmark),
+                cells::mean_and_stddev(benchmark, Metric::MaxRss),
+            )?;
+        }
+    }
+
+    write_scaling(results, out)?;
+    write_failures(results, out)?;
+
+    if !charts.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "## Charts")?;
+        for chart in charts {
+            writeln!(out)?;
+            writeln!(out, "![{}]({})", chart.title, chart.path.display())?;
+        }
+    }
+
+    writeln!(out)?;
+    write_footnotes(results, out)?;
+    Ok(())
+}
+
+/// Formats a benchmark's mean wall time relative to the fastest, in bold for the fastest itself.
+fn relative(benchmark: &BenchmarkResults, fastest: f64) -> String {
+    if cells::ratio(benchmark, fastest) == Some(1.0) {
+        "**1.00×**".to_string()
+    } else {
+        cells::relative(benchmark, fastest)
+    }
+}
+
+/// Formats a benchmark's throughput in bytes and lines, e.g. `120.5 MB/s, 3.2 M lines/s`.
+fn throughput(benchmark: &BenchmarkResults) -> String {
+    let rates: Vec<String> = [
+        (benchmark.bytes_per_second(), Unit::BytesPerSecond),
+        (benchmark.lines_per_second(), Unit::LinesPerSecond),
+    ]
+    .iter()
+    .filter_map(|&(rate, unit)| Some(unit.format(rate?)))
+    .collect();
+    if rates.is_empty() {
+        "–".to_string()
+    } else {
+        rates.join(", ")
+    }
+}
+
+/// Formats a benchmark's mean wall time per byte and line of its sample, e.g.
+/// `0.52 ns/byte, 31.0 ns/line`, which can be compared across samples of different sizes.
+fn cost(benchmark: &BenchmarkResults) -> String {
+    let costs: Vec<String> = [
+        (benchmark.seconds_per_byte(), Unit::SecondsPerByte),
+        (benchmark.seconds_per_line(), Unit::SecondsPerLine),
+    ]
+    .iter()
+    .filter_map(|&(cost, unit)| Some(unit.format(cost?)))
+    .collect();
+    if costs.is_empty() {
+        "–".to_string()
+    } else {
+        costs.join(", ")
+    }
+}
+
+/// Tabulates how each case run on several sample sizes scales, if any was.
+fn write_scaling(results: &SuiteResults, out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    let fits = scaling::fits(results);
+    if fits.is_empty() {
+        return Ok(());
+    }
+    writeln!(out)?;
+    writeln!(out, "## Scaling")?;
+    writeln!(out)?;
+    writeln!(out, "| Case | Best fit | Growth | Fixed cost | R² |")?;
+    writeln!(out, "|:-----|:---------|-------:|-----------:|---:|")?;
+    for (case, fit) in fits {
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {:.3} |",
+            escape(&case),
+            fit.model.name(),
+            fit.describe(),
+            Unit::Seconds.format(fit.intercept),
+            fit.r_squared
+        )?;
+    }
+    Ok(())
+}
+
+/// Lists the benchmarks with failed iterations, with what the last failure wrote to stderr, those
+/// whose output differed between iterations, and those that gave wrong answers. Their numbers
+/// can't be trusted, so they need pointing out.
+fn write_failures(results: &SuiteResults, out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    let failed: Vec<&BenchmarkResults> = results
+        .benchmarks
+        .iter()
+        .filter(|benchmark| {
+            !benchmark.failures().is_empty()
+                || benchmark.inconsistent_output
+                || !benchmark.wrong_answers.is_empty()
+        })
+        .collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+    writeln!(out)?;
+    writeln!(out, "## Failures")?;
+    for benchmark in failed {
+        if benchmark.inconsistent_output {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "**{}** on `{}`: output differed between iterations.",
+                benchmark.case_label(),
+                benchmark.sample
+            )?;
+        }
+        if !benchmark.wrong_answers.is_empty() {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "**{}** on `{}`: wrong answers, compared with the reference grouping:",
+                benchmark.case_label(),
+                benchmark.sample
+            )?;
+            writeln!(out)?;
+            for difference in &benchmark.wrong_answers {
+                writeln!(out, "- {}", difference)?;
+            }
+        }
+        let failures = benchmark.failures();
+        let Some(last) = failures.last() else {
+            continue;
+        };
+        writeln!(out)?;
+        writeln!(
+            out,
+            "**{}** on `{}`: {} of {} iteration(s) failed; the last with {}.",
+            benchmark.case_label(),
+            benchmark.sample,
+            failures.len(),
+            benchmark.iterations.len(),
+            last.status()
+        )?;
+        if !last.stderr.trim().is_empty() {
+            writeln!(out)?;
+            writeln!(out, "```")?;
+            writeln!(out, "{}", last.stderr.trim_end())?;
+            writeln!(out, "```")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_footnotes(results: &SuiteResults, out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    let metadata = &results.metadata;
+    let mut details = vec![];
+    if !metadata.started_at.is_empty() {
+        details.push(format!("Run started at {}.", metadata.started_at));
+    }
+    let sources: Vec<String> = results
+        .sources()
+        .iter()
+        .map(|git| format!("`{}`", git.describe()))
+        .collect();
+    if !sources.is_empty() {
+        details.push(format!("Commands built from {}.", sources.join(", ")));
+    }
+    if let Some(environment) = &metadata.environment {
+        details.push(format!("Ran on {}.", environment.describe()));
+    }
+    if !results.runs.is_empty() {
+        let runs: Vec<String> = results
+            .runs
+            .iter()
+            .map(|(name, run)| match &run.environment {
+                Some(environment) => format!(
+                    "{} (started at {} on {})",
+                    name,
+                    run.started_at,
+                    environment.describe()
+                ),
+                None => format!("{} (started at {})", name, run.started_at),
+            })
+            .collect();
+        details.push(format!("Merged from {}.", runs.join("; ")));
+    }
+    if !metadata.tags.is_empty() {
+        details.push(format!("Tagged {}.", metadata.tags.join(", ")));
+    }
+    if let Some(parallel) = metadata.parallel {
+        details.push(format!(
+            "Up to {} cases ran at once, so timings are less precise than usual.",
+            parallel
+        ));
+    }
+    if let Some(suite) = &metadata.suite {
+        details.push(format!(
+            "{} measured iteration(s) and {} warmup iteration(s) per benchmark; outlier \
+             detection: {}; page cache: {}.",
+            suite.iterations,
+            suite.warmup,
+            suite.outliers.name(),
+            suite.cache.name()
+        ));
+    }
+    details.push(
+        "Times are means ± standard deviations; relative speeds compare mean wall times \
+         within each table; throughput is the sample's size over the mean wall time, and cost \
+         the mean wall time per byte and line of it."
+            .to_string(),
+    );
+    writeln!(out, "[^run]: {}", details.join(" "))?;
+    Ok(())
+}
+
+/// Escapes the characters that would break a Markdown table cell.
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod write_tests {
+    use super::*;
+    use crate::stats::Summary;
+
+    fn benchmark(case: &str, sample: &str, wall_time: f64) -> BenchmarkResults {
+        let mut benchmark = BenchmarkResults {
+            case: case.to_string(),
+            sample: sample.to_string(),
+            command: vec![case.to_string()],
+            iterations: vec![],
+            ..Default::default()
+        };
+        benchmark
+            .summary
+            .insert(Metric::WallTime, Summary::of(&[wall_time], &[]).unwrap());
+        benchmark
+    }
+
+    #[test]
+    fn writes_one_table_per_sample() {
+        let results = SuiteResults {
+            benchmarks: vec![
+                benchmark("groupby", "small.txt", 1.0),
+                benchmark("sort|uniq", "small.txt", 2.5),
+                benchmark("groupby", "large.txt", 10.0),
+            ],
+            ..Default::default()
+        };
+        let mut out = vec![];
+        write(&results, &[], &mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+
+        assert!(report.contains("## `small.txt`"));
+        assert!(report.contains("## `large.txt`"));
+        assert!(report.contains("| groupby | 1.000 s ± 0.0 µs | **1.00×** | – | – | – |"));
+        assert!(report.contains("| sort\\|uniq | 2.500 s ± 0.0 µs | 2.50× | – | – | – |"));
+        assert!(report.contains("[^run]:"));
+        assert!(!report.contains("## Charts"));
+    }
+
+    #[test]
+    fn shows_throughput() {
+        let mut benchmark = benchmark("groupby", "small.txt", 2.0);
+        benchmark.sample_bytes = Some(3_000_000);
+        benchmark.sample_lines = Some(50_000);
+        let results = SuiteResults {
+            benchmarks: vec![benchmark],
+            ..Default::default()
+        };
+        let mut out = vec![];
+        write(&results, &[], &mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("| 1.5 MB/s, 25.0 k lines/s | 666.67 ns/byte, 40000.0 ns/line |"));
+    }
+
+    #[test]
+    fn shows_scaling() {
+        let benchmarks = [1_000_000, 10_000_000, 100_000_000]
+            .into_iter()
+            .map(|bytes| {
+                let mut benchmark = benchmark("groupby", &bytes.to_string(), bytes as f64 * 1e-9);
+                benchmark.sample_bytes = Some(bytes);
+                benchmark
+            })
+            .collect();
+        let results = SuiteResults {
+            benchmarks,
+            ..Default::default()
+        };
+        let mut out = vec![];
+        write(&results, &[], &mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("## Scaling"));
+        assert!(report.contains("| groupby | O(n) | 1.000 ns × n |"));
+    }
+
+    #[test]
+    fn references_charts() {
+        let results = SuiteResults::default();
+        let charts = [Chart {
+            title: "Wall time".to_string(),
+            path: "charts/wall_time.svg".into(),
+        }];
+        let mut out = vec![];
+        write(&results, &charts, &mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("## Charts\n\n![Wall time](charts/wall_time.svg)\n"));
+    }
+}
@@ -0,0 +1,137 @@
+//! Reports that present results for people, as opposed to exports for other tools.
+
+mod cells;
+mod csv;
+mod html;
+mod markdown;
+mod pr_comment;
+mod table;
+
+use crate::charts::Chart;
+use crate::compare::plural;
+use crate::results::{Metric, SuiteResults};
+use clap::ValueEnum;
+use std::error::Error;
+use std::io::Write;
+
+/// A report format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// One row per (benchmark, metric, statistic), for spreadsheets and data frames.
+    Csv,
+
+    /// Markdown tables per sample, for pull requests and release notes.
+    Md,
+
+    /// A single self-contained HTML page with interactive charts and sortable tables.
+    Html,
+
+    /// Aligned plain-text tables per sample, for the terminal.
+    Table,
+
+    /// Markdown for a GitHub pull request comment, with a collapsible section per sample and
+    /// each benchmark's change marked.
+    PrComment,
+}
+
+/// Writes a report on `results` to `out` in the given format. Formats that can show images
+/// reference `charts`, which must already have been rendered. Formats that show changes compare
+/// the results with `baseline`, if there is one.
+pub fn write(
+    results: &SuiteResults,
+    format: Format,
+    charts: &[Chart],
+    baseline: Option<&SuiteResults>,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Csv => csv::write(results, out),
+        Format::Md => markdown::write(results, charts, out),
+        Format::Html => html::write(results, out),
+        Format::Table => table::write(results, out),
+        Format::PrComment => pr_comment::write(results, baseline, out),
+    }
+}
+
+/// Sums `results` up in one line for commit messages and chat notifications, e.g.
+/// `12 benchmarks on 4 samples, groupby fastest on 3 of them, 1 with problems`. Problems are
+/// failed iterations, output that differed between iterations, and wrong answers.
+pub fn tldr(results: &SuiteResults) -> String {
+    let samples = results.samples();
+    let mut parts = vec![format!(
+        "{} on {}",
+        plural(results.benchmarks.len(), "benchmark"),
+        plural(samples.len(), "sample")
+    )];
+
+    // How many samples each case was fastest on, in the order the cases first won.
+    let mut wins: Vec<(String, usize)> = vec![];
+    for sample in &samples {
+        let fastest = results
+            .benchmarks
+            .iter()
+            .filter(|b| b.sample == *sample && !b.timed_out)
+            .filter_map(|b| Some((b, b.summary.get(&Metric::WallTime)?.mean)))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((benchmark, _)) = fastest {
+            let case = benchmark.case_label();
+            match wins.iter_mut().find(|(name, _)| *name == case) {
+                Some((_, count)) => *count += 1,
+                None => wins.push((case, 1)),
+            }
+        }
+    }
+    if let Some((case, count)) = wins.iter().rev().max_by_key(|(_, count)| *count) {
+        parts.push(format!("{} fastest on {} of them", case, count));
+    }
+
+    let problems = results
+        .benchmarks
+        .iter()
+        .filter(|b| {
+            !b.failures().is_empty() || b.inconsistent_output || !b.wrong_answers.is_empty()
+        })
+        .count();
+    if problems > 0 {
+        parts.push(format!("{} with problems", problems));
+    }
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tldr_tests {
+    use super::*;
+    use crate::results::BenchmarkResults;
+    use crate::stats::Summary;
+
+    fn benchmark(case: &str, sample: &str, wall_time: f64) -> BenchmarkResults {
+        let mut benchmark = BenchmarkResults {
+            case: case.to_string(),
+            sample: sample.to_string(),
+            ..Default::default()
+        };
+        benchmark
+            .summary
+            .insert(Metric::WallTime, Summary::of(&[wall_time], &[]).unwrap());
+        benchmark
+    }
+
+    #[test]
+    fn works() {
+        let mut wrong = benchmark("awk", "large.txt", 3.0);
+        wrong.wrong_answers = vec!["a: expected 1, got 2".to_string()];
+        let results = SuiteResults {
+            benchmarks: vec![
+                benchmark("groupby", "small.txt", 1.0),
+                benchmark("awk", "small.txt", 2.0),
+                benchmark("groupby", "large.txt", 2.0),
+                wrong,
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            "4 benchmarks on 2 samples, groupby fastest on 2 of them, 1 with problems",
+            tldr(&results)
+        );
+    }
+}
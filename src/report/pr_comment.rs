@@ -0,0 +1,230 @@
+//! A report shaped for posting as a pull request comment on GitHub: a one-line verdict up top,
+//! then one collapsible section per sample, with a marker on every row that changed. Sections
+//! with regressions start expanded, so they're the first thing a reviewer sees. There are no links
+//! or images, so the comment reads the same wherever it's pasted.
+//!
+//! Each benchmark is compared with its counterpart in the baseline results, if there are any, or
+//! else with the first variant of its case, as when the PR's build and the base branch's were run
+//! side by side with `--build-ref`.
+
+use super::cells;
+use crate::compare::{self, Comparison, Report, Verdict};
+use crate::results::{BenchmarkResults, Metric, SuiteResults, Unit};
+use crate::DEFAULT_ALPHA;
+use std::error::Error;
+use std::io::Write;
+
+pub fn write(
+    results: &SuiteResults,
+    baseline: Option<&SuiteResults>,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let comparisons: Vec<(&BenchmarkResults, Option<Comparison>)> = results
+        .benchmarks
+        .iter()
+        .map(|benchmark| {
+            let comparison = counterpart(results, baseline, benchmark).and_then(|old| {
+                compare::comparison(
+                    benchmark.id(),
+                    old,
+                    benchmark,
+                    Metric::WallTime,
+                    DEFAULT_ALPHA,
+                )
+            });
+            (benchmark, comparison)
+        })
+        .collect();
+
+    writeln!(out, "### Benchmark results")?;
+    writeln!(out)?;
+    let report = Report {
+        comparisons: comparisons.iter().filter_map(|(_, c)| c.clone()).collect(),
+        ..Default::default()
+    };
+    if report.comparisons.is_empty() {
+        writeln!(out, "**{}**", super::tldr(results))?;
+    } else {
+        writeln!(out, "**{}**", report.tldr())?;
+    }
+
+    for sample in results.samples() {
+        let rows: Vec<&(&BenchmarkResults, Option<Comparison>)> = comparisons
+            .iter()
+            .filter(|(benchmark, _)| benchmark.sample == sample)
+            .collect();
+        let verdicts = |verdict| {
+            rows.iter()
+                .filter(|(_, c)| c.as_ref().is_some_and(|c| c.verdict == verdict))
+                .count()
+        };
+        let regressions = verdicts(Verdict::Regression);
+        let improvements = verdicts(Verdict::Improvement);
+
+        let mut summary = format!("<code>{}</code>", escape(sample));
+        if let Some(bytes) = rows[0].0.sample_bytes {
+            summary += &format!(" ({})", Unit::Bytes.format(bytes as f64));
+        }
+        let mut counts = vec![];
+        if regressions > 0 {
+            counts.push(format!("{} {}", marker(Verdict::Regression), regressions));
+        }
+        if improvements > 0 {
+            counts.push(format!("{} {}", marker(Verdict::Improvement), improvements));
+        }
+        if !counts.is_empty() {
+            summary += &format!(": {}", counts.join(" "));
+        }
+
+        writeln!(out)?;
+        let open = if regressions > 0 { " open" } else { "" };
+        writeln!(out, "<details{}><summary>{}</summary>", open, summary)?;
+        writeln!(out)?;
+        writeln!(out, "| | Case | Wall time | Change | p |")?;
+        writeln!(out, "|:-:|:-----|----------:|-------:|--:|")?;
+        for (benchmark, comparison) in rows {
+            let (marker, change, p) = match comparison {
+                Some(c) => (
+                    marker(c.verdict),
+                    format!("{:+.1}%", c.relative_change * 100.0),
+                    format!("{:.3}", c.p_value),
+                ),
+                None => ("", "–".to_string(), "–".to_string()),
+            };
+            let noisier = if comparison.as_ref().is_some_and(|c| c.noisier) {
+                " ⚠️ noisier"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "| {} | {} | {} | {}{} | {} |",
+                marker,
+                escape(&benchmark.case_label()),
+                cells::wall_time(benchmark),
+                change,
+                noisier,
+                p
+            )?;
+        }
+        writeln!(out)?;
+        writeln!(out, "</details>")?;
+    }
+    Ok(())
+}
+
+/// Finds the benchmark to compare `benchmark` with: the one with the same ID in `baseline`, or
+/// without a baseline, the first variant of its case, unless that's `benchmark` itself.
+fn counterpart<'a>(
+    results: &'a SuiteResults,
+    baseline: Option<&'a SuiteResults>,
+    benchmark: &BenchmarkResults,
+) -> Option<&'a BenchmarkResults> {
+    match baseline {
+        Some(baseline) => {
+            let id = benchmark.id();
+            baseline.benchmarks.iter().find(|b| b.id() == id)
+        }
+        None => {
+            benchmark.variant.as_ref()?;
+            results
+                .benchmarks
+                .iter()
+                .find(|b| {
+                    b.case == benchmark.case
+                        && b.sample == benchmark.sample
+                        && b.threads == benchmark.threads
+                })
+                .filter(|first| !std::ptr::eq(*first, benchmark))
+        }
+    }
+}
+
+/// The marker for a verdict: red for a regression, green for an improvement, nothing otherwise.
+fn marker(verdict: Verdict) -> &'static str {
+    match verdict {
+        Verdict::Regression => "🔴",
+        Verdict::Improvement => "🟢",
+        Verdict::Unchanged => "",
+    }
+}
+
+/// Escapes the characters that would break a table cell or the HTML around it.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod write_tests {
+    use super::*;
+    use crate::results::Iteration;
+
+    fn benchmark(case: &str, sample: &str, wall_times: &[f64]) -> BenchmarkResults {
+        let mut benchmark = BenchmarkResults {
+            case: case.to_string(),
+            sample: sample.to_string(),
+            iterations: wall_times
+                .iter()
+                .map(|&wall_time| {
+                    let mut iteration = Iteration::default();
+                    iteration.metrics.insert(Metric::WallTime, wall_time);
+                    iteration
+                })
+                .collect(),
+            ..Default::default()
+        };
+        benchmark.summarize(&[]);
+        benchmark
+    }
+
+    const FAST: [f64; 6] = [1.0, 1.1, 1.2, 1.0, 1.1, 1.2];
+    const SLOW: [f64; 6] = [2.0, 2.1, 2.2, 2.0, 2.1, 2.2];
+
+    #[test]
+    fn marks_changes_against_a_baseline() {
+        let baseline = SuiteResults {
+            benchmarks: vec![
+                benchmark("a", "x.txt", &FAST),
+                benchmark("a", "y.txt", &FAST),
+            ],
+            ..Default::default()
+        };
+        let results = SuiteResults {
+            benchmarks: vec![
+                benchmark("a", "x.txt", &SLOW),
+                benchmark("a", "y.txt", &FAST),
+            ],
+            ..Default::default()
+        };
+        let mut out = vec![];
+        write(&results, Some(&baseline), &mut out).unwrap();
+        let comment = String::from_utf8(out).unwrap();
+
+        assert!(comment.contains("**1 regression (worst +90.9% on a/x.txt), 0 improvements"));
+        assert!(comment.contains("<details open><summary><code>x.txt</code>: 🔴 1</summary>"));
+        assert!(comment.contains("| 🔴 | a | 2.100 s ± 89.4 ms | +90.9% | 0.005 |"));
+        assert!(comment.contains("<details><summary><code>y.txt</code></summary>"));
+        assert!(comment.contains("|  | a | 1.100 s ± 89.4 ms | +0.0% | 1.000 |"));
+        assert!(!comment.contains("]("));
+    }
+
+    #[test]
+    fn compares_variants_without_a_baseline() {
+        let variant = |name: &str, wall_times| BenchmarkResults {
+            variant: Some(name.to_string()),
+            ..benchmark("a", "x.txt", wall_times)
+        };
+        let results = SuiteResults {
+            benchmarks: vec![variant("main", &SLOW), variant("pr", &FAST)],
+            ..Default::default()
+        };
+        let mut out = vec![];
+        write(&results, None, &mut out).unwrap();
+        let comment = String::from_utf8(out).unwrap();
+        assert!(comment.contains("|  | a [main] | 2.100 s ± 89.4 ms | – | – |"));
+        assert!(comment.contains("| 🟢 | a [pr] |"));
+    }
+}
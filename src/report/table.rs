@@ -0,0 +1,189 @@
+//! Plain-text tables for the terminal, one per sample with a row per case, aligned so the columns
+//! can be compared at a glance. `bench run` prints this after every run.
+//!
+//! Failed iterations are left out of the statistics, so each table is followed by a note about any
+//! benchmark that had some, lest a command that crashes early look fast. The same goes for
+//! benchmarks whose output differed between iterations.
+//!
+//! Each row ends with a histogram of the case's wall times drawn in block characters, so a
+//! bimodal or long-tailed distribution stands out even though the columns only show the mean.
+
+use super::cells;
+use crate::results::{BenchmarkResults, Metric, SuiteResults};
+use std::error::Error;
+use std::io::Write;
+
+const HEADER: [&str; 5] = ["Case", "Wall time", "Relative", "Max RSS", "Distribution"];
+
+/// The number of bins, and so characters, in each histogram.
+const HISTOGRAM_BINS: usize = 12;
+
+pub fn write(results: &SuiteResults, out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    for (i, sample) in results.samples().into_iter().enumerate() {
+        let benchmarks: Vec<&BenchmarkResults> = results
+            .benchmarks
+            .iter()
+            .filter(|b| b.sample == sample)
+            .collect();
+        let fastest = cells::fastest(benchmarks.iter().copied());
+
+        let mut rows = vec![HEADER.map(String::from)];
+        rows.extend(benchmarks.iter().map(|benchmark| {
+            [
+                benchmark.case_label(),
+                cells::wall_time(benchmark),
+                cells::relative(benchmark, fastest),
+                cells::mean_and_stddev(benchmark, Metric::MaxRss),
+                histogram(&benchmark.values(Metric::WallTime), HISTOGRAM_BINS),
+            ]
+        }));
+
+        // Widths are in characters, not bytes, since units like "µs" aren't ASCII.
+        let mut widths = [0; HEADER.len()];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        if i > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "{}", sample)?;
+        for row in &rows {
+            // The case and histogram are left-aligned; everything in between is a number.
+            let mut line = format!("  {:<width$}", row[0], width = widths[0]);
+            for (cell, width) in row.iter().zip(widths).skip(1).take(HEADER.len() - 2) {
+                line.push_str(&format!("  {:>width$}", cell, width = width));
+            }
+            line.push_str(&format!("  {}", row[HEADER.len() - 1]));
+            writeln!(out, "{}", line.trim_end())?;
+        }
+        for benchmark in &benchmarks {
+            if benchmark.inconsistent_output {
+                writeln!(
+                    out,
+                    "  ! {}: output differed between iterations",
+                    benchmark.case_label()
+                )?;
+            }
+            if !benchmark.wrong_answers.is_empty() {
+                writeln!(
+                    out,
+                    "  ! {}: wrong answers ({})",
+                    benchmark.case_label(),
+                    benchmark.wrong_answers[0]
+                )?;
+            }
+            if let Some(failure) = benchmark.failures().first() {
+                writeln!(
+                    out,
+                    "  ! {}: {} of {} iteration(s) failed ({})",
+                    benchmark.case_label(),
+                    benchmark.failures().len(),
+                    benchmark.iterations.len(),
+                    failure.status()
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Draws a histogram of `values` as `bins` block characters, from the minimum value on the left to
+/// the maximum on the right. Each bar's height is relative to the fullest bin, and empty bins are
+/// blank, so gaps between clusters show.
+fn histogram(values: &[f64], bins: usize) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    if values.is_empty() {
+        return String::new();
+    }
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+    let mut counts = vec![0; bins];
+    for &value in values {
+        // With no spread at all, everything lands in the first bin.
+        let bin = if max > min {
+            ((value - min) / (max - min) * bins as f64) as usize
+        } else {
+            0
+        };
+        counts[bin.min(bins - 1)] += 1;
+    }
+
+    let fullest = *counts.iter().max().unwrap();
+    counts
+        .iter()
+        .map(|&count| match count {
+            0 => ' ',
+            _ => BARS[(count * BARS.len()).div_ceil(fullest) - 1],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod histogram_tests {
+    use super::*;
+
+    #[test]
+    fn shows_clusters() {
+        assert_eq!(
+            "█▃  ▃█",
+            histogram(&[1.0, 1.0, 1.0, 1.2, 2.0, 2.0, 2.0, 1.8], 6)
+        );
+    }
+
+    #[test]
+    fn without_spread_fills_first_bin() {
+        assert_eq!("█   ", histogram(&[5.0, 5.0], 4));
+    }
+
+    #[test]
+    fn without_values_is_empty() {
+        assert_eq!("", histogram(&[], 4));
+    }
+}
+
+#[cfg(test)]
+mod write_tests {
+    use super::*;
+    use crate::stats::Summary;
+
+    fn benchmark(case: &str, sample: &str, wall_time: f64) -> BenchmarkResults {
+        let mut benchmark = BenchmarkResults {
+            case: case.to_string(),
+            sample: sample.to_string(),
+            ..Default::default()
+        };
+        benchmark
+            .summary
+            .insert(Metric::WallTime, Summary::of(&[wall_time], &[]).unwrap());
+        benchmark
+    }
+
+    #[test]
+    fn aligns_columns() {
+        let results = SuiteResults {
+            benchmarks: vec![
+                benchmark("groupby", "small.txt", 0.5),
+                benchmark("sort", "small.txt", 1.25),
+                benchmark("groupby", "large.txt", 10.0),
+            ],
+            ..Default::default()
+        };
+        let mut out = vec![];
+        write(&results, &mut out).unwrap();
+        let expected = "\
+small.txt
+  Case             Wall time  Relative  Max RSS  Distribution
+  groupby  500.0 ms ± 0.0 µs     1.00×        –
+  sort      1.250 s ± 0.0 µs     2.50×        –
+
+large.txt
+  Case             Wall time  Relative  Max RSS  Distribution
+  groupby  10.000 s ± 0.0 µs     1.00×        –
+";
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+}
@@ -0,0 +1,1217 @@
+//! Benchmark results: the raw measurements taken for every benchmark in a suite run.
+
+use crate::cache::CacheMode;
+use crate::config::Suite;
+use crate::environment::Environment;
+use crate::git::GitState;
+use crate::stats::{self, OutlierMethod, Summary};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Something we measure about each iteration of a benchmark.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum Metric {
+    /// Elapsed real time from spawning the process until it exits, in seconds.
+    WallTime,
+
+    /// Peak resident set size of the process, in bytes.
+    MaxRss,
+
+    /// Elapsed real time from spawning the process until it writes its first byte to stdout, in
+    /// seconds. Absent if the process writes nothing.
+    FirstOutput,
+
+    /// CPU time spent in user mode, in seconds.
+    UserTime,
+
+    /// CPU time spent in the kernel on the process's behalf, in seconds.
+    SystemTime,
+
+    /// Instructions retired, if hardware counters were read.
+    Instructions,
+
+    /// CPU cycles, if hardware counters were read.
+    Cycles,
+
+    /// Mispredicted branches, if hardware counters were read.
+    BranchMisses,
+
+    /// Cache misses (usually of the last-level cache), if hardware counters were read.
+    CacheMisses,
+
+    /// System calls made by the process and its children, if they were traced.
+    Syscalls,
+
+    /// Calls to `read` and its relatives, if system calls were traced.
+    Reads,
+
+    /// Calls to `write` and its relatives, if system calls were traced.
+    Writes,
+
+    /// Mean bytes returned per read call, if system calls were traced and there were any reads.
+    ReadSize,
+
+    /// Mean bytes written per write call, if system calls were traced and there were any writes.
+    WriteSize,
+
+    /// Bytes the process and its children read, from the page cache or storage (Linux only).
+    BytesRead,
+
+    /// Bytes the process and its children caused to be fetched from storage, i.e. that weren't
+    /// in the page cache (Linux only).
+    DiskRead,
+
+    /// Bytes the process and its children wrote (Linux only).
+    BytesWritten,
+
+    /// Bytes the process and its children caused to be sent to storage (Linux only).
+    DiskWritten,
+
+    /// Times the process gave up the CPU before its time slice ran out, usually to wait for I/O.
+    VoluntarySwitches,
+
+    /// Times the process was preempted, e.g. by a higher-priority process or at the end of its
+    /// time slice.
+    InvoluntarySwitches,
+
+    /// Page faults that needed I/O, e.g. to read a mapped file page from disk.
+    MajorFaults,
+
+    /// Page faults served without I/O, e.g. the first touch of freshly allocated memory.
+    MinorFaults,
+}
+
+/// The unit in which a metric is measured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Seconds,
+    Bytes,
+
+    /// A number of events, such as instructions.
+    Count,
+    BytesPerSecond,
+    LinesPerSecond,
+
+    /// The inverses of the rates: the time spent on each byte or line of a sample.
+    SecondsPerByte,
+    SecondsPerLine,
+}
+
+impl Metric {
+    /// The metric's name in results files, e.g. `wall_time`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::WallTime => "wall_time",
+            Metric::MaxRss => "max_rss",
+            Metric::FirstOutput => "first_output",
+            Metric::UserTime => "user_time",
+            Metric::SystemTime => "system_time",
+            Metric::Instructions => "instructions",
+            Metric::Cycles => "cycles",
+            Metric::BranchMisses => "branch_misses",
+            Metric::CacheMisses => "cache_misses",
+            Metric::Syscalls => "syscalls",
+            Metric::Reads => "reads",
+            Metric::Writes => "writes",
+            Metric::ReadSize => "read_size",
+            Metric::WriteSize => "write_size",
+            Metric::BytesRead => "bytes_read",
+            Metric::DiskRead => "disk_read",
+            Metric::BytesWritten => "bytes_written",
+            Metric::DiskWritten => "disk_written",
+            Metric::VoluntarySwitches => "voluntary_switches",
+            Metric::InvoluntarySwitches => "involuntary_switches",
+            Metric::MajorFaults => "major_faults",
+            Metric::MinorFaults => "minor_faults",
+        }
+    }
+
+    /// A short, human-readable name for the metric.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Metric::WallTime => "wall time",
+            Metric::MaxRss => "max RSS",
+            Metric::FirstOutput => "first output",
+            Metric::UserTime => "user time",
+            Metric::SystemTime => "system time",
+            Metric::Instructions => "instructions",
+            Metric::Cycles => "cycles",
+            Metric::BranchMisses => "branch misses",
+            Metric::CacheMisses => "cache misses",
+            Metric::Syscalls => "system calls",
+            Metric::Reads => "read calls",
+            Metric::Writes => "write calls",
+            Metric::ReadSize => "mean read size",
+            Metric::WriteSize => "mean write size",
+            Metric::BytesRead => "bytes read",
+            Metric::DiskRead => "read from disk",
+            Metric::BytesWritten => "bytes written",
+            Metric::DiskWritten => "written to disk",
+            Metric::VoluntarySwitches => "voluntary cs",
+            Metric::InvoluntarySwitches => "involuntary cs",
+            Metric::MajorFaults => "major faults",
+            Metric::MinorFaults => "minor faults",
+        }
+    }
+
+    pub fn unit(&self) -> Unit {
+        match self {
+            Metric::WallTime | Metric::FirstOutput | Metric::UserTime | Metric::SystemTime => {
+                Unit::Seconds
+            }
+            Metric::MaxRss
+            | Metric::ReadSize
+            | Metric::WriteSize
+            | Metric::BytesRead
+            | Metric::DiskRead
+            | Metric::BytesWritten
+            | Metric::DiskWritten => Unit::Bytes,
+            Metric::Instructions
+            | Metric::Cycles
+            | Metric::BranchMisses
+            | Metric::CacheMisses
+            | Metric::Syscalls
+            | Metric::Reads
+            | Metric::Writes
+            | Metric::VoluntarySwitches
+            | Metric::InvoluntarySwitches
+            | Metric::MajorFaults
+            | Metric::MinorFaults => Unit::Count,
+        }
+    }
+}
+
+impl Unit {
+    /// The unit's name in machine-readable output, e.g. `seconds`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Unit::Seconds => "seconds",
+            Unit::Bytes => "bytes",
+            Unit::Count => "count",
+            Unit::BytesPerSecond => "bytes_per_second",
+            Unit::LinesPerSecond => "lines_per_second",
+            Unit::SecondsPerByte => "seconds_per_byte",
+            Unit::SecondsPerLine => "seconds_per_line",
+        }
+    }
+
+    /// Formats a value in this unit for humans, e.g. `1.234 s`, `56.7 MiB`, or `120.5 MB/s`.
+    /// Rates use decimal prefixes, as is usual for throughput.
+    pub fn format(&self, value: f64) -> String {
+        match self {
+            Unit::Seconds => {
+                if value < 1e-3 {
+                    format!("{:.1} µs", value * 1e6)
+                } else if value < 1.0 {
+                    format!("{:.1} ms", value * 1e3)
+                } else {
+                    format!("{:.3} s", value)
+                }
+            }
+            Unit::Bytes => {
+                const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+                let mut value = value;
+                let mut unit = 0;
+                while value >= 1024.0 && unit < UNITS.len() - 1 {
+                    value /= 1024.0;
+                    unit += 1;
+                }
+                if unit == 0 {
+                    format!("{} {}", value, UNITS[unit])
+                } else {
+                    format!("{:.1} {}", value, UNITS[unit])
+                }
+            }
+            Unit::Count => decimal(value).trim_end().to_string(),
+            Unit::BytesPerSecond => format!("{}B/s", decimal(value)),
+            Unit::LinesPerSecond => format!("{} lines/s", decimal(value).trim_end()),
+            // Per-byte costs are fractions of a nanosecond for fast commands, so keep two places.
+            Unit::SecondsPerByte => format!("{:.2} ns/byte", value * 1e9),
+            Unit::SecondsPerLine => format!("{:.1} ns/line", value * 1e9),
+        }
+    }
+}
+
+/// Scales `value` by a decimal prefix, e.g. `12.3 M` for 12.3 million, ready for a unit symbol to
+/// follow.
+fn decimal(value: f64) -> String {
+    const PREFIXES: [&str; 4] = ["", "k", "M", "G"];
+    let mut value = value;
+    let mut prefix = 0;
+    while value >= 1000.0 && prefix < PREFIXES.len() - 1 {
+        value /= 1000.0;
+        prefix += 1;
+    }
+    format!("{:.1} {}", value, PREFIXES[prefix])
+}
+
+/// The version of the results file format. Bump it whenever a change would stop older versions
+/// of this program from reading newer files correctly.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The results of a whole suite run. This is the canonical results format: `bench run` writes it,
+/// and every other command reads it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuiteResults {
+    /// The version of the format this was written in. See `SCHEMA_VERSION`.
+    pub schema_version: u32,
+
+    pub metadata: Metadata,
+
+    pub benchmarks: Vec<BenchmarkResults>,
+
+    /// If these results were merged from several runs, each run's own metadata, by the name its
+    /// benchmarks' `run` refers to it by.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub runs: BTreeMap<String, Metadata>,
+}
+
+/// Information about a suite run as a whole.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Metadata {
+    /// When the run started, as an RFC 3339 timestamp.
+    #[serde(default)]
+    pub started_at: String,
+
+    /// When the run finished, as an RFC 3339 timestamp.
+    #[serde(default)]
+    pub finished_at: String,
+
+    /// The git checkout this program was built from, if known.
+    #[serde(default)]
+    pub bench_git: Option<GitState>,
+
+    /// The machine the suite ran on.
+    #[serde(default)]
+    pub environment: Option<Environment>,
+
+    /// The path of the config file the suite was read from.
+    #[serde(default)]
+    pub config_path: Option<PathBuf>,
+
+    /// The suite configuration, exactly as it was run.
+    #[serde(default)]
+    pub suite: Option<Suite>,
+
+    /// Free-form tags describing the run, such as the machine or experiment.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// How many cases were allowed to run at once, if more than one. Cases running side by side
+    /// compete for the machine, so their timings are less precise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel: Option<usize>,
+}
+
+impl Default for SuiteResults {
+    fn default() -> Self {
+        SuiteResults {
+            schema_version: SCHEMA_VERSION,
+            metadata: Metadata::default(),
+            benchmarks: vec![],
+            runs: BTreeMap::new(),
+        }
+    }
+}
+
+/// The results of running one case against one sample.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BenchmarkResults {
+    /// The name of the case.
+    pub case: String,
+
+    /// The name of the sample.
+    pub sample: String,
+
+    /// If the results were merged from several runs, the name of the one this benchmark came
+    /// from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run: Option<String>,
+
+    /// The variant of the case, if it has several: the program run in place of the command's own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+
+    /// The thread count the command was told to use, if the case was swept across thread counts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+
+    /// The size of the sample in bytes, if it was known when the benchmark ran.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_bytes: Option<u64>,
+
+    /// The number of lines in the sample, if it was known when the benchmark ran.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_lines: Option<u64>,
+
+    /// The number of groups `mksample` found in the sample once it was built, if it counted them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_keys: Option<usize>,
+
+    /// The hash of how the sample was generated, if it was generated here or by `mksample`. Runs
+    /// on samples of the same name but different hashes aren't comparable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_spec: Option<String>,
+
+    /// The command that was run.
+    pub command: Vec<String>,
+
+    /// What was done with the page cache before the benchmark and its iterations.
+    #[serde(default)]
+    pub cache: CacheMode,
+
+    /// The git checkout the command was built from, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitState>,
+
+    /// The measured iterations, in the order they were run.
+    pub iterations: Vec<Iteration>,
+
+    /// Whether the iterations that succeeded didn't all produce the same output.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub inconsistent_output: bool,
+
+    /// How the command's answers differed from the reference grouping, if the case was verified
+    /// and they did (up to a handful of groups' worth).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wrong_answers: Vec<String>,
+
+    /// Whether an iteration ran past the suite's timeout. The benchmark was abandoned there, and
+    /// the iteration that timed out isn't among `iterations`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+
+    /// Whether throttled iterations are left out of the summary statistics, as the suite's
+    /// `exclude_throttled` said. They're still flagged `throttled`, not `outlier`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclude_throttled: bool,
+
+    /// Summary statistics over the iterations, for every metric they recorded.
+    #[serde(default)]
+    pub summary: BTreeMap<Metric, Summary>,
+}
+
+/// The measurements taken from a single execution of a command.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Iteration {
+    pub metrics: BTreeMap<Metric, f64>,
+
+    /// Whether this iteration was flagged as an outlier. Outliers are kept in the results but
+    /// excluded from summary statistics.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub outlier: bool,
+
+    /// The process's RSS over time, if memory sampling was enabled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memory_timeline: Vec<MemorySample>,
+
+    /// The mean CPU frequency while the process ran, in MHz, if it could be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_mhz: Option<f64>,
+
+    /// The highest CPU temperature while the process ran, in degrees Celsius, if it could be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_celsius: Option<f64>,
+
+    /// Whether the CPU was thermally throttled while the process ran, either by the kernel's
+    /// count or because its frequency dropped well below the benchmark's norm.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub throttled: bool,
+
+    /// Whether the process was killed for running past the suite's timeout.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+
+    /// The process's exit code, if it exited normally.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+
+    /// The signal that killed the process, if one did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+
+    /// A hash of what the process wrote to stdout, if its output was checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_hash: Option<String>,
+
+    /// The end of what the process wrote to stderr.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub stderr: String,
+
+    /// How earlier attempts at this iteration failed, e.g. `exit code 137`, if they were retried.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retried: Vec<String>,
+}
+
+/// A single point in a memory-over-time series.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MemorySample {
+    /// Seconds since the process was spawned.
+    pub time: f64,
+
+    /// Resident set size at that time, in bytes.
+    pub rss: u64,
+}
+
+impl BenchmarkResults {
+    /// Uniquely identifies this benchmark within a suite run.
+    pub fn id(&self) -> String {
+        format!("{}/{}", self.case_label(), self.sample)
+    }
+
+    /// Names the case for humans, including the variant, thread count, and run if there are any,
+    /// e.g. `groupby [groupby-new] (4 threads) on laptop`.
+    pub fn case_label(&self) -> String {
+        let case = self.case_and_variant();
+        let label = match self.threads {
+            Some(1) => format!("{} (1 thread)", case),
+            Some(threads) => format!("{} ({} threads)", case, threads),
+            None => case,
+        };
+        match &self.run {
+            Some(run) => format!("{} on {}", label, run),
+            None => label,
+        }
+    }
+
+    /// Names the case and its variant, if there is one, e.g. `groupby [groupby-new]`.
+    pub fn case_and_variant(&self) -> String {
+        match &self.variant {
+            Some(variant) => format!("{} [{}]", self.case, variant),
+            None => self.case.clone(),
+        }
+    }
+
+    /// Describes the benchmarked command as a shell command line, e.g. `groupby -f < sample.txt`.
+    pub fn command_line(&self) -> String {
+        let mut words: Vec<String> = self.command.iter().map(|word| shell_quote(word)).collect();
+        words.push("<".to_string());
+        words.push(shell_quote(&self.sample));
+        words.join(" ")
+    }
+
+    /// Returns every recorded value of `metric`, in iteration order. Outliers, failures,
+    /// throttled iterations if they're excluded, and iterations that didn't record the metric are
+    /// skipped.
+    pub fn values(&self, metric: Metric) -> Vec<f64> {
+        self.iterations
+            .iter()
+            .filter(|iteration| !iteration.outlier && !iteration.failed())
+            .filter(|iteration| !(self.exclude_throttled && iteration.throttled))
+            .filter_map(|iteration| iteration.metrics.get(&metric).copied())
+            .collect()
+    }
+
+    /// Flags outlying iterations by their wall time, replacing any previous flags. Returns the
+    /// number of outliers found. Only iterations that succeeded are weighed, or flagged: a crash
+    /// is a failure, not an outlier, and its time says nothing about the others'.
+    pub fn flag_outliers(&mut self, method: OutlierMethod) -> usize {
+        // Every iteration records its wall time, so the flags line up with those that succeeded.
+        let wall_times: Vec<f64> = self
+            .iterations
+            .iter()
+            .filter(|iteration| !iteration.failed())
+            .map(|iteration| iteration.metrics[&Metric::WallTime])
+            .collect();
+        let mut flags = method.detect(&wall_times).into_iter();
+        for iteration in &mut self.iterations {
+            iteration.outlier = !iteration.failed() && flags.next().unwrap_or(false);
+        }
+        self.iterations
+            .iter()
+            .filter(|iteration| iteration.outlier)
+            .count()
+    }
+
+    /// Checks whether the iterations that succeeded all produced the same output, setting
+    /// `inconsistent_output` if they didn't. Returns the number of distinct outputs.
+    pub fn check_output(&mut self) -> usize {
+        let hashes: BTreeSet<&str> = self
+            .iterations
+            .iter()
+            .filter(|iteration| !iteration.failed())
+            .filter_map(|iteration| iteration.output_hash.as_deref())
+            .collect();
+        self.inconsistent_output = hashes.len() > 1;
+        hashes.len()
+    }
+
+    /// Returns the iterations that failed, in order.
+    pub fn failures(&self) -> Vec<&Iteration> {
+        self.iterations
+            .iter()
+            .filter(|iteration| iteration.failed())
+            .collect()
+    }
+
+    /// The mean rate at which the command consumed its sample, in bytes per second: the sample's
+    /// size divided by the mean wall time. `None` if either is unknown.
+    pub fn bytes_per_second(&self) -> Option<f64> {
+        self.per_second(self.sample_bytes?)
+    }
+
+    /// Like `bytes_per_second`, but in lines of the sample per second.
+    pub fn lines_per_second(&self) -> Option<f64> {
+        self.per_second(self.sample_lines?)
+    }
+
+    /// The mean wall time per byte of the sample, which, unlike the wall time itself, can be
+    /// compared across samples of different sizes. `None` if either is unknown.
+    pub fn seconds_per_byte(&self) -> Option<f64> {
+        self.per_unit(self.sample_bytes?)
+    }
+
+    /// Like `seconds_per_byte`, but per line of the sample.
+    pub fn seconds_per_line(&self) -> Option<f64> {
+        self.per_unit(self.sample_lines?)
+    }
+
+    fn per_second(&self, amount: u64) -> Option<f64> {
+        match self.summary.get(&Metric::WallTime) {
+            Some(summary) if summary.mean > 0.0 => Some(amount as f64 / summary.mean),
+            _ => None,
+        }
+    }
+
+    fn per_unit(&self, amount: u64) -> Option<f64> {
+        match self.summary.get(&Metric::WallTime) {
+            Some(summary) if amount > 0 => Some(summary.mean / amount as f64),
+            _ => None,
+        }
+    }
+
+    /// Flags iterations whose mean CPU frequency fell more than `max_drop` (e.g. `0.1` for 10%)
+    /// below the median across the benchmark's iterations, adding to any flags set while
+    /// measuring. Returns the number of throttled iterations.
+    pub fn flag_throttled(&mut self, max_drop: f64) -> usize {
+        let mut frequencies: Vec<f64> = self.iterations.iter().filter_map(|i| i.cpu_mhz).collect();
+        if !frequencies.is_empty() {
+            frequencies.sort_by(f64::total_cmp);
+            let floor = stats::percentile(&frequencies, 50.0) * (1.0 - max_drop);
+            for iteration in &mut self.iterations {
+                if iteration.cpu_mhz.is_some_and(|mhz| mhz < floor) {
+                    iteration.throttled = true;
+                }
+            }
+        }
+        self.iterations.iter().filter(|i| i.throttled).count()
+    }
+
+    /// Recomputes `summary` from the iterations.
+    pub fn summarize(&mut self, percentiles: &[f64]) {
+        let metrics: BTreeSet<Metric> = self
+            .iterations
+            .iter()
+            .flat_map(|iteration| iteration.metrics.keys().copied())
+            .collect();
+        self.summary = metrics
+            .into_iter()
+            .filter_map(|metric| Some((metric, Summary::of(&self.values(metric), percentiles)?)))
+            .collect();
+    }
+}
+
+impl Iteration {
+    /// Whether the process failed, by exiting with a nonzero code or being killed. Failed
+    /// iterations are kept in the results but excluded from summary statistics, since a command
+    /// that stops early can look misleadingly fast.
+    pub fn failed(&self) -> bool {
+        self.exit_code.is_some_and(|code| code != 0) || self.signal.is_some()
+    }
+
+    /// Describes how the process exited, e.g. `exit code 2` or `killed by signal 9`.
+    pub fn status(&self) -> String {
+        match (self.exit_code, self.signal) {
+            (Some(code), _) => format!("exit code {}", code),
+            (None, Some(signal)) => format!("killed by signal {}", signal),
+            (None, None) => "unknown exit status".to_string(),
+        }
+    }
+
+    /// Formats one of this iteration's metrics for humans, or `-` if it wasn't recorded.
+    pub fn format(&self, metric: Metric) -> String {
+        match self.metrics.get(&metric) {
+            Some(&value) => metric.unit().format(value),
+            None => "-".to_string(),
+        }
+    }
+}
+
+impl SuiteResults {
+    /// Returns the distinct sample names in the results, in the order they first appear.
+    pub fn samples(&self) -> Vec<&str> {
+        let mut samples: Vec<&str> = vec![];
+        for benchmark in &self.benchmarks {
+            if !samples.contains(&benchmark.sample.as_str()) {
+                samples.push(&benchmark.sample);
+            }
+        }
+        samples
+    }
+
+    /// Returns the distinct git checkouts the benchmarked commands were built from, in the order
+    /// they first appear.
+    pub fn sources(&self) -> Vec<&GitState> {
+        let mut sources: Vec<&GitState> = vec![];
+        for git in self.benchmarks.iter().filter_map(|b| b.git.as_ref()) {
+            if !sources.contains(&git) {
+                sources.push(git);
+            }
+        }
+        sources
+    }
+
+    /// Combines the results of several runs, each given a name, into one set. Each benchmark
+    /// records the name of the run it came from, and each run's metadata is kept in `runs`. Runs
+    /// that were themselves merged keep the names they were already given, prefixed with the new.
+    pub fn merge(inputs: Vec<(String, SuiteResults)>) -> Result<SuiteResults, Box<dyn Error>> {
+        let mut merged = SuiteResults::default();
+        let mut environments = vec![];
+        for (name, results) in inputs {
+            let mut runs = results.runs;
+            if runs.is_empty() {
+                runs.insert(String::new(), results.metadata);
+            }
+            for (run, metadata) in runs {
+                let run = if run.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}/{}", name, run)
+                };
+                if merged.runs.contains_key(&run) {
+                    return Err(format!("two runs are named {}", run).into());
+                }
+                environments.push(metadata.environment.clone());
+                for tag in &metadata.tags {
+                    if !merged.metadata.tags.contains(tag) {
+                        merged.metadata.tags.push(tag.clone());
+                    }
+                }
+                merged.runs.insert(run, metadata);
+            }
+            for mut benchmark in results.benchmarks {
+                benchmark.run = Some(match benchmark.run {
+                    Some(run) => format!("{}/{}", name, run),
+                    None => name.clone(),
+                });
+                merged.benchmarks.push(benchmark);
+            }
+        }
+
+        let runs = merged.runs.values();
+        let started = runs
+            .clone()
+            .map(|run| &run.started_at)
+            .filter(|t| !t.is_empty());
+        merged.metadata.started_at = started.min().cloned().unwrap_or_default();
+        merged.metadata.finished_at = runs
+            .map(|run| &run.finished_at)
+            .max()
+            .cloned()
+            .unwrap_or_default();
+        // Runs on one machine keep describing it; runs on several describe none in particular.
+        if environments.windows(2).all(|pair| pair[0] == pair[1]) {
+            merged.metadata.environment = environments.pop().flatten();
+        }
+        Ok(merged)
+    }
+
+    /// Reads results previously written by `save`.
+    pub fn load(path: &Path) -> Result<SuiteResults, Box<dyn Error>> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let results: SuiteResults = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if results.schema_version != SCHEMA_VERSION {
+            return Err(format!(
+                "{}: unsupported results schema version {} (expected {})",
+                path.display(),
+                results.schema_version,
+                SCHEMA_VERSION
+            )
+            .into());
+        }
+        Ok(results)
+    }
+
+    /// Writes the results to `path` as pretty-printed JSON.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
+
+/// Quotes `word` for a POSIX shell, if it needs quoting.
+fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+%@".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Returns the path at which the results of a run are stored within `results_dir`. Runs are
+/// named by when they started, minus the colons that some file systems forbid.
+pub fn run_path(results_dir: &Path, metadata: &Metadata) -> PathBuf {
+    results_dir
+        .join("runs")
+        .join(format!("{}.json", metadata.started_at.replace(':', "-")))
+}
+
+/// Returns the path of the copy of the most recent run's results within `results_dir`.
+pub fn latest_path(results_dir: &Path) -> PathBuf {
+    results_dir.join("latest.json")
+}
+
+/// Returns the path of the results of the run in progress within `results_dir`, saved after each
+/// benchmark so an interrupted run can be resumed, and removed once the run finishes.
+pub fn partial_path(results_dir: &Path) -> PathBuf {
+    results_dir.join("partial.json")
+}
+
+/// Returns the path at which the named baseline is stored within `results_dir`.
+pub fn baseline_path(results_dir: &Path, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    // Baseline names become file names, so keep them to something that can't escape the
+    // directory or surprise a shell.
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        && !name.starts_with('.');
+    if !valid {
+        return Err(format!(
+            "invalid baseline name {:?}: use letters, digits, '-', '_', and '.'",
+            name
+        )
+        .into());
+    }
+    Ok(results_dir.join("baselines").join(format!("{}.json", name)))
+}
+
+#[cfg(test)]
+mod suite_results_tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}-{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn round_trips_through_file() {
+        let path = temp_path("round-trip");
+        let mut results = SuiteResults::default();
+        results.metadata.started_at = "2022-05-01T12:34:56Z".to_string();
+        results.save(&path).unwrap();
+
+        let loaded = SuiteResults::load(&path).unwrap();
+        assert_eq!(SCHEMA_VERSION, loaded.schema_version);
+        assert_eq!("2022-05-01T12:34:56Z", loaded.metadata.started_at);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_with_unknown_schema_version_fails() {
+        let path = temp_path("future-version");
+        std::fs::write(
+            &path,
+            r#"{"schema_version": 999, "metadata": {}, "benchmarks": []}"#,
+        )
+        .unwrap();
+        let error = SuiteResults::load(&path).unwrap_err().to_string();
+        assert!(error.contains("schema version 999"), "{}", error);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn merge_keeps_provenance() {
+        let run = |started_at: &str, tags: &[&str], case: &str| SuiteResults {
+            metadata: Metadata {
+                started_at: started_at.to_string(),
+                finished_at: started_at.to_string(),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                ..Default::default()
+            },
+            benchmarks: vec![BenchmarkResults {
+                case: case.to_string(),
+                sample: "a.txt".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let merged = SuiteResults::merge(vec![
+            (
+                "laptop".to_string(),
+                run("2024-02-01T00:00:00Z", &["x"], "a"),
+            ),
+            (
+                "server".to_string(),
+                run("2024-01-01T00:00:00Z", &["x", "y"], "b"),
+            ),
+        ])
+        .unwrap();
+        assert_eq!("2024-01-01T00:00:00Z", merged.metadata.started_at);
+        assert_eq!("2024-02-01T00:00:00Z", merged.metadata.finished_at);
+        assert_eq!(vec!["x", "y"], merged.metadata.tags);
+        assert_eq!(
+            vec!["laptop", "server"],
+            merged.runs.keys().collect::<Vec<_>>()
+        );
+        assert_eq!("a on laptop/a.txt", merged.benchmarks[0].id());
+        assert_eq!("b on server/a.txt", merged.benchmarks[1].id());
+
+        let remerged = SuiteResults::merge(vec![
+            ("old".to_string(), merged),
+            ("new".to_string(), run("2024-03-01T00:00:00Z", &[], "a")),
+        ])
+        .unwrap();
+        assert_eq!(
+            vec!["new", "old/laptop", "old/server"],
+            remerged.runs.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(Some("old/laptop"), remerged.benchmarks[0].run.as_deref());
+    }
+
+    #[test]
+    fn merge_with_duplicate_names_fails() {
+        let inputs = vec![
+            ("a".to_string(), SuiteResults::default()),
+            ("a".to_string(), SuiteResults::default()),
+        ];
+        assert!(SuiteResults::merge(inputs).is_err());
+    }
+
+    #[test]
+    fn sources_are_distinct() {
+        let git = |commit: &str| GitState {
+            commit: commit.to_string(),
+            branch: None,
+            dirty: false,
+        };
+        let benchmark = |git| BenchmarkResults {
+            git,
+            ..Default::default()
+        };
+        let results = SuiteResults {
+            benchmarks: vec![
+                benchmark(Some(git("a"))),
+                benchmark(None),
+                benchmark(Some(git("b"))),
+                benchmark(Some(git("a"))),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(vec![&git("a"), &git("b")], results.sources());
+    }
+}
+
+#[cfg(test)]
+mod run_path_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        let metadata = Metadata {
+            started_at: "2022-05-01T12:34:56Z".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            PathBuf::from("results/runs/2022-05-01T12-34-56Z.json"),
+            run_path(Path::new("results"), &metadata)
+        );
+    }
+}
+
+#[cfg(test)]
+mod baseline_path_tests {
+    use super::*;
+
+    #[test]
+    fn with_valid_name_works() {
+        assert_eq!(
+            PathBuf::from("results/baselines/main.json"),
+            baseline_path(Path::new("results"), "main").unwrap()
+        );
+        assert!(baseline_path(Path::new("results"), "v0.3.0-pre_1").is_ok());
+    }
+
+    #[test]
+    fn with_invalid_name_fails() {
+        for name in ["", "../main", "a/b", ".hidden", "two words"] {
+            assert!(
+                baseline_path(Path::new("results"), name).is_err(),
+                "{}",
+                name
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod metric_tests {
+    use super::*;
+
+    #[test]
+    fn name_matches_serialized_name() {
+        for metric in Metric::value_variants() {
+            let serialized = serde_json::to_value(metric).unwrap();
+            assert_eq!(serialized.as_str().unwrap(), metric.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn format_seconds_works() {
+        assert_eq!("12.0 µs", Unit::Seconds.format(0.000_012));
+        assert_eq!("250.0 ms", Unit::Seconds.format(0.25));
+        assert_eq!("3.500 s", Unit::Seconds.format(3.5));
+    }
+
+    #[test]
+    fn format_bytes_works() {
+        assert_eq!("512 B", Unit::Bytes.format(512.0));
+        assert_eq!("1.5 KiB", Unit::Bytes.format(1536.0));
+        assert_eq!("30.0 MiB", Unit::Bytes.format(30.0 * 1024.0 * 1024.0));
+    }
+
+    #[test]
+    fn format_rates_works() {
+        assert_eq!("512.0 B/s", Unit::BytesPerSecond.format(512.0));
+        assert_eq!("120.5 MB/s", Unit::BytesPerSecond.format(120_500_000.0));
+        assert_eq!("3.2 M lines/s", Unit::LinesPerSecond.format(3_200_000.0));
+        assert_eq!("0.52 ns/byte", Unit::SecondsPerByte.format(0.52e-9));
+        assert_eq!("31.0 ns/line", Unit::SecondsPerLine.format(31e-9));
+    }
+}
+
+#[cfg(test)]
+mod iteration_tests {
+    use super::*;
+
+    #[test]
+    fn metrics_round_trip_through_json() {
+        let mut iteration = Iteration::default();
+        iteration.metrics.insert(Metric::WallTime, 1.5);
+        iteration.metrics.insert(Metric::MaxRss, 4096.0);
+
+        let json = serde_json::to_string(&iteration).unwrap();
+        assert_eq!(r#"{"metrics":{"wall_time":1.5,"max_rss":4096.0}}"#, json);
+
+        let parsed: Iteration = serde_json::from_str(&json).unwrap();
+        assert_eq!(iteration.metrics, parsed.metrics);
+    }
+}
+
+#[cfg(test)]
+mod benchmark_results_tests {
+    use super::*;
+
+    fn iteration(wall_time: f64, first_output: Option<f64>) -> Iteration {
+        let mut iteration = Iteration::default();
+        iteration.metrics.insert(Metric::WallTime, wall_time);
+        if let Some(first_output) = first_output {
+            iteration.metrics.insert(Metric::FirstOutput, first_output);
+        }
+        iteration
+    }
+
+    #[test]
+    fn summarize_covers_every_recorded_metric() {
+        let mut benchmark = BenchmarkResults {
+            case: "case".to_string(),
+            sample: "sample".to_string(),
+            command: vec!["cat".to_string()],
+            iterations: vec![
+                iteration(1.0, Some(0.5)),
+                iteration(2.0, None),
+                iteration(3.0, Some(1.5)),
+            ],
+            ..Default::default()
+        };
+        benchmark.summarize(&[90.0]);
+
+        assert_eq!(2, benchmark.summary.len());
+        assert_eq!(2.0, benchmark.summary[&Metric::WallTime].mean);
+        assert_eq!(1.0, benchmark.summary[&Metric::FirstOutput].mean);
+        assert!(!benchmark.summary.contains_key(&Metric::MaxRss));
+    }
+
+    #[test]
+    fn id_includes_threads() {
+        let mut benchmark = BenchmarkResults {
+            case: "groupby".to_string(),
+            sample: "sample.txt".to_string(),
+            ..Default::default()
+        };
+        assert_eq!("groupby/sample.txt", benchmark.id());
+        benchmark.threads = Some(4);
+        assert_eq!("groupby (4 threads)/sample.txt", benchmark.id());
+    }
+
+    #[test]
+    fn command_line_quotes_as_needed() {
+        let benchmark = BenchmarkResults {
+            case: "case".to_string(),
+            sample: "my sample.txt".to_string(),
+            command: vec![
+                "groupby".to_string(),
+                "-r".to_string(),
+                "^(\\w+)'s".to_string(),
+            ],
+            iterations: vec![],
+            ..Default::default()
+        };
+        assert_eq!(
+            r#"groupby -r '^(\w+)'\''s' < 'my sample.txt'"#,
+            benchmark.command_line()
+        );
+    }
+
+    #[test]
+    fn outliers_are_flagged_and_excluded_from_summary() {
+        let mut benchmark = BenchmarkResults {
+            case: "case".to_string(),
+            sample: "sample".to_string(),
+            command: vec!["cat".to_string()],
+            iterations: [1.0, 1.1, 0.9, 1.0, 9.0]
+                .iter()
+                .map(|&wall_time| iteration(wall_time, None))
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(1, benchmark.flag_outliers(OutlierMethod::Iqr));
+        assert!(benchmark.iterations[4].outlier);
+        assert_eq!(5, benchmark.iterations.len());
+
+        benchmark.summarize(&[]);
+        assert_eq!(1.1, benchmark.summary[&Metric::WallTime].max);
+
+        // A crash isn't an outlier, and doesn't move the fences for the rest.
+        benchmark.iterations[2]
+            .metrics
+            .insert(Metric::WallTime, 0.01);
+        benchmark.iterations[2].exit_code = Some(101);
+        assert_eq!(1, benchmark.flag_outliers(OutlierMethod::Iqr));
+        assert!(!benchmark.iterations[2].outlier);
+        assert!(benchmark.iterations[4].outlier);
+    }
+
+    #[test]
+    fn check_output_catches_differences() {
+        let mut benchmark = BenchmarkResults {
+            iterations: ["a", "a", "b"]
+                .iter()
+                .map(|hash| Iteration {
+                    output_hash: Some(hash.to_string()),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(2, benchmark.check_output());
+        assert!(benchmark.inconsistent_output);
+
+        // A failed iteration's output doesn't count.
+        benchmark.iterations[2].exit_code = Some(1);
+        assert_eq!(1, benchmark.check_output());
+        assert!(!benchmark.inconsistent_output);
+    }
+
+    #[test]
+    fn failures_are_excluded_from_summary() {
+        let mut benchmark = BenchmarkResults {
+            iterations: [1.0, 2.0, 0.1]
+                .iter()
+                .map(|&wall_time| iteration(wall_time, None))
+                .collect(),
+            ..Default::default()
+        };
+        benchmark.iterations[0].exit_code = Some(0);
+        benchmark.iterations[2].exit_code = Some(101);
+        benchmark.summarize(&[]);
+
+        assert_eq!(1, benchmark.failures().len());
+        assert_eq!("exit code 101", benchmark.failures()[0].status());
+        assert_eq!(1.0, benchmark.summary[&Metric::WallTime].min);
+    }
+
+    #[test]
+    fn flag_throttled_catches_frequency_drops() {
+        let mut benchmark = BenchmarkResults {
+            iterations: [Some(3000.0), Some(2950.0), Some(2000.0), None, Some(3000.0)]
+                .iter()
+                .map(|&cpu_mhz| Iteration {
+                    cpu_mhz,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        benchmark.iterations[4].throttled = true;
+
+        assert_eq!(2, benchmark.flag_throttled(0.1));
+        let flags: Vec<bool> = benchmark.iterations.iter().map(|i| i.throttled).collect();
+        assert_eq!(vec![false, false, true, false, true], flags);
+    }
+
+    #[test]
+    fn excluded_throttled_iterations_leave_the_summary_but_not_as_outliers() {
+        let mut benchmark = BenchmarkResults {
+            iterations: [1.0, 2.0, 9.0]
+                .iter()
+                .map(|&wall_time| Iteration {
+                    metrics: BTreeMap::from([(Metric::WallTime, wall_time)]),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        benchmark.iterations[2].throttled = true;
+        benchmark.summarize(&[]);
+        assert_eq!(9.0, benchmark.summary[&Metric::WallTime].max);
+
+        benchmark.exclude_throttled = true;
+        benchmark.summarize(&[]);
+        assert_eq!(2.0, benchmark.summary[&Metric::WallTime].max);
+        assert!(benchmark
+            .iterations
+            .iter()
+            .all(|iteration| !iteration.outlier));
+    }
+
+    #[test]
+    fn throughput_uses_mean_wall_time() {
+        let mut benchmark = BenchmarkResults {
+            sample_bytes: Some(3000),
+            sample_lines: Some(150),
+            iterations: vec![iteration(1.0, None), iteration(2.0, None)],
+            ..Default::default()
+        };
+        assert_eq!(None, benchmark.bytes_per_second());
+
+        benchmark.summarize(&[]);
+        assert_eq!(Some(2000.0), benchmark.bytes_per_second());
+        assert_eq!(Some(100.0), benchmark.lines_per_second());
+        assert_eq!(Some(0.0005), benchmark.seconds_per_byte());
+        assert_eq!(Some(0.01), benchmark.seconds_per_line());
+
+        benchmark.sample_lines = None;
+        assert_eq!(None, benchmark.lines_per_second());
+    }
+}
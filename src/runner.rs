@@ -0,0 +1,1073 @@
+//! Runs the benchmarks in a suite, printing progress as it goes.
+
+use crate::affinity::CpuSet;
+use crate::cgroup::Cgroup;
+use crate::config::{Case, Cooldown, GeneratedSample, Suite, Verify};
+use crate::environment::Environment;
+use crate::git;
+use crate::measure::{self, Input, InputMode, MeasureOptions};
+use crate::priority;
+use crate::progress::Progress;
+use crate::rawlog::RawLog;
+use crate::reference::{self, Grouping};
+use crate::results::{self, BenchmarkResults, Iteration, Metric, SuiteResults, Unit};
+use crate::stats;
+use crate::thermal::CpuFiles;
+use crate::timestamp;
+use crate::verbosity::{self, Verbosity};
+use groupby_benchmarking::sample::Metadata as SampleMetadata;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How far below a benchmark's median CPU frequency an iteration's may fall before we consider it
+/// throttled.
+const MAX_FREQUENCY_DROP: f64 = 0.1;
+
+/// How many groups' worth of wrong answers to record when a case's answers are checked.
+const MAX_WRONG_ANSWERS: usize = 5;
+
+/// Runs every benchmark in the suite, in order, on a machine described by `environment`, saving
+/// the results so far to `results::partial_path(results_dir)` after each. If `resume` is set,
+/// picks up where the run that saved them left off, skipping the benchmarks it finished. With
+/// `parallel` above 1, runs up to that many cases at once, as long as they don't share samples.
+pub fn run_suite(
+    suite: &Suite,
+    environment: Environment,
+    results_dir: &Path,
+    resume: bool,
+    parallel: usize,
+) -> Result<SuiteResults, Box<dyn Error>> {
+    let options = suite.measure_options();
+    let harness_cpus = suite.harness_cpus();
+    if !harness_cpus.is_empty() {
+        // Threads inherit the affinity of the thread that starts them, so this covers the
+        // harness's background threads too.
+        CpuSet::new(&harness_cpus)?
+            .apply()
+            .map_err(|e| format!("pinning the harness to CPUs: {}", e))?;
+    }
+    if let Some(class) = suite
+        .priority
+        .as_ref()
+        .and_then(|priority| priority.harness_io)
+    {
+        priority::set_io_class(Some(class))
+            .map_err(|e| format!("setting the harness's I/O priority: {}", e))?;
+    }
+    let partial_path = results::partial_path(results_dir);
+    let mut results = match resume_from(suite, &partial_path, resume)? {
+        Some(results) => results,
+        None => {
+            let mut results = SuiteResults::default();
+            results.metadata.started_at = timestamp::rfc3339(SystemTime::now());
+            results.metadata.bench_git = git::own_state();
+            results.metadata.environment = Some(environment);
+            results.metadata.suite = Some(suite.clone());
+            results
+        }
+    };
+    if parallel > 1 {
+        results.metadata.parallel = Some(parallel);
+    }
+    let log_path = results_dir.join("measurements.jsonl");
+    let log = RawLog::open(&log_path, &results.metadata.started_at)?;
+    let benchmarks = suite
+        .cases
+        .iter()
+        .map(|case| case.variants().len() * case.thread_counts().len() * case.samples.len())
+        .sum();
+    let iterations_each = suite.warmup + suite.iterations;
+    let shared = Shared {
+        options,
+        iterations_each,
+        log: Mutex::new(log),
+        progress: Mutex::new(Progress::start(benchmarks, iterations_each)),
+        references: Mutex::new(HashMap::new()),
+        results: Mutex::new(results),
+        partial_path,
+    };
+
+    let schedule = Schedule::new(suite.cases.len());
+    thread::scope(|scope| {
+        for _ in 0..parallel.clamp(1, suite.cases.len().max(1)) {
+            scope.spawn(|| {
+                while let Some(i) = schedule.next(suite) {
+                    let result = run_case(suite, i, &shared).map_err(|e| e.to_string());
+                    schedule.done(suite, i, result.err());
+                }
+            });
+        }
+    });
+    if let Some(e) = schedule.error() {
+        return Err(e.into());
+    }
+
+    let mut results = shared.results.into_inner().unwrap();
+    // Cases run in parallel finish out of order.
+    results.benchmarks.sort_by_key(|benchmark| {
+        suite
+            .cases
+            .iter()
+            .position(|case| case.name == benchmark.case)
+    });
+    results.metadata.finished_at = timestamp::rfc3339(SystemTime::now());
+    Ok(results)
+}
+
+/// The number of lines in each group, by key.
+type GroupSizes = HashMap<String, u64>;
+
+/// What the cases of a suite share while it runs, behind locks, since several may run at once.
+struct Shared {
+    options: MeasureOptions,
+    iterations_each: usize,
+    log: Mutex<RawLog>,
+    progress: Mutex<Progress>,
+
+    /// Reference groupings, by sample and grouping, so each is only worked out once.
+    references: Mutex<HashMap<(String, Grouping), Arc<GroupSizes>>>,
+
+    /// The results so far, saved to `partial_path` as each benchmark finishes.
+    results: Mutex<SuiteResults>,
+    partial_path: PathBuf,
+}
+
+/// Hands out a suite's cases, in order, to the threads running them. Two cases that share a
+/// sample never run at once, since preparing the page cache for one would disturb the other.
+struct Schedule {
+    state: Mutex<ScheduleState>,
+    changed: Condvar,
+}
+
+struct ScheduleState {
+    /// The indices of the cases not started yet.
+    pending: Vec<usize>,
+
+    /// The samples of the cases running now.
+    busy: Vec<String>,
+
+    /// The first error a case failed with, after which no more are started.
+    error: Option<String>,
+}
+
+impl Schedule {
+    fn new(cases: usize) -> Schedule {
+        Schedule {
+            state: Mutex::new(ScheduleState {
+                pending: (0..cases).collect(),
+                busy: vec![],
+                error: None,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Waits until a case can start, and returns its index, or `None` if there are no more to
+    /// start, or a case failed.
+    fn next(&self, suite: &Suite) -> Option<usize> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.error.is_some() || state.pending.is_empty() {
+                return None;
+            }
+            let free = state.pending.iter().position(|&i| {
+                suite.cases[i]
+                    .samples
+                    .iter()
+                    .all(|sample| !state.busy.contains(sample))
+            });
+            if let Some(position) = free {
+                let i = state.pending.remove(position);
+                state.busy.extend(suite.cases[i].samples.iter().cloned());
+                return Some(i);
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Records that the case at index `i` finished, with `error` if it failed.
+    fn done(&self, suite: &Suite, i: usize, error: Option<String>) {
+        let mut state = self.state.lock().unwrap();
+        for sample in &suite.cases[i].samples {
+            if let Some(position) = state.busy.iter().position(|busy| busy == sample) {
+                state.busy.remove(position);
+            }
+        }
+        if state.error.is_none() {
+            state.error = error;
+        }
+        self.changed.notify_all();
+    }
+
+    fn error(&self) -> Option<String> {
+        self.state.lock().unwrap().error.clone()
+    }
+}
+
+/// Runs every benchmark of the case at index `i`, skipping any a resumed run already finished.
+fn run_case(suite: &Suite, i: usize, shared: &Shared) -> Result<(), Box<dyn Error>> {
+    let case = &suite.cases[i];
+    let iterations_each = shared.iterations_each;
+    let mut options = shared.options.clone();
+    options.input_mode = case.input;
+    options.output_check = case.output_check;
+    options.env = case.env.clone();
+    options.cwd = case.cwd.clone();
+    if let Some(mib) = case.memory_limit_mib {
+        let bytes = mib
+            .checked_mul(1024 * 1024)
+            .ok_or_else(|| format!("{}: memory_limit_mib is too large", case.name))?;
+        let cgroup = Cgroup::create(&i.to_string(), bytes)
+            .map_err(|e| format!("{}: limiting memory: {}", case.name, e))?;
+        options.cgroup = Some(Arc::new(cgroup));
+    }
+    let mut sources = vec![];
+    for (variant, program) in case.variants() {
+        let source = case.source_dir(&program);
+        let git = source.as_deref().and_then(git::state);
+        if let Some(git) = git.as_ref().filter(|git| git.dirty) {
+            println!(
+                "warning: {} was built from a checkout with uncommitted changes ({})",
+                variant.as_deref().unwrap_or(&case.name),
+                git.describe()
+            );
+        }
+        sources.push(git);
+    }
+
+    for threads in case.thread_counts() {
+        for sample in &case.samples {
+            // Every variant's results are saved together, so one's as good as all.
+            let done = shared
+                .results
+                .lock()
+                .unwrap()
+                .benchmarks
+                .iter()
+                .any(|benchmark| {
+                    benchmark.case == case.name
+                        && benchmark.sample == *sample
+                        && benchmark.threads == threads
+                });
+            if done {
+                let mut progress = shared.progress.lock().unwrap();
+                progress.skipped(case.variants().len(), iterations_each);
+                continue;
+            }
+            // In the JSON log, the span's closing event times the sample's benchmarks.
+            let span = tracing::info_span!("sample", case = %case.name, sample = %sample, threads);
+            let _span = span.enter();
+            let path = suite.sample_path(sample);
+            let generated = suite.generated.get(sample);
+            let staged = match (&suite.stage_dir, generated) {
+                (dir, Some(generated)) if generated.ephemeral => {
+                    let dir = dir.as_deref().unwrap_or(&suite.sample_dir);
+                    Some(
+                        StagedSample::generate(generated, sample, dir)
+                            .map_err(|e| format!("generating {}: {}", sample, e))?,
+                    )
+                }
+                (Some(dir), None) => Some(
+                    StagedSample::copy(&path, dir)
+                        .map_err(|e| format!("staging {}: {}", path.display(), e))?,
+                ),
+                _ => None,
+            };
+            let input = match (generated, &staged) {
+                (_, Some(staged)) => Input::File(&staged.path),
+                (Some(generated), None) => Input::Generated(generated),
+                (None, None) => Input::File(&path),
+            };
+            let progress = shared
+                .progress
+                .lock()
+                .unwrap()
+                .starting(case.variants().len());
+            if verbosity::shows(Verbosity::Normal) {
+                println!("[{}]", progress);
+            }
+            let (sample_spec, sample_keys) = match generated {
+                Some(generated) => (Some(generated.spec_hash()), None),
+                None => match SampleMetadata::read(&path) {
+                    Some(metadata) => (
+                        Some(metadata.spec_hash),
+                        metadata.groups.map(|groups| groups.keys),
+                    ),
+                    None => (None, None),
+                },
+            };
+            let benchmarks =
+                run_benchmarks(suite, case, threads, sample, input, &options, &shared.log)?;
+            for benchmark in &benchmarks {
+                // Timed-out iterations aren't kept, but did take their time.
+                let iterations =
+                    suite.warmup + benchmark.iterations.len() + benchmark.timed_out as usize;
+                let mut progress = shared.progress.lock().unwrap();
+                progress.finished(iterations, iterations_each);
+            }
+            let mut finished = vec![];
+            for (mut benchmark, git) in benchmarks.into_iter().zip(&sources) {
+                benchmark.git = git.clone();
+                benchmark.sample_spec = sample_spec.clone();
+                benchmark.sample_keys = sample_keys;
+                if let (Some(verify), false) = (&case.verify, benchmark.timed_out) {
+                    if verbosity::shows(Verbosity::Normal) {
+                        println!("{}", benchmark.id());
+                    }
+                    match input {
+                        Input::File(path) => {
+                            let reference = reference_counts(shared, sample, verify, path)?;
+                            benchmark.wrong_answers = check_answers(
+                                &benchmark.id(),
+                                case,
+                                verify,
+                                &benchmark.command,
+                                path,
+                                &reference,
+                            );
+                        }
+                        Input::Generated(_) => {
+                            if verbosity::shows(Verbosity::Normal) {
+                                println!("  note: answers can't be verified on a generated sample");
+                            }
+                        }
+                    }
+                }
+                finished.push(benchmark);
+            }
+            // An ephemeral sample is generated afresh for every case, so its grouping can't be
+            // reused.
+            if generated.is_some_and(|generated| generated.ephemeral) {
+                let mut references = shared.references.lock().unwrap();
+                references.retain(|(name, _), _| name != sample);
+            }
+            let mut results = shared.results.lock().unwrap();
+            results.benchmarks.extend(finished);
+            checkpoint(&results, &shared.partial_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the reference grouping of `sample`, read from `path`, working it out if no case has
+/// yet.
+fn reference_counts(
+    shared: &Shared,
+    sample: &str,
+    verify: &Verify,
+    path: &Path,
+) -> Result<Arc<GroupSizes>, Box<dyn Error>> {
+    let key = (sample.to_string(), verify.group_by);
+    if let Some(counts) = shared.references.lock().unwrap().get(&key) {
+        return Ok(counts.clone());
+    }
+    // Reading the sample takes a while, and other cases shouldn't wait on it, so we do it without
+    // the lock. Two cases checking the same sample at once may both work it out, and the first to
+    // finish wins.
+    let counts = reference::group_counts(BufReader::new(File::open(path)?), verify.group_by)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut references = shared.references.lock().unwrap();
+    Ok(references.entry(key).or_insert(Arc::new(counts)).clone())
+}
+
+/// Loads the results of an interrupted run of `suite` from `path` if `resume` is set and there are
+/// any, making sure the suite hasn't changed since.
+fn resume_from(
+    suite: &Suite,
+    path: &Path,
+    resume: bool,
+) -> Result<Option<SuiteResults>, Box<dyn Error>> {
+    if !resume {
+        return Ok(None);
+    }
+    if !path.exists() {
+        println!("There's no interrupted run to resume; starting from the beginning");
+        return Ok(None);
+    }
+    let results = SuiteResults::load(path)?;
+    if serde_json::to_value(&results.metadata.suite)? != serde_json::to_value(Some(suite))? {
+        return Err(format!(
+            "{}: the suite has changed since the interrupted run, so its results can't be \
+             resumed; run without --resume to start over",
+            path.display()
+        )
+        .into());
+    }
+    if verbosity::shows(Verbosity::Normal) {
+        println!(
+            "Resuming the run started at {}, which finished {} benchmark(s)",
+            results.metadata.started_at,
+            results.benchmarks.len()
+        );
+    }
+    Ok(Some(results))
+}
+
+/// Saves the results so far to `path`, replacing the previous save only once it's complete.
+fn checkpoint(results: &SuiteResults, path: &Path) -> Result<(), Box<dyn Error>> {
+    let new = path.with_extension("json.new");
+    results.save(&new)?;
+    fs::rename(&new, path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// A copy of a sample in the staging directory, or an ephemeral sample written out for the
+/// benchmarks that use it, deleted again when dropped.
+pub struct StagedSample {
+    pub path: PathBuf,
+}
+
+impl StagedSample {
+    /// Writes the generated sample `name` to a file in `dir`.
+    pub fn generate(sample: &GeneratedSample, name: &str, dir: &Path) -> io::Result<StagedSample> {
+        let path = dir.join(format!("bench-{}-{}", std::process::id(), name));
+        let staged = StagedSample { path };
+        // Dropping the half-written sample deletes it if, say, the disk fills up.
+        let mut file = BufWriter::new(File::create(&staged.path)?);
+        io::copy(&mut sample.reader(), &mut file)?;
+        file.flush()?;
+        Ok(staged)
+    }
+
+    fn copy(sample: &Path, dir: &Path) -> io::Result<StagedSample> {
+        let name = sample.file_name().unwrap_or_default().to_string_lossy();
+        let path = dir.join(format!("bench-{}-{}", std::process::id(), name));
+        // Don't leave a partial copy behind if, say, the staging directory fills up.
+        if let Err(e) = fs::copy(sample, &path) {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+        Ok(StagedSample { path })
+    }
+}
+
+impl Drop for StagedSample {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Runs one case against one sample, at one thread count if the case is swept, once for each of
+/// its variants: first the warmup iterations, then the measured ones. With several variants, each
+/// round runs one iteration of every variant in turn.
+fn run_benchmarks(
+    suite: &Suite,
+    case: &Case,
+    threads: Option<usize>,
+    sample: &str,
+    input: Input,
+    options: &MeasureOptions,
+    log: &Mutex<RawLog>,
+) -> Result<Vec<BenchmarkResults>, Box<dyn Error>> {
+    // We count a file's lines only once the iterations are done, as reading it would fill the
+    // page cache that cold and as-is cache modes mean to leave alone.
+    let (sample_bytes, sample_lines) = match input {
+        Input::File(path) => (fs::metadata(path).ok().map(|metadata| metadata.len()), None),
+        Input::Generated(generated) => (generated.bytes(), generated.lines.map(|n| n as u64)),
+    };
+    let mut benchmarks: Vec<BenchmarkResults> = case
+        .variants()
+        .into_iter()
+        .map(|(variant, program)| {
+            let mut command = case.command_with_threads(threads);
+            command[0] = program;
+            BenchmarkResults {
+                case: case.name.clone(),
+                variant,
+                sample: sample.to_string(),
+                threads,
+                sample_bytes,
+                sample_lines,
+                command,
+                cache: suite.cache,
+                iterations: Vec::with_capacity(suite.iterations),
+                ..Default::default()
+            }
+        })
+        .collect();
+    if verbosity::shows(Verbosity::Normal) {
+        for benchmark in &benchmarks {
+            println!("{}", benchmark.id());
+        }
+    }
+    // Generated samples never touch the page cache.
+    if let Input::File(path) = input {
+        suite
+            .cache
+            .before_benchmark(path)
+            .map_err(|e| format!("{}: preparing the page cache: {}", benchmarks[0].id(), e))?;
+    }
+    let interleaved = benchmarks.len() > 1;
+    let mut first = true;
+
+    // Warmup iterations settle the page cache and CPU frequency; we run them exactly like
+    // measured iterations, but throw their measurements away.
+    for i in 0..suite.warmup {
+        for benchmark in benchmarks
+            .iter_mut()
+            .filter(|benchmark| !benchmark.timed_out)
+        {
+            if !first {
+                cool_down(case, options);
+            }
+            first = false;
+            let iteration = run_iteration(suite, benchmark, input, options)?;
+            log.lock().unwrap().record(benchmark, true, i, &iteration)?;
+            if iteration.timed_out {
+                abandon(benchmark, suite);
+                continue;
+            }
+            print_iteration(benchmark, interleaved, "warmup", i, &iteration);
+        }
+    }
+
+    // With a precision target, `iterations` is only the minimum: we keep going until the
+    // confidence interval of the mean wall time is narrow enough or we hit the cap.
+    loop {
+        let mut ran = false;
+        for benchmark in &mut benchmarks {
+            if benchmark.timed_out
+                || (benchmark.iterations.len() >= suite.iterations
+                    && !needs_more_iterations(suite, benchmark))
+            {
+                continue;
+            }
+            if !first {
+                cool_down(case, options);
+            }
+            first = false;
+            ran = true;
+            let iteration = run_iteration(suite, benchmark, input, options)?;
+            let index = benchmark.iterations.len();
+            log.lock()
+                .unwrap()
+                .record(benchmark, false, index, &iteration)?;
+            if iteration.timed_out {
+                abandon(benchmark, suite);
+                continue;
+            }
+            print_iteration(benchmark, interleaved, "iteration", index, &iteration);
+            benchmark.iterations.push(iteration);
+        }
+        if !ran {
+            break;
+        }
+    }
+    if let Input::File(path) = input {
+        let sample_lines = count_lines(path).ok();
+        for benchmark in &mut benchmarks {
+            benchmark.sample_lines = sample_lines;
+        }
+    }
+
+    for benchmark in benchmarks
+        .iter_mut()
+        .filter(|benchmark| !benchmark.timed_out)
+    {
+        if interleaved && verbosity::shows(Verbosity::Normal) {
+            println!("{}", benchmark.id());
+        }
+        finish_benchmark(suite, benchmark);
+    }
+    Ok(benchmarks)
+}
+
+/// Flags a benchmark's outliers, throttled iterations, and failures, summarizes it, and prints the
+/// summary, or when quiet, just the failures.
+fn finish_benchmark(suite: &Suite, benchmark: &mut BenchmarkResults) {
+    let outliers = benchmark.flag_outliers(suite.outliers);
+    let outlier_list = flagged(benchmark, |iteration| iteration.outlier);
+    let throttled = benchmark.flag_throttled(MAX_FREQUENCY_DROP);
+    let throttled_list = flagged(benchmark, |iteration| iteration.throttled);
+    benchmark.exclude_throttled = suite.exclude_throttled;
+    benchmark.summarize(&suite.percentiles);
+    let outputs = benchmark.check_output();
+    let failures = benchmark.failures();
+    tracing::info!(
+        benchmark = %benchmark.id(),
+        iterations = benchmark.iterations.len(),
+        mean_seconds = benchmark.summary.get(&Metric::WallTime).map(|summary| summary.mean),
+        failures = failures.len(),
+        outliers,
+        throttled,
+        inconsistent_output = benchmark.inconsistent_output,
+        "finished benchmark"
+    );
+    if verbosity::shows(Verbosity::Normal) {
+        print_summary(benchmark);
+        if outliers > 0 {
+            println!(
+                "  excluded {} outlier(s): iteration {}",
+                outliers, outlier_list
+            );
+        }
+        let retried = flagged(benchmark, |iteration| !iteration.retried.is_empty());
+        if !retried.is_empty() {
+            let attempts: usize = benchmark
+                .iterations
+                .iter()
+                .map(|iteration| iteration.retried.len())
+                .sum();
+            println!(
+                "  retried {} failed attempt(s): iteration {}",
+                attempts, retried
+            );
+        }
+        if throttled > 0 {
+            println!(
+                "  {} {} throttled iteration(s): iteration {}",
+                if suite.exclude_throttled {
+                    "excluded"
+                } else {
+                    "kept"
+                },
+                throttled,
+                throttled_list
+            );
+        }
+    } else if benchmark.inconsistent_output || !failures.is_empty() {
+        println!("{}", benchmark.id());
+    }
+    if benchmark.inconsistent_output {
+        println!(
+            "  WARNING: the iterations produced {} different outputs; see `output_check`",
+            outputs
+        );
+    }
+    if let Some(last) = failures.last() {
+        println!(
+            "  excluded {} FAILED iteration(s): iteration {}",
+            failures.len(),
+            flagged(benchmark, Iteration::failed)
+        );
+        println!("  the last failure ({}) said:", last.status());
+        for line in last.stderr.lines() {
+            println!("    {}", line);
+        }
+    }
+}
+
+/// Runs a benchmark's command once more, asking it for the size of each group, and compares its
+/// answers with `reference`. Returns how they differed, printing a line either way unless quiet,
+/// when only wrong answers are printed, after the benchmark's `id`.
+fn check_answers(
+    id: &str,
+    case: &Case,
+    verify: &Verify,
+    command: &[String],
+    sample: &Path,
+    reference: &HashMap<String, u64>,
+) -> Vec<String> {
+    let mut command_line = command.to_vec();
+    command_line.extend(verify.arguments.iter().cloned());
+    let mut command = Command::new(&command_line[0]);
+    command.envs(&case.env);
+    if let Some(cwd) = &case.cwd {
+        command.current_dir(cwd);
+    }
+    if case.input == InputMode::Argument {
+        let sample = match std::path::absolute(sample) {
+            Ok(sample) => sample,
+            Err(e) => return wrong_answer(id, format!("{}: {}", sample.display(), e)),
+        };
+        command.args(measure::arguments_with_sample(&command_line[1..], &sample));
+    } else {
+        command.args(&command_line[1..]);
+        match File::open(sample) {
+            Ok(file) => command.stdin(file),
+            Err(e) => return wrong_answer(id, format!("{}: {}", sample.display(), e)),
+        };
+    }
+    let output = match command.output() {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            return wrong_answer(
+                id,
+                format!(
+                    "asking for group sizes failed ({}): {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            )
+        }
+        Err(e) => return wrong_answer(id, format!("asking for group sizes: {}", e)),
+    };
+    let counts =
+        match reference::parse_counts(&String::from_utf8_lossy(&output.stdout), verify.count) {
+            Ok(counts) => counts,
+            Err(e) => return wrong_answer(id, format!("reading group sizes: {}", e)),
+        };
+    let differences = reference::differences(reference, &counts, MAX_WRONG_ANSWERS);
+    if differences.is_empty() {
+        if verbosity::shows(Verbosity::Normal) {
+            println!(
+                "  answers match the reference grouping ({} groups)",
+                reference.len()
+            );
+        }
+    } else {
+        tracing::warn!(benchmark = %id, differences = differences.len(), "wrong answers");
+        name_if_quiet(id);
+        println!("  WRONG ANSWERS, compared with the reference grouping:");
+        for difference in &differences {
+            println!("    {}", difference);
+        }
+    }
+    differences
+}
+
+/// Prints and returns a reason a case's answers couldn't be checked, which counts against them.
+fn wrong_answer(id: &str, reason: String) -> Vec<String> {
+    tracing::warn!(benchmark = %id, reason, "answers couldn't be checked");
+    name_if_quiet(id);
+    println!("  WRONG ANSWERS: {}", reason);
+    vec![reason]
+}
+
+/// Prints a benchmark's `id` ahead of a problem with it when quiet, since the line naming it
+/// otherwise wasn't printed.
+fn name_if_quiet(id: &str) {
+    if !verbosity::shows(Verbosity::Normal) {
+        println!("{}", id);
+    }
+}
+
+/// Marks a benchmark as timed out, keeping (and summarizing) whatever iterations finished before.
+fn abandon(benchmark: &mut BenchmarkResults, suite: &Suite) {
+    benchmark.timed_out = true;
+    benchmark.summarize(&suite.percentiles);
+    tracing::warn!(benchmark = %benchmark.id(), "timed out");
+    println!(
+        "  timed out after {}; skipping the rest of {}",
+        Unit::Seconds.format(suite.timeout_ms.unwrap_or(0) as f64 / 1000.0),
+        benchmark.id()
+    );
+}
+
+/// Prepares the page cache for an iteration of `benchmark`, then runs and measures it, retrying
+/// as many times as the suite allows if it fails.
+fn run_iteration(
+    suite: &Suite,
+    benchmark: &BenchmarkResults,
+    input: Input,
+    options: &MeasureOptions,
+) -> Result<Iteration, Box<dyn Error>> {
+    let mut retried = vec![];
+    loop {
+        if let Input::File(path) = input {
+            suite
+                .cache
+                .before_iteration(path)
+                .map_err(|e| format!("{}: preparing the page cache: {}", benchmark.id(), e))?;
+        }
+        let mut iteration = measure::measure(&benchmark.command, input, options)
+            .map_err(|e| format!("{}: {}", benchmark.id(), e))?;
+        // A timeout isn't transient, and would only cost another timeout's worth of waiting.
+        if !iteration.failed() || iteration.timed_out || retried.len() >= suite.retries {
+            iteration.retried = retried;
+            return Ok(iteration);
+        }
+        tracing::warn!(
+            benchmark = %benchmark.id(),
+            status = iteration.status(),
+            "attempt failed; retrying"
+        );
+        if verbosity::shows(Verbosity::Normal) {
+            println!("  attempt failed ({}); retrying", iteration.status());
+        }
+        retried.push(iteration.status());
+    }
+}
+
+/// Pauses before an iteration as the case's cooldown asks, if it has one.
+fn cool_down(case: &Case, options: &MeasureOptions) {
+    let Some(cooldown) = &case.cooldown else {
+        return;
+    };
+    thread::sleep(Duration::from_millis(cooldown.ms));
+    if let (Some(limit), Some(files)) = (cooldown.below_celsius, &options.cpu_files) {
+        if let Err(celsius) = wait_until_cool(files, limit, cooldown) {
+            println!(
+                "  warning: CPU still at {:.1} °C after waiting {} ms to cool below {:.1} °C",
+                celsius, cooldown.max_wait_ms, limit
+            );
+        }
+    }
+}
+
+/// Waits until the CPU is no hotter than `limit` degrees Celsius, or returns the last temperature
+/// seen if it's still hotter after `cooldown.max_wait_ms`.
+fn wait_until_cool(files: &CpuFiles, limit: f64, cooldown: &Cooldown) -> Result<(), f64> {
+    let deadline = Instant::now() + Duration::from_millis(cooldown.max_wait_ms);
+    loop {
+        match files.temperature() {
+            Some(celsius) if celsius > limit => {
+                if Instant::now() >= deadline {
+                    return Err(celsius);
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// Counts the lines in a file. A final line without a trailing newline still counts.
+fn count_lines(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut lines = 0;
+    let mut last = b'\n';
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        lines += buffer[..n].iter().filter(|&&b| b == b'\n').count() as u64;
+        last = buffer[n - 1];
+    }
+    if last != b'\n' {
+        lines += 1;
+    }
+    Ok(lines)
+}
+
+/// Decides whether an adaptive benchmark needs another iteration to meet its precision target.
+fn needs_more_iterations(suite: &Suite, benchmark: &BenchmarkResults) -> bool {
+    let target = match suite.target_precision {
+        Some(target) => target,
+        None => return false,
+    };
+    if benchmark.iterations.len() >= suite.max_iterations {
+        return false;
+    }
+    match stats::relative_ci_half_width(&benchmark.values(Metric::WallTime)) {
+        Some(precision) => precision > target,
+        None => true,
+    }
+}
+
+/// Prints a one-line summary of an iteration, e.g. `  warmup 1: ...`, with the benchmark's
+/// variant in front if its iterations are interleaved with other variants', if verbose. With
+/// `Verbosity::Raw`, also prints every metric's raw value.
+fn print_iteration(
+    benchmark: &BenchmarkResults,
+    interleaved: bool,
+    label: &str,
+    index: usize,
+    iteration: &Iteration,
+) {
+    if !verbosity::shows(Verbosity::Verbose) {
+        return;
+    }
+    let label = match (&benchmark.variant, interleaved) {
+        (Some(variant), true) => format!("[{}] {}", variant, label),
+        _ => label.to_string(),
+    };
+    let failure = if iteration.failed() {
+        format!("  FAILED ({})", iteration.status())
+    } else {
+        String::new()
+    };
+    println!(
+        "  {:>9} {:>3}: {:>10}  first output {:>10}  max RSS {:>10}{}",
+        label,
+        index + 1,
+        iteration.format(Metric::WallTime),
+        iteration.format(Metric::FirstOutput),
+        iteration.format(Metric::MaxRss),
+        failure
+    );
+    if verbosity::shows(Verbosity::Raw) {
+        let metrics: Vec<String> = iteration
+            .metrics
+            .iter()
+            .map(|(metric, value)| format!("{}={}", metric.name(), value))
+            .collect();
+        println!("  {:>13}  {}", "", metrics.join(" "));
+    }
+}
+
+/// Lists the (1-based) numbers of the iterations matching `predicate`, e.g. `2, 7`.
+fn flagged(benchmark: &BenchmarkResults, predicate: impl Fn(&Iteration) -> bool) -> String {
+    let numbers: Vec<String> = benchmark
+        .iterations
+        .iter()
+        .enumerate()
+        .filter(|(_, iteration)| predicate(iteration))
+        .map(|(i, _)| (i + 1).to_string())
+        .collect();
+    numbers.join(", ")
+}
+
+/// Prints the summary statistics of a finished benchmark, one line per metric.
+fn print_summary(benchmark: &BenchmarkResults) {
+    for (metric, summary) in &benchmark.summary {
+        let unit = metric.unit();
+        let percentiles: Vec<String> = summary
+            .percentiles
+            .iter()
+            .map(|(name, &value)| format!("{} {}", name, unit.format(value)))
+            .collect();
+        println!(
+            "  {:>15}: {} ± {}  median {}  [{} … {}]  {}",
+            metric.label(),
+            unit.format(summary.mean),
+            unit.format(summary.stddev),
+            unit.format(summary.median),
+            unit.format(summary.min),
+            unit.format(summary.max),
+            percentiles.join("  "),
+        );
+    }
+
+    let rates: Vec<String> = [
+        (benchmark.bytes_per_second(), Unit::BytesPerSecond),
+        (benchmark.lines_per_second(), Unit::LinesPerSecond),
+    ]
+    .iter()
+    .filter_map(|&(rate, unit)| Some(unit.format(rate?)))
+    .collect();
+    if !rates.is_empty() {
+        println!("  {:>15}: {}", "throughput", rates.join("  "));
+    }
+}
+
+#[cfg(test)]
+mod count_lines_tests {
+    use super::*;
+
+    fn count(contents: &str) -> u64 {
+        let path = std::env::temp_dir().join(format!("count-lines-{}.txt", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        let lines = count_lines(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        lines
+    }
+
+    #[test]
+    fn works() {
+        assert_eq!(0, count(""));
+        assert_eq!(2, count("a\nb\n"));
+        assert_eq!(2, count("a\nb"));
+    }
+}
+
+#[cfg(test)]
+mod staged_sample_tests {
+    use super::*;
+
+    #[test]
+    fn copies_and_cleans_up() {
+        let dir = std::env::temp_dir();
+        let sample = dir.join(format!("staged-sample-{}.txt", std::process::id()));
+        fs::write(&sample, "a\nb\n").unwrap();
+        let staged = StagedSample::copy(&sample, &dir).unwrap();
+        assert_eq!("a\nb\n", fs::read_to_string(&staged.path).unwrap());
+        let path = staged.path.clone();
+        drop(staged);
+        assert!(!path.exists());
+        fs::remove_file(&sample).unwrap();
+    }
+
+    #[test]
+    fn generates_and_cleans_up() {
+        let sample = GeneratedSample {
+            line_length: Some([5, 80]),
+            characters: Some(1_000),
+            ephemeral: true,
+            ..Default::default()
+        };
+        let staged = StagedSample::generate(&sample, "ephemeral.txt", &std::env::temp_dir());
+        let staged = staged.unwrap();
+        assert_eq!(1_000, fs::metadata(&staged.path).unwrap().len());
+        let path = staged.path.clone();
+        drop(staged);
+        assert!(!path.exists());
+    }
+}
+
+#[cfg(test)]
+mod schedule_tests {
+    use super::*;
+
+    #[test]
+    fn never_runs_cases_sharing_a_sample_at_once() {
+        let suite: Suite = toml::from_str(
+            r#"
+            [[case]]
+            name = "a"
+            command = ["cat"]
+            samples = ["x.txt"]
+
+            [[case]]
+            name = "b"
+            command = ["cat"]
+            samples = ["x.txt"]
+
+            [[case]]
+            name = "c"
+            command = ["cat"]
+            samples = ["y.txt"]
+            "#,
+        )
+        .unwrap();
+        let schedule = Schedule::new(suite.cases.len());
+        assert_eq!(Some(0), schedule.next(&suite));
+        assert_eq!(Some(2), schedule.next(&suite));
+        schedule.done(&suite, 0, None);
+        assert_eq!(Some(1), schedule.next(&suite));
+        schedule.done(&suite, 2, None);
+        schedule.done(&suite, 1, Some("failed".to_string()));
+        assert_eq!(None, schedule.next(&suite));
+        assert_eq!(Some("failed".to_string()), schedule.error());
+    }
+}
+
+#[cfg(test)]
+mod run_iteration_tests {
+    use super::*;
+
+    #[test]
+    fn retries_failed_attempts() {
+        let marker = std::env::temp_dir().join(format!("retry-{}", std::process::id()));
+        let suite: Suite = toml::from_str(
+            r#"
+            retries = 2
+
+            [[case]]
+            name = "flaky"
+            command = ["sh"]
+            samples = ["a.txt"]
+            "#,
+        )
+        .unwrap();
+        // Fails the first time, then succeeds.
+        let script = format!(
+            "if [ -e {0} ]; then rm {0}; else touch {0}; exit 3; fi",
+            marker.display()
+        );
+        let benchmark = BenchmarkResults {
+            command: vec!["sh".to_string(), "-c".to_string(), script],
+            ..Default::default()
+        };
+        let options = MeasureOptions::default();
+        let iteration = run_iteration(
+            &suite,
+            &benchmark,
+            Input::File(Path::new("Cargo.toml")),
+            &options,
+        )
+        .unwrap();
+        assert!(!iteration.failed());
+        assert_eq!(vec!["exit code 3"], iteration.retried);
+        assert!(!marker.exists());
+    }
+}
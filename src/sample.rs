@@ -0,0 +1,2129 @@
+//! Generates bounded random sample text for benchmarking `groupby`. `mksample` uses this to write
+//! sample files, and `bench` uses it to stream samples straight into a command without writing
+//! them anywhere.
+
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io::{self, prelude::*, BufWriter};
+use std::mem;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Specifies the bounds for the lengths of lines in a sample file (excluding the newline character).
+#[derive(Clone, Debug)]
+pub enum LineLength {
+    /// Each line will be exactly this many characters.
+    Fixed(usize),
+
+    /// Each line's length will be randomly chosen from this range.
+    Range(Range<usize>),
+}
+
+/// Specifies the length of the output file as either a number of lines or a number of characters
+/// (including newlines). Characters are counted in the code units of the sample's encoding, i.e.
+/// bytes, except in UTF-16.
+#[derive(Debug)]
+pub enum SampleLength {
+    Lines(usize),
+    Characters(usize),
+}
+
+/// The characters samples are made of, by default. An alphabet of the first `n` of them has
+/// log₂ `n` bits of entropy per character, so it goes from a single repeated character, which
+/// compresses to almost nothing, to about six bits per character with all 62.
+pub const ALPHANUMERIC: &[u8; 62] =
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Returns a character generator drawing uniformly from the first `size` characters of
+/// `ALPHANUMERIC`, or all of them if `size` is larger, for samples of a chosen entropy.
+///
+/// # Panics
+///
+/// The generator panics if `size` is 0.
+pub fn alphabet(size: usize) -> impl Fn() -> char + Copy + Send {
+    let size = size.min(ALPHANUMERIC.len());
+    move || ALPHANUMERIC[fastrand::usize(..size)] as char
+}
+
+/// Generates alphanumerics and the letters of Latin-1 (`À` to `ÿ`, but for `×` and `÷`) at random,
+/// uniformly, for text in Western European languages. Every character is a single byte in Latin-1
+/// and Windows-1252, but half of them are two in UTF-8.
+pub fn accented() -> char {
+    loop {
+        let i = fastrand::usize(..ALPHANUMERIC.len() + 64);
+        let Some(i) = i.checked_sub(ALPHANUMERIC.len()) else {
+            return ALPHANUMERIC[i] as char;
+        };
+        let c = char::from(0xC0 + i as u8);
+        if c != '×' && c != '÷' {
+            return c;
+        }
+    }
+}
+
+/// The `index`th of `vocabulary` pseudo-words: `word` and the index, zero-padded to at least five
+/// digits, and to the width of the largest index, so every word is as long as every other.
+pub fn word(index: usize, vocabulary: usize) -> String {
+    let width = vocabulary.saturating_sub(1).to_string().len().max(5);
+    format!("word{:0width$}", index, width = width)
+}
+
+/// A line template: text with placeholders, which are filled in afresh for every line.
+///
+/// - `{KEY}` is a pseudo-word, as `word` makes, drawn from a vocabulary.
+/// - `{INT:a-b}` is an integer from `a` to `b`, inclusive.
+/// - `{RAND:n}` is `n` characters from the character generator.
+/// - `{PATH}` is a URL path of one to three segments, each of one to eight characters from the
+///   character generator.
+/// - `{{` and `}}` are literal braces.
+///
+/// For example, `{KEY} [{INT:100-599}] GET {PATH} {RAND:12}` makes lines like a web server's log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template(Vec<Part>);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Key,
+    Int(u64, u64),
+    Rand(usize),
+    Path,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, String> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(i) = rest.find(['{', '}']) {
+            text.push_str(&rest[..i]);
+            let brace = &rest[i..i + 1];
+            rest = &rest[i + 1..];
+            if let Some(after) = rest.strip_prefix(brace) {
+                text.push_str(brace);
+                rest = after;
+                continue;
+            }
+            let end = match (brace, rest.find('}')) {
+                ("{", Some(end)) => end,
+                _ => return Err(format!("unmatched {} in template {:?}", brace, template)),
+            };
+            if !text.is_empty() {
+                parts.push(Part::Text(mem::take(&mut text)));
+            }
+            parts.push(Part::parse(&rest[..end])?);
+            rest = &rest[end + 1..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template(parts))
+    }
+}
+
+impl Part {
+    fn parse(placeholder: &str) -> Result<Part, String> {
+        let invalid = || format!("invalid placeholder {{{}}}", placeholder);
+        let (name, argument) = match placeholder.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (placeholder, None),
+        };
+        match (name, argument) {
+            ("KEY", None) => Ok(Part::Key),
+            ("PATH", None) => Ok(Part::Path),
+            ("RAND", Some(n)) => n.parse().map(Part::Rand).map_err(|_| invalid()),
+            ("INT", Some(range)) => {
+                let (min, max) = range.split_once('-').ok_or_else(invalid)?;
+                match (min.parse(), max.parse()) {
+                    (Ok(min), Ok(max)) if min <= max => Ok(Part::Int(min, max)),
+                    _ => Err(invalid()),
+                }
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Template {
+    /// Writes the template as it would be parsed, e.g. `{KEY} [{INT:100-599}]`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => write!(f, "{}", text.replace('{', "{{").replace('}', "}}"))?,
+                Part::Key => write!(f, "{{KEY}}")?,
+                Part::Int(min, max) => write!(f, "{{INT:{}-{}}}", min, max)?,
+                Part::Rand(n) => write!(f, "{{RAND:{}}}", n)?,
+                Part::Path => write!(f, "{{PATH}}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Template {
+    /// Whether the template has a `{KEY}`.
+    pub fn has_key(&self) -> bool {
+        self.0.contains(&Part::Key)
+    }
+
+    /// Whether every line made from the template starts with its key, as a word of its own, so
+    /// lines group by first word just as they do by key.
+    pub fn starts_with_key(&self) -> bool {
+        match self.0.as_slice() {
+            [Part::Key] => true,
+            [Part::Key, Part::Text(text), ..] => text.starts_with(' '),
+            _ => false,
+        }
+    }
+
+    /// The most characters a line made from the template can have, excluding the newline, with
+    /// keys drawn from `vocabulary`.
+    pub fn max_length(&self, vocabulary: usize) -> usize {
+        self.0
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.chars().count(),
+                Part::Key => word(0, vocabulary).len(),
+                Part::Int(_, max) => max.to_string().len(),
+                Part::Rand(n) => *n,
+                Part::Path => 3 * 9,
+            })
+            .sum()
+    }
+
+    /// Whether the template's own text is all ASCII.
+    pub fn is_ascii(&self) -> bool {
+        self.0.iter().all(|part| match part {
+            Part::Text(text) => text.is_ascii(),
+            _ => true,
+        })
+    }
+
+    /// Fills in the template's placeholders, with keys drawn from `vocabulary`.
+    pub fn fill(&self, character_generator: &impl Fn() -> char, vocabulary: usize) -> String {
+        let mut line = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => line.push_str(text),
+                Part::Key => line.push_str(&word(fastrand::usize(..vocabulary), vocabulary)),
+                Part::Int(min, max) => line.push_str(&fastrand::u64(*min..=*max).to_string()),
+                Part::Rand(n) => line.extend((0..*n).map(|_| character_generator())),
+                Part::Path => {
+                    for _ in 0..fastrand::usize(1..=3) {
+                        line.push('/');
+                        line.extend((0..fastrand::usize(1..=8)).map(|_| character_generator()));
+                    }
+                }
+            }
+        }
+        line
+    }
+}
+
+/// How a sample's text is encoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Encoding {
+    #[default]
+    #[serde(rename = "utf-8")]
+    Utf8,
+
+    /// ISO 8859-1, which encodes the first 256 code points as single bytes.
+    #[serde(rename = "latin-1")]
+    Latin1,
+
+    /// Latin-1 with printable characters such as `€` and `Œ` in place of the C1 controls, as legacy
+    /// Windows programs write it.
+    #[serde(rename = "windows-1252")]
+    Windows1252,
+
+    /// UTF-16, little-endian, as Windows tools export text.
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+
+    /// UTF-16, big-endian.
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+}
+
+/// The characters Windows-1252 has in place of Latin-1's C1 controls, from 0x80 to 0x9F. The five
+/// bytes it leaves undefined are NUL here, which no character maps to.
+const WINDOWS_1252_C1: [char; 32] = [
+    '€', '\0', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\0', 'Ž', '\0', //
+    '\0', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\0', 'ž', 'Ÿ',
+];
+
+impl Encoding {
+    /// The encoding's name in config files, e.g. `utf-16le`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Latin1 => "latin-1",
+            Encoding::Windows1252 => "windows-1252",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+        }
+    }
+
+    /// The byte order mark in this encoding, or nothing if it hasn't one.
+    pub fn bom(&self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => &BOM,
+            Encoding::Latin1 | Encoding::Windows1252 => &[],
+            Encoding::Utf16Le => &[0xFF, 0xFE],
+            Encoding::Utf16Be => &[0xFE, 0xFF],
+        }
+    }
+
+    /// The size of the encoding's code units, in bytes.
+    pub fn unit_size(&self) -> usize {
+        match self {
+            Encoding::Utf16Le | Encoding::Utf16Be => 2,
+            _ => 1,
+        }
+    }
+
+    /// Re-encodes a line generated as UTF-8, and sets its length to its code units. Characters
+    /// the encoding lacks become `?`, as do invalid UTF-8 sequences. A line's length in
+    /// characters is its length in code units in the single-byte encodings, and in UTF-16 for
+    /// characters in the Basic Multilingual Plane, so a `SampleLength::Characters` stays exact as
+    /// long as the characters are.
+    fn encode(&self, line: Line) -> Line {
+        let to_bytes = match self {
+            Encoding::Utf8 => return line,
+            Encoding::Utf16Le => u16::to_le_bytes,
+            Encoding::Utf16Be => u16::to_be_bytes,
+            Encoding::Latin1 | Encoding::Windows1252 => return self.encode_single_byte(line),
+        };
+        let units: Vec<u16> = String::from_utf8_lossy(&line.bytes)
+            .encode_utf16()
+            .collect();
+        Line {
+            length: units.len(),
+            bytes: units.into_iter().flat_map(to_bytes).collect(),
+        }
+    }
+
+    /// Encodes a line in Latin-1 or Windows-1252.
+    fn encode_single_byte(&self, line: Line) -> Line {
+        let byte = match self {
+            Encoding::Latin1 => |c: char| u8::try_from(c).ok(),
+            Encoding::Windows1252 => |c: char| match c as u32 {
+                0..=0x7F | 0xA0..=0xFF => Some(c as u8),
+                _ => WINDOWS_1252_C1
+                    .iter()
+                    .position(|&d| d == c && c != '\0')
+                    .map(|i| 0x80 + i as u8),
+            },
+            _ => unreachable!("{:?} isn't a single-byte encoding", self),
+        };
+        let bytes: Vec<u8> = String::from_utf8_lossy(&line.bytes)
+            .chars()
+            .map(|c| byte(c).unwrap_or(b'?'))
+            .collect();
+        Line {
+            length: bytes.len(),
+            bytes,
+        }
+    }
+}
+
+/// Bytes that are invalid UTF-8 wherever they appear among ASCII, and among each other: stray
+/// continuation bytes, lead bytes that could only start overlong encodings, and bytes UTF-8 never
+/// uses. Each is one invalid sequence on its own, which keeps a line's length in bytes exact.
+const INVALID_UTF8: [u8; 77] = {
+    let mut bytes = [0; 77];
+    let mut i = 0;
+    while i < bytes.len() {
+        bytes[i] = match i {
+            0..=63 => 0x80 + i as u8,
+            64 | 65 => 0xC0 + (i - 64) as u8,
+            _ => 0xF5 + (i - 66) as u8,
+        };
+        i += 1;
+    }
+    bytes
+};
+
+/// The UTF-8 byte order mark.
+const BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+
+/// How many of the most recent distinct lines duplicates are drawn from. Drawing from a window
+/// rather than from every line so far keeps memory bounded on samples of any size, and keeps
+/// duplicates close enough to their originals to still be in a cache, as in real logs.
+const DUPLICATE_WINDOW: usize = 1024;
+
+/// Shapes a sample's content beyond the lengths of its lines. The defaults make every line fresh
+/// random text.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// The fraction of lines, from 0 to 1, that are exact copies of one of the last
+    /// `DUPLICATE_WINDOW` distinct lines.
+    pub duplicate_ratio: f64,
+
+    /// The fraction of characters, from 0 to 1, that are NUL bytes instead, for binary-ish input.
+    pub nul_rate: f64,
+
+    /// The fraction of characters, from 0 to 1, that are a byte that's never valid UTF-8 where it
+    /// is instead, for exercising lossy decoding and encoding errors.
+    pub invalid_utf8_rate: f64,
+
+    /// Whether the sample starts with a byte order mark, as files exported on Windows often do. It
+    /// counts towards a `SampleLength::Characters` as its code units, three in UTF-8 and one in
+    /// UTF-16, so the sample's size in bytes is still exact.
+    pub bom: bool,
+
+    /// Whether the sample's last line goes without a newline, a classic edge case for line
+    /// readers. A `SampleLength::Characters` is still exact: the last line takes the newline's
+    /// place.
+    pub omit_final_newline: bool,
+
+    /// The fraction of lines, from 0 to 1, that end in CRLF rather than LF, as in files that have
+    /// passed through both Windows and Unix tools.
+    pub crlf_ratio: f64,
+
+    /// The fraction of lines, from 0 to 1, made only of spaces and tabs. They're as long as any
+    /// other line, so they're blank but not empty, unless the line length allows for 0.
+    pub blank_ratio: f64,
+
+    /// If set, lines are made of pseudo-words drawn at random from this many, `word00000` and
+    /// up, separated by spaces, in place of the character generator's text. The last word is cut
+    /// off where the line ends. Made this way, a sample and the groups a command finds in it are
+    /// easy to check by eye, and the number of distinct first words is known.
+    pub words: Option<usize>,
+
+    /// If set, lines cycle through this many fixed template lines, the first one first, with no
+    /// randomness at all: each sample of a given length is the same, and has exactly this many
+    /// distinct lines, and as many distinct first characters up to 62. Lengths in a range are
+    /// spread over it by template rather than drawn at random. See `cycled_line`.
+    pub cycle: Option<usize>,
+
+    /// If set, lines are made from this template rather than to the sample's line length, which
+    /// only applies to the last line of a sample given in characters, to make its length exact.
+    /// Its `{KEY}`s are drawn from `words`, or are all `word00000` if that isn't set.
+    pub template: Option<Template>,
+
+    /// If set, lines are fixed-width records, as in mainframe exports and printed reports, with
+    /// fields of these widths. See `record`. Like a templated line, a record makes its own length.
+    pub columns: Option<Vec<usize>>,
+
+    /// How the sample is encoded. The single-byte encodings have no byte order mark, so `bom`
+    /// does nothing in them, and only UTF-8 can be invalid, so `invalid_utf8_rate` just makes
+    /// question marks in the others.
+    pub encoding: Encoding,
+}
+
+impl Options {
+    /// Describes the options that shape the sample's content, by name, leaving out those at their
+    /// defaults, e.g. `words=1000 encoding=latin-1`. An option added later is left out of the
+    /// description of every sample that doesn't use it, so their hashes stay the same.
+    pub fn spec(&self) -> String {
+        let mut parts = vec![];
+        let rates = [
+            ("duplicate_ratio", self.duplicate_ratio),
+            ("nul_rate", self.nul_rate),
+            ("invalid_utf8_rate", self.invalid_utf8_rate),
+            ("crlf_ratio", self.crlf_ratio),
+            ("blank_ratio", self.blank_ratio),
+        ];
+        for (name, rate) in rates {
+            if rate != 0.0 {
+                parts.push(format!("{}={}", name, rate));
+            }
+        }
+        if self.bom {
+            parts.push("bom".to_string());
+        }
+        if self.omit_final_newline {
+            parts.push("omit_final_newline".to_string());
+        }
+        if let Some(words) = self.words {
+            parts.push(format!("words={}", words));
+        }
+        if let Some(cycle) = self.cycle {
+            parts.push(format!("cycle={}", cycle));
+        }
+        if let Some(template) = &self.template {
+            parts.push(format!("template={:?}", template.to_string()));
+        }
+        if let Some(columns) = &self.columns {
+            let columns: Vec<String> = columns.iter().map(usize::to_string).collect();
+            parts.push(format!("columns={}", columns.join(",")));
+        }
+        if self.encoding != Encoding::Utf8 {
+            parts.push(format!("encoding={}", self.encoding.name()));
+        }
+        parts.join(" ")
+    }
+
+    /// Whether these options make a sample with a known number of groups, keyed by first word,
+    /// worth counting once it's built. Other samples' lines are mostly unique, so counting them
+    /// would take memory to match the sample, and tell us nothing. Nor would counting the lines of
+    /// a template that doesn't start with its `{KEY}`, whose first words aren't the keys.
+    pub fn has_groups(&self) -> bool {
+        let keyed = self.template.as_ref().is_none_or(Template::starts_with_key);
+        (self.words.is_some() || self.cycle.is_some()) && keyed && self.encoding.unit_size() == 1
+    }
+}
+
+/// Checks that a sample can be built with these parameters, rather than panicking partway through,
+/// and suggests a fix if it can't.
+pub fn validate(
+    line_length: &LineLength,
+    sample_length: &SampleLength,
+    options: &Options,
+) -> Result<(), String> {
+    if let LineLength::Range(range) = line_length {
+        if range.is_empty() {
+            return Err(format!(
+                "the line length range {:?} is empty; ranges exclude their end, so for lines of \
+                 exactly {} characters, use LineLength::Fixed({})",
+                range, range.start, range.start
+            ));
+        }
+    }
+    if matches!(
+        sample_length,
+        SampleLength::Lines(0) | SampleLength::Characters(0)
+    ) {
+        return Err("the sample would be empty; give it a length of at least 1".to_string());
+    }
+    if options.words == Some(0) {
+        return Err("words must be at least 1".to_string());
+    }
+    if options.cycle == Some(0) {
+        return Err("cycle must be at least 1".to_string());
+    }
+    if let Some(columns) = &options.columns {
+        if columns.is_empty() || columns.contains(&0) {
+            return Err(format!(
+                "columns {:?} needs at least one field, each at least 1 wide",
+                columns
+            ));
+        }
+    }
+    if let (Some(cycle), SampleLength::Lines(lines)) = (options.cycle, sample_length) {
+        if cycle > *lines {
+            return Err(format!(
+                "a cycle of {} lines doesn't fit in {} lines, so the sample would have fewer \
+                 groups than it says; lower cycle or raise the number of lines",
+                cycle, lines
+            ));
+        }
+    }
+    if let Some(cycle) = options.cycle {
+        let shortest = match line_length {
+            LineLength::Fixed(n) => *n,
+            LineLength::Range(range) => range.start,
+        };
+        let width = base62_width(cycle - 1);
+        if shortest < width {
+            return Err(format!(
+                "lines of {} characters can't tell a cycle of {} lines apart, which takes {}; \
+                 lower cycle or lengthen the lines",
+                shortest, cycle, width
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The version of the generator's semantics. Bump it whenever the same spec would make text that's
+/// shaped differently, so samples made before the change no longer match their specs' hashes.
+pub const GENERATOR_VERSION: u32 = 1;
+
+/// Describes how a sample is generated: its line length, its length, if it has one of its own, and
+/// the options that shape its content, e.g. `5..81 10000 lines words=1000`. It names every part
+/// explicitly, rather than by how Rust happens to format it, so it only changes with the sample.
+pub fn spec(
+    line_length: &LineLength,
+    sample_length: Option<&SampleLength>,
+    options: &Options,
+) -> String {
+    let mut parts = vec![match line_length {
+        LineLength::Fixed(n) => n.to_string(),
+        LineLength::Range(range) => format!("{}..{}", range.start, range.end),
+    }];
+    parts.extend(sample_length.map(|sample_length| match sample_length {
+        SampleLength::Lines(n) => format!("{} lines", n),
+        SampleLength::Characters(n) => format!("{} characters", n),
+    }));
+    parts.push(options.spec());
+    parts.retain(|part| !part.is_empty());
+    parts.join(" ")
+}
+
+/// Hashes a description of how a sample is generated, along with `GENERATOR_VERSION`, to eight hex
+/// digits. Samples with the same hash were made the same way, so their timings can be compared.
+///
+/// The hash is 32-bit FNV-1a, which is stable across versions of Rust and of this crate.
+pub fn spec_hash(spec: &str) -> String {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in format!("{} {}", GENERATOR_VERSION, spec).bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    format!("{:08x}", hash)
+}
+
+/// Mixes a sample's name into the seed, with 64-bit FNV-1a, which is stable across versions of
+/// Rust, unlike the standard library's hasher.
+pub fn sample_seed(seed: u64, name: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in name.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    seed ^ hash
+}
+
+/// What's recorded about a sample file, next to it, when it's built.
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Metadata {
+    /// How the sample was generated.
+    pub spec: String,
+
+    /// The hash of `spec`, from `spec_hash`.
+    pub spec_hash: String,
+
+    /// How the sample's lines actually fell into groups, if it was generated with groups in mind.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub groups: Option<GroupStats>,
+}
+
+impl Metadata {
+    /// Describes a sample generated with these parameters. The character generator is a closure,
+    /// which can't be described, so the sample's name stands in for it.
+    pub fn new(
+        name: &str,
+        line_length: &LineLength,
+        sample_length: &SampleLength,
+        options: &Options,
+    ) -> Metadata {
+        Metadata::from_spec(format!(
+            "{} {}",
+            name,
+            spec(line_length, Some(sample_length), options)
+        ))
+    }
+
+    /// Describes a sample, or a dataset, generated as `spec` says.
+    pub fn from_spec(spec: String) -> Metadata {
+        Metadata {
+            spec_hash: spec_hash(&spec),
+            spec,
+            groups: None,
+        }
+    }
+
+    /// Where the metadata of the sample at `sample` is kept: alongside it, as `<name>.meta.json`.
+    pub fn path(sample: &Path) -> PathBuf {
+        let mut path = sample.as_os_str().to_owned();
+        path.push(".meta.json");
+        PathBuf::from(path)
+    }
+
+    /// Reads the metadata of the sample at `sample`, if it has any.
+    pub fn read(sample: &Path) -> Option<Metadata> {
+        let json = fs::read_to_string(Metadata::path(sample)).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Writes this as the metadata of the sample at `sample`.
+    pub fn write(&self, sample: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(Metadata::path(sample), json + "\n")
+    }
+}
+
+/// How many of the largest groups `GroupStats` lists.
+const LARGEST_GROUPS: usize = 10;
+
+/// The groups a built sample's lines realized, keyed by each line's first word, the text up to its
+/// first space. These are what random draws actually produced, which for a small sample or a large
+/// vocabulary can be well short of what was asked for.
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct GroupStats {
+    /// The number of distinct keys.
+    pub keys: usize,
+
+    /// The number of lines, in all the groups together.
+    pub lines: u64,
+
+    /// The largest groups' keys and sizes, largest first.
+    pub largest: Vec<(String, u64)>,
+
+    /// How many groups have each size, in powers of two: the count under 4 is of the groups of 4
+    /// to 7 lines.
+    pub size_histogram: BTreeMap<u64, usize>,
+
+    /// The Gini coefficient of the groups' sizes: 0 if they're all the same size, approaching 1
+    /// as a few groups take nearly all the lines.
+    pub gini: f64,
+}
+
+/// Counts the lines of each group in one or more samples.
+#[derive(Default)]
+pub struct GroupCounter {
+    sizes: HashMap<Vec<u8>, u64>,
+}
+
+impl GroupCounter {
+    /// Counts the lines `reader` reads into their groups.
+    pub fn count(&mut self, mut reader: impl BufRead) -> io::Result<()> {
+        let mut line = vec![];
+        while reader.read_until(b'\n', &mut line)? > 0 {
+            let text = line.strip_suffix(b"\n").unwrap_or(&line);
+            let text = text.strip_suffix(b"\r").unwrap_or(text);
+            let key = text.split(|&b| b == b' ').next().unwrap_or_default();
+            match self.sizes.get_mut(key) {
+                Some(size) => *size += 1,
+                None => {
+                    self.sizes.insert(key.to_vec(), 1);
+                }
+            }
+            line.clear();
+        }
+        Ok(())
+    }
+
+    /// Summarizes the groups counted so far.
+    pub fn stats(&self) -> GroupStats {
+        let mut groups: Vec<(&Vec<u8>, u64)> =
+            self.sizes.iter().map(|(key, &size)| (key, size)).collect();
+        // Ties go to the smallest key, so the list doesn't depend on the hash map's order.
+        groups.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let largest = groups
+            .iter()
+            .take(LARGEST_GROUPS)
+            .map(|(key, size)| (String::from_utf8_lossy(key).into_owned(), *size))
+            .collect();
+        let mut size_histogram = BTreeMap::new();
+        for (_, size) in &groups {
+            *size_histogram.entry(1 << size.ilog2()).or_default() += 1;
+        }
+
+        // With the sizes in increasing order, G = Σ (2i - n - 1) x_i / (n Σ x_i), counting from 1.
+        let n = groups.len() as f64;
+        let lines: u64 = groups.iter().map(|(_, size)| size).sum();
+        let weighted: f64 = groups
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, (_, size))| (2.0 * (i + 1) as f64 - n - 1.0) * *size as f64)
+            .sum();
+        let gini = if lines == 0 {
+            0.0
+        } else {
+            weighted / (n * lines as f64)
+        };
+
+        GroupStats {
+            keys: groups.len(),
+            lines,
+            largest,
+            size_histogram,
+            gini,
+        }
+    }
+}
+
+/// Builds a sample based on the provided parameters and writes it to `file`.
+pub fn build_sample(
+    file: impl Write,
+    line_length: LineLength,
+    sample_length: SampleLength,
+    character_generator: impl Fn() -> char,
+) {
+    build_sample_with(
+        file,
+        line_length,
+        sample_length,
+        character_generator,
+        Options::default(),
+    )
+}
+
+/// How the sizes of a dataset's files are drawn, in characters.
+#[derive(Clone, Debug)]
+pub enum SizeDistribution {
+    /// Every file is this size.
+    Fixed(usize),
+
+    /// Sizes are drawn uniformly from this range.
+    Uniform(Range<usize>),
+
+    /// Sizes' logarithms are drawn uniformly from the range's, so there are as many files between
+    /// 1 and 10 kB as between 1 and 10 MB, and most of the data is in a few big files, as in a
+    /// real directory of logs.
+    ///
+    /// # Panics
+    ///
+    /// Drawing a size panics if the range starts at 0.
+    LogUniform(Range<usize>),
+}
+
+impl SizeDistribution {
+    /// Checks that sizes can be drawn, suggesting a fix if they can't.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            SizeDistribution::Fixed(0) => Err("files of size 0 would be empty".to_string()),
+            SizeDistribution::Uniform(r) | SizeDistribution::LogUniform(r) if r.is_empty() => {
+                Err(format!(
+                    "the size range {:?} is empty; ranges exclude their end, so for files of \
+                     exactly {} characters, use SizeDistribution::Fixed({})",
+                    r, r.start, r.start
+                ))
+            }
+            SizeDistribution::Uniform(r) | SizeDistribution::LogUniform(r) if r.start == 0 => Err(
+                format!("the size range {:?} includes empty files; start it at 1", r),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Draws a size.
+    pub fn draw(&self) -> usize {
+        match self {
+            SizeDistribution::Fixed(n) => *n,
+            SizeDistribution::Uniform(r) => fastrand::usize(r.clone()),
+            SizeDistribution::LogUniform(r) => {
+                assert_ne!(0, r.start);
+                let (min, max) = ((r.start as f64).ln(), (r.end as f64).ln());
+                let size = (min + fastrand::f64() * (max - min)).exp() as usize;
+                size.clamp(r.start, r.end - 1)
+            }
+        }
+    }
+}
+
+/// Builds a dataset of `files` samples in the directory `dir`, creating it if need be, named
+/// `part-0000.txt` and up, with sizes drawn from `sizes`, and lines shaped alike by `line_length`
+/// and `options`. With `Options::words`, or a template's `{KEY}`s, every file draws keys from the
+/// same vocabulary, so groups span files, as when a command runs over `*.log`.
+pub fn build_dataset(
+    dir: &Path,
+    files: usize,
+    sizes: &SizeDistribution,
+    line_length: LineLength,
+    character_generator: impl Fn() -> char,
+    options: Options,
+) {
+    fs::create_dir_all(dir).unwrap();
+    let width = files.saturating_sub(1).to_string().len().max(4);
+    for i in 0..files {
+        let path = dir.join(format!("part-{:0width$}.txt", i, width = width));
+        build_sample_with(
+            fs::File::create(path).unwrap(),
+            line_length.clone(),
+            SampleLength::Characters(sizes.draw()),
+            &character_generator,
+            options.clone(),
+        );
+    }
+}
+
+/// Builds a sample shaped by `options` and writes it to `file`.
+pub fn build_sample_with(
+    file: impl Write,
+    line_length: LineLength,
+    sample_length: SampleLength,
+    character_generator: impl Fn() -> char,
+    options: Options,
+) {
+    // Let's buffer our writer, since we'll make lots of small writes.
+    let mut file = BufWriter::new(file);
+    let mut reader =
+        SampleReader::with_options(line_length, sample_length, character_generator, options);
+    io::copy(&mut reader, &mut file).unwrap();
+
+    // For safety.
+    file.flush().unwrap();
+}
+
+/// Generates a sample lazily, one line at a time, as it's read. This lets a sample of any size be
+/// streamed somewhere (say, into a benchmarked command's stdin) without ever existing in full,
+/// in memory or on disk.
+pub struct SampleReader<F> {
+    line_length: LineLength,
+    sample_length: SampleLength,
+    character_generator: F,
+    options: Options,
+
+    /// The last distinct lines generated, which duplicates are copied from.
+    recent: VecDeque<Line>,
+
+    /// How much of the sample has been generated so far, in lines and in characters.
+    lines_generated: usize,
+    chars_generated: usize,
+
+    /// The line being read, and how much of it has been read already.
+    line: Vec<u8>,
+    position: usize,
+}
+
+impl<F: Fn() -> char> SampleReader<F> {
+    pub fn new(
+        line_length: LineLength,
+        sample_length: SampleLength,
+        character_generator: F,
+    ) -> Self {
+        Self::with_options(
+            line_length,
+            sample_length,
+            character_generator,
+            Options::default(),
+        )
+    }
+
+    pub fn with_options(
+        line_length: LineLength,
+        sample_length: SampleLength,
+        character_generator: F,
+        options: Options,
+    ) -> Self {
+        // The byte order mark goes out before the first line, as if it were a line of its own,
+        // unless the sample is too short to hold it.
+        let bom = options.encoding.bom();
+        let bom_units = bom.len() / options.encoding.unit_size();
+        let bom = options.bom
+            && match sample_length {
+                SampleLength::Lines(_) => true,
+                SampleLength::Characters(limit) => limit >= bom_units,
+            };
+        let (line, chars_generated) = if bom {
+            (options.encoding.bom().to_vec(), bom_units)
+        } else {
+            (vec![], 0)
+        };
+        SampleReader {
+            line_length,
+            sample_length,
+            character_generator,
+            options,
+            recent: VecDeque::new(),
+            lines_generated: 0,
+            chars_generated,
+            line,
+            position: 0,
+        }
+    }
+
+    /// Generates the next line of the sample, or returns `None` if the sample is complete.
+    fn next_line(&mut self) -> Option<Line> {
+        let omit_newline = self.options.omit_final_newline;
+        let crlf = self.options.crlf_ratio > 0.0 && fastrand::f64() < self.options.crlf_ratio;
+        let line = match self.sample_length {
+            SampleLength::Lines(n) if self.lines_generated + 1 == n && omit_newline => {
+                without_newline(self.full_line())
+            }
+            SampleLength::Lines(n) if self.lines_generated < n => {
+                with_terminator(self.full_line(), crlf)
+            }
+            SampleLength::Lines(_) => return None,
+            SampleLength::Characters(limit) => {
+                // Calculate the upper bound on the length of a line so that we can handle the
+                // last line specially and ensure that we get the overall sample length just right.
+                let max_line_length = match (self.max_text_length(), &self.line_length) {
+                    (Some(n), _) => n + 1,
+                    (None, LineLength::Fixed(n)) => n + 1, // +1 for newline.
+                    (None, LineLength::Range(r)) => r.end, // Range is half open, so no need for +1.
+                } + crlf as usize;
+
+                // Be careful not to subtract from limit here or you'll get subtract with overflow.
+                if self.chars_generated + max_line_length < limit {
+                    with_terminator(self.full_line(), crlf)
+                } else if self.chars_generated < limit {
+                    // The last line takes up exactly what's left, with whatever ends it.
+                    let left = limit - self.chars_generated;
+                    if omit_newline {
+                        without_newline(self.build_line(&LineLength::Fixed(left)))
+                    } else if crlf && left >= 2 {
+                        with_terminator(self.build_line(&LineLength::Fixed(left - 2)), true)
+                    } else {
+                        self.build_line(&LineLength::Fixed(left - 1))
+                    }
+                } else {
+                    return None;
+                }
+            }
+        };
+        self.lines_generated += 1;
+        self.chars_generated += line.length;
+        Some(self.options.encoding.encode(line))
+    }
+
+    /// Generates a line of the configured length, or duplicates a recent one, as often as
+    /// `duplicate_ratio` says. Fresh lines are blank as often as `blank_ratio` says.
+    fn full_line(&mut self) -> Line {
+        let ratio = self.options.duplicate_ratio;
+        if !self.recent.is_empty() && fastrand::f64() < ratio {
+            return self.recent[fastrand::usize(..self.recent.len())].clone();
+        }
+        let blank = self.options.blank_ratio;
+        let line = if blank > 0.0 && fastrand::f64() < blank {
+            build_line(&self.line_length, &|| {
+                if fastrand::bool() {
+                    ' '
+                } else {
+                    '\t'
+                }
+            })
+        } else if let Some(text) = self.text() {
+            self.corrupt(text_line(text))
+        } else {
+            self.build_line(&self.line_length)
+        };
+        if ratio > 0.0 {
+            if self.recent.len() == DUPLICATE_WINDOW {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(line.clone());
+        }
+        line
+    }
+
+    /// Builds a fresh line of the given length, with NUL bytes and invalid UTF-8 as often as
+    /// `nul_rate` and `invalid_utf8_rate` say. A templated line or a record is cut off or padded
+    /// with generated characters to fit.
+    fn build_line(&self, line_length: &LineLength) -> Line {
+        let line = match (self.text(), self.options.cycle, self.options.words) {
+            (Some(text), ..) => {
+                let text = RefCell::new(text.chars());
+                build_line(line_length, &|| {
+                    let next = text.borrow_mut().next();
+                    next.unwrap_or_else(&self.character_generator)
+                })
+            }
+            (None, Some(count), _) => cycled_line(self.lines_generated % count, count, line_length),
+            (None, None, Some(vocabulary)) => build_word_line(line_length, vocabulary),
+            (None, None, None) => build_line(line_length, &self.character_generator),
+        };
+        self.corrupt(line)
+    }
+
+    /// The text of a line that makes its own length, from the template or as a record, if lines
+    /// are made either way.
+    fn text(&self) -> Option<String> {
+        if let Some(template) = &self.options.template {
+            return Some(template.fill(&self.character_generator, self.vocabulary()));
+        }
+        let widths = self.options.columns.as_ref()?;
+        Some(record(widths, &self.character_generator))
+    }
+
+    /// The most characters `text` can return, if lines make their own length.
+    fn max_text_length(&self) -> Option<usize> {
+        if let Some(template) = &self.options.template {
+            return Some(template.max_length(self.vocabulary()));
+        }
+        Some(self.options.columns.as_ref()?.iter().sum())
+    }
+
+    /// The number of pseudo-words lines and `{KEY}`s are made from.
+    fn vocabulary(&self) -> usize {
+        self.options.words.unwrap_or(1)
+    }
+
+    /// Replaces characters of `line` with NUL bytes and invalid UTF-8 as often as `nul_rate` and
+    /// `invalid_utf8_rate` say. Each replaces a whole character, never a byte of one, so a line
+    /// keeps its length in characters, and re-encodes to as many code units as it would have.
+    fn corrupt(&self, line: Line) -> Line {
+        let Options {
+            nul_rate,
+            invalid_utf8_rate,
+            ..
+        } = self.options;
+        if nul_rate <= 0.0 && invalid_utf8_rate <= 0.0 {
+            return line;
+        }
+        let text = String::from_utf8(line.bytes).expect("lines are built as UTF-8");
+        let (text, newline) = text.split_at(text.len() - 1); // Leave the newline be.
+        let mut bytes = Vec::with_capacity(text.len() + 1);
+        for c in text.chars() {
+            let draw = fastrand::f64();
+            if draw < nul_rate {
+                bytes.push(0);
+            } else if draw < nul_rate + invalid_utf8_rate {
+                bytes.push(INVALID_UTF8[fastrand::usize(..INVALID_UTF8.len())]);
+            } else {
+                bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+        }
+        bytes.extend_from_slice(newline.as_bytes());
+        Line {
+            bytes,
+            length: line.length,
+        }
+    }
+}
+
+impl<F: Fn() -> char> Read for SampleReader<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Fill as much of the buffer as we can, rather than returning a line at a time, so a
+        // reader writing into a pipe makes one system call per buffer and not one per line.
+        let mut filled = 0;
+        while filled < buf.len() {
+            if self.position == self.line.len() {
+                match self.next_line() {
+                    Some(line) => {
+                        self.line = line.bytes;
+                        self.position = 0;
+                    }
+                    None => break,
+                }
+            }
+            let n = (buf.len() - filled).min(self.line.len() - self.position);
+            buf[filled..filled + n].copy_from_slice(&self.line[self.position..self.position + n]);
+            self.position += n;
+            filled += n;
+        }
+        Ok(filled)
+    }
+}
+
+/// Returned from `build_line`.
+#[derive(Clone)]
+pub struct Line {
+    /// A fully formed line (including newline). It's bytes rather than a `String` because it
+    /// needn't be valid UTF-8, e.g. with `Options::invalid_utf8_rate`.
+    pub bytes: Vec<u8>,
+
+    /// The length of the line in chars (including newline).
+    pub length: usize,
+}
+
+/// Builds a line based on the provided parameters.
+///
+/// # Panics
+///
+/// Panics if given a `LineLength::Range(r)` where `r` is empty, e.g. `0..0` or `6..6`.
+pub fn build_line(line_length: &LineLength, character_generator: &impl Fn() -> char) -> Line {
+    let mut bytes: Vec<u8>;
+    let length: usize;
+    match line_length {
+        LineLength::Fixed(n) => {
+            bytes = Vec::with_capacity(n + 1);
+            length = *n + 1;
+            for _ in 0..*n {
+                push_char(&mut bytes, character_generator());
+            }
+        }
+        LineLength::Range(r) => {
+            assert_ne!(r.start, r.end);
+
+            // The length of the line, including newline.
+            length = fastrand::usize(r.clone()) + 1;
+
+            bytes = Vec::with_capacity(length);
+            for _ in 0..(length - 1) {
+                push_char(&mut bytes, character_generator());
+            }
+        }
+    }
+    bytes.push(b'\n');
+
+    Line { bytes, length }
+}
+
+/// Builds a line of random pseudo-words from a vocabulary of `vocabulary`, as `build_line` would
+/// build one of characters.
+fn build_word_line(line_length: &LineLength, vocabulary: usize) -> Line {
+    // The text of the word being spelled out, reversed so its next character can be popped.
+    let spelling = RefCell::new(vec![]);
+    let first = Cell::new(true);
+    build_line(line_length, &|| {
+        let mut spelling = spelling.borrow_mut();
+        if spelling.is_empty() {
+            if !first.replace(false) {
+                spelling.push(b' ');
+            }
+            let word = word(fastrand::usize(..vocabulary), vocabulary);
+            spelling.splice(0..0, word.bytes().rev());
+        }
+        spelling.pop().unwrap() as char
+    })
+}
+
+/// Builds a fixed-width record, with a field of each of the given widths. Each field is from one
+/// to its width of generated characters, left-aligned and padded with spaces, so that with
+/// single-byte characters, every field starts at the same byte column in every record.
+pub fn record(widths: &[usize], character_generator: &impl Fn() -> char) -> String {
+    let mut record = String::new();
+    for &width in widths {
+        let length = fastrand::usize(width.min(1)..=width);
+        record.extend((0..length).map(|_| character_generator()));
+        record.extend((length..width).map(|_| ' '));
+    }
+    record
+}
+
+/// Makes `text` a line, adding the newline.
+fn text_line(mut text: String) -> Line {
+    text.push('\n');
+    Line {
+        length: text.chars().count(),
+        bytes: text.into_bytes(),
+    }
+}
+
+/// Builds the `index`th of `count` template lines for `Options::cycle`: `index` in base 62, with
+/// `ALPHANUMERIC` for digits, least significant first and zero-padded to the width of the largest
+/// index, then repeated to the line's length. The first characters of the templates differ first,
+/// so grouping by the first few characters finds as many groups as there can be, up to `count`.
+///
+/// # Panics
+///
+/// Panics if given a `LineLength::Range(r)` where `r` is empty.
+fn cycled_line(index: usize, count: usize, line_length: &LineLength) -> Line {
+    let length = match line_length {
+        LineLength::Fixed(n) => *n,
+        LineLength::Range(r) => r.start + index % r.len(),
+    };
+    let mut digits = vec![];
+    let mut index = index;
+    for _ in 0..base62_width(count.saturating_sub(1)) {
+        digits.push(ALPHANUMERIC[index % ALPHANUMERIC.len()]);
+        index /= ALPHANUMERIC.len();
+    }
+    let mut bytes: Vec<u8> = digits.into_iter().cycle().take(length).collect();
+    bytes.push(b'\n');
+    Line {
+        bytes,
+        length: length + 1,
+    }
+}
+
+/// The number of digits `n` has in base 62, at least 1.
+fn base62_width(mut n: usize) -> usize {
+    let mut width = 1;
+    while n >= ALPHANUMERIC.len() {
+        n /= ALPHANUMERIC.len();
+        width += 1;
+    }
+    width
+}
+
+/// Ends `line` in CRLF instead of LF, if `crlf`.
+fn with_terminator(mut line: Line, crlf: bool) -> Line {
+    if crlf {
+        line.bytes.insert(line.bytes.len() - 1, b'\r');
+        line.length += 1;
+    }
+    line
+}
+
+/// Removes the newline from the end of `line`.
+fn without_newline(mut line: Line) -> Line {
+    line.bytes.pop();
+    line.length -= 1;
+    line
+}
+
+/// Appends `c` to `bytes`, encoded as UTF-8.
+fn push_char(bytes: &mut Vec<u8>, c: char) {
+    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+#[cfg(test)]
+mod build_line_tests {
+    use super::*;
+
+    const CG_CHAR: char = 'c';
+
+    fn cg() -> char {
+        CG_CHAR
+    }
+
+    #[test]
+    fn with_fixed_length_works() {
+        let line = build_line(&LineLength::Fixed(5), &cg);
+        assert_eq!(line.bytes, b"ccccc\n");
+        assert_eq!(line.length, 6);
+        assert_eq!(line.bytes.len(), line.length); // Sanity check.
+    }
+
+    #[test]
+    fn with_fixed_length_0_works() {
+        let line = build_line(&LineLength::Fixed(0), &cg);
+        assert_eq!(line.bytes, b"\n");
+        assert_eq!(line.length, 1);
+        assert_eq!(line.bytes.len(), line.length); // Sanity check.
+    }
+
+    #[test]
+    fn with_range_length_works() {
+        // We can't actually definitively test this, since there's intentional randomness, so we'll
+        // generate multiple lines and verify that they're all in-range.
+        let range = 6..12;
+        let tries = 100;
+        for _ in 0..tries {
+            let line = build_line(&LineLength::Range(range.clone()), &cg);
+
+            // Verify that the number of CG_CHAR characters is within range.
+            let cg_char_count = line.bytes.iter().filter(|&&b| b == CG_CHAR as u8).count();
+            assert!(range.start <= cg_char_count);
+            assert!(cg_char_count < range.end);
+
+            // Verify that length is correct.
+            assert_eq!(cg_char_count + 1, line.length);
+            assert_eq!(line.bytes.len(), line.length); // Sanity check.
+
+            // Verify that there's a newline at the end. If so, the string must consist of k
+            // repetitions of CG_CHAR followed by a single '\n' (for some k in range).
+            assert_eq!(b'\n', *line.bytes.last().unwrap());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn with_empty_range_length_panics() {
+        build_line(&LineLength::Range(10..10), &cg);
+    }
+}
+
+/// Testing for `build_sample` is much simpler than it might seem. We don't need to test all
+/// permutations of `line_length`, nor do we need to meaningfully test `character_generator`,
+/// because we don't actually use them in `build_sample`, we just pass them through to
+/// `build_line`, where they're used and tested. We really just need to test our `sample_length`
+/// processing with cursory checks that `build_line` is being called appropriately.
+#[cfg(test)]
+mod build_sample_tests {
+    use super::*;
+
+    const CG_CHAR: char = 'c';
+
+    fn cg() -> char {
+        CG_CHAR
+    }
+
+    #[test]
+    fn with_sample_length_lines_works() {
+        let mut sample = vec![];
+        build_sample(
+            &mut sample,
+            LineLength::Fixed(3),
+            SampleLength::Lines(3),
+            cg,
+        );
+
+        let expected: Vec<u8> = "ccc\nccc\nccc\n".bytes().collect();
+        assert_eq!(expected, sample);
+    }
+
+    #[test]
+    fn with_sample_length_lines_0_works() {
+        let mut sample = vec![];
+        build_sample(
+            &mut sample,
+            LineLength::Fixed(3),
+            SampleLength::Lines(0),
+            cg,
+        );
+
+        let expected: Vec<u8> = vec![];
+        assert_eq!(expected, sample);
+    }
+
+    #[test]
+    fn with_sample_length_lines_and_large_value_works() {
+        let line_length = 200;
+        let line_count = 1_000;
+        let mut sample = vec![];
+        build_sample(
+            &mut sample,
+            LineLength::Fixed(line_length),
+            SampleLength::Lines(line_count),
+            cg,
+        );
+
+        let mut line: String = "c".repeat(line_length);
+        line.push('\n');
+        let expected: String = line.repeat(line_count);
+        assert_eq!(expected.as_bytes(), sample);
+    }
+
+    #[test]
+    fn with_sample_length_characters_works() {
+        let mut sample = vec![];
+        build_sample(
+            &mut sample,
+            LineLength::Fixed(3),
+            SampleLength::Characters(12),
+            cg,
+        );
+
+        let expected: Vec<u8> = "ccc\nccc\nccc\n".bytes().collect();
+        assert_eq!(expected, sample);
+    }
+
+    #[test]
+    fn with_sample_length_characters_0_works() {
+        let mut sample = vec![];
+        build_sample(
+            &mut sample,
+            LineLength::Fixed(3),
+            SampleLength::Characters(0),
+            cg,
+        );
+
+        let expected: Vec<u8> = vec![];
+        assert_eq!(expected, sample);
+    }
+
+    #[test]
+    fn with_uneven_last_line_length_matches_size_precisely() {
+        let char_count = 31;
+        let line_length = 7;
+        let mut sample = vec![];
+        build_sample(
+            &mut sample,
+            LineLength::Fixed(line_length),
+            SampleLength::Characters(char_count),
+            cg,
+        );
+        assert_eq!(char_count, sample.len());
+    }
+
+    #[test]
+    fn with_sample_length_characters_and_large_value_works() {
+        let line_length = 200;
+        let line_count = 1_000;
+        let jagged_last_line = "ccccc\n";
+        let char_count = (line_length + 1) * line_count + jagged_last_line.len();
+        let mut sample = vec![];
+        build_sample(
+            &mut sample,
+            LineLength::Fixed(line_length),
+            SampleLength::Characters(char_count),
+            cg,
+        );
+
+        let mut line: String = "c".repeat(line_length);
+        line.push('\n');
+        let mut expected: String = line.repeat(line_count);
+        expected.push_str(jagged_last_line);
+        assert_eq!(expected.as_bytes(), sample);
+    }
+}
+
+#[cfg(test)]
+mod sample_reader_tests {
+    use super::*;
+
+    fn cg() -> char {
+        'c'
+    }
+
+    #[test]
+    fn reads_in_small_chunks() {
+        let mut reader = SampleReader::new(LineLength::Fixed(3), SampleLength::Characters(10), cg);
+        let mut sample = vec![];
+        let mut buffer = [0; 3];
+        loop {
+            match reader.read(&mut buffer).unwrap() {
+                0 => break,
+                n => sample.extend_from_slice(&buffer[..n]),
+            }
+        }
+        assert_eq!(b"ccc\nccc\nc\n".to_vec(), sample);
+    }
+}
+
+#[cfg(test)]
+mod duplicate_ratio_tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn duplicates_the_given_fraction_of_lines() {
+        let options = Options {
+            duplicate_ratio: 0.3,
+            ..Default::default()
+        };
+        let reader = SampleReader::with_options(
+            LineLength::Fixed(20),
+            SampleLength::Lines(10_000),
+            fastrand::alphanumeric,
+            options,
+        );
+        let sample = io::read_to_string(reader).unwrap();
+        let mut seen = HashSet::new();
+        let duplicates = sample.lines().filter(|line| !seen.insert(*line)).count();
+        assert!(
+            (2_700..3_300).contains(&duplicates),
+            "{} duplicates",
+            duplicates
+        );
+    }
+}
+
+#[cfg(test)]
+mod alphabet_tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn draws_from_the_first_characters() {
+        let generator = alphabet(3);
+        let drawn: HashSet<char> = (0..1_000).map(|_| generator()).collect();
+        assert_eq!(HashSet::from(['0', '1', '2']), drawn);
+        assert_eq!('0', alphabet(1)());
+        assert!(alphabet(1_000)().is_ascii_alphanumeric());
+    }
+}
+
+#[cfg(test)]
+mod nul_rate_tests {
+    use super::*;
+
+    #[test]
+    fn injects_nul_bytes_into_lines() {
+        let options = Options {
+            nul_rate: 0.1,
+            ..Default::default()
+        };
+        let reader = SampleReader::with_options(
+            LineLength::Fixed(99),
+            SampleLength::Characters(100_000),
+            fastrand::alphanumeric,
+            options,
+        );
+        let sample = io::read_to_string(reader).unwrap();
+        assert_eq!(100_000, sample.len());
+        assert_eq!(1_000, sample.lines().count());
+        let nuls = sample.matches('\0').count();
+        assert!((9_000..11_000).contains(&nuls), "{} NUL bytes", nuls);
+    }
+}
+
+#[cfg(test)]
+mod invalid_utf8_rate_tests {
+    use super::*;
+
+    #[test]
+    fn injects_invalid_bytes_into_lines() {
+        let options = Options {
+            invalid_utf8_rate: 0.1,
+            ..Default::default()
+        };
+        let mut reader = SampleReader::with_options(
+            LineLength::Fixed(99),
+            SampleLength::Characters(100_000),
+            fastrand::alphanumeric,
+            options,
+        );
+        let mut sample = vec![];
+        reader.read_to_end(&mut sample).unwrap();
+        assert_eq!(100_000, sample.len());
+        assert_eq!(1_000, sample.iter().filter(|&&b| b == b'\n').count());
+        let invalid = String::from_utf8_lossy(&sample).matches('\u{FFFD}').count();
+        assert!((9_000..11_000).contains(&invalid), "{} invalid", invalid);
+        assert_eq!(0xFF, INVALID_UTF8[76]);
+    }
+
+    #[test]
+    fn replaces_whole_accented_characters() {
+        for encoding in [Encoding::Utf8, Encoding::Latin1, Encoding::Utf16Le] {
+            let options = Options {
+                nul_rate: 0.05,
+                invalid_utf8_rate: 0.1,
+                encoding,
+                ..Default::default()
+            };
+            let mut reader = SampleReader::with_options(
+                LineLength::Range(5..81),
+                SampleLength::Characters(100_000),
+                || 'é',
+                options,
+            );
+            let mut sample = vec![];
+            reader.read_to_end(&mut sample).unwrap();
+            match encoding {
+                Encoding::Utf8 => {
+                    let text = String::from_utf8_lossy(&sample);
+                    assert_eq!(100_000, text.chars().count());
+                    let invalid = text.matches('\u{FFFD}').count();
+                    assert!((9_000..11_000).contains(&invalid), "{} invalid", invalid);
+                }
+                _ => assert_eq!(100_000 * encoding.unit_size(), sample.len()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod bom_tests {
+    use super::*;
+
+    fn sample(bom: bool, sample_length: SampleLength) -> String {
+        let options = Options {
+            bom,
+            ..Default::default()
+        };
+        let reader =
+            SampleReader::with_options(LineLength::Fixed(3), sample_length, || 'c', options);
+        io::read_to_string(reader).unwrap()
+    }
+
+    #[test]
+    fn starts_the_sample() {
+        assert_eq!("\u{FEFF}ccc\nccc\n", sample(true, SampleLength::Lines(2)));
+        assert_eq!(
+            "\u{FEFF}ccc\ncc\n",
+            sample(true, SampleLength::Characters(10))
+        );
+        assert_eq!("ccc\nccc\n", sample(false, SampleLength::Lines(2)));
+    }
+
+    #[test]
+    fn is_left_out_if_it_does_not_fit() {
+        assert_eq!("c\n", sample(true, SampleLength::Characters(2)));
+    }
+}
+
+#[cfg(test)]
+mod omit_final_newline_tests {
+    use super::*;
+
+    fn sample(sample_length: SampleLength) -> String {
+        let options = Options {
+            omit_final_newline: true,
+            duplicate_ratio: 0.5,
+            ..Default::default()
+        };
+        let reader =
+            SampleReader::with_options(LineLength::Fixed(3), sample_length, || 'c', options);
+        io::read_to_string(reader).unwrap()
+    }
+
+    #[test]
+    fn works() {
+        assert_eq!("ccc\nccc", sample(SampleLength::Lines(2)));
+        assert_eq!("ccc\nccc\nc", sample(SampleLength::Characters(9)));
+        assert_eq!("ccc\nccc\ncccc", sample(SampleLength::Characters(12)));
+        assert_eq!("", sample(SampleLength::Lines(0)));
+        assert_eq!("", sample(SampleLength::Characters(0)));
+    }
+}
+
+#[cfg(test)]
+mod crlf_ratio_tests {
+    use super::*;
+
+    fn sample(crlf_ratio: f64, sample_length: SampleLength) -> String {
+        let options = Options {
+            crlf_ratio,
+            ..Default::default()
+        };
+        let reader =
+            SampleReader::with_options(LineLength::Range(1..20), sample_length, || 'c', options);
+        io::read_to_string(reader).unwrap()
+    }
+
+    #[test]
+    fn mixes_line_endings() {
+        let text = sample(0.25, SampleLength::Characters(100_000));
+        assert_eq!(100_000, text.len());
+        let lines = text.lines().count();
+        let crlf = text.matches("\r\n").count();
+        assert_eq!(lines, text.matches('\n').count());
+        assert!(
+            (0.2..0.3).contains(&(crlf as f64 / lines as f64)),
+            "{} of {} lines end in CRLF",
+            crlf,
+            lines
+        );
+    }
+
+    #[test]
+    fn can_end_every_line_in_crlf() {
+        assert_eq!(
+            5,
+            sample(1.0, SampleLength::Lines(5)).matches("\r\n").count()
+        );
+        assert_eq!("\r\n", sample(1.0, SampleLength::Characters(2)));
+        assert_eq!("\n", sample(1.0, SampleLength::Characters(1)));
+    }
+}
+
+#[cfg(test)]
+mod blank_ratio_tests {
+    use super::*;
+
+    #[test]
+    fn makes_lines_of_spaces_and_tabs() {
+        let options = Options {
+            blank_ratio: 0.2,
+            ..Default::default()
+        };
+        let reader = SampleReader::with_options(
+            LineLength::Range(1..20),
+            SampleLength::Lines(10_000),
+            fastrand::alphanumeric,
+            options,
+        );
+        let sample = io::read_to_string(reader).unwrap();
+        let blank = sample.lines().filter(|line| line.trim().is_empty()).count();
+        assert!((1_800..2_200).contains(&blank), "{} blank lines", blank);
+        assert!(sample.lines().all(|line| !line.is_empty()));
+        assert!(sample.contains('\t'));
+    }
+}
+
+#[cfg(test)]
+mod words_tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn spells_words() {
+        assert_eq!("word00421", word(421, 1000));
+        assert_eq!("word0000007", word(7, 1_000_001));
+    }
+
+    #[test]
+    fn makes_lines_of_words() {
+        let options = Options {
+            words: Some(10),
+            ..Default::default()
+        };
+        let reader = SampleReader::with_options(
+            LineLength::Range(1..40),
+            SampleLength::Lines(1_000),
+            fastrand::alphanumeric,
+            options,
+        );
+        let sample = io::read_to_string(reader).unwrap();
+        let vocabulary: Vec<String> = (0..10).map(|i| word(i, 10)).collect();
+        let mut first_words = HashSet::new();
+        for line in sample.lines() {
+            let words: Vec<&str> = line.split(' ').collect();
+            let (last, whole) = words.split_last().unwrap();
+            assert!(whole.iter().all(|w| vocabulary.iter().any(|v| v == w)));
+            assert!(vocabulary.iter().any(|v| v.starts_with(last)));
+            first_words.insert(words[0]);
+        }
+        assert!(vocabulary.iter().all(|v| first_words.contains(v.as_str())));
+    }
+}
+
+#[cfg(test)]
+mod cycle_tests {
+    use super::*;
+
+    fn sample(count: usize, line_length: LineLength, sample_length: SampleLength) -> String {
+        let options = Options {
+            cycle: Some(count),
+            ..Default::default()
+        };
+        let reader =
+            SampleReader::with_options(line_length, sample_length, fastrand::alphanumeric, options);
+        io::read_to_string(reader).unwrap()
+    }
+
+    #[test]
+    fn cycles_through_templates() {
+        assert_eq!(
+            "0000\n1111\n2222\n0\n",
+            sample(3, LineLength::Fixed(4), SampleLength::Characters(17))
+        );
+        assert_eq!(
+            "000\n1010\n20202\n303\n",
+            sample(63, LineLength::Range(3..6), SampleLength::Lines(4))
+        );
+    }
+}
+
+#[cfg(test)]
+mod template_tests {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let template: Template = "{KEY} [{INT:100-599}] {{GET}} {PATH} {RAND:12}"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            Template(vec![
+                Part::Key,
+                Part::Text(" [".to_string()),
+                Part::Int(100, 599),
+                Part::Text("] {GET} ".to_string()),
+                Part::Path,
+                Part::Text(" ".to_string()),
+                Part::Rand(12),
+            ]),
+            template
+        );
+        for invalid in [
+            "{KEY",
+            "KEY}",
+            "{INT:5-1}",
+            "{INT:5}",
+            "{RAND}",
+            "{KEY:1}",
+            "{NOPE}",
+        ] {
+            assert!(invalid.parse::<Template>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn fills_placeholders() {
+        let template: Template = "{KEY} [{INT:100-599}] GET {PATH} {RAND:12}"
+            .parse()
+            .unwrap();
+        for _ in 0..100 {
+            let line = template.fill(&|| 'x', 10);
+            let fields: Vec<&str> = line.split(' ').collect();
+            assert!(fields[0].starts_with("word0000"));
+            let status: u64 = fields[1][1..4].parse().unwrap();
+            assert!((100..=599).contains(&status));
+            assert_eq!("GET", fields[2]);
+            assert!(fields[3].starts_with("/x"));
+            assert_eq!("xxxxxxxxxxxx", fields[4]);
+            assert!(line.len() <= template.max_length(10));
+        }
+    }
+
+    #[test]
+    fn fits_the_sample_length() {
+        let options = Options {
+            template: Some("{KEY} {INT:0-99999} {PATH}".parse().unwrap()),
+            words: Some(100),
+            ..Default::default()
+        };
+        let reader = SampleReader::with_options(
+            LineLength::Fixed(0),
+            SampleLength::Characters(10_000),
+            fastrand::alphanumeric,
+            options,
+        );
+        let sample = io::read_to_string(reader).unwrap();
+        assert_eq!(10_000, sample.len());
+        let lines: Vec<&str> = sample.lines().collect();
+        let (last, lines) = lines.split_last().unwrap();
+        assert!(lines.iter().all(|line| line.starts_with("word000")));
+        // The sample ends wherever its length runs out, maybe partway through the key.
+        assert!(last.starts_with("word000") || "word000".starts_with(last));
+    }
+
+    #[test]
+    fn groups_only_when_the_key_comes_first() {
+        let options = |template: &str| Options {
+            template: Some(template.parse().unwrap()),
+            words: Some(100),
+            ..Default::default()
+        };
+        assert!(options("{KEY}").has_groups());
+        assert!(options("{KEY} {INT:0-9}").has_groups());
+        assert!(!options("[{INT:0-9}] {KEY}").has_groups());
+        assert!(!options("{KEY}{INT:0-9}").has_groups());
+        assert!(!options("{KEY}-{PATH}").has_groups());
+    }
+}
+
+#[cfg(test)]
+mod record_tests {
+    use super::*;
+
+    #[test]
+    fn aligns_fields() {
+        let widths = [4, 1, 0, 6];
+        for _ in 0..100 {
+            let record = record(&widths, &|| 'x');
+            assert_eq!(11, record.len());
+            assert!(record.starts_with('x'));
+            assert_eq!("x", &record[4..5]);
+            assert!(record[5..].starts_with('x'));
+            assert!(record.trim_end().len() > 5);
+        }
+    }
+
+    #[test]
+    fn fits_the_sample_length() {
+        let options = Options {
+            columns: Some(vec![8, 12]),
+            ..Default::default()
+        };
+        let reader = SampleReader::with_options(
+            LineLength::Fixed(0),
+            SampleLength::Characters(10_000),
+            fastrand::alphanumeric,
+            options,
+        );
+        let sample = io::read_to_string(reader).unwrap();
+        assert_eq!(10_000, sample.len());
+        let lines: Vec<&str> = sample.lines().collect();
+        let (last, records) = lines.split_last().unwrap();
+        assert!(records.iter().all(|record| record.len() == 20));
+        assert!(last.len() <= 20);
+    }
+}
+
+#[cfg(test)]
+mod metadata_tests {
+    use super::*;
+
+    #[test]
+    fn hashes_the_spec() {
+        let metadata = |sample_length| {
+            Metadata::new(
+                "a.txt",
+                &LineLength::Range(5..81),
+                &sample_length,
+                &Options::default(),
+            )
+        };
+        let metadata_10 = metadata(SampleLength::Lines(10));
+        assert_eq!(8, metadata_10.spec_hash.len());
+        assert_eq!(metadata_10, metadata(SampleLength::Lines(10)));
+        assert_ne!(
+            metadata_10.spec_hash,
+            metadata(SampleLength::Lines(11)).spec_hash
+        );
+        assert_eq!(
+            PathBuf::from("samples/a.txt.meta.json"),
+            Metadata::path(Path::new("samples/a.txt"))
+        );
+    }
+
+    #[test]
+    fn names_only_options_in_use() {
+        let options = Options {
+            words: Some(1000),
+            template: Some("{KEY} {{x}} {INT:1-9}".parse().unwrap()),
+            encoding: Encoding::Latin1,
+            ..Default::default()
+        };
+        assert_eq!(
+            "a.txt 5..81 10 lines words=1000 template=\"{KEY} {{x}} {INT:1-9}\" encoding=latin-1",
+            Metadata::new(
+                "a.txt",
+                &LineLength::Range(5..81),
+                &SampleLength::Lines(10),
+                &options
+            )
+            .spec
+        );
+        assert_eq!(
+            "20 10 characters",
+            spec(
+                &LineLength::Fixed(20),
+                Some(&SampleLength::Characters(10)),
+                &Options::default()
+            )
+        );
+    }
+}
+
+#[cfg(test)]
+mod validate_tests {
+    use super::*;
+
+    #[test]
+    fn catches_what_would_panic() {
+        let check =
+            |line_length, sample_length, options| validate(&line_length, &sample_length, &options);
+        let characters = SampleLength::Characters(1_000);
+        assert_eq!(
+            Ok(()),
+            check(LineLength::Range(5..81), characters, Options::default())
+        );
+        let error = check(
+            LineLength::Range(10..10),
+            SampleLength::Lines(1),
+            Options::default(),
+        )
+        .unwrap_err();
+        assert!(error.contains("LineLength::Fixed(10)"));
+        assert!(check(
+            LineLength::Fixed(5),
+            SampleLength::Characters(0),
+            Options::default()
+        )
+        .is_err());
+        let columns = Options {
+            columns: Some(vec![4, 0]),
+            ..Default::default()
+        };
+        assert!(check(LineLength::Fixed(0), SampleLength::Lines(1), columns).is_err());
+        let cycle = Options {
+            cycle: Some(10),
+            ..Default::default()
+        };
+        assert!(check(LineLength::Fixed(5), SampleLength::Lines(9), cycle.clone()).is_err());
+        // 63 lines take two base-62 digits to tell apart.
+        let cycle = Options {
+            cycle: Some(63),
+            ..Default::default()
+        };
+        let lines = || SampleLength::Lines(100);
+        assert_eq!(
+            Ok(()),
+            check(LineLength::Range(2..5), lines(), cycle.clone())
+        );
+        assert!(check(LineLength::Range(1..5), lines(), cycle.clone()).is_err());
+        assert!(check(LineLength::Fixed(1), lines(), cycle).is_err());
+
+        assert_eq!(Ok(()), SizeDistribution::LogUniform(1..10).validate());
+        assert!(SizeDistribution::LogUniform(0..10).validate().is_err());
+        assert!(SizeDistribution::Uniform(10..10).validate().is_err());
+    }
+}
+
+#[cfg(test)]
+mod group_counter_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        let mut counter = GroupCounter::default();
+        counter.count("b x\na y\r\nb z\nc\nb\n".as_bytes()).unwrap();
+        counter.count("a\n".as_bytes()).unwrap();
+        let stats = counter.stats();
+        assert_eq!(3, stats.keys);
+        assert_eq!(6, stats.lines);
+        assert_eq!(
+            vec![
+                ("b".to_string(), 3),
+                ("a".to_string(), 2),
+                ("c".to_string(), 1)
+            ],
+            stats.largest
+        );
+        assert_eq!(BTreeMap::from([(1, 1), (2, 2)]), stats.size_histogram);
+        // Sizes 1, 2, 3: (-2 * 1 + 0 * 2 + 2 * 3) / (3 * 6).
+        assert!((stats.gini - 4.0 / 18.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn equal_groups_are_not_skewed() {
+        let mut counter = GroupCounter::default();
+        counter.count("a\nb\na\nb\n".as_bytes()).unwrap();
+        assert_eq!(0.0, counter.stats().gini);
+        assert_eq!(0.0, GroupCounter::default().stats().gini);
+    }
+}
+
+#[cfg(test)]
+mod dataset_tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn shares_keys_across_files() {
+        let dir = std::env::temp_dir().join(format!("dataset-test-{}", std::process::id()));
+        let options = Options {
+            words: Some(20),
+            ..Default::default()
+        };
+        let sizes = SizeDistribution::LogUniform(1_000..5_000);
+        build_dataset(
+            &dir,
+            3,
+            &sizes,
+            LineLength::Range(9..10),
+            fastrand::alphanumeric,
+            options,
+        );
+        let mut keys: Vec<HashSet<String>> = vec![];
+        for i in 0..3 {
+            let sample = fs::read_to_string(dir.join(format!("part-000{}.txt", i))).unwrap();
+            assert!((1_000..5_000).contains(&sample.len()));
+            keys.push(sample.lines().map(str::to_string).collect());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(keys.iter().all(|keys| keys.len() <= 20 + 1)); // +1 for a cut-off last line.
+        assert!(!keys[0].is_disjoint(&keys[1]));
+    }
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use super::*;
+
+    fn encode(encoding: Encoding, text: &str) -> Vec<u8> {
+        let line = Line {
+            bytes: text.as_bytes().to_vec(),
+            length: text.chars().count(),
+        };
+        let line = encoding.encode(line);
+        if encoding != Encoding::Utf8 {
+            assert_eq!(line.bytes.len(), line.length * encoding.unit_size());
+        }
+        line.bytes
+    }
+
+    #[test]
+    fn works() {
+        assert_eq!(b"a\xE9\xFF?\n", &encode(Encoding::Latin1, "aéÿ€\n")[..]);
+        assert_eq!(
+            b"a\xE9\xFF\x80\n",
+            &encode(Encoding::Windows1252, "aéÿ€\n")[..]
+        );
+        assert_eq!(b"?\x8C", &encode(Encoding::Windows1252, "\u{81}Œ")[..]);
+        assert_eq!("aé\n".as_bytes(), &encode(Encoding::Utf8, "aé\n")[..]);
+        assert_eq!(b"a\0\xE9\0\n\0", &encode(Encoding::Utf16Le, "aé\n")[..]);
+        assert_eq!(b"\0a\0\xE9\0\n", &encode(Encoding::Utf16Be, "aé\n")[..]);
+    }
+
+    #[test]
+    fn counts_utf16_code_units() {
+        let options = Options {
+            encoding: Encoding::Utf16Le,
+            bom: true,
+            ..Default::default()
+        };
+        let mut reader = SampleReader::with_options(
+            LineLength::Range(5..81),
+            SampleLength::Characters(10_000),
+            accented,
+            options,
+        );
+        let mut sample = vec![];
+        reader.read_to_end(&mut sample).unwrap();
+        assert_eq!(20_000, sample.len());
+        assert_eq!([0xFF, 0xFE], sample[..2]);
+        assert_eq!([b'\n', 0], sample[sample.len() - 2..]);
+    }
+
+    #[test]
+    fn keeps_the_size_exact() {
+        let options = Options {
+            encoding: Encoding::Latin1,
+            ..Default::default()
+        };
+        let mut reader = SampleReader::with_options(
+            LineLength::Range(5..81),
+            SampleLength::Characters(10_000),
+            accented,
+            options,
+        );
+        let mut sample = vec![];
+        reader.read_to_end(&mut sample).unwrap();
+        assert_eq!(10_000, sample.len());
+        assert!(sample.iter().any(|&b| b >= 0xC0));
+        assert!(!sample.contains(&b'?'));
+        assert!(!sample.contains(&0xD7));
+    }
+}
+
+#[cfg(test)]
+mod sample_seed_tests {
+    use super::*;
+
+    #[test]
+    fn depends_on_the_name() {
+        assert_eq!(sample_seed(7, "a.txt"), sample_seed(7, "a.txt"));
+        assert_ne!(sample_seed(7, "a.txt"), sample_seed(7, "b.txt"));
+        assert_ne!(sample_seed(7, "a.txt"), sample_seed(8, "a.txt"));
+    }
+}
@@ -0,0 +1,218 @@
+//! Fits how a case's wall time grows with the size of its sample to candidate complexity models,
+//! so a change that makes it scale worse (say, from O(n) to O(n log n)) shows up as such, rather
+//! than as a regression that just happens to be bigger on the bigger samples.
+
+use crate::results::{BenchmarkResults, Metric, SuiteResults};
+
+/// The fewest distinct sample sizes worth fitting: any two points fit either model exactly.
+const MIN_SIZES: usize = 3;
+
+/// How much of the better-scaling model's unexplained variation a worse-scaling model has to leave
+/// at most, to be the better fit. Over the sizes samples come in, log n varies so little that the
+/// two models fit almost equally well, and without a margin, noise alone would pick between them.
+const WORSE_MODEL_MAX_UNEXPLAINED: f64 = 0.5;
+
+/// A candidate model of wall time against sample size `n`, in bytes, ordered from the best scaling
+/// to the worst.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Model {
+    Linear,
+    Linearithmic,
+}
+
+impl Model {
+    const ALL: [Model; 2] = [Model::Linear, Model::Linearithmic];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::Linear => "O(n)",
+            Model::Linearithmic => "O(n log n)",
+        }
+    }
+
+    /// The term the model multiplies its coefficient by.
+    fn term(&self) -> &'static str {
+        match self {
+            Model::Linear => "n",
+            Model::Linearithmic => "n log₂ n",
+        }
+    }
+
+    fn apply(&self, n: f64) -> f64 {
+        match self {
+            Model::Linear => n,
+            Model::Linearithmic => n * n.log2(),
+        }
+    }
+}
+
+/// A model fitted to a case's timings: wall time ≈ `intercept` + `coefficient` × the model's term.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fit {
+    pub model: Model,
+
+    /// Seconds per unit of the model's term, e.g. per byte for O(n).
+    pub coefficient: f64,
+
+    /// Seconds of fixed cost, such as starting up.
+    pub intercept: f64,
+
+    /// How much of the variation in wall time the model explains, from 0 to 1.
+    pub r_squared: f64,
+}
+
+impl Fit {
+    /// Describes the fitted growth, e.g. `0.521 ns × n`.
+    pub fn describe(&self) -> String {
+        format!("{:.3} ns × {}", self.coefficient * 1e9, self.model.term())
+    }
+}
+
+/// Fits each model to `points` of sample size and wall time, and returns the best fit, or `None`
+/// with fewer than `MIN_SIZES` distinct sizes. Each point is weighted by the inverse square of its
+/// time, so that the fit minimizes relative error and small samples count as much as large ones.
+/// A model that scales worse only wins if it fits markedly better; see
+/// `WORSE_MODEL_MAX_UNEXPLAINED`.
+pub fn best_fit(points: &[(f64, f64)]) -> Option<Fit> {
+    let mut sizes: Vec<f64> = points.iter().map(|&(n, _)| n).collect();
+    sizes.sort_by(f64::total_cmp);
+    sizes.dedup();
+    if sizes.len() < MIN_SIZES || points.iter().any(|&(n, time)| n <= 0.0 || time <= 0.0) {
+        return None;
+    }
+    Model::ALL
+        .iter()
+        .filter_map(|&model| fit(model, points))
+        .reduce(|best, worse| {
+            let unexplained = |fit: &Fit| 1.0 - fit.r_squared;
+            if unexplained(&worse) < WORSE_MODEL_MAX_UNEXPLAINED * unexplained(&best) {
+                worse
+            } else {
+                best
+            }
+        })
+}
+
+/// Fits `model` to `points` by weighted least squares.
+fn fit(model: Model, points: &[(f64, f64)]) -> Option<Fit> {
+    let weighted: Vec<(f64, f64, f64)> = points
+        .iter()
+        .map(|&(n, time)| (model.apply(n), time, 1.0 / (time * time)))
+        .collect();
+    let sum = |f: fn(&(f64, f64, f64)) -> f64| weighted.iter().map(f).sum::<f64>();
+    let w = sum(|p| p.2);
+    let x = sum(|p| p.2 * p.0);
+    let y = sum(|p| p.2 * p.1);
+    let xx = sum(|p| p.2 * p.0 * p.0);
+    let xy = sum(|p| p.2 * p.0 * p.1);
+    let denominator = w * xx - x * x;
+    if denominator <= 0.0 {
+        return None;
+    }
+    let coefficient = (w * xy - x * y) / denominator;
+    let intercept = (y - coefficient * x) / w;
+
+    let mean = y / w;
+    let (mut sse, mut sst) = (0.0, 0.0);
+    for &(term, time, weight) in &weighted {
+        sse += weight * (time - intercept - coefficient * term).powi(2);
+        sst += weight * (time - mean).powi(2);
+    }
+    Some(Fit {
+        model,
+        coefficient,
+        intercept,
+        r_squared: if sst > 0.0 { 1.0 - sse / sst } else { 1.0 },
+    })
+}
+
+/// Fits every case run on enough sample sizes, labeled as in `BenchmarkResults::case_label`, in
+/// the order the cases first appear. Benchmarks that timed out or lack a size or wall time are
+/// left out.
+pub fn fits(results: &SuiteResults) -> Vec<(String, Fit)> {
+    let mut cases: Vec<(String, Vec<(f64, f64)>)> = vec![];
+    for benchmark in &results.benchmarks {
+        let Some(point) = point(benchmark) else {
+            continue;
+        };
+        let label = benchmark.case_label();
+        match cases.iter_mut().find(|(case, _)| *case == label) {
+            Some((_, points)) => points.push(point),
+            None => cases.push((label, vec![point])),
+        }
+    }
+    cases
+        .into_iter()
+        .filter_map(|(case, points)| Some((case, best_fit(&points)?)))
+        .collect()
+}
+
+/// A benchmark's sample size and mean wall time.
+fn point(benchmark: &BenchmarkResults) -> Option<(f64, f64)> {
+    if benchmark.timed_out {
+        return None;
+    }
+    let bytes = benchmark.sample_bytes?;
+    let time = benchmark.summary.get(&Metric::WallTime)?.mean;
+    Some((bytes as f64, time))
+}
+
+/// Describes each case whose best fit is a worse model in `new` than in `old`, e.g.
+/// `groupby: O(n) -> O(n log n)`.
+pub fn regressions(old: &SuiteResults, new: &SuiteResults) -> Vec<String> {
+    let old = fits(old);
+    fits(new)
+        .into_iter()
+        .filter_map(|(case, new)| {
+            let (_, old) = old.iter().find(|(name, _)| *name == case)?;
+            (new.model > old.model)
+                .then(|| format!("{}: {} -> {}", case, old.model.name(), new.model.name()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod best_fit_tests {
+    use super::*;
+
+    const SIZES: [f64; 4] = [1e6, 1e7, 1e8, 1e9];
+
+    #[test]
+    fn finds_linear_growth() {
+        let points: Vec<(f64, f64)> = SIZES.iter().map(|&n| (n, 0.002 + 2e-9 * n)).collect();
+        let fit = best_fit(&points).unwrap();
+        assert_eq!(Model::Linear, fit.model);
+        assert!((fit.coefficient - 2e-9).abs() < 1e-12);
+        assert!((fit.intercept - 0.002).abs() < 1e-6);
+        assert!(fit.r_squared > 0.999);
+        assert_eq!("2.000 ns × n", fit.describe());
+    }
+
+    #[test]
+    fn finds_linearithmic_growth() {
+        let points: Vec<(f64, f64)> = SIZES
+            .iter()
+            .map(|&n| (n, 0.002 + 1e-10 * n * n.log2()))
+            .collect();
+        assert_eq!(Model::Linearithmic, best_fit(&points).unwrap().model);
+    }
+
+    #[test]
+    fn noisy_linear_growth_stays_linear() {
+        // Within 5% either way, as timings on a busy machine are, and by chance a little closer to
+        // O(n log n) than to O(n).
+        let noise = [1.04, 0.95, 0.98, 1.04, 1.03, 1.04];
+        let sizes = [1e7, 3e7, 1e8, 3e8, 1e9, 3e9];
+        let points: Vec<(f64, f64)> = sizes
+            .iter()
+            .zip(noise)
+            .map(|(&n, noise)| (n, (0.002 + 2e-9 * n) * noise))
+            .collect();
+        assert_eq!(Model::Linear, best_fit(&points).unwrap().model);
+    }
+
+    #[test]
+    fn needs_three_sizes() {
+        assert_eq!(None, best_fit(&[(1e6, 0.01), (1e7, 0.1), (1e7, 0.11)]));
+    }
+}
@@ -0,0 +1,237 @@
+//! Turns a real input file into a sample that can be shared: one with the same structure, but none
+//! of the content. Every line keeps its length in bytes, every delimiter (whitespace, punctuation,
+//! anything that isn't a letter or digit) stays where it was, and each word, a run of letters and
+//! digits, is replaced by a random one of the same shape. Equal words get equal replacements, and
+//! different words different ones, so a field groups the same way before and after. Bytes that
+//! aren't valid UTF-8, such as Latin-1 text's accented letters, are taken for letters, and replaced
+//! by other such bytes.
+//!
+//! Replacements are remembered for the whole file, so memory grows with its distinct words.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+
+/// How many times we try to draw a replacement that no other word has before giving up and
+/// sharing one. Only words with no letters or digits of their own shape left to draw, such as the
+/// 63rd distinct word of one accented letter, can run out.
+const MAX_DRAWS: usize = 1000;
+
+/// Replaces words consistently.
+#[derive(Default)]
+pub struct Scrambler {
+    replacements: HashMap<Vec<u8>, Vec<u8>>,
+    used: HashSet<Vec<u8>>,
+}
+
+/// What `Scrambler::scramble` did.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub lines: usize,
+    pub words: usize,
+}
+
+impl Scrambler {
+    /// Scrambles everything `input` reads into `output`, line by line.
+    pub fn scramble(
+        &mut self,
+        mut input: impl BufRead,
+        mut output: impl Write,
+    ) -> io::Result<Summary> {
+        let mut line = vec![];
+        let mut lines = 0;
+        while input.read_until(b'\n', &mut line)? > 0 {
+            output.write_all(&self.scramble_line(&line))?;
+            line.clear();
+            lines += 1;
+        }
+        output.flush()?;
+        Ok(Summary {
+            lines,
+            words: self.replacements.len(),
+        })
+    }
+
+    fn scramble_line(&mut self, line: &[u8]) -> Vec<u8> {
+        let mut scrambled = Vec::with_capacity(line.len());
+        let mut token = vec![];
+        let mut in_word = false;
+        for unit in units(line) {
+            if unit.is_word() != in_word && !token.is_empty() {
+                self.push_token(&token, in_word, &mut scrambled);
+                token.clear();
+            }
+            in_word = unit.is_word();
+            unit.push_to(&mut token);
+        }
+        self.push_token(&token, in_word, &mut scrambled);
+        scrambled
+    }
+
+    /// Pushes a run of word or delimiter bytes onto `scrambled`, replacing words.
+    fn push_token(&mut self, token: &[u8], word: bool, scrambled: &mut Vec<u8>) {
+        if word {
+            scrambled.extend_from_slice(self.replacement(token));
+        } else {
+            scrambled.extend_from_slice(token);
+        }
+    }
+
+    /// The word's replacement, drawing a new one if it hasn't been seen before.
+    fn replacement(&mut self, word: &[u8]) -> &[u8] {
+        if !self.replacements.contains_key(word) {
+            let mut replacement = reshape(word);
+            for _ in 1..MAX_DRAWS {
+                if !self.used.contains(&replacement) {
+                    break;
+                }
+                replacement = reshape(word);
+            }
+            self.used.insert(replacement.clone());
+            self.replacements.insert(word.to_vec(), replacement);
+        }
+        &self.replacements[word]
+    }
+}
+
+/// A piece of a line: a character, or a byte that isn't valid UTF-8.
+#[derive(Clone, Copy)]
+enum Unit {
+    Char(char),
+    Byte(u8),
+}
+
+impl Unit {
+    /// Whether the unit belongs to a word: a letter or digit, in any script, or a byte that isn't
+    /// valid UTF-8, which we take for a letter of some other encoding.
+    fn is_word(self) -> bool {
+        match self {
+            Unit::Char(c) => c.is_alphanumeric(),
+            Unit::Byte(_) => true,
+        }
+    }
+
+    fn push_to(self, bytes: &mut Vec<u8>) {
+        match self {
+            Unit::Char(c) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            Unit::Byte(b) => bytes.push(b),
+        }
+    }
+}
+
+/// Splits `bytes` into characters, and the bytes between them that aren't valid UTF-8.
+fn units(bytes: &[u8]) -> Vec<Unit> {
+    let mut units = vec![];
+    for chunk in bytes.utf8_chunks() {
+        units.extend(chunk.valid().chars().map(Unit::Char));
+        units.extend(chunk.invalid().iter().map(|&b| Unit::Byte(b)));
+    }
+    units
+}
+
+/// Draws a random word of the same shape as `word`: a digit for each digit, a lowercase letter for
+/// each lowercase letter, and so on, with each non-ASCII character replaced by one as long in
+/// UTF-8. Bytes that aren't valid UTF-8 are replaced by Latin-1 letters, which can't start valid
+/// UTF-8 either when followed by what follows them, so the word stays as long and as invalid.
+fn reshape(word: &[u8]) -> Vec<u8> {
+    let mut reshaped = Vec::with_capacity(word.len());
+    for unit in units(word) {
+        match unit {
+            Unit::Char(c) => Unit::Char(like(c)).push_to(&mut reshaped),
+            Unit::Byte(_) => reshaped.push(latin1_letter() as u8),
+        }
+    }
+    reshaped
+}
+
+/// A random Latin-1 letter, one of À to ÿ but for × and ÷.
+fn latin1_letter() -> char {
+    loop {
+        let c = char::from(fastrand::u8(0xC0..=0xFF));
+        if c != '×' && c != '÷' {
+            break c;
+        }
+    }
+}
+
+/// A random character of the same kind as `c`, and as long in UTF-8.
+fn like(c: char) -> char {
+    match c.len_utf8() {
+        1 if c.is_ascii_digit() => fastrand::digit(10),
+        1 if c.is_ascii_uppercase() => fastrand::uppercase(),
+        1 => fastrand::lowercase(),
+        2 => latin1_letter(),
+        // CJK ideographs.
+        3 => char::from_u32(fastrand::u32(0x4E00..=0x9FFF)).unwrap(),
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod scramble_tests {
+    use super::*;
+
+    fn scramble(text: &str) -> String {
+        let mut output = vec![];
+        Scrambler::default()
+            .scramble(text.as_bytes(), &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn keeps_structure() {
+        let text = "alice,42,Paris\nbob,42,Zürich\n\nalice,7,Paris";
+        let scrambled = scramble(text);
+        assert_eq!(text.len(), scrambled.len());
+        let lines: Vec<Vec<&str>> = scrambled
+            .split('\n')
+            .map(|line| line.split(',').collect())
+            .collect();
+        assert_eq!(4, lines.len());
+        assert_eq!(vec![""], lines[2]);
+        for (original, scrambled) in text.split('\n').zip(scrambled.split('\n')) {
+            assert_eq!(original.len(), scrambled.len());
+        }
+        assert_eq!(lines[0][0], lines[3][0]);
+        assert_eq!(lines[0][1], lines[1][1]);
+        assert_ne!(lines[0][1], lines[3][1]);
+        assert_eq!(lines[0][2], lines[3][2]);
+        assert!(lines[0][1].bytes().all(|b| b.is_ascii_digit()));
+        assert!(lines[0][2].starts_with(|c: char| c.is_ascii_uppercase()));
+        assert_ne!("alice", lines[0][0]);
+    }
+
+    #[test]
+    fn keeps_non_ascii_delimiters() {
+        let scrambled = scramble("alice\u{a0}42 «Zürich»—café");
+        let pieces: Vec<&str> = scrambled.split(['\u{a0}', ' ', '«', '»', '—']).collect();
+        assert_eq!(6, pieces.len());
+        assert_eq!(5, pieces[0].len());
+        assert_eq!(7, pieces[3].len());
+        assert_eq!(4, pieces[5].chars().count());
+    }
+
+    #[test]
+    fn replaces_invalid_utf8() {
+        // Latin-1, in which é is the byte 0xE9.
+        let text = b"caf\xE9,caf\xE9,\xE9t\xE9\n";
+        let mut output = vec![];
+        Scrambler::default()
+            .scramble(&text[..], &mut output)
+            .unwrap();
+        assert_eq!(text.len(), output.len());
+        let fields: Vec<&[u8]> = output[..output.len() - 1].split(|&b| b == b',').collect();
+        assert_eq!(fields[0], fields[1]);
+        assert_ne!(&text[..4], fields[0]);
+        assert!(fields[2][0] >= 0xC0 && fields[2][2] >= 0xC0);
+        assert!(std::str::from_utf8(fields[2]).is_err());
+    }
+
+    #[test]
+    fn keeps_distinct_words_distinct() {
+        let text: String = (0..10).map(|digit| format!("{}\n", digit)).collect();
+        let scrambled = scramble(&text);
+        let words: HashSet<&str> = scrambled.lines().collect();
+        assert_eq!(10, words.len());
+    }
+}
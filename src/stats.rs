@@ -0,0 +1,513 @@
+//! Descriptive statistics over the measured iterations of a benchmark.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Summary statistics for one metric over a set of iterations.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub mean: f64,
+    pub median: f64,
+
+    /// The sample standard deviation (i.e. with Bessel's correction). Zero for a single value.
+    pub stddev: f64,
+
+    pub min: f64,
+    pub max: f64,
+
+    /// Requested percentiles, keyed by name (e.g. `p90`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub percentiles: BTreeMap<String, f64>,
+}
+
+impl Summary {
+    /// Summarizes `values`, computing each of `percentiles` (given as numbers from 0 to 100)
+    /// along the way. Returns `None` if `values` is empty.
+    pub fn of(values: &[f64], percentiles: &[f64]) -> Option<Summary> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        Some(Summary {
+            mean: mean(values),
+            median: percentile(&sorted, 50.0),
+            stddev: stddev(values),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            percentiles: percentiles
+                .iter()
+                .map(|&p| (percentile_name(p), percentile(&sorted, p)))
+                .collect(),
+        })
+    }
+}
+
+/// The arithmetic mean of `values`, which must not be empty.
+pub fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// The sample standard deviation of `values`, or zero if there are fewer than two.
+pub fn stddev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    let sum_of_squares: f64 = values.iter().map(|v| (v - mean) * (v - mean)).sum();
+    (sum_of_squares / (values.len() - 1) as f64).sqrt()
+}
+
+/// The `p`th percentile (0 to 100) of `sorted`, which must be sorted and not empty. Interpolates
+/// linearly between the closest ranks, like NumPy's default.
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// The median absolute deviation from the median of `values`, which must not be empty.
+pub fn median_absolute_deviation(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = percentile(&sorted, 50.0);
+    let mut deviations: Vec<f64> = values.iter().map(|v| (v - median).abs()).collect();
+    deviations.sort_by(f64::total_cmp);
+    percentile(&deviations, 50.0)
+}
+
+/// The interquartile range of `values`, which must not be empty.
+pub fn interquartile_range(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    percentile(&sorted, 75.0) - percentile(&sorted, 25.0)
+}
+
+/// Tests whether `a` and `b` are equally spread out, returning the p-value, or `None` if either is
+/// empty. This is a rank-based take on the Brown-Forsythe test: it compares each value's distance
+/// from its set's median with a Mann-Whitney U test. Distances are relative to the median, so a
+/// set that's slower across the board isn't mistaken for a noisier one.
+pub fn spread_test(a: &[f64], b: &[f64]) -> Option<f64> {
+    let deviations = |values: &[f64]| -> Option<Vec<f64>> {
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let median = percentile(&sorted, 50.0);
+        (median != 0.0).then(|| {
+            values
+                .iter()
+                .map(|v| ((v - median) / median).abs())
+                .collect()
+        })
+    };
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    mann_whitney_u(&deviations(a)?, &deviations(b)?)
+}
+
+/// The two-sided critical value of Student's t-distribution at 95% confidence with `df` degrees
+/// of freedom.
+///
+/// Small `df` come from a table; beyond it, we use the Cornish-Fisher expansion around the normal
+/// quantile, which is accurate to about three decimal places there.
+pub fn t_critical_95(df: usize) -> f64 {
+    const TABLE: [f64; 10] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    ];
+    if df == 0 {
+        return f64::INFINITY;
+    }
+    if df <= TABLE.len() {
+        return TABLE[df - 1];
+    }
+    let z: f64 = 1.959_964;
+    let df = df as f64;
+    z + (z.powi(3) + z) / (4.0 * df)
+        + (5.0 * z.powi(5) + 16.0 * z.powi(3) + 3.0 * z) / (96.0 * df.powi(2))
+        + (3.0 * z.powi(7) + 19.0 * z.powi(5) + 17.0 * z.powi(3) - 15.0 * z) / (384.0 * df.powi(3))
+}
+
+/// The half-width of the 95% confidence interval of the mean of `values`, relative to the mean
+/// (so `0.02` means "mean ± 2%"). Returns `None` if there are fewer than two values or the mean is
+/// zero.
+pub fn relative_ci_half_width(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    let mean = mean(values);
+    if mean == 0.0 {
+        return None;
+    }
+    let standard_error = stddev(values) / (values.len() as f64).sqrt();
+    Some(t_critical_95(values.len() - 1) * standard_error / mean.abs())
+}
+
+/// The cumulative distribution function of the standard normal distribution.
+pub fn normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+/// The complementary error function, via the rational approximation in Numerical Recipes
+/// (`erfcc`), which is accurate to about 1.2e-7 everywhere.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let result = t * poly.exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+/// Performs a two-sided Mann-Whitney U test of whether `a` and `b` come from the same
+/// distribution, returning the p-value. Returns `None` if either is empty.
+///
+/// We use the normal approximation with tie and continuity corrections. It's a little rough for
+/// very small samples (fewer than about 8 values each), where it tends to be conservative.
+pub fn mann_whitney_u(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    // Rank all values together, giving tied values the average of their ranks.
+    let mut all: Vec<(f64, bool)> = a
+        .iter()
+        .map(|&v| (v, true))
+        .chain(b.iter().map(|&v| (v, false)))
+        .collect();
+    all.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    let mut rank_sum_a = 0.0;
+    let mut tie_correction = 0.0;
+    let mut i = 0;
+    while i < all.len() {
+        let mut j = i;
+        while j + 1 < all.len() && all[j + 1].0 == all[i].0 {
+            j += 1;
+        }
+        let ties = (j - i + 1) as f64;
+        let average_rank = (i + j) as f64 / 2.0 + 1.0;
+        rank_sum_a += average_rank * all[i..=j].iter().filter(|(_, in_a)| *in_a).count() as f64;
+        tie_correction += ties.powi(3) - ties;
+        i = j + 1;
+    }
+
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let n = n1 + n2;
+    let u = rank_sum_a - n1 * (n1 + 1.0) / 2.0;
+    let mean_u = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_correction / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        // Every value is identical, so there's no evidence of any difference.
+        return Some(1.0);
+    }
+
+    let z = ((u - mean_u).abs() - 0.5).max(0.0) / variance.sqrt();
+    Some((2.0 * (1.0 - normal_cdf(z))).min(1.0))
+}
+
+/// A method for detecting outliers among a benchmark's iterations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutlierMethod {
+    /// Don't detect outliers at all.
+    #[default]
+    None,
+
+    /// Tukey's fences: values more than 1.5 interquartile ranges outside the quartiles.
+    Iqr,
+
+    /// Values whose modified z-score, based on the median absolute deviation, exceeds 3.5.
+    Mad,
+}
+
+/// The fewest values we'll look for outliers among. With fewer, quartiles and medians are too
+/// unstable to say that anything is out of the ordinary.
+const MIN_VALUES_FOR_OUTLIERS: usize = 4;
+
+impl OutlierMethod {
+    /// The method's name in config files, e.g. `iqr`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutlierMethod::None => "none",
+            OutlierMethod::Iqr => "iqr",
+            OutlierMethod::Mad => "mad",
+        }
+    }
+
+    /// Flags the outliers in `values`, returning one flag per value, in order.
+    pub fn detect(&self, values: &[f64]) -> Vec<bool> {
+        if values.len() < MIN_VALUES_FOR_OUTLIERS {
+            return vec![false; values.len()];
+        }
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        match self {
+            OutlierMethod::None => vec![false; values.len()],
+            OutlierMethod::Iqr => {
+                let q1 = percentile(&sorted, 25.0);
+                let q3 = percentile(&sorted, 75.0);
+                let fence = 1.5 * (q3 - q1);
+                values
+                    .iter()
+                    .map(|&v| v < q1 - fence || v > q3 + fence)
+                    .collect()
+            }
+            OutlierMethod::Mad => {
+                let median = percentile(&sorted, 50.0);
+                let mad = median_absolute_deviation(values);
+                if mad == 0.0 {
+                    // More than half the values are identical, so nothing is unusual by this
+                    // measure. (Dividing by zero would flag everything else instead.)
+                    return vec![false; values.len()];
+                }
+                values
+                    .iter()
+                    .map(|v| 0.6745 * (v - median).abs() / mad > 3.5)
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Names a percentile for use as a key, e.g. `p90` or `p99.9`.
+pub fn percentile_name(p: f64) -> String {
+    format!("p{}", p)
+}
+
+#[cfg(test)]
+mod summary_tests {
+    use super::*;
+
+    #[test]
+    fn with_empty_values_returns_none() {
+        assert_eq!(None, Summary::of(&[], &[90.0]));
+    }
+
+    #[test]
+    fn with_single_value_works() {
+        let summary = Summary::of(&[3.0], &[90.0]).unwrap();
+        assert_eq!(3.0, summary.mean);
+        assert_eq!(3.0, summary.median);
+        assert_eq!(0.0, summary.stddev);
+        assert_eq!(3.0, summary.min);
+        assert_eq!(3.0, summary.max);
+        assert_eq!(3.0, summary.percentiles["p90"]);
+    }
+
+    #[test]
+    fn with_unsorted_values_works() {
+        let summary = Summary::of(&[4.0, 1.0, 3.0, 2.0, 5.0], &[90.0, 99.9]).unwrap();
+        assert_eq!(3.0, summary.mean);
+        assert_eq!(3.0, summary.median);
+        assert!((summary.stddev - 2.5f64.sqrt()).abs() < 1e-12);
+        assert_eq!(1.0, summary.min);
+        assert_eq!(5.0, summary.max);
+        assert!((summary.percentiles["p90"] - 4.6).abs() < 1e-12);
+        assert!(summary.percentiles.contains_key("p99.9"));
+    }
+}
+
+#[cfg(test)]
+mod percentile_tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_between_ranks() {
+        let sorted = [10.0, 20.0, 30.0, 40.0];
+        assert_eq!(10.0, percentile(&sorted, 0.0));
+        assert_eq!(25.0, percentile(&sorted, 50.0));
+        assert_eq!(40.0, percentile(&sorted, 100.0));
+    }
+
+    #[test]
+    fn with_out_of_range_p_clamps() {
+        let sorted = [10.0, 20.0];
+        assert_eq!(10.0, percentile(&sorted, -5.0));
+        assert_eq!(20.0, percentile(&sorted, 150.0));
+    }
+}
+
+#[cfg(test)]
+mod median_absolute_deviation_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        // Median 2.5; deviations 0.5, 0.5, 0.5, 1.5, 1.5, 3.5 have a median of 1.
+        assert_eq!(
+            1.0,
+            median_absolute_deviation(&[1.0, 3.0, 2.0, 2.0, 4.0, 6.0])
+        );
+    }
+}
+
+#[cfg(test)]
+mod interquartile_range_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        assert_eq!(2.0, interquartile_range(&[5.0, 1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(0.0, interquartile_range(&[7.0]));
+    }
+}
+
+#[cfg(test)]
+mod spread_test_tests {
+    use super::*;
+
+    const STEADY: [f64; 10] = [1.00, 1.01, 0.99, 1.00, 1.02, 0.98, 1.00, 1.01, 0.99, 1.00];
+
+    #[test]
+    fn detects_more_spread() {
+        let noisy = [1.0, 1.3, 0.8, 1.1, 1.5, 0.7, 1.0, 1.4, 0.9, 1.2];
+        assert!(spread_test(&STEADY, &noisy).unwrap() < 0.01);
+    }
+
+    #[test]
+    fn ignores_a_shift() {
+        let slower: Vec<f64> = STEADY.iter().map(|v| v * 2.0).collect();
+        assert!(spread_test(&STEADY, &slower).unwrap() > 0.5);
+    }
+}
+
+#[cfg(test)]
+mod t_critical_95_tests {
+    use super::*;
+
+    #[test]
+    fn matches_published_values() {
+        // From standard t tables.
+        for (df, expected) in [
+            (1, 12.706),
+            (10, 2.228),
+            (11, 2.201),
+            (30, 2.042),
+            (120, 1.980),
+        ] {
+            assert!((t_critical_95(df) - expected).abs() < 0.002, "df = {}", df);
+        }
+    }
+
+    #[test]
+    fn with_zero_df_is_infinite() {
+        assert_eq!(f64::INFINITY, t_critical_95(0));
+    }
+}
+
+#[cfg(test)]
+mod relative_ci_half_width_tests {
+    use super::*;
+
+    #[test]
+    fn with_too_few_values_returns_none() {
+        assert_eq!(None, relative_ci_half_width(&[1.0]));
+    }
+
+    #[test]
+    fn with_identical_values_is_zero() {
+        assert_eq!(Some(0.0), relative_ci_half_width(&[2.0, 2.0, 2.0]));
+    }
+
+    #[test]
+    fn works() {
+        // mean 2, stddev √(2/3) ≈ 0.816, n 4: half-width = 3.182 * 0.816 / 2 ≈ 1.299, relative
+        // to a mean of 2.
+        let values = [1.0, 2.0, 2.0, 3.0];
+        let expected = 3.182 * (2.0f64 / 3.0).sqrt() / 2.0 / 2.0;
+        assert!((relative_ci_half_width(&values).unwrap() - expected).abs() < 1e-9);
+    }
+}
+
+#[cfg(test)]
+mod normal_cdf_tests {
+    use super::*;
+
+    #[test]
+    fn matches_published_values() {
+        for (x, expected) in [(0.0, 0.5), (1.0, 0.841_344_7), (-1.96, 0.024_997_9)] {
+            assert!((normal_cdf(x) - expected).abs() < 1e-6, "x = {}", x);
+        }
+    }
+}
+
+#[cfg(test)]
+mod mann_whitney_u_tests {
+    use super::*;
+
+    #[test]
+    fn with_empty_input_returns_none() {
+        assert_eq!(None, mann_whitney_u(&[], &[1.0]));
+    }
+
+    #[test]
+    fn with_identical_values_returns_one() {
+        assert_eq!(Some(1.0), mann_whitney_u(&[2.0; 5], &[2.0; 5]));
+    }
+
+    #[test]
+    fn with_separated_samples_is_significant() {
+        let a = [1.0, 1.1, 1.2, 1.3, 1.4, 1.5, 1.6, 1.7, 1.8, 1.9];
+        let b = [2.0, 2.1, 2.2, 2.3, 2.4, 2.5, 2.6, 2.7, 2.8, 2.9];
+        // U = 0; SciPy's normal approximation with continuity correction gives p = 0.000183.
+        let p = mann_whitney_u(&a, &b).unwrap();
+        assert!((p - 0.000_183).abs() < 1e-5, "p = {}", p);
+    }
+
+    #[test]
+    fn with_interleaved_samples_is_not_significant() {
+        let a = [1.0, 3.0, 5.0, 7.0, 9.0];
+        let b = [2.0, 4.0, 6.0, 8.0, 10.0];
+        assert!(mann_whitney_u(&a, &b).unwrap() > 0.5);
+    }
+}
+
+#[cfg(test)]
+mod outlier_method_tests {
+    use super::*;
+
+    const VALUES: [f64; 8] = [1.0, 1.1, 0.9, 1.0, 1.05, 0.95, 5.0, 1.0];
+    const EXPECTED: [bool; 8] = [false, false, false, false, false, false, true, false];
+
+    #[test]
+    fn none_flags_nothing() {
+        assert_eq!(vec![false; 8], OutlierMethod::None.detect(&VALUES));
+    }
+
+    #[test]
+    fn iqr_flags_outlier() {
+        assert_eq!(EXPECTED.to_vec(), OutlierMethod::Iqr.detect(&VALUES));
+    }
+
+    #[test]
+    fn mad_flags_outlier() {
+        assert_eq!(EXPECTED.to_vec(), OutlierMethod::Mad.detect(&VALUES));
+    }
+
+    #[test]
+    fn mad_with_mostly_identical_values_flags_nothing() {
+        let values = [1.0, 1.0, 1.0, 1.0, 2.0];
+        assert_eq!(vec![false; 5], OutlierMethod::Mad.detect(&values));
+    }
+
+    #[test]
+    fn with_too_few_values_flags_nothing() {
+        let values = [1.0, 1.0, 100.0];
+        assert_eq!(vec![false; 3], OutlierMethod::Iqr.detect(&values));
+    }
+}
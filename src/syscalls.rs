@@ -0,0 +1,177 @@
+//! System call counts, read by running each iteration under `strace` (Linux only).
+//!
+//! The counts show whether a command's I/O strategy, rather than its algorithm, is what's slow: a
+//! command reading its input 4 KiB at a time makes sixteen times the system calls of one reading
+//! 64 KiB at a time, and on a fast disk the difference can dominate its run time. Tracing slows
+//! the command down considerably, so timings taken with it on are only good for comparing with
+//! each other, and the counts are best gathered in a suite of their own.
+
+use crate::results::Metric;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The system calls that read or write data, whose sizes we total up.
+const READS: [&str; 4] = ["read", "pread64", "readv", "preadv"];
+const WRITES: [&str; 4] = ["write", "pwrite64", "writev", "pwritev"];
+
+/// Numbers the output files of `strace` runs, so no two share one.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// One iteration's run under `strace`.
+pub struct SyscallTrace {
+    /// Where `strace` writes its trace.
+    output: PathBuf,
+}
+
+impl SyscallTrace {
+    pub fn new() -> SyscallTrace {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        SyscallTrace {
+            output: std::env::temp_dir().join(format!(
+                "bench-strace-{}-{}.txt",
+                std::process::id(),
+                id
+            )),
+        }
+    }
+
+    /// Returns `command` wrapped in `strace`, following any processes it starts and leaving out
+    /// the data passed to each call, which we don't need.
+    pub fn wrap(&self, command: &[String]) -> Vec<String> {
+        let mut wrapped: Vec<String> =
+            ["strace", "-f", "-qq", "-s", "0", "-e", "signal=none", "-o"]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect();
+        wrapped.push(self.output.to_string_lossy().into_owned());
+        wrapped.push("--".to_string());
+        wrapped.extend(command.iter().cloned());
+        wrapped
+    }
+
+    /// Reads the counts once the command has exited.
+    pub fn finish(self) -> io::Result<Vec<(Metric, f64)>> {
+        let text = fs::read_to_string(&self.output);
+        let _ = fs::remove_file(&self.output);
+        Ok(Counts::parse(&text?).metrics())
+    }
+}
+
+/// What a trace adds up to.
+#[derive(Debug, Default, PartialEq)]
+struct Counts {
+    syscalls: u64,
+    reads: u64,
+    read_bytes: u64,
+    writes: u64,
+    written_bytes: u64,
+}
+
+impl Counts {
+    /// Adds up a trace written by `strace -f`, whose lines look like `1234 read(3, ""..., 65536) =
+    /// 65536`. A call interrupted by another process's is split over two lines, the first ending
+    /// in `<unfinished ...>` and the second starting `<... read resumed>`; we count the second.
+    fn parse(text: &str) -> Counts {
+        let mut counts = Counts::default();
+        for line in text.lines() {
+            // Skip the process ID, if there is one.
+            let line = line
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .trim_start();
+            if line.ends_with("<unfinished ...>") || line.starts_with("+++") {
+                continue;
+            }
+            let name = match line.strip_prefix("<... ") {
+                Some(resumed) => resumed.split(' ').next(),
+                None => line.split('(').next(),
+            };
+            let Some(name) = name.filter(|name| !name.is_empty()) else {
+                continue;
+            };
+            counts.syscalls += 1;
+            // Failed calls return -1, and calls that never return (like `exit_group`) `?`.
+            let bytes = line
+                .rsplit_once(" = ")
+                .and_then(|(_, result)| result.split(' ').next()?.parse::<u64>().ok())
+                .unwrap_or(0);
+            if READS.contains(&name) {
+                counts.reads += 1;
+                counts.read_bytes += bytes;
+            } else if WRITES.contains(&name) {
+                counts.writes += 1;
+                counts.written_bytes += bytes;
+            }
+        }
+        counts
+    }
+
+    /// Returns the counts as metrics. Mean read and write sizes are left out if there were none.
+    fn metrics(&self) -> Vec<(Metric, f64)> {
+        let mut metrics = vec![
+            (Metric::Syscalls, self.syscalls as f64),
+            (Metric::Reads, self.reads as f64),
+            (Metric::Writes, self.writes as f64),
+        ];
+        if self.reads > 0 {
+            metrics.push((Metric::ReadSize, self.read_bytes as f64 / self.reads as f64));
+        }
+        if self.writes > 0 {
+            metrics.push((
+                Metric::WriteSize,
+                self.written_bytes as f64 / self.writes as f64,
+            ));
+        }
+        metrics
+    }
+}
+
+#[cfg(test)]
+mod counts_tests {
+    use super::*;
+
+    #[test]
+    fn parse_works() {
+        let text = "\
+4242  execve(\"/usr/bin/groupby\", [\"groupby\"], 0x7ffc /* 20 vars */) = 0
+4242  read(0, \"\"..., 65536) = 65536
+4242  read(0,  <unfinished ...>
+4243  write(1, \"\"..., 100) = 100
+4242  <... read resumed>\"\"..., 65536) = 1000
+4242  read(0, \"\", 65536) = 0
+4243  write(1, \"\"..., 100) = -1 EPIPE (Broken pipe)
+4242  exit_group(0) = ?
+4242  +++ exited with 0 +++
+";
+        assert_eq!(
+            Counts {
+                syscalls: 7,
+                reads: 3,
+                read_bytes: 66536,
+                writes: 2,
+                written_bytes: 100,
+            },
+            Counts::parse(text)
+        );
+    }
+
+    #[test]
+    fn metrics_leave_out_sizes_without_calls() {
+        let counts = Counts {
+            syscalls: 10,
+            reads: 4,
+            read_bytes: 1000,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                (Metric::Syscalls, 10.0),
+                (Metric::Reads, 4.0),
+                (Metric::Writes, 0.0),
+                (Metric::ReadSize, 250.0),
+            ],
+            counts.metrics()
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Tags attached to runs, like `laptop` or `nvme`, and expressions that select runs by them, like
+//! `laptop & !nvme`, so results from different machines or experiments aren't mixed by accident.
+//!
+//! Expressions combine tags with `!` (not), `&` (and), and `|` (or), in that order of precedence,
+//! and parentheses.
+
+/// Checks that `tag` is usable in tag expressions: nonempty, and made only of letters, digits, and
+/// `-`, `_`, `.`, or `:`.
+pub fn validate(tag: &str) -> Result<(), String> {
+    if !tag.is_empty() && tag.chars().all(is_tag_char) {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid tag {:?}: use letters, digits, and - _ . : only",
+            tag
+        ))
+    }
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ':')
+}
+
+/// A parsed tag expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TagFilter {
+    Tag(String),
+    Not(Box<TagFilter>),
+    And(Box<TagFilter>, Box<TagFilter>),
+    Or(Box<TagFilter>, Box<TagFilter>),
+}
+
+impl TagFilter {
+    /// Parses an expression such as `laptop & (nvme | ssd)`.
+    pub fn parse(text: &str) -> Result<TagFilter, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let filter = parser.or()?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(filter),
+            Some(token) => Err(format!("unexpected {:?} in {:?}", token, text)),
+        }
+    }
+
+    /// Whether a run with `tags` matches.
+    pub fn matches(&self, tags: &[String]) -> bool {
+        match self {
+            TagFilter::Tag(tag) => tags.contains(tag),
+            TagFilter::Not(filter) => !filter.matches(tags),
+            TagFilter::And(a, b) => a.matches(tags) && b.matches(tags),
+            TagFilter::Or(a, b) => a.matches(tags) || b.matches(tags),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if "!&|()".contains(c) {
+            tokens.push(c.to_string());
+            chars.next();
+        } else if is_tag_char(c) {
+            let mut tag = String::new();
+            while let Some(&c) = chars.peek().filter(|&&c| is_tag_char(c)) {
+                tag.push(c);
+                chars.next();
+            }
+            tokens.push(tag);
+        } else {
+            return Err(format!("unexpected {:?} in {:?}", c, text));
+        }
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser over the tokens of an expression, one method per precedence level.
+struct Parser {
+    tokens: Vec<String>,
+    next: usize,
+}
+
+impl Parser {
+    fn or(&mut self) -> Result<TagFilter, String> {
+        let mut filter = self.and()?;
+        while self.eat("|") {
+            filter = TagFilter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<TagFilter, String> {
+        let mut filter = self.not()?;
+        while self.eat("&") {
+            filter = TagFilter::And(Box::new(filter), Box::new(self.not()?));
+        }
+        Ok(filter)
+    }
+
+    fn not(&mut self) -> Result<TagFilter, String> {
+        if self.eat("!") {
+            return Ok(TagFilter::Not(Box::new(self.not()?)));
+        }
+        if self.eat("(") {
+            let filter = self.or()?;
+            if !self.eat(")") {
+                return Err("missing )".to_string());
+            }
+            return Ok(filter);
+        }
+        match self.tokens.get(self.next) {
+            Some(token) if validate(token).is_ok() => {
+                self.next += 1;
+                Ok(TagFilter::Tag(token.clone()))
+            }
+            Some(token) => Err(format!("expected a tag, found {:?}", token)),
+            None => Err("expected a tag, found the end".to_string()),
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.tokens.get(self.next).map(String::as_str) == Some(token) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod validate_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        assert!(validate("nvme").is_ok());
+        assert!(validate("host:box-1.lan").is_ok());
+        assert!(validate("").is_err());
+        assert!(validate("a b").is_err());
+        assert!(validate("a&b").is_err());
+    }
+}
+
+#[cfg(test)]
+mod tag_filter_tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn single_tag_matches() {
+        let filter = TagFilter::parse("laptop").unwrap();
+        assert!(filter.matches(&tags(&["laptop", "nvme"])));
+        assert!(!filter.matches(&tags(&["desktop"])));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let filter = TagFilter::parse("a | b & c").unwrap();
+        assert!(filter.matches(&tags(&["a"])));
+        assert!(!filter.matches(&tags(&["b"])));
+        assert!(filter.matches(&tags(&["b", "c"])));
+    }
+
+    #[test]
+    fn not_and_parentheses_work() {
+        let filter = TagFilter::parse("!(a | b) & c").unwrap();
+        assert!(filter.matches(&tags(&["c"])));
+        assert!(!filter.matches(&tags(&["a", "c"])));
+        assert!(!filter.matches(&tags(&[])));
+    }
+
+    #[test]
+    fn malformed_expressions_fail() {
+        for text in ["", "a &", "(a", "a b", "a ^ b", "a)"] {
+            assert!(TagFilter::parse(text).is_err(), "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Watches the CPU's frequency, temperature, and thermal throttling while an iteration runs.
+//!
+//! A CPU that gets too hot slows itself down, and an iteration that runs while it does takes
+//! longer for reasons that have nothing to do with the code. We poll what Linux exposes in sysfs
+//! so such iterations can be flagged and left out of the statistics. On systems without these
+//! files, there's nothing to watch and nothing is flagged.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often to check the CPU while an iteration runs.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The sysfs files that describe the CPU, found once up front so polling doesn't have to search.
+#[derive(Debug, Default)]
+pub struct CpuFiles {
+    /// Each CPU's current frequency, in kHz.
+    frequencies: Vec<PathBuf>,
+
+    /// Each thermal zone's temperature, in thousandths of a degree Celsius.
+    temperatures: Vec<PathBuf>,
+
+    /// Each CPU's count of thermal throttling events.
+    throttle_counts: Vec<PathBuf>,
+}
+
+/// What a `CpuMonitor` observed over an iteration.
+#[derive(Debug, Default, PartialEq)]
+pub struct CpuObservation {
+    /// The mean frequency across CPUs and polls, in MHz.
+    pub mhz: Option<f64>,
+
+    /// The highest temperature seen, in degrees Celsius.
+    pub celsius: Option<f64>,
+
+    /// Whether the kernel counted any thermal throttling events.
+    pub throttled: bool,
+}
+
+impl CpuFiles {
+    /// Finds the CPU files on this system. Returns `None` if there are none.
+    pub fn discover() -> Option<CpuFiles> {
+        CpuFiles::discover_in(
+            Path::new("/sys/devices/system/cpu"),
+            Path::new("/sys/class/thermal"),
+        )
+    }
+
+    fn discover_in(sys_cpu: &Path, thermal: &Path) -> Option<CpuFiles> {
+        let existing = |dir: &Path, prefix: &str, file: &str| -> Vec<PathBuf> {
+            let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with(prefix))
+                .map(|entry| entry.path().join(file))
+                .filter(|path| path.exists())
+                .collect();
+            paths.sort();
+            paths
+        };
+        let files = CpuFiles {
+            frequencies: existing(sys_cpu, "cpu", "cpufreq/scaling_cur_freq"),
+            temperatures: existing(thermal, "thermal_zone", "temp"),
+            throttle_counts: existing(sys_cpu, "cpu", "thermal_throttle/core_throttle_count"),
+        };
+        let empty = files.frequencies.is_empty()
+            && files.temperatures.is_empty()
+            && files.throttle_counts.is_empty();
+        (!empty).then_some(files)
+    }
+
+    /// Returns the mean of the CPUs' current frequencies, in MHz.
+    fn frequency_mhz(&self) -> Option<f64> {
+        let khz: Vec<f64> = read_numbers(&self.frequencies);
+        (!khz.is_empty()).then(|| khz.iter().sum::<f64>() / khz.len() as f64 / 1000.0)
+    }
+
+    /// Returns the hottest thermal zone's temperature, in degrees Celsius.
+    pub fn temperature(&self) -> Option<f64> {
+        let millidegrees = read_numbers(&self.temperatures);
+        (!millidegrees.is_empty())
+            .then(|| millidegrees.iter().copied().fold(f64::MIN, f64::max) / 1000.0)
+    }
+
+    /// Returns the total number of throttling events so far.
+    fn throttle_count(&self) -> f64 {
+        read_numbers(&self.throttle_counts).iter().sum()
+    }
+}
+
+fn read_numbers(paths: &[PathBuf]) -> Vec<f64> {
+    paths
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok()?.trim().parse().ok())
+        .collect()
+}
+
+/// Polls the CPU in a background thread for as long as an iteration runs.
+pub struct CpuMonitor {
+    done: Arc<AtomicBool>,
+    handle: JoinHandle<CpuObservation>,
+}
+
+impl CpuMonitor {
+    pub fn start(files: Arc<CpuFiles>) -> Self {
+        let done = Arc::new(AtomicBool::new(false));
+        let throttles_before = files.throttle_count();
+        let handle = {
+            let done = done.clone();
+            thread::spawn(move || {
+                let mut frequencies = vec![];
+                let mut observation = CpuObservation::default();
+                loop {
+                    frequencies.extend(files.frequency_mhz());
+                    if let Some(celsius) = files.temperature() {
+                        observation.celsius =
+                            Some(observation.celsius.unwrap_or(celsius).max(celsius));
+                    }
+                    if done.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+                if !frequencies.is_empty() {
+                    observation.mhz =
+                        Some(frequencies.iter().sum::<f64>() / frequencies.len() as f64);
+                }
+                observation.throttled = files.throttle_count() > throttles_before;
+                observation
+            })
+        };
+        CpuMonitor { done, handle }
+    }
+
+    /// Stops polling and returns what was observed.
+    pub fn stop(self) -> CpuObservation {
+        self.done.store(true, Ordering::Relaxed);
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod cpu_files_tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, contents: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn reads_sysfs() {
+        let root = std::env::temp_dir().join(format!("cpu-files-{}", std::process::id()));
+        let (sys_cpu, thermal) = (root.join("cpu"), root.join("thermal"));
+        write(&sys_cpu, "cpu0/cpufreq/scaling_cur_freq", "2000000\n");
+        write(&sys_cpu, "cpu1/cpufreq/scaling_cur_freq", "3000000\n");
+        write(&sys_cpu, "cpu0/thermal_throttle/core_throttle_count", "4\n");
+        write(&thermal, "thermal_zone0/temp", "45000\n");
+        write(&thermal, "thermal_zone1/temp", "61500\n");
+
+        let files = CpuFiles::discover_in(&sys_cpu, &thermal).unwrap();
+        assert_eq!(Some(2500.0), files.frequency_mhz());
+        assert_eq!(Some(61.5), files.temperature());
+        assert_eq!(4.0, files.throttle_count());
+
+        // The monitor notices the counter going up while it runs.
+        let monitor = CpuMonitor::start(Arc::new(files));
+        thread::sleep(POLL_INTERVAL);
+        write(&sys_cpu, "cpu0/thermal_throttle/core_throttle_count", "5\n");
+        let observation = monitor.stop();
+        assert_eq!(Some(2500.0), observation.mhz);
+        assert_eq!(Some(61.5), observation.celsius);
+        assert!(observation.throttled);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn without_sysfs_is_none() {
+        let missing = Path::new("/nonexistent");
+        assert!(CpuFiles::discover_in(missing, missing).is_none());
+    }
+}
@@ -0,0 +1,112 @@
+//! Formats wall-clock times as RFC 3339 timestamps, and reads back the ones we wrote, which is all
+//! the date handling we need.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Formats `time` as an RFC 3339 timestamp in UTC, e.g. `2022-05-01T12:34:56Z`.
+pub fn rfc3339(time: SystemTime) -> String {
+    let (date, (hours, minutes, seconds)) = split(time);
+    format!("{}T{:02}:{:02}:{:02}Z", date, hours, minutes, seconds)
+}
+
+/// Formats `seconds` since the Unix epoch as a `YYYY-MM-DD` date in UTC.
+pub fn date(seconds: u64) -> String {
+    split(UNIX_EPOCH + Duration::from_secs(seconds)).0
+}
+
+/// Parses a UTC timestamp of the form `rfc3339` writes into seconds since the Unix epoch, or
+/// returns `None` if it isn't one.
+pub fn parse_rfc3339(timestamp: &str) -> Option<u64> {
+    let (date, time) = timestamp.strip_suffix('Z')?.split_once('T')?;
+    let number = |text: &str| text.parse::<u32>().ok();
+    let date: Vec<u32> = date.split('-').map(number).collect::<Option<_>>()?;
+    let time: Vec<u32> = time.split(':').map(number).collect::<Option<_>>()?;
+    let (&[year, month, day], &[hours, minutes, seconds]) = (&date[..], &time[..]) else {
+        return None;
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hours > 23 || minutes > 59 {
+        return None;
+    }
+    let days = days_from_civil(year as i64, month, day);
+    let seconds = days * 86_400 + (hours * 3600 + minutes * 60 + seconds) as i64;
+    u64::try_from(seconds).ok()
+}
+
+/// Splits `time` into a `YYYY-MM-DD` date and the time of day, in UTC.
+fn split(time: SystemTime) -> (String, (u64, u64, u64)) {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let days = (seconds / 86_400) as i64;
+    let of_day = seconds % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        (of_day / 3600, of_day % 3600 / 60, of_day % 60),
+    )
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date in the proleptic Gregorian
+/// calendar, using Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Converts a (year, month, day) date to days since 1970-01-01, the inverse of
+/// `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let day_of_year = (153 * mp + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod rfc3339_tests {
+    use super::*;
+
+    #[test]
+    fn at_epoch_works() {
+        assert_eq!("1970-01-01T00:00:00Z", rfc3339(UNIX_EPOCH));
+    }
+
+    #[test]
+    fn works() {
+        // 2000-02-29 is a leap day in a century year divisible by 400.
+        let time = UNIX_EPOCH + Duration::from_secs(951_827_696);
+        assert_eq!("2000-02-29T12:34:56Z", rfc3339(time));
+    }
+}
+
+#[cfg(test)]
+mod parse_rfc3339_tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        assert_eq!(Some(0), parse_rfc3339("1970-01-01T00:00:00Z"));
+        assert_eq!(Some(951_827_696), parse_rfc3339("2000-02-29T12:34:56Z"));
+        assert_eq!("2000-02-29", date(951_827_696));
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        assert_eq!(None, parse_rfc3339("2000-02-29 12:34:56"));
+        assert_eq!(None, parse_rfc3339("2000-13-01T00:00:00Z"));
+        assert_eq!(None, parse_rfc3339(""));
+    }
+}
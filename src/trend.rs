@@ -0,0 +1,138 @@
+//! Follows each benchmark through the history database over time, and picks out the steps where it
+//! got significantly slower, so that slow creep over many small commits, none of which is a
+//! regression worth flagging on its own, shows up as the trend it is.
+
+use crate::history::Entry;
+use crate::stats;
+
+/// The recorded history of one benchmark, ordered by time.
+#[derive(Debug, PartialEq)]
+pub struct Trend {
+    pub case: String,
+    pub sample: String,
+    pub points: Vec<Point>,
+}
+
+/// One recorded run of a benchmark.
+#[derive(Debug, PartialEq)]
+pub struct Point {
+    /// When the commit measured was made, or if that isn't known, when the run started, in
+    /// seconds since the Unix epoch.
+    pub time: u64,
+
+    pub commit: String,
+    pub mean: f64,
+    pub stddev: f64,
+    pub iterations: usize,
+
+    /// The relative change from the previous point, if it was a significant regression.
+    pub regression: Option<f64>,
+}
+
+impl Trend {
+    /// The relative change from the first point to the last, if there's more than one.
+    pub fn overall_change(&self) -> Option<f64> {
+        let [first, .., last] = &self.points[..] else {
+            return None;
+        };
+        (first.mean != 0.0).then(|| (last.mean - first.mean) / first.mean)
+    }
+}
+
+/// Groups `entries` into one trend per case and sample, in the order they first appear, placing
+/// each entry at `time(entry)`, and marks the regressions.
+pub fn trends(entries: Vec<Entry>, mut time: impl FnMut(&Entry) -> u64) -> Vec<Trend> {
+    let mut trends: Vec<Trend> = vec![];
+    for entry in entries {
+        let point = Point {
+            time: time(&entry),
+            commit: entry.commit.clone(),
+            mean: entry.mean,
+            stddev: entry.stddev,
+            iterations: entry.iterations,
+            regression: None,
+        };
+        match trends
+            .iter_mut()
+            .find(|t| t.case == entry.case && t.sample == entry.sample)
+        {
+            Some(trend) => trend.points.push(point),
+            None => trends.push(Trend {
+                case: entry.case,
+                sample: entry.sample,
+                points: vec![point],
+            }),
+        }
+    }
+    for trend in &mut trends {
+        // Entries come oldest run first, which needn't be the order of their commits. The sort is
+        // stable, so runs of the same commit stay in the order they ran.
+        trend.points.sort_by_key(|point| point.time);
+        for i in 1..trend.points.len() {
+            let (old, new) = (&trend.points[i - 1], &trend.points[i]);
+            if significantly_slower(old, new) {
+                trend.points[i].regression = Some((new.mean - old.mean) / old.mean);
+            }
+        }
+    }
+    trends
+}
+
+/// Whether `new` is slower than `old` at 95% confidence, by Welch's t-test. The history only keeps
+/// summary statistics, so this is the test we can do; it takes at least two iterations a side.
+fn significantly_slower(old: &Point, new: &Point) -> bool {
+    if old.iterations < 2 || new.iterations < 2 || old.mean <= 0.0 {
+        return false;
+    }
+    let old_variance = old.stddev.powi(2) / old.iterations as f64;
+    let new_variance = new.stddev.powi(2) / new.iterations as f64;
+    let variance = old_variance + new_variance;
+    if variance == 0.0 {
+        return new.mean > old.mean;
+    }
+    let df = variance.powi(2)
+        / (old_variance.powi(2) / (old.iterations - 1) as f64
+            + new_variance.powi(2) / (new.iterations - 1) as f64);
+    (new.mean - old.mean) / variance.sqrt() > stats::t_critical_95(df.floor() as usize)
+}
+
+#[cfg(test)]
+mod trends_tests {
+    use super::*;
+
+    fn entry(case: &str, started_at: &str, mean: f64) -> Entry {
+        Entry {
+            case: case.to_string(),
+            sample: "sample.txt".to_string(),
+            commit: String::new(),
+            started_at: started_at.to_string(),
+            iterations: 10,
+            mean,
+            stddev: 0.01,
+        }
+    }
+
+    #[test]
+    fn marks_significant_steps() {
+        let entries = vec![
+            entry("a", "3", 1.0),
+            entry("a", "1", 1.0),
+            entry("b", "1", 2.0),
+            entry("a", "2", 1.005),
+            entry("a", "4", 1.2),
+        ];
+        let trends = trends(entries, |entry| entry.started_at.parse().unwrap());
+        assert_eq!(2, trends.len());
+        let a = &trends[0];
+        assert_eq!("a", a.case);
+        let times: Vec<u64> = a.points.iter().map(|p| p.time).collect();
+        assert_eq!(vec![1, 2, 3, 4], times);
+        let regressions: Vec<Option<f64>> = a.points.iter().map(|p| p.regression).collect();
+        assert_eq!(None, regressions[1]);
+        assert_eq!(None, regressions[2]);
+        assert!((regressions[3].unwrap() - 0.2).abs() < 1e-9);
+        assert!((a.overall_change().unwrap() - 0.2).abs() < 1e-9);
+        assert_eq!(1, trends[1].points.len());
+        assert_eq!(None, trends[1].overall_change());
+    }
+}
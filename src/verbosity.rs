@@ -0,0 +1,56 @@
+//! How much `bench run` prints, set once from the command line: just the problems when run from a
+//! script, summaries interactively, or every iteration when watching a suite closely.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only failures, warnings, and wrong answers.
+    Quiet,
+
+    /// Progress, each benchmark's summary, and the results table.
+    Normal,
+
+    /// Each iteration's timings too.
+    Verbose,
+
+    /// Each iteration's raw measurements too, every metric unformatted.
+    Raw,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+impl Verbosity {
+    /// The verbosity asked for by `-q` and any number of `-v`s.
+    pub fn from_flags(quiet: bool, verbose: u8) -> Verbosity {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Raw,
+        }
+    }
+}
+
+/// Sets how much to print from now on.
+pub fn set(verbosity: Verbosity) {
+    LEVEL.store(verbosity as u8, Ordering::Relaxed);
+}
+
+/// Whether to print what's printed at `verbosity` and above.
+pub fn shows(verbosity: Verbosity) -> bool {
+    verbosity as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod from_flags_tests {
+    use super::*;
+
+    #[test]
+    fn works() {
+        assert_eq!(Verbosity::Quiet, Verbosity::from_flags(true, 0));
+        assert_eq!(Verbosity::Normal, Verbosity::from_flags(false, 0));
+        assert_eq!(Verbosity::Verbose, Verbosity::from_flags(false, 1));
+        assert_eq!(Verbosity::Raw, Verbosity::from_flags(false, 3));
+    }
+}
@@ -0,0 +1,67 @@
+//! Watches the benchmarked program for rebuilds, so `bench watch` can rerun a few quick cases on
+//! every build and show what the last change did, without a trip back to the shell.
+
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// What identifies one build of a program: when its file was last written, and how big it is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+/// Returns the stamp of the file at `path`, or `None` if there's no file there, as while the
+/// linker is replacing it.
+pub fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(Stamp {
+        modified: metadata.modified().ok()?,
+        len: metadata.len(),
+    })
+}
+
+/// Checks the file at `path` every `interval` until it differs from `last`, then waits for it to
+/// stay the same for one more interval, so we don't run a build the linker is still writing.
+/// Returns the new build's stamp. With no `last`, returns as soon as there's a file.
+pub fn wait_for_change(path: &Path, last: Option<Stamp>, interval: Duration) -> Stamp {
+    let mut previous = None;
+    loop {
+        let current = stamp(path);
+        if let Some(current) = current {
+            if last.is_none() || (Some(current) != last && previous == Some(current)) {
+                return current;
+            }
+        }
+        previous = current;
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod wait_for_change_tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_a_rewrite() {
+        let path = std::env::temp_dir().join(format!("watch-{}", std::process::id()));
+        fs::write(&path, "old").unwrap();
+        let interval = Duration::from_millis(10);
+        let old = wait_for_change(&path, None, interval);
+
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                fs::write(&path, "newer").unwrap();
+            })
+        };
+        let new = wait_for_change(&path, Some(old), interval);
+        writer.join().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(5, new.len);
+        assert_ne!(old, new);
+    }
+}